@@ -515,17 +515,35 @@ class LanceDataset(pa.dataset.Dataset):
         - **num_sub_vectors**
 
         Optional parameters for "IVF_PQ":
+        - **num_bits**: the number of bits of a PQ code, either 4 or 8 (default).
+            4-bit codes halve the index size, and require an even ``num_sub_vectors``.
         - **use_opq**: whether to use OPQ (Optimized Product Quantization).
             Must have feature 'opq' enabled in Rust.
         - **max_opq_iterations**: the maximum number of iterations for training OPQ.
         - **ivf_centroids**: K-mean centroids for IVF clustering.
 
+        If ``index_type`` is "IVF_SQ", then the following parameters are required:
+        - **num_partitions**
+
+        Each dimension of the residual vectors is quantized to 8 bits with
+        SQ (Scalar Quantization).
+
         If ``index_type`` is "DISKANN", then the following parameters are optional:
 
         - **r**: out-degree bound
         - **l**: number of levels in the graph.
         - **alpha**: distance threshold for the graph.
 
+        If ``index_type`` is "HNSW", then the following parameters are optional:
+
+        - **m**: number of neighbors of each vertex on the upper levels.
+        - **ef_construction**: size of the candidate list to build the graph.
+        - **ef_search**: size of the candidate list to search the graph.
+
+        If ``index_type`` is "IVF_HNSW", an HNSW graph is built in each IVF
+        partition. ``num_partitions`` is required, and the "HNSW" parameters
+        are optional.
+
         Examples
         --------
 
@@ -579,10 +597,15 @@ class LanceDataset(pa.dataset.Dataset):
         ]:
             raise ValueError(f"Metric {metric} not supported.")
         index_type = index_type.upper()
-        if index_type not in ["IVF_PQ", "DISKANN"]:
+        if index_type not in ["IVF_PQ", "IVF_SQ", "DISKANN", "HNSW", "IVF_HNSW"]:
             raise NotImplementedError(
-                f"Only IVF_PQ or DiskANN index_types supported. Got {index_type}"
+                "Only IVF_PQ, IVF_SQ, DiskANN, HNSW or IVF_HNSW index_types supported. "
+                f"Got {index_type}"
             )
+        if index_type in ["IVF_SQ", "IVF_HNSW"]:
+            if num_partitions is None:
+                raise ValueError(f"num_partitions is required for {index_type}")
+            kwargs["num_partitions"] = num_partitions
         if index_type == "IVF_PQ":
             if num_partitions is None or num_sub_vectors is None:
                 raise ValueError(
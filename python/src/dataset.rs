@@ -27,6 +27,7 @@ use lance::datatypes::Schema;
 use lance::format::Fragment;
 use lance::index::vector::ivf::IvfBuildParams;
 use lance::index::vector::pq::PQBuildParams;
+use lance::index::vector::sq::SQBuildParams;
 use pyo3::exceptions::{PyIOError, PyKeyError, PyValueError};
 use pyo3::prelude::*;
 use pyo3::types::{IntoPyDict, PyBool, PyDict, PyFloat, PyInt, PyLong};
@@ -40,6 +41,7 @@ use lance::dataset::{
 };
 use lance::index::{
     vector::diskann::DiskANNParams,
+    vector::hnsw::HNSWParams,
     vector::{MetricType, VectorIndexParams},
     DatasetIndexExt, IndexType,
 };
@@ -344,7 +346,7 @@ impl Dataset {
         kwargs: Option<&PyDict>,
     ) -> PyResult<()> {
         let idx_type = match index_type.to_uppercase().as_str() {
-            "IVF_PQ" | "DISKANN" => IndexType::Vector,
+            "IVF_PQ" | "IVF_SQ" | "DISKANN" | "HNSW" | "IVF_HNSW" => IndexType::Vector,
             _ => {
                 return Err(PyValueError::new_err(format!(
                     "Index type '{index_type}' is not supported."
@@ -409,6 +411,15 @@ impl Dataset {
                 }
                 VectorIndexParams::with_ivf_pq_params(m_type, ivf_params, pq_params)
             }
+            "IVF_SQ" => {
+                let mut ivf_params = IvfBuildParams::default();
+                if let Some(kwargs) = kwargs {
+                    if let Some(n) = kwargs.get_item("num_partitions") {
+                        ivf_params.num_partitions = PyAny::downcast::<PyInt>(n)?.extract()?
+                    };
+                }
+                VectorIndexParams::with_ivf_sq_params(m_type, ivf_params, SQBuildParams::default())
+            }
             "DISKANN" => {
                 let mut params = DiskANNParams::default();
                 if let Some(kwargs) = kwargs {
@@ -426,6 +437,32 @@ impl Dataset {
                 }
                 VectorIndexParams::with_diskann_params(m_type, params)
             }
+            "HNSW" | "IVF_HNSW" => {
+                let mut ivf_params = IvfBuildParams::default();
+                let mut params = HNSWParams::default();
+                if let Some(kwargs) = kwargs {
+                    if let Some(n) = kwargs.get_item("num_partitions") {
+                        ivf_params.num_partitions = PyAny::downcast::<PyInt>(n)?.extract()?
+                    };
+
+                    if let Some(n) = kwargs.get_item("m") {
+                        params.m = PyAny::downcast::<PyInt>(n)?.extract()?
+                    };
+
+                    if let Some(n) = kwargs.get_item("ef_construction") {
+                        params.ef_construction = PyAny::downcast::<PyInt>(n)?.extract()?
+                    };
+
+                    if let Some(n) = kwargs.get_item("ef_search") {
+                        params.ef_search = PyAny::downcast::<PyInt>(n)?.extract()?
+                    };
+                }
+                if index_type.to_uppercase() == "IVF_HNSW" {
+                    VectorIndexParams::with_ivf_hnsw_params(m_type, ivf_params, params)
+                } else {
+                    VectorIndexParams::with_hnsw_params(m_type, params)
+                }
+            }
             _ => {
                 return Err(PyValueError::new_err(format!(
                     "Index type '{index_type}' is not supported."
@@ -17,6 +17,7 @@ use std::sync::Arc;
 use arrow_array::{FixedSizeListArray, RecordBatch, RecordBatchIterator, RecordBatchReader};
 use arrow_schema::{DataType, Field, FieldRef, Schema};
 use criterion::{criterion_group, criterion_main, Criterion};
+use futures::TryStreamExt;
 use lance::{
     arrow::*,
     dataset::{WriteMode, WriteParams},
@@ -103,6 +104,49 @@ fn bench_ivf_pq_index(c: &mut Criterion) {
             });
         },
     );
+
+    // Search with 8-bit and 4-bit (fast scan) PQ codes.
+    let query = generate_random_array(DIM);
+    for num_bits in [8, 4] {
+        let params =
+            VectorIndexParams::ivf_pq(ivf_partition, num_bits, pq, false, MetricType::L2, 50);
+        let dataset = rt.block_on(async {
+            dataset
+                .create_index(
+                    vec!["vector"].as_slice(),
+                    IndexType::Vector,
+                    None,
+                    &params,
+                    true,
+                )
+                .await
+                .unwrap()
+        });
+
+        c.bench_function(
+            format!(
+                "SearchIVF{},PQ{}x{}(d={})",
+                ivf_partition, pq, num_bits, DIM
+            )
+            .as_str(),
+            |b| {
+                b.to_async(&rt).iter(|| async {
+                    let results = dataset
+                        .scan()
+                        .nearest("vector", &query, 10)
+                        .unwrap()
+                        .nprobs(10)
+                        .try_into_stream()
+                        .await
+                        .unwrap()
+                        .try_collect::<Vec<_>>()
+                        .await
+                        .unwrap();
+                    assert!(!results.is_empty());
+                });
+            },
+        );
+    }
 }
 
 #[cfg(target_os = "linux")]
@@ -20,7 +20,7 @@ use futures::TryStreamExt;
 
 use lance::dataset::Dataset;
 use lance::index::{
-    vector::{MetricType, VectorIndexParams},
+    vector::{hnsw::HNSWParams, ivf::IvfBuildParams, MetricType, VectorIndexParams},
     DatasetIndexExt,
 };
 use lance::{Error, Result};
@@ -70,7 +70,7 @@ enum Commands {
         #[arg(short = 't', long = "type", value_enum, value_name = "TYPE")]
         index_type: Option<IndexType>,
 
-        /// Nunber of IVF partitions. Only useful when the index type is 'ivf-pq'.
+        /// Nunber of IVF partitions. Only useful when the index type is 'ivf-pq', 'ivf-sq' or 'ivf-hnsw'.
         #[arg(short = 'p', long, default_value_t = 64, value_name = "NUM")]
         num_partitions: usize,
 
@@ -78,12 +78,28 @@ enum Commands {
         #[arg(short = 's', long, default_value_t = 8, value_name = "NUM")]
         num_sub_vectors: usize,
 
+        /// Number of bits of a Product Quantizer code, either 4 or 8.
+        #[arg(short = 'b', long, default_value_t = 8, value_name = "NUM")]
+        num_bits: u8,
+
         /// Distance metric type. Only support 'l2' and 'cosine'.
         #[arg(short = 'm', long, value_name = "DISTANCE")]
         metric_type: Option<String>,
 
         #[arg(long, default_value_t = false)]
         use_opq: bool,
+
+        /// Number of neighbors of each vertex in HNSW. Only useful when the index type is 'hnsw' or 'ivf-hnsw'.
+        #[arg(long = "hnsw-m", default_value_t = 16, value_name = "NUM")]
+        hnsw_m: usize,
+
+        /// Size of the candidate list to build HNSW. Only useful when the index type is 'hnsw' or 'ivf-hnsw'.
+        #[arg(long, default_value_t = 100, value_name = "NUM")]
+        ef_construction: usize,
+
+        /// Size of the candidate list to search HNSW. Only useful when the index type is 'hnsw' or 'ivf-hnsw'.
+        #[arg(long, default_value_t = 50, value_name = "NUM")]
+        ef_search: usize,
     },
 }
 
@@ -95,6 +111,9 @@ enum IndexAction {
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
 enum IndexType {
     IvfPQ,
+    IvfSQ,
+    Hnsw,
+    IvfHnsw,
 }
 
 #[tokio::main]
@@ -135,8 +154,12 @@ async fn main() -> Result<()> {
             index_type,
             num_partitions,
             num_sub_vectors,
+            num_bits,
             metric_type,
             use_opq,
+            hnsw_m,
+            ef_construction,
+            ef_search,
         } => {
             let dataset = Dataset::open(uri).await.unwrap();
             match action {
@@ -148,8 +171,10 @@ async fn main() -> Result<()> {
                         index_type,
                         num_partitions,
                         num_sub_vectors,
+                        *num_bits,
                         metric_type,
                         *use_opq,
+                        HNSWParams::new(*hnsw_m, *ef_construction, *ef_search),
                     )
                     .await
                 }
@@ -166,13 +191,15 @@ async fn create_index(
     index_type: &Option<IndexType>,
     num_partitions: &usize,
     num_sub_vectors: &usize,
+    num_bits: u8,
     metric_type: &Option<String>,
     use_opq: bool,
+    hnsw_params: HNSWParams,
 ) -> Result<()> {
     let col = column.as_ref().ok_or_else(|| Error::Index {
         message: "Must specify column".to_string(),
     })?;
-    let _ = index_type.ok_or_else(|| Error::Index {
+    let index_type = index_type.ok_or_else(|| Error::Index {
         message: "Must specify index type".to_string(),
     })?;
     let mt = match metric_type.as_ref().unwrap_or(&"l2".to_string()).as_str() {
@@ -196,12 +223,29 @@ async fn create_index(
             });
         }
     };
+    let params = match index_type {
+        IndexType::IvfPQ => VectorIndexParams::ivf_pq(
+            *num_partitions,
+            num_bits,
+            *num_sub_vectors,
+            use_opq,
+            mt,
+            100,
+        ),
+        IndexType::IvfSQ => VectorIndexParams::ivf_sq(*num_partitions, mt, 100),
+        IndexType::Hnsw => VectorIndexParams::with_hnsw_params(mt, hnsw_params),
+        IndexType::IvfHnsw => VectorIndexParams::with_ivf_hnsw_params(
+            mt,
+            IvfBuildParams::new(*num_partitions),
+            hnsw_params,
+        ),
+    };
     dataset
         .create_index(
             &[col],
             lance::index::IndexType::Vector,
             name.clone(),
-            &VectorIndexParams::ivf_pq(*num_partitions, 8, *num_sub_vectors, use_opq, mt, 100),
+            &params,
             true,
         )
         .await
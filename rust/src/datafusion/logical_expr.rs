@@ -16,7 +16,7 @@
 
 use arrow_schema::DataType;
 use datafusion::logical_expr::expr::ScalarFunction;
-use datafusion::logical_expr::{BuiltinScalarFunction, Operator};
+use datafusion::logical_expr::{Between, BuiltinScalarFunction, Operator};
 use datafusion::scalar::ScalarValue;
 use datafusion::{logical_expr::BinaryExpr, prelude::*};
 
@@ -120,6 +120,27 @@ pub fn resolve_expr(expr: &Expr, schema: &Schema) -> Result<Expr> {
                 _ => Ok(expr.clone()),
             }
         }
+        Expr::Between(Between {
+            expr: value,
+            negated,
+            low,
+            high,
+        }) => {
+            let Expr::Column(c) = value.as_ref() else {
+                return Ok(expr.clone());
+            };
+            let Some(field) = schema.field(&c.flat_name()) else {
+                return Err(Error::IO {
+                    message: format!("Column {} does not exist in the dataset.", c.flat_name()),
+                });
+            };
+            Ok(Expr::Between(Between::new(
+                value.clone(),
+                *negated,
+                Box::new(coerce_expr(low, &field.data_type())?),
+                Box::new(coerce_expr(high, &field.data_type())?),
+            )))
+        }
         _ => {
             // Passthrough
             Ok(expr.clone())
@@ -23,35 +23,49 @@ use std::time::SystemTime;
 use arrow_array::{
     cast::as_struct_array, RecordBatch, RecordBatchReader, StructArray, UInt64Array,
 };
-use arrow_schema::Schema as ArrowSchema;
+use arrow_schema::{DataType, Schema as ArrowSchema};
 use arrow_select::{concat::concat_batches, take::take};
 use chrono::prelude::*;
-use futures::stream::{self, StreamExt, TryStreamExt};
+use futures::stream::{self, Stream, StreamExt, TryStreamExt};
 use log::warn;
 use object_store::path::Path;
 use uuid::Uuid;
 
+pub mod cleanup;
 mod feature_flags;
 pub mod fragment;
 mod hash_joiner;
+mod merge_insert;
+pub mod optimize;
 pub mod scanner;
+mod schema_evolution;
+pub mod tags;
+pub(crate) mod transaction;
 pub mod updater;
 mod write;
 
+use self::cleanup::{CleanupParams, RemovalStats};
 use self::feature_flags::{apply_feature_flags, can_read_dataset, can_write_dataset};
 use self::fragment::FileFragment;
+use self::optimize::{CompactionMetrics, CompactionOptions};
 use self::scanner::Scanner;
+use self::tags::Tags;
+use self::transaction::{commit_transaction, Operation};
 use crate::datatypes::Schema;
 use crate::error::box_error;
 use crate::format::{pb, Fragment, Index, Manifest};
+use crate::io::commit::write_manifest_file_to_path;
+use crate::io::exec::Planner;
 use crate::io::object_store::ObjectStoreParams;
 use crate::io::{
     object_reader::{read_message, read_struct},
-    read_manifest, read_metadata_offset, write_manifest, FileWriter, ObjectStore,
+    read_manifest, read_metadata_offset, FileWriter, ObjectStore,
 };
 use crate::session::Session;
 use crate::{Error, Result};
-use hash_joiner::HashJoiner;
+pub use hash_joiner::MergeParams;
+use hash_joiner::{build_join_side, JoinSide};
+pub use merge_insert::{MergeInsertBuilder, MergeInsertStats};
 pub use scanner::ROW_ID;
 pub use write::*;
 
@@ -106,6 +120,86 @@ async fn new_file_writer(
     FileWriter::try_new(object_store, &full_path, schema.clone()).await
 }
 
+/// Write batches to new data files under `<DATA_DIR>`, and return the fragments.
+///
+/// The fragments are numbered from zero. They must be renumbered when appended to a dataset.
+pub(crate) async fn write_fragments<E>(
+    object_store: &ObjectStore,
+    base: &Path,
+    schema: &Schema,
+    batches: impl IntoIterator<Item = std::result::Result<RecordBatch, E>>,
+    params: &WriteParams,
+) -> Result<Vec<Fragment>>
+where
+    Error: From<E>,
+{
+    let batches = stream::iter(batches).map(|batch| batch.map_err(Error::from));
+    write_fragment_stream(object_store, base, schema, batches, params).await
+}
+
+/// Write a stream of batches to new data files under `<DATA_DIR>`, see [write_fragments].
+///
+/// Only the batches of the current row group are buffered in memory.
+pub(crate) async fn write_fragment_stream(
+    object_store: &ObjectStore,
+    base: &Path,
+    schema: &Schema,
+    batches: impl Stream<Item = Result<RecordBatch>>,
+    params: &WriteParams,
+) -> Result<Vec<Fragment>> {
+    let mut fragment_id = 0;
+    let mut fragments: Vec<Fragment> = vec![];
+
+    let mut batches = Box::pin(batches);
+    let mut writer = None;
+    let mut buffer: Vec<RecordBatch> = Vec::new();
+    let mut num_rows: usize = 0;
+    while let Some(batch_result) = batches.next().await {
+        let batch: RecordBatch = batch_result?;
+        buffer.push(batch.clone());
+        num_rows += batch.num_rows();
+        if num_rows >= params.max_rows_per_group {
+            // TODO: the max rows per group boundary is not accurately calculated yet.
+            if writer.is_none() {
+                writer = {
+                    let file_path = format!("{}.lance", Uuid::new_v4());
+                    let fragment = Fragment::with_file(fragment_id, &file_path, schema);
+                    fragments.push(fragment);
+                    fragment_id += 1;
+                    Some(new_file_writer(object_store, base, &file_path, schema).await?)
+                }
+            };
+
+            writer.as_mut().unwrap().write(&buffer).await?;
+            buffer = Vec::new();
+            num_rows = 0;
+        }
+        if let Some(w) = writer.as_mut() {
+            if w.len() >= params.max_rows_per_file {
+                w.finish().await?;
+                writer = None;
+            }
+        }
+    }
+    if num_rows > 0 {
+        if writer.is_none() {
+            writer = {
+                let file_path = format!("{}.lance", Uuid::new_v4());
+                let fragment = Fragment::with_file(fragment_id, &file_path, schema);
+                fragments.push(fragment);
+                Some(new_file_writer(object_store, base, &file_path, schema).await?)
+            }
+        };
+        writer.as_mut().unwrap().write(&buffer).await?;
+    }
+    if let Some(w) = writer.as_mut() {
+        // Drop the last writer.
+        w.finish().await?;
+        drop(writer);
+    };
+    Ok(fragments)
+}
+
 /// Get the manifest file path for a version.
 fn manifest_path(base: &Path, version: u64) -> Path {
     base.child(VERSIONS_DIR)
@@ -186,10 +280,31 @@ impl Dataset {
         } else {
             Arc::new(Session::new(params.index_cache_size))
         };
-        Self::checkout_manifest(
-            Arc::new(object_store),
-            base_path,
+        let object_store = Arc::new(object_store);
+        let dataset = Self::checkout_manifest(
+            object_store.clone(),
+            base_path.clone(),
             &latest_manifest_path,
+            session.clone(),
+        )
+        .await?;
+
+        // `_latest.manifest` is written after the version manifest is committed, so it can lag
+        // behind a concurrent commit. Catch up with the newest committed version.
+        let mut version = dataset.manifest.version;
+        while object_store
+            .exists(&manifest_path(&base_path, version + 1))
+            .await?
+        {
+            version += 1;
+        }
+        if version == dataset.manifest.version {
+            return Ok(dataset);
+        }
+        Self::checkout_manifest(
+            object_store,
+            base_path.clone(),
+            &manifest_path(&base_path, version),
             session,
         )
         .await
@@ -207,7 +322,10 @@ impl Dataset {
         version: u64,
         params: &ReadParams,
     ) -> Result<Self> {
-        let (mut object_store, base_path) = ObjectStore::from_uri(uri).await?;
+        let (mut object_store, base_path) = match params.store_options.clone() {
+            Some(store_options) => ObjectStore::from_uri_and_params(uri, store_options).await?,
+            None => ObjectStore::from_uri(uri).await?,
+        };
         if let Some(block_size) = params.block_size {
             object_store.set_block_size(block_size);
         };
@@ -234,6 +352,42 @@ impl Dataset {
         .await
     }
 
+    /// Check out the version of this dataset a tag points to.
+    pub async fn checkout_tag(&self, tag: &str) -> Result<Self> {
+        let version = self.tags().get_version(tag).await?;
+        self.checkout_version(version).await
+    }
+
+    /// Tags of the dataset, i.e., to pin a version by name.
+    pub fn tags(&self) -> Tags<'_> {
+        Tags::new(self)
+    }
+
+    /// Restore an old version of the dataset.
+    ///
+    /// The fragments, schema and indices of `version` are committed as a new version,
+    /// so the versions in between are preserved in the history.
+    pub async fn restore(&mut self, version: u64) -> Result<()> {
+        let restored = self.checkout_version(version).await?;
+        let indices = restored.load_indices().await?;
+        let operation = Operation::Restore {
+            fragments: restored.manifest.fragments.as_ref().clone(),
+            schema: restored.manifest.schema.clone(),
+            indices,
+        };
+        let manifest = commit_transaction(
+            &self.object_store,
+            &self.base,
+            Some(self.manifest.as_ref()),
+            &operation,
+        )
+        .await?;
+
+        self.manifest = Arc::new(manifest);
+
+        Ok(())
+    }
+
     async fn checkout_manifest(
         object_store: Arc<ObjectStore>,
         base_path: Path,
@@ -368,105 +522,21 @@ impl Dataset {
             }
         }
 
-        let mut fragment_id = if matches!(params.mode, WriteMode::Append) {
-            dataset.as_ref().map_or(0, |d| {
-                d.manifest
-                    .fragments
-                    .iter()
-                    .map(|f| f.id)
-                    .max()
-                    .map(|id| id + 1)
-                    .unwrap_or(0)
-            })
-        } else {
-            // Create or Overwrite.
-            // Overwrite resets the fragment ID to zero.
-            0
-        };
-
-        let mut fragments: Vec<Fragment> = if matches!(params.mode, WriteMode::Append) {
-            dataset
-                .as_ref()
-                .map_or(vec![], |d| d.manifest.fragments.as_ref().clone())
-        } else {
-            // Create or Overwrite create new fragments.
-            vec![]
-        };
-
-        let mut writer = None;
-        let mut batches: Vec<RecordBatch> = Vec::new();
-        let mut num_rows: usize = 0;
-        for batch_result in peekable {
-            let batch: RecordBatch = batch_result?;
-            batches.push(batch.clone());
-            num_rows += batch.num_rows();
-            if num_rows >= params.max_rows_per_group {
-                // TODO: the max rows per group boundary is not accurately calculated yet.
-                if writer.is_none() {
-                    writer = {
-                        let file_path = format!("{}.lance", Uuid::new_v4());
-                        let fragment = Fragment::with_file(fragment_id, &file_path, &schema);
-                        fragments.push(fragment);
-                        fragment_id += 1;
-                        Some(new_file_writer(&object_store, &base, &file_path, &schema).await?)
-                    }
-                };
-
-                writer.as_mut().unwrap().write(&batches).await?;
-                batches = Vec::new();
-                num_rows = 0;
-            }
-            if let Some(w) = writer.as_mut() {
-                if w.len() >= params.max_rows_per_file {
-                    w.finish().await?;
-                    writer = None;
-                }
-            }
-        }
-        if num_rows > 0 {
-            if writer.is_none() {
-                writer = {
-                    let file_path = format!("{}.lance", Uuid::new_v4());
-                    let fragment = Fragment::with_file(fragment_id, &file_path, &schema);
-                    fragments.push(fragment);
-                    Some(new_file_writer(&object_store, &base, &file_path, &schema).await?)
-                }
-            };
-            writer.as_mut().unwrap().write(&batches).await?;
-        }
-        if let Some(w) = writer.as_mut() {
-            // Drop the last writer.
-            w.finish().await?;
-            drop(writer);
-        };
+        let fragments = write_fragments(&object_store, &base, &schema, peekable, &params).await?;
 
-        let mut manifest = Manifest::new(&schema, Arc::new(fragments));
-        manifest.version = match dataset.as_ref() {
-            Some(d) => d
-                .latest_manifest()
-                .await
-                .map(|m| m.version + 1)
-                .unwrap_or(1),
-            None => 1,
-        };
-        // Inherit the index if we're just appending rows
-        let indices = match (&dataset, &params.mode) {
-            (Some(d), WriteMode::Append) => Some(d.load_indices().await?),
-            _ => None,
+        let operation = match params.mode {
+            WriteMode::Append => Operation::Append { fragments },
+            // Create or Overwrite.
+            _ => Operation::Overwrite { fragments, schema },
         };
-        write_manifest_file(
-            &object_store,
-            &base,
-            &mut manifest,
-            indices,
-            Default::default(),
-        )
-        .await?;
+        let current_manifest = dataset.as_ref().map(|d| d.manifest.as_ref());
+        let manifest =
+            commit_transaction(&object_store, &base, current_manifest, &operation).await?;
 
         Ok(Self {
             object_store: Arc::new(object_store),
             base,
-            manifest: Arc::new(manifest.clone()),
+            manifest: Arc::new(manifest),
             session: Arc::new(Session::default()),
         })
     }
@@ -494,42 +564,58 @@ impl Dataset {
         fragments: &[Fragment],
         mode: WriteMode,
     ) -> Result<Self> {
-        let (object_store, base) = ObjectStore::from_uri(base_uri).await?;
-        let latest_manifest = latest_manifest_path(&base);
-        let mut indices = vec![];
-        let mut version = 1;
-        let schema = if object_store.exists(&latest_manifest).await? {
-            let dataset = Self::open(base_uri).await?;
-            version = dataset.version().version + 1;
-
-            if matches!(mode, WriteMode::Append) {
-                // Append mode: inherit indices from previous version.
-                indices = dataset.load_indices().await?;
-            }
+        Self::commit_with_params(base_uri, schema, fragments, mode, &Default::default()).await
+    }
 
+    /// Create a new version of [`Dataset`] from a collection of fragments, with the object
+    /// store params, i.e., the [CommitHandler](crate::io::commit::CommitHandler) to use.
+    pub async fn commit_with_params(
+        base_uri: &str,
+        schema: &Schema,
+        fragments: &[Fragment],
+        mode: WriteMode,
+        store_params: &ObjectStoreParams,
+    ) -> Result<Self> {
+        let (object_store, base) =
+            ObjectStore::from_uri_and_params(base_uri, store_params.clone()).await?;
+        let latest_manifest = latest_manifest_path(&base);
+        let dataset = if object_store.exists(&latest_manifest).await? {
+            let params = ReadParams {
+                store_options: Some(store_params.clone()),
+                ..Default::default()
+            };
+            Some(Self::open_with_params(base_uri, &params).await?)
+        } else {
+            None
+        };
+        let schema = if let Some(dataset) = dataset.as_ref() {
             let dataset_schema = dataset.schema();
             let added_on_schema = schema.exclude(dataset_schema)?;
+            if matches!(mode, WriteMode::Append) && !added_on_schema.fields.is_empty() {
+                return Err(Error::invalid_input(
+                    "Appended fragments must have the same schema as the dataset",
+                ));
+            }
             dataset_schema.merge(&added_on_schema)?
         } else {
             schema.clone()
         };
 
-        let mut manifest = Manifest::new(&schema, Arc::new(fragments.to_vec()));
-        manifest.version = version;
-
-        // Preserve indices.
-        write_manifest_file(
-            &object_store,
-            &base,
-            &mut manifest,
-            Some(indices),
-            Default::default(),
-        )
-        .await?;
+        let fragments = fragments.to_vec();
+        let operation = if matches!(mode, WriteMode::Append) && dataset.is_some() {
+            // Append mode: add the fragments, and inherit indices from previous version.
+            // Concurrent appends are rebased on each other, instead of conflicting.
+            Operation::Append { fragments }
+        } else {
+            Operation::Overwrite { fragments, schema }
+        };
+        let current_manifest = dataset.as_ref().map(|d| d.manifest.as_ref());
+        let manifest =
+            commit_transaction(&object_store, &base, current_manifest, &operation).await?;
         Ok(Self {
             object_store: Arc::new(object_store),
             base,
-            manifest: Arc::new(manifest.clone()),
+            manifest: Arc::new(manifest),
             session: Arc::new(Session::default()),
         })
     }
@@ -539,8 +625,9 @@ impl Dataset {
     /// Parameters:
     ///
     /// - `stream`: the stream of [`RecordBatch`] to merge.
-    /// - `left_on`: the column name to join on the left side (self).
-    /// - `right_on`: the column name to join on the right side (stream).
+    /// - `left_on`: the column names to join on the left side (self).
+    /// - `right_on`: the column names to join on the right side (stream), in the
+    ///   same order as `left_on`.
     ///
     /// Returns: a new version of dataset.
     ///
@@ -548,25 +635,44 @@ impl Dataset {
     async fn merge_impl(
         &mut self,
         stream: Box<dyn RecordBatchReader + Send>,
-        left_on: &str,
-        right_on: &str,
+        left_on: &[&str],
+        right_on: &[&str],
+        params: &MergeParams,
     ) -> Result<()> {
         // Sanity check.
-        if self.schema().field(left_on).is_none() {
+        if left_on.is_empty() || left_on.len() != right_on.len() {
             return Err(Error::invalid_input(format!(
-                "Column {} does not exist in the left side dataset",
-                left_on
+                "The join keys must be the same number of columns on both sides, got {:?} and {:?}",
+                left_on, right_on
             )));
-        };
+        }
         let right_schema = stream.schema();
-        if right_schema.field_with_name(right_on).is_err() {
-            return Err(Error::invalid_input(format!(
-                "Column {} does not exist in the right side dataset",
-                right_on
-            )));
-        };
+        for (left_key, right_key) in left_on.iter().zip(right_on) {
+            let Some(left_field) = self.schema().field(left_key) else {
+                return Err(Error::invalid_input(format!(
+                    "Column {} does not exist in the left side dataset",
+                    left_key
+                )));
+            };
+            let Ok(right_field) = right_schema.field_with_name(right_key) else {
+                return Err(Error::invalid_input(format!(
+                    "Column {} does not exist in the right side dataset",
+                    right_key
+                )));
+            };
+            // Check the key types before reading any data.
+            if &left_field.data_type() != right_field.data_type() {
+                return Err(Error::invalid_input(format!(
+                    "Join key type mismatch: {} is {} on the left side, but {} is {} on the right side",
+                    left_key,
+                    left_field.data_type(),
+                    right_key,
+                    right_field.data_type()
+                )));
+            }
+        }
         for field in right_schema.fields() {
-            if field.name() == right_on {
+            if right_on.contains(&field.name().as_str()) {
                 // right_on is allowed to exist in the dataset, since it may be
                 // the same as left_on.
                 continue;
@@ -580,38 +686,59 @@ impl Dataset {
         }
 
         // Hash join
-        let joiner = Arc::new(HashJoiner::try_new(stream, right_on).await?);
+        let join_side = build_join_side(stream, right_on, params).await?;
         // Final schema is union of current schema, plus the RHS schema without
-        // the right_on key.
-        let new_schema: Schema = self.schema().merge(joiner.out_schema().as_ref())?;
-
-        // Write new data file to each fragment. Parallelism is done over columns,
-        // so no parallelism done at this level.
-        let updated_fragments: Vec<Fragment> = stream::iter(self.get_fragments())
-            .then(|f| {
-                let joiner = joiner.clone();
-                async move { f.merge(left_on, &joiner).await.map(|f| f.metadata) }
-            })
-            .try_collect::<Vec<_>>()
-            .await?;
-
-        // Inherit the index, since we are just adding columns.
-        let indices = self.load_indices().await?;
-
-        let mut manifest = Manifest::new(self.schema(), Arc::new(updated_fragments));
-        manifest.version = self
-            .latest_manifest()
-            .await
-            .map(|m| m.version + 1)
-            .unwrap_or(1);
-        manifest.schema = new_schema;
+        // the right_on keys.
+        let new_schema: Schema = self.schema().merge(join_side.out_schema().as_ref())?;
+
+        let updated_fragments: Vec<Fragment> = match join_side {
+            JoinSide::InMemory(joiner) => {
+                // Write new data file to each fragment. Parallelism is done over columns,
+                // so no parallelism done at this level.
+                let joiner = &joiner;
+                stream::iter(self.get_fragments())
+                    .then(|f| async move { f.merge(left_on, joiner).await.map(|f| f.metadata) })
+                    .try_collect::<Vec<_>>()
+                    .await?
+            }
+            JoinSide::Spilled(joiner) => {
+                let joiner = &joiner;
+                // The keys of the left side are read once, and spilled by partition.
+                stream::iter(self.get_fragments())
+                    .map(|f| async move { f.partition_join_keys(left_on, joiner).await })
+                    .buffer_unordered(num_cpus::get())
+                    .try_collect::<Vec<_>>()
+                    .await?;
+                // Only one partition of the right side is in memory at a time.
+                for partition in 0..joiner.num_partitions() {
+                    let Some(partition_joiner) = joiner.load_partition(partition).await? else {
+                        continue;
+                    };
+                    let partition_joiner = &partition_joiner;
+                    stream::iter(self.fragments().iter())
+                        .map(|f| joiner.join_partition(f.id, partition, partition_joiner))
+                        .buffer_unordered(num_cpus::get())
+                        .try_collect::<Vec<_>>()
+                        .await?;
+                }
+                stream::iter(self.get_fragments())
+                    .then(|f| async move {
+                        f.merge_spilled(left_on, joiner).await.map(|f| f.metadata)
+                    })
+                    .try_collect::<Vec<_>>()
+                    .await?
+            }
+        };
 
-        write_manifest_file(
+        let operation = Operation::Merge {
+            fragments: updated_fragments,
+            schema: new_schema,
+        };
+        let manifest = commit_transaction(
             &self.object_store,
             &self.base,
-            &mut manifest,
-            Some(indices),
-            Default::default(),
+            Some(self.manifest.as_ref()),
+            &operation,
         )
         .await?;
 
@@ -625,10 +752,61 @@ impl Dataset {
         stream: impl RecordBatchReader + Send + 'static,
         left_on: &str,
         right_on: &str,
+    ) -> Result<()> {
+        self.merge_with_params(stream, left_on, right_on, &MergeParams::default())
+            .await
+    }
+
+    /// Merge with [MergeParams], i.e., to limit the memory used to hold `stream`.
+    ///
+    /// If `stream` does not fit in [MergeParams::memory_limit], it is partitioned by the
+    /// hash of `right_on` and spilled to disk. The keys of the fragments are read once, and
+    /// spilled by the same partitions. Then each partition is joined with the keys of all
+    /// the fragments in turn, and the matches are spilled again, until the new columns of
+    /// each fragment are written.
+    pub async fn merge_with_params(
+        &mut self,
+        stream: impl RecordBatchReader + Send + 'static,
+        left_on: &str,
+        right_on: &str,
+        params: &MergeParams,
+    ) -> Result<()> {
+        let stream = Box::new(stream);
+        self.merge_impl(stream, &[left_on], &[right_on], params)
+            .await
+    }
+
+    /// Merge on composite keys, made of several columns.
+    ///
+    /// `right_on` are the key columns of `stream`, in the same order as `left_on`.
+    pub async fn merge_on_columns(
+        &mut self,
+        stream: impl RecordBatchReader + Send + 'static,
+        left_on: &[&str],
+        right_on: &[&str],
+        params: &MergeParams,
     ) -> Result<()> {
         let stream = Box::new(stream);
-        self.merge_impl(stream, left_on, right_on).await
+        self.merge_impl(stream, left_on, right_on, params).await
+    }
+
+    /// Merge the source rows into the dataset, keyed on the `on` columns.
+    ///
+    /// Returns a [MergeInsertBuilder] to choose what happens to the matched and not
+    /// matched rows, i.e., `when_matched_update_all().when_not_matched_insert_all()`
+    /// for an upsert.
+    pub fn merge_insert(
+        &mut self,
+        on: &[&str],
+        source: impl RecordBatchReader + Send + 'static,
+    ) -> MergeInsertBuilder<'_> {
+        MergeInsertBuilder::new(
+            self,
+            on.iter().map(|c| c.to_string()).collect(),
+            Box::new(source),
+        )
     }
+
     /// Create a Scanner to scan the dataset.
     pub fn scan(&self) -> Scanner {
         Scanner::new(Arc::new(self.clone()))
@@ -756,37 +934,142 @@ impl Dataset {
 
     /// Delete rows based on a predicate.
     pub async fn delete(&mut self, predicate: &str) -> Result<()> {
-        let mut updated_fragments = stream::iter(self.get_fragments())
-            .map(|f| async move { f.delete(predicate).await.map(|f| f.map(|f| f.metadata)) })
+        let results = stream::iter(self.get_fragments())
+            .map(|f| async move {
+                let original = f.metadata.clone();
+                f.delete(predicate)
+                    .await
+                    .map(|f| (original, f.map(|f| f.metadata)))
+            })
             .buffer_unordered(num_cpus::get())
-            // Drop the fragments that were deleted.
-            .try_filter_map(|f| futures::future::ready(Ok(f)))
             .try_collect::<Vec<_>>()
             .await?;
 
-        // Maintain the order of fragment IDs.
-        updated_fragments.sort_by_key(|f| f.id);
+        let mut updated_fragments = vec![];
+        let mut deleted_fragment_ids = vec![];
+        for (original, fragment) in results {
+            match fragment {
+                // All rows of the fragment were deleted.
+                None => deleted_fragment_ids.push(original.id),
+                Some(fragment) if fragment != original => updated_fragments.push(fragment),
+                // Nothing was deleted from this fragment.
+                Some(_) => {}
+            }
+        }
 
-        // Inherit the index, unless we deleted all the fragments.
-        let indices = if updated_fragments.is_empty() {
-            None
-        } else {
-            Some(self.load_indices().await?)
+        let operation = Operation::Delete {
+            updated_fragments,
+            deleted_fragment_ids,
         };
+        let manifest = commit_transaction(
+            &self.object_store,
+            &self.base,
+            Some(self.manifest.as_ref()),
+            &operation,
+        )
+        .await?;
 
-        let mut manifest = Manifest::new(self.schema(), Arc::new(updated_fragments));
-        manifest.version = self
-            .latest_manifest()
-            .await
-            .map(|m| m.version + 1)
-            .unwrap_or(1);
+        self.manifest = Arc::new(manifest);
 
-        write_manifest_file(
+        Ok(())
+    }
+
+    /// Update the rows matching the predicate.
+    ///
+    /// `updates` maps the column names to SQL expressions of their new values, which are
+    /// evaluated against the original rows, i.e., `{"price": "price * 1.1"}`.
+    ///
+    /// The original rows are marked as deleted, and the updated rows are written to new
+    /// fragments, all committed as one new version.
+    pub async fn update(
+        &mut self,
+        predicate: &str,
+        updates: HashMap<String, String>,
+    ) -> Result<()> {
+        if updates.is_empty() {
+            return Err(Error::invalid_input("No column to update"));
+        }
+        let arrow_schema = Arc::new(ArrowSchema::from(self.schema()));
+        let planner = Planner::new(arrow_schema);
+        let updates = updates
+            .iter()
+            .map(|(column, expr)| {
+                if !self.schema().fields.iter().any(|f| &f.name == column) {
+                    return Err(Error::invalid_input(format!(
+                        "Column {column} does not exist in the dataset, \
+                        or is not a top-level column"
+                    )));
+                }
+                let expr = planner.parse_expr(expr)?;
+                Ok((column.clone(), planner.create_physical_expr(&expr)?))
+            })
+            .collect::<Result<HashMap<_, _>>>()?;
+
+        // The updated rows of all the fragments are streamed into the new fragments, and
+        // only the offsets of the original rows are kept to delete them afterwards.
+        let updates = &updates;
+        let fragments = self.get_fragments();
+        let mut row_ids: HashMap<u64, Vec<u32>> = HashMap::new();
+        let batches = stream::iter(fragments.iter())
+            .then(|f| async move {
+                let id = f.metadata.id;
+                let batches = f.updated_batches(predicate, updates).await?;
+                Ok::<_, Error>(batches.map_ok(move |(ids, batch)| (id, ids, batch)))
+            })
+            .try_flatten()
+            .map_ok(|(id, ids, batch)| {
+                if !ids.is_empty() {
+                    row_ids.entry(id).or_default().extend(ids);
+                }
+                batch
+            });
+        let new_fragments = write_fragment_stream(
             &self.object_store,
             &self.base,
-            &mut manifest,
-            indices,
-            Default::default(),
+            self.schema(),
+            batches,
+            &WriteParams::default(),
+        )
+        .await?;
+        if row_ids.is_empty() {
+            // Nothing matched the predicate.
+            return Ok(());
+        }
+
+        let results = stream::iter(fragments)
+            .filter_map(|f| {
+                let ids = row_ids.remove(&f.metadata.id);
+                async move { ids.map(|ids| (f, ids)) }
+            })
+            .map(|(f, ids)| async move {
+                let original = f.metadata.clone();
+                f.delete_rows(ids)
+                    .await
+                    .map(|f| (original, f.map(|f| f.metadata)))
+            })
+            .buffer_unordered(num_cpus::get())
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        let mut updated_fragments = vec![];
+        let mut deleted_fragment_ids = vec![];
+        for (original, fragment) in results {
+            match fragment {
+                None => deleted_fragment_ids.push(original.id),
+                Some(fragment) => updated_fragments.push(fragment),
+            }
+        }
+
+        let operation = Operation::Update {
+            updated_fragments,
+            deleted_fragment_ids,
+            new_fragments,
+        };
+        let manifest = commit_transaction(
+            &self.object_store,
+            &self.base,
+            Some(self.manifest.as_ref()),
+            &operation,
         )
         .await?;
 
@@ -795,24 +1078,88 @@ impl Dataset {
         Ok(())
     }
 
-    pub(crate) fn object_store(&self) -> &ObjectStore {
-        &self.object_store
+    /// Add new columns, as pairs of `(name, expression)`, i.e., `("area", "width * height")`.
+    ///
+    /// The SQL expressions are evaluated over the existing columns of each fragment, and
+    /// the results are written into new data files. All the columns are committed as one
+    /// new version.
+    pub async fn add_columns(&mut self, columns: Vec<(&str, &str)>) -> Result<()> {
+        schema_evolution::add_columns(self, columns).await
     }
 
-    fn versions_dir(&self) -> Path {
-        self.base.child(VERSIONS_DIR)
+    /// Remove columns from the dataset.
+    ///
+    /// Only the metadata is changed, the data files are not rewritten. Nested fields are
+    /// dropped by their path, i.e., `"parent.child"`. The indices built on the dropped
+    /// columns are removed.
+    pub async fn drop_columns(&mut self, columns: &[&str]) -> Result<()> {
+        schema_evolution::drop_columns(self, columns).await
     }
 
-    fn manifest_file(&self, version: u64) -> Path {
-        self.versions_dir().child(format!("{version}.manifest"))
+    /// Rename columns, as pairs of `(column, new_name)`.
+    ///
+    /// Nested fields are renamed by their path, i.e., `("parent.child", "new_child")`.
+    /// Only the metadata is changed, the field ids, and thus the data files and indices,
+    /// are kept.
+    pub async fn alter_columns(&mut self, renames: &[(&str, &str)]) -> Result<()> {
+        schema_evolution::alter_columns(self, renames).await
+    }
+
+    /// Change the data type of a top-level column, i.e., to widen `int32` to `int64`.
+    ///
+    /// The column is cast with `arrow_cast`, and written into new data files, fragment
+    /// by fragment. The other columns are not rewritten, and the field id of the column
+    /// is kept. The indices built on the column are removed.
+    pub async fn alter_column_type(&mut self, name: &str, data_type: DataType) -> Result<()> {
+        schema_evolution::alter_column_type(self, name, data_type).await
+    }
+
+    /// Compact the data files of the dataset.
+    ///
+    /// Small adjacent fragments are merged into larger ones, and deleted rows are removed
+    /// from the rewritten files. The result is committed as a new version.
+    ///
+    /// Rewriting changes the row ids, which invalidates the indices covering the rewritten
+    /// fragments. Compaction fails with [Error::InvalidInput] if there are any, unless
+    /// [CompactionOptions::drop_indices] is set, in which case they are removed and need to
+    /// be rebuilt. The other indices are kept.
+    pub async fn compact_files(&mut self, options: CompactionOptions) -> Result<CompactionMetrics> {
+        optimize::compact_files(self, options).await
+    }
+
+    /// Remove the versions created longer than `older_than` ago, and the data, deletion and
+    /// index files no longer referenced by the remaining versions.
+    ///
+    /// The latest version is always kept. If `keep_tagged` is set, the tagged versions
+    /// are kept as well.
+    pub async fn cleanup_old_versions(
+        &self,
+        older_than: chrono::Duration,
+        keep_tagged: bool,
+    ) -> Result<RemovalStats> {
+        let params = CleanupParams {
+            older_than,
+            keep_tagged,
+            ..Default::default()
+        };
+        self.cleanup_old_versions_with_params(&params).await
+    }
+
+    /// Remove old versions with [CleanupParams], i.e., to preview what would be removed
+    /// with [CleanupParams::dry_run].
+    pub async fn cleanup_old_versions_with_params(
+        &self,
+        params: &CleanupParams,
+    ) -> Result<RemovalStats> {
+        cleanup::cleanup_old_versions(self, params).await
     }
 
-    fn latest_manifest_path(&self) -> Path {
-        latest_manifest_path(&self.base)
+    pub(crate) fn object_store(&self) -> &ObjectStore {
+        &self.object_store
     }
 
-    pub(crate) async fn latest_manifest(&self) -> Result<Manifest> {
-        read_manifest(&self.object_store, &self.latest_manifest_path()).await
+    fn versions_dir(&self) -> Path {
+        self.base.child(VERSIONS_DIR)
     }
 
     pub(crate) fn data_dir(&self) -> Path {
@@ -880,20 +1227,7 @@ impl Dataset {
 
     /// Read all indices of this Dataset version.
     pub async fn load_indices(&self) -> Result<Vec<Index>> {
-        if let Some(pos) = self.manifest.index_section.as_ref() {
-            let manifest_file = self.manifest_file(self.version().version);
-
-            let reader = self.object_store.open(&manifest_file).await?;
-            let section: pb::IndexSection = read_message(reader.as_ref(), *pos).await?;
-
-            Ok(section
-                .indices
-                .iter()
-                .map(Index::try_from)
-                .collect::<Result<Vec<_>>>()?)
-        } else {
-            Ok(vec![])
-        }
+        read_indices(&self.object_store, &self.base, &self.manifest).await
     }
 
     pub async fn validate(&self) -> Result<()> {
@@ -945,42 +1279,71 @@ impl Default for ManifestWriteConfig {
     }
 }
 
-/// Finish writing the manifest file, and commit the changes by linking the latest manifest file
-/// to this version.
-pub(crate) async fn write_manifest_file(
+/// Read the indices recorded in the manifest of a dataset version.
+pub(crate) async fn read_indices(
     object_store: &ObjectStore,
     base_path: &Path,
-    manifest: &mut Manifest,
-    indices: Option<Vec<Index>>,
-    config: ManifestWriteConfig,
-) -> Result<()> {
-    if config.auto_set_feature_flags {
-        apply_feature_flags(manifest);
-    }
-    manifest.set_timestamp(config.timestamp);
+    manifest: &Manifest,
+) -> Result<Vec<Index>> {
+    if let Some(pos) = manifest.index_section.as_ref() {
+        let manifest_file = manifest_path(base_path, manifest.version);
 
-    let paths = vec![
-        manifest_path(base_path, manifest.version),
-        latest_manifest_path(base_path),
-    ];
+        let reader = object_store.open(&manifest_file).await?;
+        let section: pb::IndexSection = read_message(reader.as_ref(), *pos).await?;
 
-    for p in paths {
-        write_manifest_file_to_path(object_store, manifest, indices.clone(), &p).await?
+        Ok(section
+            .indices
+            .iter()
+            .map(Index::try_from)
+            .collect::<Result<Vec<_>>>()?)
+    } else {
+        Ok(vec![])
     }
-    Ok(())
 }
 
-async fn write_manifest_file_to_path(
+/// Finish writing the manifest file, and commit the changes by linking the latest manifest file
+/// to this version.
+///
+/// The version manifest is committed by the [CommitHandler](crate::io::commit::CommitHandler)
+/// of the object store. If the version already exists, i.e., another writer has committed it
+/// concurrently, [Error::CommitConflict] is returned and nothing is overwritten.
+///
+/// `_latest.manifest` is only updated if it points to an older version, so a writer that
+/// finishes after a newer concurrent commit does not roll it back. This check-then-write is
+/// not atomic: two writers can still race and leave `_latest.manifest` at the older of their
+/// versions. It is only a hint; readers must not trust it blindly, and
+/// [Dataset::open_with_params] probes forward for newer version manifests, which are the
+/// source of truth.
+pub(crate) async fn write_manifest_file(
     object_store: &ObjectStore,
+    base_path: &Path,
     manifest: &mut Manifest,
     indices: Option<Vec<Index>>,
-    path: &Path,
+    config: ManifestWriteConfig,
 ) -> Result<()> {
-    let mut object_writer = object_store.create(path).await?;
-    let pos = write_manifest(&mut object_writer, manifest, indices).await?;
-    object_writer.write_magics(pos).await?;
-    object_writer.shutdown().await?;
-    Ok(())
+    if config.auto_set_feature_flags {
+        apply_feature_flags(manifest);
+    }
+    manifest.set_timestamp(config.timestamp);
+
+    object_store
+        .commit_handler
+        .commit(
+            manifest,
+            indices.clone(),
+            &manifest_path(base_path, manifest.version),
+            object_store,
+        )
+        .await?;
+
+    let latest_path = latest_manifest_path(base_path);
+    if object_store.exists(&latest_path).await? {
+        let latest = read_manifest(object_store, &latest_path).await?;
+        if latest.version >= manifest.version {
+            return Ok(());
+        }
+    }
+    write_manifest_file_to_path(object_store, manifest, indices, &latest_path).await
 }
 
 #[cfg(test)]
@@ -995,7 +1358,10 @@ mod tests {
     use crate::index::IndexType;
     use crate::index::{vector::VectorIndexParams, DatasetIndexExt};
     use crate::io::deletion::read_deletion_file;
-    use crate::{datatypes::Schema, utils::testing::generate_random_array};
+    use crate::{
+        datatypes::Schema,
+        utils::testing::{generate_random_array, sequence_batches},
+    };
 
     use crate::dataset::WriteMode::Overwrite;
     use arrow_array::{
@@ -1221,6 +1587,7 @@ mod tests {
         );
 
         // Write with custom manifest
+        manifest.version += 1;
         manifest.writer_feature_flags = 5; // Set another flag
         manifest.reader_feature_flags = 5;
         write_manifest_file(
@@ -1715,6 +2082,15 @@ mod tests {
         assert!(dataset.manifest.index_section.is_none());
         assert!(dataset.load_indices().await.unwrap().is_empty());
         dataset.validate().await.unwrap();
+
+        // Restore should bring the index back
+        let mut dataset = dataset;
+        dataset.restore(3).await.unwrap();
+        assert_eq!(dataset.version().version, 5);
+        let indices = dataset.load_indices().await.unwrap();
+        assert_eq!(indices.len(), 1);
+        assert_eq!(indices[0].dataset_version, 2);
+        dataset.validate().await.unwrap();
     }
 
     async fn create_bad_file() -> Result<Dataset> {
@@ -1851,6 +2227,192 @@ mod tests {
         assert_eq!(actual, expected);
     }
 
+    #[tokio::test]
+    async fn test_merge_composite_keys() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("video_id", DataType::Utf8, false),
+            Field::new("frame_idx", DataType::Int32, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from(vec!["a", "a", "b", "b"])),
+                Arc::new(Int32Array::from(vec![0, 1, 0, 1])),
+            ],
+        )
+        .unwrap();
+        let batches = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+        let mut dataset = Dataset::write(batches, test_uri, None).await.unwrap();
+
+        let right_schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("vid", DataType::Utf8, false),
+            Field::new("frame_idx", DataType::Int32, false),
+            Field::new("label", DataType::Utf8, true),
+        ]));
+        let right_batch = RecordBatch::try_new(
+            right_schema.clone(),
+            vec![
+                Arc::new(StringArray::from(vec!["b", "a", "c"])),
+                Arc::new(Int32Array::from(vec![1, 1, 0])),
+                Arc::new(StringArray::from(vec!["b1", "a1", "c0"])),
+            ],
+        )
+        .unwrap();
+
+        // The key types are checked before reading the right side.
+        let batches = RecordBatchIterator::new(vec![Ok(right_batch.clone())], right_schema.clone());
+        let result = dataset
+            .merge_on_columns(
+                batches,
+                &["video_id", "frame_idx"],
+                &["frame_idx", "vid"],
+                &MergeParams::default(),
+            )
+            .await;
+        assert!(matches!(result, Err(Error::InvalidInput { .. })));
+        let batches = RecordBatchIterator::new(vec![Ok(right_batch.clone())], right_schema.clone());
+        let result = dataset
+            .merge_on_columns(
+                batches,
+                &["video_id"],
+                &["vid", "frame_idx"],
+                &MergeParams::default(),
+            )
+            .await;
+        assert!(matches!(result, Err(Error::InvalidInput { .. })));
+        assert_eq!(dataset.version().version, 1);
+
+        let batches = RecordBatchIterator::new(vec![Ok(right_batch)], right_schema.clone());
+        dataset
+            .merge_on_columns(
+                batches,
+                &["video_id", "frame_idx"],
+                &["vid", "frame_idx"],
+                &MergeParams::default(),
+            )
+            .await
+            .unwrap();
+        dataset.validate().await.unwrap();
+
+        let actual_batches = dataset
+            .scan()
+            .try_into_stream()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let actual = concat_batches(&actual_batches[0].schema(), &actual_batches).unwrap();
+        let field_names = actual
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| f.name().clone())
+            .collect::<Vec<_>>();
+        assert_eq!(field_names, vec!["video_id", "frame_idx", "label"]);
+        assert_eq!(
+            actual["label"].as_ref(),
+            &StringArray::from(vec![None, Some("a1"), None, Some("b1")])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_merge_spilled() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let schema = Arc::new(ArrowSchema::new(vec![Field::new(
+            "i",
+            DataType::Int32,
+            false,
+        )]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values(0..300))],
+        )
+        .unwrap();
+        let write_params = WriteParams {
+            max_rows_per_file: 100,
+            ..Default::default()
+        };
+        let batches = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+        let mut dataset = Dataset::write(batches, test_uri, Some(write_params))
+            .await
+            .unwrap();
+        dataset.delete("i % 7 = 0").await.unwrap();
+        assert_eq!(dataset.fragments().len(), 3);
+
+        // Only the even keys are on the right side, in reverse order.
+        let right_schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("i2", DataType::Int32, false),
+            Field::new("y", DataType::Utf8, true),
+        ]));
+        let keys = (0..300).rev().step_by(2).collect::<Vec<_>>();
+        let right_batches = keys
+            .chunks(30)
+            .map(|keys| {
+                RecordBatch::try_new(
+                    right_schema.clone(),
+                    vec![
+                        Arc::new(Int32Array::from(keys.to_vec())),
+                        Arc::new(StringArray::from_iter_values(
+                            keys.iter().map(|k| format!("y_{k}")),
+                        )),
+                    ],
+                )
+            })
+            .collect::<Vec<_>>();
+        let data_size = right_batches
+            .iter()
+            .map(|b| hash_joiner::batch_data_size(b.as_ref().unwrap()).unwrap())
+            .sum::<usize>();
+        let batches = RecordBatchIterator::new(right_batches, right_schema.clone());
+
+        let spill_dir = tempdir().unwrap();
+        let params = MergeParams {
+            memory_limit: data_size / 2,
+            num_partitions: 4,
+            spill_dir: Some(spill_dir.path().to_path_buf()),
+        };
+        dataset
+            .merge_with_params(batches, "i", "i2", &params)
+            .await
+            .unwrap();
+        dataset.validate().await.unwrap();
+        // The spilled files are removed once the merge is done.
+        assert_eq!(std::fs::read_dir(spill_dir.path()).unwrap().count(), 0);
+
+        let actual_batches = dataset
+            .scan()
+            .try_into_stream()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let actual = concat_batches(&actual_batches[0].schema(), &actual_batches).unwrap();
+        let remaining = (0..300).filter(|i| i % 7 != 0).collect::<Vec<_>>();
+        let expected = RecordBatch::try_new(
+            Arc::new(ArrowSchema::new(vec![
+                Field::new("i", DataType::Int32, false),
+                Field::new("y", DataType::Utf8, true),
+            ])),
+            vec![
+                Arc::new(Int32Array::from(remaining.clone())),
+                Arc::new(StringArray::from_iter(
+                    remaining
+                        .iter()
+                        .map(|i| (i % 2 == 0).then(|| format!("y_{i}"))),
+                )),
+            ],
+        )
+        .unwrap();
+        assert_eq!(actual, expected);
+    }
+
     #[tokio::test]
     async fn test_delete() {
         fn sequence_data(range: Range<u32>) -> RecordBatch {
@@ -1933,4 +2495,332 @@ mod tests {
         let fragments = dataset.get_fragments();
         assert_eq!(fragments.len(), 0);
     }
+
+    #[tokio::test]
+    async fn test_update() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("i", DataType::UInt32, false),
+            Field::new("s", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(UInt32Array::from_iter_values(0..100)),
+                Arc::new(StringArray::from_iter_values(
+                    (0..100).map(|v| format!("s-{v}")),
+                )),
+            ],
+        )
+        .unwrap();
+        let write_params = WriteParams {
+            max_rows_per_file: 50,
+            max_rows_per_group: 10,
+            ..Default::default()
+        };
+        let batches = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+        let mut dataset = Dataset::write(batches, test_uri, Some(write_params))
+            .await
+            .unwrap();
+
+        let count = |dataset: Dataset, filter: &'static str| async move {
+            let batches = dataset
+                .scan()
+                .filter(filter)
+                .unwrap()
+                .try_into_stream()
+                .await
+                .unwrap()
+                .try_collect::<Vec<_>>()
+                .await
+                .unwrap();
+            batches.iter().map(|b| b.num_rows()).sum::<usize>()
+        };
+
+        // Update rows in both fragments, with new values computed from the old ones.
+        dataset
+            .update(
+                "i >= 45 AND i < 55",
+                HashMap::from([
+                    ("i".to_string(), "i + 1000".to_string()),
+                    ("s".to_string(), "'updated'".to_string()),
+                ]),
+            )
+            .await
+            .unwrap();
+        dataset.validate().await.unwrap();
+        assert_eq!(dataset.version().version, 2);
+        assert_eq!(dataset.count_rows().await.unwrap(), 100);
+        assert_eq!(dataset.get_fragments().len(), 3);
+        assert_eq!(count(dataset.clone(), "s = 'updated'").await, 10);
+        assert_eq!(count(dataset.clone(), "i >= 1045 AND i < 1055").await, 10);
+        assert_eq!(count(dataset.clone(), "i >= 45 AND i < 55").await, 0);
+
+        // Nothing matches, no new version.
+        dataset
+            .update(
+                "i > 2000",
+                HashMap::from([("s".to_string(), "'none'".to_string())]),
+            )
+            .await
+            .unwrap();
+        assert_eq!(dataset.version().version, 2);
+
+        // Updating a column which does not exist.
+        let err = dataset
+            .update(
+                "i < 10",
+                HashMap::from([("x".to_string(), "1".to_string())]),
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidInput { .. }));
+
+        // The new values do not fit into the column type.
+        let err = dataset
+            .update(
+                "i < 10",
+                HashMap::from([("i".to_string(), "-1".to_string())]),
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidInput { .. }));
+        assert_eq!(dataset.version().version, 2);
+        assert_eq!(count(dataset.clone(), "i < 10").await, 10);
+    }
+
+    #[tokio::test]
+    async fn test_restore() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let mut dataset = Dataset::write(sequence_batches(0..100), test_uri, None)
+            .await
+            .unwrap();
+        dataset.delete("i < 50").await.unwrap();
+        assert_eq!(dataset.count_rows().await.unwrap(), 50);
+
+        dataset.restore(1).await.unwrap();
+        assert_eq!(dataset.version().version, 3);
+        assert_eq!(dataset.count_rows().await.unwrap(), 100);
+        assert_eq!(
+            dataset.fragments(),
+            dataset.checkout_version(1).await.unwrap().fragments()
+        );
+
+        // The history is preserved.
+        let dataset = Dataset::open(test_uri).await.unwrap();
+        assert_eq!(dataset.version().version, 3);
+        assert_eq!(dataset.versions().await.unwrap().len(), 3);
+        let deleted = dataset.checkout_version(2).await.unwrap();
+        assert_eq!(deleted.count_rows().await.unwrap(), 50);
+
+        let mut dataset = dataset;
+        assert!(dataset.restore(10).await.is_err());
+        assert_eq!(dataset.version().version, 3);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_appends() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        Dataset::write(sequence_batches(0..100), test_uri, None)
+            .await
+            .unwrap();
+
+        let appends = (1..5).map(|i| {
+            let write_params = WriteParams {
+                mode: WriteMode::Append,
+                ..Default::default()
+            };
+            Dataset::write(
+                sequence_batches(i * 100..(i + 1) * 100),
+                test_uri,
+                Some(write_params),
+            )
+        });
+        let results = futures::future::join_all(appends).await;
+        let mut versions = results
+            .into_iter()
+            .map(|r| r.unwrap().version().version)
+            .collect::<Vec<_>>();
+        versions.sort();
+        assert_eq!(versions, vec![2, 3, 4, 5]);
+
+        // None of the appends were lost.
+        let dataset = Dataset::open(test_uri).await.unwrap();
+        assert_eq!(dataset.version().version, 5);
+        assert_eq!(dataset.count_rows().await.unwrap(), 500);
+        assert_eq!(dataset.get_fragments().len(), 5);
+        dataset.validate().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_commit_concurrent_appended_fragments() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let dataset = Dataset::write(sequence_batches(0..100), test_uri, None)
+            .await
+            .unwrap();
+
+        let mut fragments = vec![];
+        for i in 1..3 {
+            let mut reader = sequence_batches(i * 100..(i + 1) * 100);
+            fragments.push(
+                FileFragment::create(test_uri, 0, &mut reader, None)
+                    .await
+                    .unwrap(),
+            );
+        }
+        let commits = fragments.iter().map(|f| {
+            Dataset::commit(
+                test_uri,
+                dataset.schema(),
+                std::slice::from_ref(f),
+                WriteMode::Append,
+            )
+        });
+        for result in futures::future::join_all(commits).await {
+            result.unwrap();
+        }
+
+        // Both appends are kept, with new fragment ids.
+        let dataset = Dataset::open(test_uri).await.unwrap();
+        assert_eq!(dataset.version().version, 3);
+        assert_eq!(dataset.count_rows().await.unwrap(), 300);
+        let ids = dataset.fragments().iter().map(|f| f.id).collect::<Vec<_>>();
+        assert_eq!(ids, vec![0, 1, 2]);
+        dataset.validate().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_stale_latest_manifest() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let dataset = Dataset::write(sequence_batches(0..100), test_uri, None)
+            .await
+            .unwrap();
+        let write_params = WriteParams {
+            mode: WriteMode::Append,
+            ..Default::default()
+        };
+        Dataset::write(sequence_batches(100..200), test_uri, Some(write_params))
+            .await
+            .unwrap();
+
+        // A writer that finishes late rolls `_latest.manifest` back to an older version.
+        write_manifest_file_to_path(
+            dataset.object_store(),
+            &dataset.manifest,
+            None,
+            &latest_manifest_path(&dataset.base),
+        )
+        .await
+        .unwrap();
+        let dataset = Dataset::open(test_uri).await.unwrap();
+        assert_eq!(dataset.version().version, 2);
+        assert_eq!(dataset.count_rows().await.unwrap(), 200);
+
+        // Committing an older version does not move `_latest.manifest` back.
+        let mut manifest = dataset.manifest.as_ref().clone();
+        manifest.version = 1;
+        dataset
+            .object_store()
+            .inner
+            .delete(&manifest_path(&dataset.base, 1))
+            .await
+            .unwrap();
+        write_manifest_file(
+            dataset.object_store(),
+            &dataset.base,
+            &mut manifest,
+            None,
+            Default::default(),
+        )
+        .await
+        .unwrap();
+        let latest = read_manifest(dataset.object_store(), &latest_manifest_path(&dataset.base))
+            .await
+            .unwrap();
+        assert_eq!(latest.version, 2);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_deletes() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let write_params = WriteParams {
+            max_rows_per_file: 50,
+            max_rows_per_group: 10,
+            ..Default::default()
+        };
+        Dataset::write(sequence_batches(0..100), test_uri, Some(write_params))
+            .await
+            .unwrap();
+
+        let mut first = Dataset::open(test_uri).await.unwrap();
+        let mut second = Dataset::open(test_uri).await.unwrap();
+        assert_eq!(first.get_fragments().len(), 2);
+
+        // Deletes on disjoint fragments are rebased on each other.
+        first.delete("i < 10").await.unwrap();
+        second.delete("i >= 90").await.unwrap();
+        assert_eq!(second.version().version, 3);
+        assert_eq!(second.count_rows().await.unwrap(), 80);
+        second.validate().await.unwrap();
+
+        // Deletes on the same fragment conflict.
+        let mut third = Dataset::open(test_uri).await.unwrap();
+        second.delete("i >= 80").await.unwrap();
+        let err = third.delete("i >= 70").await.unwrap_err();
+        assert!(matches!(err, Error::CommitConflict { .. }), "{err}");
+
+        let dataset = Dataset::open(test_uri).await.unwrap();
+        assert_eq!(dataset.version().version, 4);
+        assert_eq!(dataset.count_rows().await.unwrap(), 70);
+    }
+
+    #[tokio::test]
+    async fn test_commit_conflicts() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let mut dataset = Dataset::write(sequence_batches(0..100), test_uri, None)
+            .await
+            .unwrap();
+
+        // A stale writer can not claim an existing version.
+        let mut manifest = dataset.manifest.as_ref().clone();
+        let err = write_manifest_file(
+            dataset.object_store(),
+            &dataset.base,
+            &mut manifest,
+            None,
+            Default::default(),
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(err, Error::CommitConflict { version: 1, .. }));
+
+        // Merge conflicts with any concurrent change.
+        let stale = dataset.clone();
+        dataset.delete("i < 10").await.unwrap();
+        let operation = Operation::Merge {
+            fragments: stale.fragments().as_ref().clone(),
+            schema: stale.schema().clone(),
+        };
+        let err = commit_transaction(
+            stale.object_store(),
+            &stale.base,
+            Some(stale.manifest.as_ref()),
+            &operation,
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(err, Error::CommitConflict { version: 2, .. }));
+
+        // Temporary files are cleaned up, and only committed versions are listed.
+        let versions = dataset.versions().await.unwrap();
+        assert_eq!(versions.len(), 2);
+    }
 }
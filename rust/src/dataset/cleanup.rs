@@ -0,0 +1,382 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Removal of old dataset versions.
+//!
+//! A version is removed by deleting its manifest. Data, deletion and index files are
+//! removed once no retained version references them anymore.
+//!
+//! The versions the indices of the retained versions were built on are retained too,
+//! since they are checked out to find the fragments covered by the indices.
+
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Duration, Utc};
+use futures::TryStreamExt;
+use object_store::{path::Path, ObjectMeta};
+
+use super::{read_indices, Dataset, DATA_DIR, DELETION_DIRS, INDICES_DIR, VERSIONS_DIR};
+use crate::format::{Index, Manifest};
+use crate::io::{deletion_file_path, read_manifest, ObjectStore};
+use crate::Result;
+
+/// Parameters of [Dataset::cleanup_old_versions_with_params].
+#[derive(Debug, Clone)]
+pub struct CleanupParams {
+    /// Versions created longer than this ago are removed.
+    /// The latest version is always kept.
+    pub older_than: Duration,
+
+    /// Keep the tagged versions, regardless of their age.
+    pub keep_tagged: bool,
+
+    /// Only collect the statistics of what would be removed, without removing anything.
+    pub dry_run: bool,
+}
+
+impl Default for CleanupParams {
+    fn default() -> Self {
+        Self {
+            older_than: Duration::weeks(2),
+            keep_tagged: true,
+            dry_run: false,
+        }
+    }
+}
+
+/// Statistics of the files removed by a cleanup.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RemovalStats {
+    /// Number of versions removed.
+    pub old_versions: usize,
+
+    /// Number of files removed, including the manifests of the removed versions.
+    pub files_removed: usize,
+
+    /// Total size of the removed files.
+    pub bytes_removed: u64,
+}
+
+impl RemovalStats {
+    fn add(&mut self, meta: &ObjectMeta) {
+        self.files_removed += 1;
+        self.bytes_removed += meta.size as u64;
+    }
+}
+
+/// Files referenced by the retained versions.
+#[derive(Default)]
+struct ReferencedFiles {
+    data_files: HashSet<Path>,
+    deletion_files: HashSet<Path>,
+    /// UUIDs of the indices, each index is stored in its own directory.
+    indices: HashSet<String>,
+}
+
+impl ReferencedFiles {
+    fn add(&mut self, base: &Path, manifest: &Manifest, indices: &[Index]) {
+        let data_dir = base.child(DATA_DIR);
+        for fragment in manifest.fragments.iter() {
+            for file in fragment.files.iter() {
+                self.data_files.insert(data_dir.child(file.path.as_str()));
+            }
+            if let Some(deletion_file) = fragment.deletion_file.as_ref() {
+                self.deletion_files
+                    .insert(deletion_file_path(base, fragment.id, deletion_file));
+            }
+        }
+        for index in indices {
+            self.indices.insert(index.uuid.to_string());
+        }
+    }
+
+    fn contains(&self, base: &Path, path: &Path) -> bool {
+        let Some(mut parts) = path.prefix_match(base) else {
+            return true;
+        };
+        let Some(dir) = parts.next() else {
+            return true;
+        };
+        match dir.as_ref() {
+            DATA_DIR => self.data_files.contains(path),
+            DELETION_DIRS => self.deletion_files.contains(path),
+            INDICES_DIR => parts
+                .next()
+                .map_or(true, |uuid| self.indices.contains(uuid.as_ref())),
+            _ => true,
+        }
+    }
+}
+
+fn is_expired(meta: &ObjectMeta, cutoff: DateTime<Utc>) -> bool {
+    meta.last_modified < cutoff
+}
+
+pub(super) async fn cleanup_old_versions(
+    dataset: &Dataset,
+    params: &CleanupParams,
+) -> Result<RemovalStats> {
+    let object_store = dataset.object_store.as_ref();
+    let base = &dataset.base;
+    let cutoff = Utc::now() - params.older_than;
+
+    let mut manifests = vec![];
+    for meta in list_files(object_store, &base.child(VERSIONS_DIR)).await? {
+        if meta.location.as_ref().ends_with(".manifest") {
+            let manifest = read_manifest(object_store, &meta.location).await?;
+            manifests.push((meta, manifest));
+        }
+    }
+    let latest_version = manifests.iter().map(|(_, m)| m.version).max();
+    let tagged_versions = if params.keep_tagged {
+        dataset
+            .tags()
+            .list()
+            .await?
+            .into_values()
+            .map(|tag| tag.version)
+            .collect()
+    } else {
+        HashSet::new()
+    };
+
+    // The indices of the retained versions, by version.
+    let mut retained = HashMap::new();
+    for (_, manifest) in manifests.iter() {
+        if Some(manifest.version) == latest_version
+            || manifest.timestamp() >= cutoff
+            || tagged_versions.contains(&manifest.version)
+        {
+            let indices = read_indices(object_store, base, manifest).await?;
+            retained.insert(manifest.version, indices);
+        }
+    }
+    let indexed_versions = retained
+        .values()
+        .flatten()
+        .map(|index| index.dataset_version)
+        .collect::<HashSet<_>>();
+
+    let mut stats = RemovalStats::default();
+    let mut referenced = ReferencedFiles::default();
+    let mut to_remove = vec![];
+    for (meta, manifest) in manifests {
+        let indices = match retained.remove(&manifest.version) {
+            Some(indices) => indices,
+            None if indexed_versions.contains(&manifest.version) => {
+                read_indices(object_store, base, &manifest).await?
+            }
+            None => {
+                stats.old_versions += 1;
+                stats.add(&meta);
+                to_remove.push(meta.location);
+                continue;
+            }
+        };
+        referenced.add(base, &manifest, &indices);
+    }
+
+    // Files that are not referenced by any version are only removed once they are old
+    // enough, so that the files of a concurrent write, that is not committed yet, are kept.
+    for dir in [DATA_DIR, DELETION_DIRS, INDICES_DIR] {
+        for meta in list_files(object_store, &base.child(dir)).await? {
+            if !referenced.contains(base, &meta.location) && is_expired(&meta, cutoff) {
+                stats.add(&meta);
+                to_remove.push(meta.location);
+            }
+        }
+    }
+
+    if !params.dry_run {
+        for path in to_remove {
+            match object_store.inner.delete(&path).await {
+                Ok(_) | Err(object_store::Error::NotFound { .. }) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    Ok(stats)
+}
+
+/// List the files under `dir`, recursively.
+async fn list_files(object_store: &ObjectStore, dir: &Path) -> Result<Vec<ObjectMeta>> {
+    Ok(object_store
+        .inner
+        .list(Some(dir))
+        .await?
+        .try_collect::<Vec<_>>()
+        .await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::UInt32Array;
+    use tempfile::tempdir;
+
+    use crate::dataset::{WriteMode, WriteParams};
+    use crate::index::scalar::ScalarIndexParams;
+    use crate::index::{DatasetIndexExt, IndexType};
+    use crate::utils::testing::sequence_batches;
+
+    async fn count_files(dataset: &Dataset, dir: &str) -> usize {
+        list_files(dataset.object_store(), &dataset.base.child(dir))
+            .await
+            .unwrap()
+            .len()
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_old_versions() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let mut dataset = Dataset::write(sequence_batches(0..100), test_uri, None)
+            .await
+            .unwrap();
+        // Version 2 overwrites all the data of version 1.
+        let write_params = WriteParams {
+            mode: WriteMode::Overwrite,
+            ..Default::default()
+        };
+        Dataset::write(sequence_batches(0..50), test_uri, Some(write_params))
+            .await
+            .unwrap();
+        // Version 3 deletes some rows.
+        dataset = dataset.checkout_version(2).await.unwrap();
+        dataset.delete("i < 10").await.unwrap();
+        assert_eq!(count_files(&dataset, VERSIONS_DIR).await, 3);
+        assert_eq!(count_files(&dataset, DATA_DIR).await, 2);
+        assert_eq!(count_files(&dataset, DELETION_DIRS).await, 1);
+
+        // Everything is recent enough.
+        let stats = dataset
+            .cleanup_old_versions(Duration::days(1), false)
+            .await
+            .unwrap();
+        assert_eq!(stats, RemovalStats::default());
+
+        let params = CleanupParams {
+            older_than: Duration::zero(),
+            keep_tagged: false,
+            dry_run: true,
+        };
+        let stats = dataset
+            .cleanup_old_versions_with_params(&params)
+            .await
+            .unwrap();
+        assert_eq!(stats.old_versions, 2);
+        // Two manifests and the data file of version 1.
+        assert_eq!(stats.files_removed, 3);
+        assert!(stats.bytes_removed > 0);
+        assert_eq!(count_files(&dataset, VERSIONS_DIR).await, 3);
+
+        let params = CleanupParams {
+            dry_run: false,
+            ..params
+        };
+        let removed = dataset
+            .cleanup_old_versions_with_params(&params)
+            .await
+            .unwrap();
+        assert_eq!(removed, stats);
+        assert_eq!(count_files(&dataset, VERSIONS_DIR).await, 1);
+        assert_eq!(count_files(&dataset, DATA_DIR).await, 1);
+        assert_eq!(count_files(&dataset, DELETION_DIRS).await, 1);
+
+        let dataset = Dataset::open(test_uri).await.unwrap();
+        assert_eq!(dataset.version().version, 3);
+        assert_eq!(dataset.count_rows().await.unwrap(), 40);
+        assert_eq!(dataset.versions().await.unwrap().len(), 1);
+        assert!(dataset.checkout_version(1).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_keeps_indexed_version() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let dataset = Dataset::write(sequence_batches(0..100), test_uri, None)
+            .await
+            .unwrap();
+        // Version 2 has the index.
+        dataset
+            .create_index(
+                &["i"],
+                IndexType::BTree,
+                None,
+                &ScalarIndexParams::default(),
+                false,
+            )
+            .await
+            .unwrap();
+        for i in 1..3 {
+            let write_params = WriteParams {
+                mode: WriteMode::Append,
+                ..Default::default()
+            };
+            Dataset::write(
+                sequence_batches(i * 100..(i + 1) * 100),
+                test_uri,
+                Some(write_params),
+            )
+            .await
+            .unwrap();
+        }
+
+        let dataset = Dataset::open(test_uri).await.unwrap();
+        let params = CleanupParams {
+            older_than: Duration::zero(),
+            keep_tagged: false,
+            dry_run: false,
+        };
+        let stats = dataset
+            .cleanup_old_versions_with_params(&params)
+            .await
+            .unwrap();
+        assert_eq!(stats.old_versions, 2);
+        let versions = dataset.versions().await.unwrap();
+        let mut versions = versions.iter().map(|v| v.version).collect::<Vec<_>>();
+        versions.sort();
+        assert_eq!(versions, vec![2, 4]);
+
+        // The index still finds the indexed rows, and the appended rows are scanned.
+        let dataset = Dataset::open(test_uri).await.unwrap();
+        let mut scanner = dataset.scan();
+        scanner.filter("i >= 50").unwrap();
+        let batches = scanner
+            .try_into_stream()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let mut values = batches
+            .iter()
+            .flat_map(|b| {
+                b["i"]
+                    .as_any()
+                    .downcast_ref::<UInt32Array>()
+                    .unwrap()
+                    .values()
+                    .to_vec()
+            })
+            .collect::<Vec<_>>();
+        values.sort();
+        assert_eq!(values, (50..300).collect::<Vec<_>>());
+    }
+}
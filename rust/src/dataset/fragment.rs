@@ -14,24 +14,31 @@
 
 //! Wraps a Fragment of the dataset.
 
+use std::collections::HashMap;
 use std::ops::Range;
 use std::sync::Arc;
 
 use arrow_array::cast::as_primitive_array;
 use arrow_array::{RecordBatch, RecordBatchReader, UInt64Array};
+use arrow_cast::{cast_with_options, CastOptions};
+use arrow_schema::Schema as ArrowSchema;
+use datafusion::physical_plan::PhysicalExpr;
 use futures::future::try_join_all;
-use futures::{join, TryFutureExt, TryStreamExt};
+use futures::stream::BoxStream;
+use futures::{join, StreamExt, TryFutureExt, TryStreamExt};
 use object_store::path::Path;
 use uuid::Uuid;
 
-use super::hash_joiner::HashJoiner;
+use super::hash_joiner::{key_columns, HashJoiner, SpilledJoiner};
 use super::scanner::Scanner;
 use super::updater::Updater;
 use crate::arrow::*;
-use crate::dataset::{Dataset, DATA_DIR};
+use crate::dataset::{Dataset, DATA_DIR, ROW_ID};
 use crate::datatypes::Schema;
-use crate::format::Fragment;
-use crate::io::deletion::{deletion_file_path, read_deletion_file, write_deletion_file};
+use crate::format::{Fragment, PageStatistics};
+use crate::io::deletion::{
+    deletion_file_path, read_deletion_file, write_deletion_file, DeletionVector,
+};
 use crate::io::{FileReader, FileWriter, ObjectStore, ReadBatchParams};
 use crate::{Error, Result};
 
@@ -180,6 +187,16 @@ impl FileFragment {
         Ok(total_rows - deletion_count)
     }
 
+    /// Get the deletion vector of the fragment, if any rows were deleted.
+    pub(crate) async fn get_deletion_vector(&self) -> Result<Option<DeletionVector>> {
+        read_deletion_file(
+            &self.dataset.base,
+            &self.metadata,
+            self.dataset.object_store(),
+        )
+        .await
+    }
+
     /// Get the number of physical rows in the fragment. This includes deleted rows.
     ///
     /// If there are no deleted rows, this is equal to the number of rows in the
@@ -303,12 +320,65 @@ impl FileFragment {
         Ok(Updater::new(self.clone(), reader, deletion_vector))
     }
 
-    pub(crate) async fn merge(mut self, join_column: &str, joiner: &HashJoiner) -> Result<Self> {
-        let mut updater = self.updater(Some(&[join_column])).await?;
+    pub(crate) async fn merge(
+        mut self,
+        join_columns: &[&str],
+        joiner: &HashJoiner,
+    ) -> Result<Self> {
+        let mut updater = self.updater(Some(join_columns)).await?;
+
+        while let Some(batch) = updater.next().await? {
+            let batch = joiner.collect(&key_columns(&batch, join_columns)).await?;
+            updater.update(batch).await?;
+        }
+
+        self.metadata = updater.finish().await?;
+
+        Ok(self)
+    }
+
+    /// Read the join keys of this fragment, and spill them by the partitions of a
+    /// [SpilledJoiner].
+    pub(crate) async fn partition_join_keys(
+        &self,
+        join_columns: &[&str],
+        joiner: &SpilledJoiner,
+    ) -> Result<()> {
+        let reader = self.open(&self.schema().project(join_columns)?).await?;
+        // Offsets of the rows, as read by the updater in [Self::merge_spilled].
+        let mut offset = 0;
+        let mut partitions = vec![vec![]; joiner.num_partitions()];
+        for batch_id in 0..reader.num_batches() {
+            let batch = reader.read_batch(batch_id, ..).await?;
+            let keys = key_columns(&batch, join_columns);
+            for (partition, keys) in joiner
+                .partition_left_keys(&keys, offset)?
+                .into_iter()
+                .enumerate()
+            {
+                partitions[partition].extend(keys);
+            }
+            offset += batch.num_rows() as u64;
+        }
+        joiner.spill_left_keys(self.metadata.id, &partitions).await
+    }
 
+    /// Write the joined columns from the matches spilled by [Self::join_partition]
+    /// for all the partitions.
+    pub(crate) async fn merge_spilled(
+        mut self,
+        join_columns: &[&str],
+        joiner: &SpilledJoiner,
+    ) -> Result<Self> {
+        let matches = joiner.load_matches(self.metadata.id).await?;
+        let mut updater = self.updater(Some(join_columns)).await?;
+
+        let mut offset = 0;
         while let Some(batch) = updater.next().await? {
-            let batch = joiner.collect(batch[join_column].clone()).await?;
+            let num_rows = batch.num_rows() as u64;
+            let batch = matches.collect(offset..offset + num_rows)?;
             updater.update(batch).await?;
+            offset += num_rows;
         }
 
         self.metadata = updater.finish().await?;
@@ -321,18 +391,7 @@ impl FileFragment {
     /// If all rows are deleted, returns `Ok(None)`. Otherwise, returns a new
     /// fragment with the updated deletion vector. This must be persisted to
     /// the manifest.
-    pub async fn delete(mut self, predicate: &str) -> Result<Option<Self>> {
-        // Load existing deletion vector
-        let mut deletion_vector = read_deletion_file(
-            &self.dataset.base,
-            &self.metadata,
-            self.dataset.object_store(),
-        )
-        .await?
-        .unwrap_or_default();
-
-        let starting_length = deletion_vector.len();
-
+    pub async fn delete(self, predicate: &str) -> Result<Option<Self>> {
         // scan with predicate and row ids
         let mut scanner = self.scan();
         scanner
@@ -340,24 +399,90 @@ impl FileFragment {
             .filter(predicate)?
             .project::<&str>(&[])?;
 
-        // As we get row ids, add them into our deletion vector
+        let mut row_ids = vec![];
         scanner
             .try_into_stream()
             .await?
             .try_for_each(|batch| {
-                let array = batch["_rowid"].clone();
-                let int_array: &UInt64Array = as_primitive_array(array.as_ref());
-
-                // _row_id is global, not within fragment level. The high bits
-                // are the fragment_id, the low bits are the row_id within the
-                // fragment.
-                let local_row_ids = int_array.iter().map(|v| v.unwrap() as u32);
-
-                deletion_vector.extend(local_row_ids);
+                row_ids.extend(local_row_ids(&batch));
                 futures::future::ready(Ok(()))
             })
             .await?;
 
+        self.delete_rows(row_ids).await
+    }
+
+    /// Compute the new values of the rows matching the predicate.
+    ///
+    /// `updates` are the expressions to compute the new values of the updated columns,
+    /// evaluated against the original rows.
+    ///
+    /// Returns a stream of the updated rows, each batch together with the offsets of its
+    /// original rows in the fragment. The updated rows must be written to new fragments,
+    /// and the original rows deleted with [`Self::delete_rows`].
+    pub(crate) async fn updated_batches(
+        &self,
+        predicate: &str,
+        updates: &HashMap<String, Arc<dyn PhysicalExpr>>,
+    ) -> Result<BoxStream<'static, Result<(Vec<u32>, RecordBatch)>>> {
+        let schema = Arc::new(ArrowSchema::from(self.schema()));
+        let updates = updates.clone();
+
+        let mut scanner = self.scan();
+        scanner.with_row_id().filter(predicate)?;
+        let stream = scanner.try_into_stream().await?;
+        Ok(stream
+            .map(move |batch| {
+                let batch = batch?;
+                let columns = schema
+                    .fields()
+                    .iter()
+                    .map(|field| match updates.get(field.name()) {
+                        Some(expr) => {
+                            let values = expr.evaluate(&batch)?.into_array(batch.num_rows());
+                            if values.data_type() == field.data_type() {
+                                return Ok(values);
+                            }
+                            let options = CastOptions {
+                                safe: false,
+                                ..Default::default()
+                            };
+                            cast_with_options(&values, field.data_type(), &options).map_err(|e| {
+                                Error::invalid_input(format!(
+                                    "Cannot update column {} of type {} with values of type {}: {e}",
+                                    field.name(),
+                                    field.data_type(),
+                                    values.data_type()
+                                ))
+                            })
+                        }
+                        None => Ok(batch[field.name().as_str()].clone()),
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                let row_ids = local_row_ids(&batch).collect();
+                Ok((row_ids, RecordBatch::try_new(schema.clone(), columns)?))
+            })
+            .boxed())
+    }
+
+    /// Mark the rows, by their offsets in the fragment, as deleted.
+    ///
+    /// If all rows are deleted, returns `Ok(None)`. Otherwise, returns a new
+    /// fragment with the updated deletion vector. This must be persisted to
+    /// the manifest.
+    pub(crate) async fn delete_rows(mut self, row_ids: Vec<u32>) -> Result<Option<Self>> {
+        // Load existing deletion vector
+        let mut deletion_vector = read_deletion_file(
+            &self.dataset.base,
+            &self.metadata,
+            self.dataset.object_store(),
+        )
+        .await?
+        .unwrap_or_default();
+
+        let starting_length = deletion_vector.len();
+        deletion_vector.extend(row_ids);
+
         // If we haven't deleted any additional rows, we can return the fragment as-is.
         if deletion_vector.len() == starting_length {
             return Ok(Some(self));
@@ -400,6 +525,16 @@ impl FileFragment {
     }
 }
 
+/// Offsets of the rows in their fragment, from the `_rowid` column of the batch.
+pub(super) fn local_row_ids(batch: &RecordBatch) -> impl Iterator<Item = u32> + '_ {
+    let int_array: &UInt64Array = as_primitive_array(batch[ROW_ID].as_ref());
+
+    // _row_id is global, not within fragment level. The high bits
+    // are the fragment_id, the low bits are the row_id within the
+    // fragment.
+    int_array.iter().map(|v| v.unwrap() as u32)
+}
+
 impl From<FileFragment> for Fragment {
     fn from(fragment: FileFragment) -> Self {
         fragment.metadata
@@ -473,6 +608,15 @@ impl FragmentReader {
         self.readers[0].0.num_rows_in_batch(batch_id as i32)
     }
 
+    /// Statistics of the pages of a column, from the data file that stores it.
+    pub(crate) fn page_statistics(&self, column: &str) -> Option<&PageStatistics> {
+        self.readers.iter().find_map(|(reader, schema)| {
+            schema
+                .field(column)
+                .and_then(|field| reader.page_statistics(field.id))
+        })
+    }
+
     pub(crate) async fn read_batch(
         &self,
         batch_id: usize,
@@ -14,16 +14,28 @@
 
 //! HashJoiner
 
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::io::{Cursor, ErrorKind};
+use std::ops::Range;
+use std::path::PathBuf;
 use std::sync::Arc;
 
-use arrow_array::ArrayRef;
-use arrow_array::{new_null_array, Array, RecordBatch, RecordBatchReader};
+use arrow_array::{
+    cast::AsArray, new_null_array, types::UInt64Type, Array, ArrayRef, RecordBatch,
+    RecordBatchReader, UInt32Array, UInt64Array,
+};
+use arrow_ipc::{reader::FileReader as IpcFileReader, writer::FileWriter as IpcFileWriter};
 use arrow_row::{OwnedRow, RowConverter, Rows, SortField};
-use arrow_schema::{DataType as ArrowDataType, SchemaRef};
-use arrow_select::interleave::interleave;
+use arrow_schema::{ArrowError, DataType as ArrowDataType, Field, Schema, SchemaRef};
+use arrow_select::{interleave::interleave, take::take};
 use dashmap::{DashMap, ReadOnlyView};
 use futures::{StreamExt, TryStreamExt};
+use tokio::fs::OpenOptions;
+use tokio::io::AsyncWriteExt;
 use tokio::task;
+use uuid::Uuid;
 
 use crate::datatypes::lance_supports_nulls;
 use crate::{Error, Result};
@@ -32,30 +44,93 @@ use crate::{Error, Result};
 pub struct HashJoiner {
     index_map: ReadOnlyView<OwnedRow, (usize, usize)>,
 
-    index_type: ArrowDataType,
+    /// Types of the key columns.
+    index_types: Vec<ArrowDataType>,
 
     batches: Vec<RecordBatch>,
 
+    /// The input batches, including the index column.
+    input_batches: Vec<RecordBatch>,
+
     out_schema: SchemaRef,
 }
 
-fn column_to_rows(column: ArrayRef) -> Result<Rows> {
-    let mut row_converter = RowConverter::new(vec![SortField::new(column.data_type().clone())])?;
-    let rows = row_converter.convert_columns(&[column])?;
+/// Convert the key columns to rows, so composite keys can be hashed and compared.
+fn columns_to_rows(columns: &[ArrayRef]) -> Result<Rows> {
+    let fields = columns
+        .iter()
+        .map(|column| SortField::new(column.data_type().clone()))
+        .collect();
+    let mut row_converter = RowConverter::new(fields)?;
+    let rows = row_converter.convert_columns(columns)?;
     Ok(rows)
 }
 
+/// Check that the key columns `on` exist in `schema`, and returns their indices.
+fn key_indices(schema: &Schema, on: &[&str]) -> Result<Vec<usize>> {
+    if on.is_empty() {
+        return Err(Error::invalid_input("HashJoiner: No key columns"));
+    }
+    let indices = on
+        .iter()
+        .map(|name| schema.index_of(name))
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    if indices.iter().collect::<HashSet<_>>().len() != indices.len() {
+        return Err(Error::invalid_input(format!(
+            "HashJoiner: Duplicated key columns {:?}",
+            on
+        )));
+    }
+    Ok(indices)
+}
+
+/// The key columns `on` of a batch.
+pub(super) fn key_columns(batch: &RecordBatch, on: &[&str]) -> Vec<ArrayRef> {
+    on.iter().map(|name| batch[*name].clone()).collect()
+}
+
+fn check_key_types(expected: &[ArrowDataType], key_columns: &[ArrayRef]) -> Result<()> {
+    let actual = key_columns
+        .iter()
+        .map(|column| column.data_type().clone())
+        .collect::<Vec<_>>();
+    if expected != actual.as_slice() {
+        return Err(Error::invalid_input(format!(
+            "Index column type mismatch: expected {}, got {}",
+            format_types(expected),
+            format_types(&actual)
+        )));
+    }
+    Ok(())
+}
+
+fn format_types(types: &[ArrowDataType]) -> String {
+    types
+        .iter()
+        .map(|t| t.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
 impl HashJoiner {
     /// Create a new `HashJoiner`, building the hash index.
     ///
     /// Will run in parallel over batches using all available cores.
-    pub async fn try_new(reader: Box<dyn RecordBatchReader + Send>, on: &str) -> Result<Self> {
-        // Check column exist
+    pub async fn try_new(reader: Box<dyn RecordBatchReader + Send>, on: &[&str]) -> Result<Self> {
+        // Check columns exist
         let schema = reader.schema();
-        schema.field_with_name(on)?;
+        key_indices(&schema, on)?;
 
-        // Hold all data in memory for simple implementation. Can do external sort later.
+        // Hold all data in memory. Use [build_join_side] to spill large inputs to disk.
         let batches = reader.collect::<std::result::Result<Vec<RecordBatch>, _>>()?;
+        Self::try_from_batches(schema, batches, on).await
+    }
+
+    async fn try_from_batches(
+        schema: SchemaRef,
+        batches: Vec<RecordBatch>,
+        on: &[&str],
+    ) -> Result<Self> {
         if batches.is_empty() {
             return Err(Error::IO {
                 message: "HashJoiner: No data".to_string(),
@@ -64,11 +139,9 @@ impl HashJoiner {
 
         let map = DashMap::new();
 
-        let keep_indices: Vec<usize> = schema
-            .fields()
-            .iter()
-            .enumerate()
-            .filter_map(|(i, field)| if field.name() == on { None } else { Some(i) })
+        let key_indices = key_indices(&schema, on)?;
+        let keep_indices: Vec<usize> = (0..schema.fields().len())
+            .filter(|i| !key_indices.contains(i))
             .collect();
         let out_schema: Arc<arrow_schema::Schema> = Arc::new(schema.project(&keep_indices)?);
         let right_batches = batches
@@ -88,10 +161,13 @@ impl HashJoiner {
             .try_for_each_concurrent(num_cpus::get(), |(batch_i, batch)| {
                 // A clone of map we can send to a new thread
                 let map = map.clone();
+                let key_columns = key_indices
+                    .iter()
+                    .map(|i| batch.column(*i).clone())
+                    .collect::<Vec<_>>();
                 async move {
-                    let column = batch[on].clone();
                     let task_result = task::spawn_blocking(move || {
-                        let rows = column_to_rows(column)?;
+                        let rows = columns_to_rows(&key_columns)?;
                         for (row_i, row) in rows.iter().enumerate() {
                             map.insert(row.owned(), (batch_i, row_i));
                         }
@@ -111,16 +187,15 @@ impl HashJoiner {
 
         let map = Arc::try_unwrap(map)
             .expect("HashJoiner: No remaining tasks should still be referencing map.");
-        let index_type = batches[0]
-            .schema()
-            .field_with_name(on)
-            .unwrap()
-            .data_type()
-            .clone();
+        let index_types = key_indices
+            .iter()
+            .map(|i| schema.field(*i).data_type().clone())
+            .collect();
         Ok(Self {
             index_map: map.into_read_only(),
-            index_type,
+            index_types,
             batches: right_batches,
+            input_batches: batches,
             out_schema,
         })
     }
@@ -132,17 +207,52 @@ impl HashJoiner {
         &self.out_schema
     }
 
-    /// Collecting the data using the index column from left table.
+    /// Find the rows on the right side matching the key columns from left table.
+    ///
+    /// Returns the `(batch_i, row_i)` of the matched row for each key, or `None` if
+    /// there is no match.
+    pub(super) fn lookup(&self, key_columns: &[ArrayRef]) -> Result<Vec<Option<(usize, usize)>>> {
+        check_key_types(&self.index_types, key_columns)?;
+        Ok(columns_to_rows(key_columns)?
+            .into_iter()
+            .map(|row| self.index_map.get(&row.owned()).copied())
+            .collect())
+    }
+
+    /// Returns the `(batch_i, row_i)` of all the rows on the right side, one for each
+    /// distinct key. For duplicated keys, any one of the rows is returned.
+    pub(super) fn row_indices(&self) -> Vec<(usize, usize)> {
+        let mut indices = self.index_map.values().copied().collect::<Vec<_>>();
+        indices.sort();
+        indices
+    }
+
+    /// Number of rows on the right side, including the rows with duplicated keys.
+    pub(super) fn num_rows(&self) -> usize {
+        self.input_batches.iter().map(|b| b.num_rows()).sum()
+    }
+
+    /// Take the rows on the right side, including the index column.
+    pub(super) fn take_rows(&self, indices: &[(usize, usize)]) -> Result<RecordBatch> {
+        let schema = self.input_batches[0].schema();
+        let columns = (0..schema.fields().len())
+            .map(|column_i| {
+                let arrays = self
+                    .input_batches
+                    .iter()
+                    .map(|batch| batch.column(column_i).as_ref())
+                    .collect::<Vec<_>>();
+                Ok(interleave(&arrays, indices)?)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(RecordBatch::try_new(schema, columns)?)
+    }
+
+    /// Collecting the data using the key columns from left table.
     ///
     /// Will run in parallel over columns using all available cores.
-    pub(super) async fn collect(&self, index_column: ArrayRef) -> Result<RecordBatch> {
-        if index_column.data_type() != &self.index_type {
-            return Err(Error::invalid_input(format!(
-                "Index column type mismatch: expected {}, got {}",
-                self.index_type,
-                index_column.data_type()
-            )));
-        }
+    pub(super) async fn collect(&self, key_columns: &[ArrayRef]) -> Result<RecordBatch> {
+        check_key_types(&self.index_types, key_columns)?;
 
         // Index to use for null values
         let null_index = self.batches.len();
@@ -150,7 +260,7 @@ impl HashJoiner {
         // Indices are a pair of (batch_i, row_i). We'll add a null batch at the
         // end with one null element, and that's what we resolve when no match is
         // found.
-        let indices = column_to_rows(index_column)?
+        let indices = columns_to_rows(key_columns)?
             .into_iter()
             .map(|row| {
                 self.index_map
@@ -179,23 +289,16 @@ impl HashJoiner {
                 async move {
                     let task_result = task::spawn_blocking(move || {
                         let array_refs = arrays.iter().map(|x| x.as_ref()).collect::<Vec<_>>();
-                        interleave(array_refs.as_ref(), indices.as_ref())
-                            .map_err(|err| Error::IO {
-                                message: format!("HashJoiner: {}", err),
-                            })
+                        interleave(array_refs.as_ref(), indices.as_ref()).map_err(|err| Error::IO {
+                            message: format!("HashJoiner: {}", err),
+                        })
                     })
                     .await;
                     match task_result {
                         Ok(Ok(array)) => {
-                            if array.null_count() > 0 && !lance_supports_nulls(array.data_type()) {
-                                return Err(Error::invalid_input(format!(
-                                    "Found rows on LHS that do not match any rows on RHS. Lance would need to write \
-                                    nulls on the RHS, but Lance does not yet support nulls for type {:?}.",
-                                    array.data_type()
-                                )));
-                            }
+                            check_supports_nulls(array.as_ref())?;
                             Ok(array)
-                        },
+                        }
                         Ok(Err(err)) => Err(err),
                         Err(err) => Err(Error::IO {
                             message: format!("HashJoiner: {}", err),
@@ -211,6 +314,512 @@ impl HashJoiner {
     }
 }
 
+fn check_supports_nulls(array: &dyn Array) -> Result<()> {
+    if array.null_count() > 0 && !lance_supports_nulls(array.data_type()) {
+        return Err(Error::invalid_input(format!(
+            "Found rows on LHS that do not match any rows on RHS. Lance would need to write \
+            nulls on the RHS, but Lance does not yet support nulls for type {:?}.",
+            array.data_type()
+        )));
+    }
+    Ok(())
+}
+
+/// Parameters of [Dataset::merge_with_params](super::Dataset::merge_with_params).
+#[derive(Debug, Clone)]
+pub struct MergeParams {
+    /// Max memory, in bytes, to hold the right side of the join.
+    ///
+    /// Larger inputs are partitioned by the hash of the join key and spilled to disk,
+    /// then joined one partition at a time.
+    pub memory_limit: usize,
+
+    /// Number of partitions to spill the right side into. Each partition must fit
+    /// in `memory_limit`, or the merge fails.
+    pub num_partitions: usize,
+
+    /// Directory of the spilled files. Defaults to the temporary directory of the system.
+    pub spill_dir: Option<PathBuf>,
+}
+
+impl Default for MergeParams {
+    fn default() -> Self {
+        Self {
+            memory_limit: 2 * 1024 * 1024 * 1024, // 2GB
+            num_partitions: 64,
+            spill_dir: None,
+        }
+    }
+}
+
+/// The right side of a join, either held in memory or spilled to disk.
+pub(super) enum JoinSide {
+    InMemory(HashJoiner),
+    Spilled(SpilledJoiner),
+}
+
+impl JoinSide {
+    pub(super) fn out_schema(&self) -> &SchemaRef {
+        match self {
+            Self::InMemory(joiner) => joiner.out_schema(),
+            Self::Spilled(joiner) => joiner.out_schema(),
+        }
+    }
+}
+
+/// Read the right side of a join, spilling it to disk if it does not fit in
+/// [MergeParams::memory_limit].
+pub(super) async fn build_join_side(
+    mut reader: Box<dyn RecordBatchReader + Send>,
+    on: &[&str],
+    params: &MergeParams,
+) -> Result<JoinSide> {
+    let schema = reader.schema();
+    key_indices(&schema, on)?;
+
+    let mut batches = vec![];
+    let mut memory_size = 0;
+    while let Some(batch) = reader.next() {
+        let batch = batch?;
+        memory_size += batch_data_size(&batch)?;
+        batches.push(batch);
+        if memory_size > params.memory_limit {
+            let remaining = batches.into_iter().map(Ok).chain(reader);
+            let joiner = SpilledJoiner::try_new(schema, on, params, remaining).await?;
+            return Ok(JoinSide::Spilled(joiner));
+        }
+    }
+    let joiner = HashJoiner::try_from_batches(schema, batches, on).await?;
+    Ok(JoinSide::InMemory(joiner))
+}
+
+/// A directory of spilled files, removed when dropped.
+///
+/// A spilled file is a sequence of chunks, each one an Arrow IPC file prefixed with its
+/// length, so that batches can be appended to it.
+struct SpillDir {
+    path: PathBuf,
+}
+
+impl SpillDir {
+    async fn try_new(parent: Option<&PathBuf>) -> Result<Self> {
+        let parent = parent.cloned().unwrap_or_else(std::env::temp_dir);
+        let path = parent.join(format!("lance-join-{}", Uuid::new_v4()));
+        tokio::fs::create_dir_all(&path).await?;
+        Ok(Self { path })
+    }
+
+    /// Append the batches to a spilled file, as one chunk.
+    async fn append(&self, name: &str, schema: &Schema, batches: &[RecordBatch]) -> Result<()> {
+        let mut writer = IpcFileWriter::try_new(vec![], schema)?;
+        for batch in batches {
+            writer.write(batch)?;
+        }
+        writer.finish()?;
+        let bytes = writer.into_inner()?;
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path.join(name))
+            .await?;
+        file.write_all(&(bytes.len() as u64).to_le_bytes()).await?;
+        file.write_all(&bytes).await?;
+        file.flush().await?;
+        Ok(())
+    }
+
+    /// Read the batches of all the chunks of a spilled file, or nothing if the file
+    /// does not exist.
+    async fn read(&self, name: &str) -> Result<Vec<RecordBatch>> {
+        let bytes = match tokio::fs::read(self.path.join(name)).await {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e.into()),
+        };
+        let mut batches = vec![];
+        let mut pos = 0;
+        while pos < bytes.len() {
+            let len_bytes = bytes[pos..pos + 8].try_into().unwrap();
+            let len = u64::from_le_bytes(len_bytes) as usize;
+            pos += 8;
+            let reader = IpcFileReader::try_new(Cursor::new(&bytes[pos..pos + len]), None)?;
+            for batch in reader {
+                batches.push(batch?);
+            }
+            pos += len;
+        }
+        Ok(batches)
+    }
+}
+
+impl Drop for SpillDir {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_dir_all(&self.path) {
+            log::warn!("Failed to remove spill directory {:?}: {}", self.path, e);
+        }
+    }
+}
+
+fn take_columns(columns: &[ArrayRef], indices: &UInt32Array) -> Result<Vec<ArrayRef>> {
+    Ok(columns
+        .iter()
+        .map(|column| take(column.as_ref(), indices, None))
+        .collect::<std::result::Result<Vec<_>, _>>()?)
+}
+
+/// The row indices of each partition, by the hash of the keys.
+fn partition_rows(rows: &Rows, num_partitions: usize) -> Vec<Vec<u32>> {
+    let mut partitions = vec![vec![]; num_partitions];
+    for (row_i, row) in rows.iter().enumerate() {
+        let mut hasher = DefaultHasher::new();
+        row.as_ref().hash(&mut hasher);
+        partitions[(hasher.finish() % num_partitions as u64) as usize].push(row_i as u32);
+    }
+    partitions
+}
+
+/// Size of the data of a batch, excluding the unused capacity of its buffers.
+pub(super) fn batch_data_size(batch: &RecordBatch) -> Result<usize> {
+    Ok(batch
+        .columns()
+        .iter()
+        .map(|column| column.to_data().get_slice_memory_size())
+        .sum::<std::result::Result<usize, _>>()?)
+}
+
+/// Hash join with the right side partitioned by the hash of the keys, and spilled to
+/// disk (grace hash join).
+///
+/// The join happens in three passes:
+/// 1. The keys of each fragment of the left side are read once, partitioned the same way,
+///    and spilled along with the offsets of the rows in the fragment.
+///    See [Self::spill_left_keys].
+/// 2. For each partition, loaded into a [HashJoiner], the matched right rows of each
+///    fragment are spilled, along with the offsets of the left rows.
+///    See [Self::join_partition].
+/// 3. For each fragment, the spilled matches are collected in the order of the left
+///    rows. See [Self::load_matches].
+pub(super) struct SpilledJoiner {
+    dir: SpillDir,
+
+    /// Schema of the right side, including the key column.
+    schema: SchemaRef,
+
+    on: Vec<String>,
+
+    index_types: Vec<ArrowDataType>,
+
+    num_partitions: usize,
+
+    out_schema: SchemaRef,
+
+    /// Schema of the spilled left keys: the offsets of the left rows, then the keys.
+    keys_schema: SchemaRef,
+
+    /// Schema of the spilled matches: the offsets of the left rows, then [Self::out_schema].
+    matches_schema: SchemaRef,
+}
+
+impl SpilledJoiner {
+    async fn try_new(
+        schema: SchemaRef,
+        on: &[&str],
+        params: &MergeParams,
+        batches: impl Iterator<Item = std::result::Result<RecordBatch, ArrowError>>,
+    ) -> Result<Self> {
+        if params.num_partitions == 0 {
+            return Err(Error::invalid_input(
+                "num_partitions must be greater than zero",
+            ));
+        }
+        let dir = SpillDir::try_new(params.spill_dir.as_ref()).await?;
+        let key_indices = key_indices(&schema, on)?;
+        let index_types = key_indices
+            .iter()
+            .map(|i| schema.field(*i).data_type().clone())
+            .collect();
+
+        // The partitions are buffered in memory, and appended to their files once
+        // the buffer of a partition exceeds its share of the memory limit.
+        let flush_size = params.memory_limit / params.num_partitions;
+        let mut buffers: Vec<Vec<RecordBatch>> = vec![vec![]; params.num_partitions];
+        let mut buffered_sizes = vec![0; params.num_partitions];
+        let mut partition_sizes = vec![0; params.num_partitions];
+        for batch in batches {
+            let batch = batch?;
+            let key_columns = key_indices
+                .iter()
+                .map(|i| batch.column(*i).clone())
+                .collect::<Vec<_>>();
+            let rows = columns_to_rows(&key_columns)?;
+            let partitions = partition_rows(&rows, params.num_partitions);
+            for (partition, row_indices) in partitions.into_iter().enumerate() {
+                if row_indices.is_empty() {
+                    continue;
+                }
+                let columns = take_columns(batch.columns(), &UInt32Array::from(row_indices))?;
+                let partition_batch = RecordBatch::try_new(schema.clone(), columns)?;
+                let size = batch_data_size(&partition_batch)?;
+                partition_sizes[partition] += size;
+                buffered_sizes[partition] += size;
+                buffers[partition].push(partition_batch);
+                if buffered_sizes[partition] > flush_size {
+                    let buffer = std::mem::take(&mut buffers[partition]);
+                    dir.append(&Self::partition_file(partition), &schema, &buffer)
+                        .await?;
+                    buffered_sizes[partition] = 0;
+                }
+            }
+        }
+        for (partition, buffer) in buffers.iter().enumerate() {
+            if !buffer.is_empty() {
+                dir.append(&Self::partition_file(partition), &schema, buffer)
+                    .await?;
+            }
+        }
+
+        // Each partition is loaded in memory as a whole.
+        if let Some((partition, size)) = partition_sizes
+            .iter()
+            .enumerate()
+            .find(|(_, size)| **size > params.memory_limit)
+        {
+            return Err(Error::invalid_input(format!(
+                "Partition {partition} of the right side has {size} bytes, more than the \
+                memory limit of {} bytes. Increase num_partitions or memory_limit.",
+                params.memory_limit
+            )));
+        }
+
+        let keep_indices = (0..schema.fields().len())
+            .filter(|i| !key_indices.contains(i))
+            .collect::<Vec<_>>();
+        let out_schema = Arc::new(schema.project(&keep_indices)?);
+        let offset_field = Arc::new(Field::new("_offset", ArrowDataType::UInt64, false));
+        let mut keys_fields = vec![offset_field.clone()];
+        // The keys of the left side may be null.
+        keys_fields.extend(key_indices.iter().map(|i| {
+            let field = schema.field(*i);
+            Arc::new(Field::new(field.name(), field.data_type().clone(), true))
+        }));
+        let keys_schema = Arc::new(Schema::new(keys_fields));
+        let mut matches_fields = vec![offset_field];
+        matches_fields.extend(out_schema.fields().iter().cloned());
+        let matches_schema = Arc::new(Schema::new(matches_fields));
+        Ok(Self {
+            dir,
+            schema,
+            on: on.iter().map(|name| name.to_string()).collect(),
+            index_types,
+            num_partitions: params.num_partitions,
+            out_schema,
+            keys_schema,
+            matches_schema,
+        })
+    }
+
+    fn partition_file(partition: usize) -> String {
+        format!("partition-{partition}.arrow")
+    }
+
+    fn keys_file(fragment_id: u64, partition: usize) -> String {
+        format!("keys-{fragment_id}-{partition}.arrow")
+    }
+
+    fn matches_file(fragment_id: u64, partition: usize) -> String {
+        format!("matches-{fragment_id}-{partition}.arrow")
+    }
+
+    /// Returns the schema of the joined columns, excluding the key column.
+    pub(super) fn out_schema(&self) -> &SchemaRef {
+        &self.out_schema
+    }
+
+    pub(super) fn num_partitions(&self) -> usize {
+        self.num_partitions
+    }
+
+    /// Partition the left `key_columns` of a batch, whose first row is at `start_offset`
+    /// in the fragment.
+    ///
+    /// Returns the keys of each partition, prefixed with the offsets of the rows. They are
+    /// to be spilled with [Self::spill_left_keys].
+    pub(super) fn partition_left_keys(
+        &self,
+        key_columns: &[ArrayRef],
+        start_offset: u64,
+    ) -> Result<Vec<Option<RecordBatch>>> {
+        check_key_types(&self.index_types, key_columns)?;
+        let rows = columns_to_rows(key_columns)?;
+        partition_rows(&rows, self.num_partitions)
+            .into_iter()
+            .map(|row_indices| {
+                if row_indices.is_empty() {
+                    return Ok(None);
+                }
+                let offsets = UInt64Array::from_iter_values(
+                    row_indices.iter().map(|row_i| start_offset + *row_i as u64),
+                );
+                let mut columns: Vec<ArrayRef> = vec![Arc::new(offsets)];
+                columns.extend(take_columns(key_columns, &UInt32Array::from(row_indices))?);
+                Ok(Some(RecordBatch::try_new(
+                    self.keys_schema.clone(),
+                    columns,
+                )?))
+            })
+            .collect()
+    }
+
+    /// Spill the partitioned keys of a fragment, from [Self::partition_left_keys].
+    pub(super) async fn spill_left_keys(
+        &self,
+        fragment_id: u64,
+        partitions: &[Vec<RecordBatch>],
+    ) -> Result<()> {
+        for (partition, keys) in partitions.iter().enumerate() {
+            if !keys.is_empty() {
+                self.dir
+                    .append(
+                        &Self::keys_file(fragment_id, partition),
+                        &self.keys_schema,
+                        keys,
+                    )
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Load one partition of the right side into memory. Returns `None` if the
+    /// partition is empty.
+    pub(super) async fn load_partition(&self, partition: usize) -> Result<Option<HashJoiner>> {
+        let batches = self.dir.read(&Self::partition_file(partition)).await?;
+        if batches.is_empty() {
+            return Ok(None);
+        }
+        let on = self.on.iter().map(String::as_str).collect::<Vec<_>>();
+        let joiner = HashJoiner::try_from_batches(self.schema.clone(), batches, &on).await?;
+        Ok(Some(joiner))
+    }
+
+    /// Join the spilled left keys of a fragment with the loaded `partition`, and spill
+    /// the matched right rows, prefixed with the offsets of the left rows.
+    pub(super) async fn join_partition(
+        &self,
+        fragment_id: u64,
+        partition: usize,
+        joiner: &HashJoiner,
+    ) -> Result<()> {
+        let keys = self
+            .dir
+            .read(&Self::keys_file(fragment_id, partition))
+            .await?;
+        let mut matches = vec![];
+        for batch in keys {
+            let key_columns = &batch.columns()[1..];
+            let matched = joiner
+                .lookup(key_columns)?
+                .into_iter()
+                .enumerate()
+                .filter_map(|(row_i, found)| found.map(|_| row_i as u32))
+                .collect::<Vec<_>>();
+            if matched.is_empty() {
+                continue;
+            }
+            let matched = UInt32Array::from(matched);
+            let offsets = take(batch.column(0).as_ref(), &matched, None)?;
+            let right = joiner
+                .collect(&take_columns(key_columns, &matched)?)
+                .await?;
+            let mut columns = vec![offsets];
+            columns.extend(right.columns().iter().cloned());
+            matches.push(RecordBatch::try_new(self.matches_schema.clone(), columns)?);
+        }
+        if matches.is_empty() {
+            return Ok(());
+        }
+        self.dir
+            .append(
+                &Self::matches_file(fragment_id, partition),
+                &self.matches_schema,
+                &matches,
+            )
+            .await
+    }
+
+    /// Load the spilled matches of a fragment, from all the partitions.
+    pub(super) async fn load_matches(&self, fragment_id: u64) -> Result<SpilledMatches> {
+        let mut batches = vec![];
+        for partition in 0..self.num_partitions {
+            batches.extend(
+                self.dir
+                    .read(&Self::matches_file(fragment_id, partition))
+                    .await?,
+            );
+        }
+        let mut positions = HashMap::new();
+        for (batch_i, batch) in batches.iter().enumerate() {
+            let offsets = batch.column(0).as_primitive::<UInt64Type>();
+            for (row_i, offset) in offsets.values().iter().enumerate() {
+                positions.insert(*offset, (batch_i, row_i));
+            }
+        }
+        Ok(SpilledMatches {
+            batches,
+            positions,
+            out_schema: self.out_schema.clone(),
+        })
+    }
+}
+
+/// The matched right rows of one fragment, from all the partitions.
+pub(super) struct SpilledMatches {
+    batches: Vec<RecordBatch>,
+
+    /// Offset of the left row to the `(batch_i, row_i)` of the matched right row.
+    positions: HashMap<u64, (usize, usize)>,
+
+    out_schema: SchemaRef,
+}
+
+impl SpilledMatches {
+    /// Collect the joined columns for the left rows at `offsets`, with nulls for the
+    /// rows without a match.
+    pub(super) fn collect(&self, offsets: Range<u64>) -> Result<RecordBatch> {
+        // As in [HashJoiner::collect], the last array holds the null value.
+        let null_index = self.batches.len();
+        let indices = offsets
+            .map(|offset| {
+                self.positions
+                    .get(&offset)
+                    .copied()
+                    .unwrap_or((null_index, 0))
+            })
+            .collect::<Vec<_>>();
+
+        let columns = self
+            .out_schema
+            .fields()
+            .iter()
+            .enumerate()
+            .map(|(column_i, field)| {
+                let mut arrays = self
+                    .batches
+                    .iter()
+                    .map(|batch| batch.column(column_i + 1).as_ref())
+                    .collect::<Vec<_>>();
+                let null_array = new_null_array(field.data_type(), 1);
+                arrays.push(null_array.as_ref());
+                let array = interleave(&arrays, &indices)?;
+                check_supports_nulls(array.as_ref())?;
+                Ok(array)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(RecordBatch::try_new(self.out_schema.clone(), columns)?)
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -247,9 +856,9 @@ mod tests {
             batches.into_iter().map(Ok),
             schema.clone(),
         ));
-        let joiner = HashJoiner::try_new(batches, "i").await.unwrap();
+        let joiner = HashJoiner::try_new(batches, &["i"]).await.unwrap();
 
-        let indices = Arc::new(Int32Array::from_iter(&[
+        let indices: ArrayRef = Arc::new(Int32Array::from_iter(&[
             Some(15),
             None,
             Some(10),
@@ -259,7 +868,7 @@ mod tests {
             Some(22),
             Some(11111), // not found
         ]));
-        let results = joiner.collect(indices).await.unwrap();
+        let results = joiner.collect(&[indices]).await.unwrap();
 
         assert_eq!(
             results.column_by_name("s").unwrap().as_ref(),
@@ -278,6 +887,50 @@ mod tests {
         assert_eq!(results.num_columns(), 1);
     }
 
+    #[tokio::test]
+    async fn test_joiner_composite_keys() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("video_id", DataType::Utf8, false),
+            Field::new("frame_idx", DataType::Int32, false),
+            Field::new("label", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from(vec!["a", "a", "b"])),
+                Arc::new(Int32Array::from(vec![0, 1, 0])),
+                Arc::new(StringArray::from(vec!["a0", "a1", "b0"])),
+            ],
+        )
+        .unwrap();
+        let batches: Box<dyn RecordBatchReader + Send> = Box::new(RecordBatchIterator::new(
+            vec![batch].into_iter().map(Ok),
+            schema.clone(),
+        ));
+        let joiner = HashJoiner::try_new(batches, &["video_id", "frame_idx"])
+            .await
+            .unwrap();
+        assert_eq!(joiner.out_schema().fields().len(), 1);
+
+        let video_ids: ArrayRef = Arc::new(StringArray::from(vec!["b", "a", "b", "a"]));
+        let frame_idx: ArrayRef = Arc::new(Int32Array::from(vec![0, 1, 1, 0]));
+        let results = joiner
+            .collect(&[video_ids, frame_idx.clone()])
+            .await
+            .unwrap();
+        assert_eq!(
+            results.column_by_name("label").unwrap().as_ref(),
+            &StringArray::from(vec![Some("b0"), Some("a1"), None, Some("a0")])
+        );
+
+        // Missing key column.
+        let result = joiner.collect(&[frame_idx]).await;
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Index column type mismatch: expected Utf8, Int32, got Int32"));
+    }
+
     #[tokio::test]
     async fn test_reject_invalid() {
         let schema = Arc::new(Schema::new(vec![
@@ -298,15 +951,131 @@ mod tests {
             schema.clone(),
         ));
 
-        let joiner = HashJoiner::try_new(batches, "i").await.unwrap();
+        let joiner = HashJoiner::try_new(batches, &["i"]).await.unwrap();
 
         // Wrong type: was Int32, passing UInt32.
-        let indices = Arc::new(UInt32Array::from_iter(&[Some(15)]));
-        let result = joiner.collect(indices).await;
+        let indices: ArrayRef = Arc::new(UInt32Array::from_iter(&[Some(15)]));
+        let result = joiner.collect(&[indices]).await;
         assert!(result.is_err());
         assert!(result
             .unwrap_err()
             .to_string()
             .contains("Index column type mismatch: expected Int32, got UInt32"));
     }
+
+    #[tokio::test]
+    async fn test_spilled_joiner() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("i", DataType::Int32, true),
+            Field::new("s", DataType::Utf8, true),
+        ]));
+        let batches: Vec<RecordBatch> = (0..5)
+            .map(|v| {
+                let values = (v * 10..v * 10 + 10).collect::<Vec<_>>();
+                RecordBatch::try_new(
+                    schema.clone(),
+                    vec![
+                        Arc::new(Int32Array::from_iter(values.iter().copied())),
+                        Arc::new(StringArray::from_iter_values(
+                            values.iter().map(|v| format!("str_{}", v)),
+                        )),
+                    ],
+                )
+                .unwrap()
+            })
+            .collect();
+        let data_size = batches
+            .iter()
+            .map(|b| batch_data_size(b).unwrap())
+            .sum::<usize>();
+        let batches: Box<dyn RecordBatchReader + Send> = Box::new(RecordBatchIterator::new(
+            batches.into_iter().map(Ok),
+            schema.clone(),
+        ));
+
+        let spill_dir = tempfile::tempdir().unwrap();
+        let params = MergeParams {
+            memory_limit: data_size * 3 / 4,
+            num_partitions: 4,
+            spill_dir: Some(spill_dir.path().to_path_buf()),
+        };
+        let JoinSide::Spilled(joiner) = build_join_side(batches, &["i"], &params).await.unwrap()
+        else {
+            panic!("Expected the right side to be spilled");
+        };
+        assert_eq!(joiner.out_schema().fields().len(), 1);
+
+        let index_column: ArrayRef = Arc::new(Int32Array::from_iter(&[
+            Some(15),
+            None,
+            Some(10),
+            Some(0),
+            Some(49),
+            Some(11111), // not found
+        ]));
+        let partitions = joiner
+            .partition_left_keys(&[index_column], 100)
+            .unwrap()
+            .into_iter()
+            .map(|keys| keys.into_iter().collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        joiner.spill_left_keys(0, &partitions).await.unwrap();
+
+        let mut total_rows = 0;
+        for partition in 0..joiner.num_partitions() {
+            let Some(partition_joiner) = joiner.load_partition(partition).await.unwrap() else {
+                continue;
+            };
+            total_rows += partition_joiner.num_rows();
+            joiner
+                .join_partition(0, partition, &partition_joiner)
+                .await
+                .unwrap();
+        }
+        assert_eq!(total_rows, 50);
+
+        let matches = joiner.load_matches(0).await.unwrap();
+        let results = matches.collect(100..106).unwrap();
+        assert_eq!(
+            results.column_by_name("s").unwrap().as_ref(),
+            &StringArray::from(vec![
+                Some("str_15"),
+                None,
+                Some("str_10"),
+                Some("str_0"),
+                Some("str_49"),
+                None
+            ])
+        );
+
+        // Wrong type: was Int32, passing UInt32.
+        let indices: ArrayRef = Arc::new(UInt32Array::from_iter(&[Some(15)]));
+        assert!(joiner.partition_left_keys(&[indices], 0).is_err());
+
+        // A partition larger than the memory limit is rejected.
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![1; 100])),
+                Arc::new(StringArray::from_iter_values(
+                    (0..100).map(|v| v.to_string()),
+                )),
+            ],
+        )
+        .unwrap();
+        let params = MergeParams {
+            memory_limit: batch_data_size(&batch).unwrap() / 2,
+            ..params
+        };
+        let batches: Box<dyn RecordBatchReader + Send> =
+            Box::new(RecordBatchIterator::new(vec![Ok(batch)], schema.clone()));
+        assert!(matches!(
+            build_join_side(batches, &["i"], &params).await,
+            Err(Error::InvalidInput { .. })
+        ));
+
+        // The spilled files are removed with the joiner.
+        drop(joiner);
+        assert_eq!(std::fs::read_dir(spill_dir.path()).unwrap().count(), 0);
+    }
 }
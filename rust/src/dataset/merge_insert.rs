@@ -0,0 +1,525 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Merge insert, a.k.a. upsert, keyed on columns.
+//!
+//! ```rust,ignore
+//! let stats = dataset
+//!     .merge_insert(&["id"], source)
+//!     .when_matched_update_all()
+//!     .when_not_matched_insert_all()
+//!     .execute()
+//!     .await?;
+//! ```
+//!
+//! The keys of the source rows must be unique.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use arrow_array::{RecordBatch, RecordBatchIterator, RecordBatchReader};
+use arrow_schema::{ArrowError, Schema as ArrowSchema};
+use futures::stream::{self, StreamExt, TryStreamExt};
+
+use super::fragment::local_row_ids;
+use super::hash_joiner::{key_columns, HashJoiner};
+use super::transaction::{commit_transaction, Operation};
+use super::{write_fragments, Dataset, WriteParams};
+use crate::{Error, Result};
+
+/// Statistics of a merge insert.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MergeInsertStats {
+    /// Number of source rows inserted, whose keys did not exist in the dataset.
+    pub num_inserted_rows: usize,
+    /// Number of dataset rows replaced by the source rows with the same keys.
+    pub num_updated_rows: usize,
+    /// Number of dataset rows deleted, whose keys do not exist in the source.
+    pub num_deleted_rows: usize,
+}
+
+/// Builder of a merge insert, created by [Dataset::merge_insert].
+///
+/// By default, nothing is changed. The modes must be enabled explicitly.
+pub struct MergeInsertBuilder<'a> {
+    dataset: &'a mut Dataset,
+    on: Vec<String>,
+    source: Box<dyn RecordBatchReader + Send>,
+    update_matched: bool,
+    insert_not_matched: bool,
+    delete_not_matched_by_source: bool,
+}
+
+impl<'a> MergeInsertBuilder<'a> {
+    pub(super) fn new(
+        dataset: &'a mut Dataset,
+        on: Vec<String>,
+        source: Box<dyn RecordBatchReader + Send>,
+    ) -> Self {
+        Self {
+            dataset,
+            on,
+            source,
+            update_matched: false,
+            insert_not_matched: false,
+            delete_not_matched_by_source: false,
+        }
+    }
+
+    /// Replace the rows of the dataset whose keys match a source row, with the source row.
+    pub fn when_matched_update_all(mut self) -> Self {
+        self.update_matched = true;
+        self
+    }
+
+    /// Insert the source rows whose keys do not match any row in the dataset.
+    pub fn when_not_matched_insert_all(mut self) -> Self {
+        self.insert_not_matched = true;
+        self
+    }
+
+    /// Delete the rows of the dataset whose keys do not match any source row.
+    pub fn when_not_matched_by_source_delete(mut self) -> Self {
+        self.delete_not_matched_by_source = true;
+        self
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.on.is_empty() {
+            return Err(Error::invalid_input(
+                "Merge insert requires at least one key column",
+            ));
+        }
+        for name in &self.on {
+            if self.dataset.schema().field(name).is_none() {
+                return Err(Error::invalid_input(format!(
+                    "Key column {} does not exist in the dataset",
+                    name
+                )));
+            }
+        }
+        // The source rows are inserted as is, so the schemas must match.
+        let schema = ArrowSchema::from(self.dataset.schema());
+        let source_schema = self.source.schema();
+        let compatible = schema.fields().len() == source_schema.fields().len()
+            && schema.fields().iter().all(|field| {
+                source_schema
+                    .field_with_name(field.name())
+                    .map(|f| f.data_type() == field.data_type())
+                    .unwrap_or(false)
+            });
+        if !compatible {
+            return Err(Error::invalid_input(format!(
+                "Merge insert source schema does not match the dataset schema: \
+                source={source_schema:?} dataset={schema:?}"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Execute the merge insert, and commit the changes as a new version of the dataset.
+    pub async fn execute(self) -> Result<MergeInsertStats> {
+        self.validate()?;
+        let Self {
+            dataset,
+            on,
+            source,
+            update_matched,
+            insert_not_matched,
+            delete_not_matched_by_source,
+        } = self;
+        let mut stats = MergeInsertStats::default();
+        if !(update_matched || insert_not_matched || delete_not_matched_by_source) {
+            return Ok(stats);
+        }
+        let on = on.iter().map(String::as_str).collect::<Vec<_>>();
+        let on = on.as_slice();
+
+        let schema = source.schema();
+        let batches = source
+            .filter(|batch| batch.as_ref().map_or(true, |b| b.num_rows() > 0))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        if batches.is_empty() {
+            // No dataset row matches an empty source, and there is nothing to insert.
+            if !delete_not_matched_by_source || dataset.fragments().is_empty() {
+                return Ok(stats);
+            }
+            stats.num_deleted_rows = dataset.count_rows().await?;
+            let operation = Operation::Delete {
+                updated_fragments: vec![],
+                deleted_fragment_ids: dataset.fragments().iter().map(|f| f.id).collect(),
+            };
+            let manifest = commit_transaction(
+                &dataset.object_store,
+                &dataset.base,
+                Some(dataset.manifest.as_ref()),
+                &operation,
+            )
+            .await?;
+            dataset.manifest = Arc::new(manifest);
+            return Ok(stats);
+        }
+        let source = RecordBatchIterator::new(batches.into_iter().map(Ok::<_, ArrowError>), schema);
+        let joiner = HashJoiner::try_new(Box::new(source), on).await?;
+        let source_indices = joiner.row_indices();
+        if source_indices.len() != joiner.num_rows() {
+            return Err(Error::invalid_input(format!(
+                "Merge insert source has duplicate keys on {:?}",
+                on
+            )));
+        }
+
+        let mut updated_fragments = vec![];
+        let mut deleted_fragment_ids = vec![];
+        let mut matched = HashSet::new();
+        let joiner = &joiner;
+        let results = stream::iter(dataset.get_fragments())
+            .map(|fragment| async move {
+                let mut scanner = fragment.scan();
+                scanner.with_row_id().project(on)?;
+                let batches = scanner
+                    .try_into_stream()
+                    .await?
+                    .try_collect::<Vec<RecordBatch>>()
+                    .await?;
+
+                let mut matched = vec![];
+                let mut to_delete = vec![];
+                let mut num_updated = 0;
+                for batch in batches {
+                    let keys = joiner.lookup(&key_columns(&batch, on))?;
+                    for (row_id, key) in local_row_ids(&batch).zip(keys) {
+                        match key {
+                            Some(index) => {
+                                matched.push(index);
+                                if update_matched {
+                                    to_delete.push(row_id);
+                                    num_updated += 1;
+                                }
+                            }
+                            None if delete_not_matched_by_source => to_delete.push(row_id),
+                            None => {}
+                        }
+                    }
+                }
+                let num_deleted = to_delete.len() - num_updated;
+                let original = fragment.metadata().clone();
+                let fragment = fragment.delete_rows(to_delete).await?;
+                Ok::<_, Error>((
+                    original,
+                    fragment.map(|f| f.metadata),
+                    matched,
+                    num_updated,
+                    num_deleted,
+                ))
+            })
+            .buffer_unordered(num_cpus::get())
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        for (original, fragment, fragment_matched, num_updated, num_deleted) in results {
+            match fragment {
+                None => deleted_fragment_ids.push(original.id),
+                Some(fragment) if fragment != original => updated_fragments.push(fragment),
+                Some(_) => {}
+            }
+            matched.extend(fragment_matched);
+            stats.num_updated_rows += num_updated;
+            stats.num_deleted_rows += num_deleted;
+        }
+
+        // Source rows to write: the replacements of the matched rows, and the new rows.
+        let (updates, inserts): (Vec<_>, Vec<_>) = source_indices
+            .into_iter()
+            .partition(|index| matched.contains(index));
+        let mut indices = vec![];
+        if update_matched {
+            indices.extend(updates);
+        }
+        if insert_not_matched {
+            stats.num_inserted_rows = inserts.len();
+            indices.extend(inserts);
+        }
+        indices.sort();
+
+        if indices.is_empty() && updated_fragments.is_empty() && deleted_fragment_ids.is_empty() {
+            // Nothing changed.
+            return Ok(stats);
+        }
+
+        let new_fragments = if indices.is_empty() {
+            vec![]
+        } else {
+            let rows = joiner.take_rows(&indices)?;
+            // Align the columns with the dataset schema.
+            let schema = Arc::new(ArrowSchema::from(dataset.schema()));
+            let columns = schema
+                .fields()
+                .iter()
+                .map(|field| rows[field.name().as_str()].clone())
+                .collect::<Vec<_>>();
+            let batch = RecordBatch::try_new(schema, columns)?;
+            write_fragments(
+                &dataset.object_store,
+                &dataset.base,
+                dataset.schema(),
+                [Ok::<_, Error>(batch)],
+                &WriteParams::default(),
+            )
+            .await?
+        };
+
+        let operation = Operation::Update {
+            updated_fragments,
+            deleted_fragment_ids,
+            new_fragments,
+        };
+        let manifest = commit_transaction(
+            &dataset.object_store,
+            &dataset.base,
+            Some(dataset.manifest.as_ref()),
+            &operation,
+        )
+        .await?;
+        dataset.manifest = Arc::new(manifest);
+
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+    use std::ops::Range;
+
+    use arrow_array::cast::{as_primitive_array, as_string_array};
+    use arrow_array::types::Int32Type;
+    use arrow_array::{Int32Array, StringArray};
+    use arrow_schema::{DataType, Field};
+    use tempfile::tempdir;
+
+    fn test_schema() -> Arc<ArrowSchema> {
+        Arc::new(ArrowSchema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("value", DataType::Utf8, true),
+        ]))
+    }
+
+    fn source(ids: Range<i32>, prefix: &str) -> impl RecordBatchReader + Send + 'static {
+        let batch = RecordBatch::try_new(
+            test_schema(),
+            vec![
+                Arc::new(Int32Array::from_iter_values(ids.clone())),
+                Arc::new(StringArray::from_iter_values(
+                    ids.map(|id| format!("{prefix}-{id}")),
+                )),
+            ],
+        )
+        .unwrap();
+        RecordBatchIterator::new(vec![Ok(batch)], test_schema())
+    }
+
+    async fn read_values(dataset: &Dataset) -> HashMap<i32, String> {
+        let batches = dataset
+            .scan()
+            .try_into_stream()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let mut values = HashMap::new();
+        for batch in batches {
+            let ids = as_primitive_array::<Int32Type>(batch["id"].as_ref());
+            let strings = as_string_array(batch["value"].as_ref());
+            for (id, value) in ids.values().iter().zip(strings.iter()) {
+                assert!(values.insert(*id, value.unwrap().to_string()).is_none());
+            }
+        }
+        values
+    }
+
+    #[tokio::test]
+    async fn test_merge_insert() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let write_params = WriteParams {
+            max_rows_per_file: 5,
+            max_rows_per_group: 5,
+            ..Default::default()
+        };
+        let mut dataset = Dataset::write(source(0..10, "old"), test_uri, Some(write_params))
+            .await
+            .unwrap();
+
+        // Upsert
+        let stats = dataset
+            .merge_insert(&["id"], source(5..15, "new"))
+            .when_matched_update_all()
+            .when_not_matched_insert_all()
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(
+            stats,
+            MergeInsertStats {
+                num_inserted_rows: 5,
+                num_updated_rows: 5,
+                num_deleted_rows: 0,
+            }
+        );
+        assert_eq!(dataset.version().version, 2);
+        dataset.validate().await.unwrap();
+        let values = read_values(&dataset).await;
+        assert_eq!(values.len(), 15);
+        for id in 0..15 {
+            let prefix = if id < 5 { "old" } else { "new" };
+            assert_eq!(values[&id], format!("{prefix}-{id}"));
+        }
+
+        // Insert only: existing rows are kept.
+        let stats = dataset
+            .merge_insert(&["id"], source(10..20, "newer"))
+            .when_not_matched_insert_all()
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(stats.num_inserted_rows, 5);
+        assert_eq!(stats.num_updated_rows, 0);
+        let values = read_values(&dataset).await;
+        assert_eq!(values.len(), 20);
+        assert_eq!(values[&10], "new-10");
+        assert_eq!(values[&15], "newer-15");
+
+        // Replace the whole dataset with the source.
+        let stats = dataset
+            .merge_insert(&["id"], source(0..3, "latest"))
+            .when_matched_update_all()
+            .when_not_matched_insert_all()
+            .when_not_matched_by_source_delete()
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(
+            stats,
+            MergeInsertStats {
+                num_inserted_rows: 0,
+                num_updated_rows: 3,
+                num_deleted_rows: 17,
+            }
+        );
+        dataset.validate().await.unwrap();
+        let values = read_values(&dataset).await;
+        assert_eq!(
+            values,
+            HashMap::from_iter((0..3).map(|id| (id, format!("latest-{id}"))))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_merge_insert_empty_source() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let mut dataset = Dataset::write(source(0..10, "old"), test_uri, None)
+            .await
+            .unwrap();
+
+        // Nothing to update or insert: the dataset is unchanged.
+        let empty = || {
+            let batches: Vec<std::result::Result<RecordBatch, ArrowError>> = vec![];
+            RecordBatchIterator::new(batches, test_schema())
+        };
+        let stats = dataset
+            .merge_insert(&["id"], empty())
+            .when_matched_update_all()
+            .when_not_matched_insert_all()
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(stats, MergeInsertStats::default());
+        let stats = dataset
+            .merge_insert(&["id"], source(0..0, "new"))
+            .when_matched_update_all()
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(stats, MergeInsertStats::default());
+        assert_eq!(dataset.version().version, 1);
+        assert_eq!(read_values(&dataset).await.len(), 10);
+
+        // No row matches the source, so all of them are deleted.
+        let stats = dataset
+            .merge_insert(&["id"], empty())
+            .when_matched_update_all()
+            .when_not_matched_by_source_delete()
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(stats.num_deleted_rows, 10);
+        assert_eq!(dataset.version().version, 2);
+        assert_eq!(dataset.count_rows().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_merge_insert_invalid() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let mut dataset = Dataset::write(source(0..10, "old"), test_uri, None)
+            .await
+            .unwrap();
+
+        let result = dataset
+            .merge_insert(&["id", "missing"], source(0..10, "new"))
+            .when_matched_update_all()
+            .execute()
+            .await;
+        assert!(matches!(result, Err(Error::InvalidInput { .. })));
+
+        let schema = Arc::new(ArrowSchema::new(vec![Field::new(
+            "id",
+            DataType::Int32,
+            false,
+        )]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values(0..10))],
+        )
+        .unwrap();
+        let result = dataset
+            .merge_insert(&["id"], RecordBatchIterator::new(vec![Ok(batch)], schema))
+            .when_matched_update_all()
+            .execute()
+            .await;
+        assert!(matches!(result, Err(Error::InvalidInput { .. })));
+
+        // Duplicate source keys are rejected.
+        let duplicates = source(0..5, "new").chain(source(3..6, "new"));
+        let result = dataset
+            .merge_insert(
+                &["id"],
+                RecordBatchIterator::new(duplicates.collect::<Vec<_>>(), test_schema()),
+            )
+            .when_matched_update_all()
+            .when_not_matched_insert_all()
+            .execute()
+            .await;
+        assert!(matches!(result, Err(Error::InvalidInput { .. })));
+        assert_eq!(dataset.version().version, 1);
+    }
+}
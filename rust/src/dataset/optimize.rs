@@ -0,0 +1,475 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Compaction of the dataset files.
+//!
+//! Frequent small appends and deletes leave many small fragments and large deletion
+//! files behind. Compaction rewrites runs of adjacent small fragments, and fragments with
+//! many deleted rows, into fewer, larger fragments without the deleted rows.
+//!
+//! The row ids of the rewritten fragments change, so the indices covering them become
+//! invalid. They are only dropped if [CompactionOptions::drop_indices] is set.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use super::fragment::FileFragment;
+use super::transaction::{commit_transaction, Operation, RewriteGroup};
+use super::{write_fragment_stream, Dataset, WriteParams};
+use crate::format::Index;
+use crate::{Error, Result};
+
+/// Options of [Dataset::compact_files].
+#[derive(Debug, Clone)]
+pub struct CompactionOptions {
+    /// The number of rows each fragment should have after compaction.
+    /// Fragments with fewer rows are candidates for compaction.
+    pub target_rows_per_fragment: usize,
+
+    /// Max number of rows per row group of the rewritten files.
+    pub max_rows_per_group: usize,
+
+    /// Fragments with a larger fraction of deleted rows are rewritten,
+    /// to materialize the deletions.
+    pub max_deletion_fraction: f32,
+
+    /// Whether to rewrite fragments to materialize their deletions.
+    pub materialize_deletions: bool,
+
+    /// Whether to drop the indices covering the rewritten fragments.
+    /// If false, compaction fails if any index covers a fragment to rewrite.
+    pub drop_indices: bool,
+}
+
+impl Default for CompactionOptions {
+    fn default() -> Self {
+        Self {
+            target_rows_per_fragment: 1024 * 1024, // 1 million
+            max_rows_per_group: 1024,
+            max_deletion_fraction: 0.1,
+            materialize_deletions: true,
+            drop_indices: false,
+        }
+    }
+}
+
+/// Metrics of a compaction.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CompactionMetrics {
+    pub fragments_removed: usize,
+    pub fragments_added: usize,
+    /// Data and deletion files no longer referenced by the new version.
+    pub files_removed: usize,
+    pub files_added: usize,
+    /// Indices dropped because they covered rewritten fragments.
+    pub indices_removed: usize,
+}
+
+/// Row counts of a fragment.
+struct FragmentStats {
+    fragment: FileFragment,
+    num_rows: usize,
+    num_deleted_rows: usize,
+}
+
+impl FragmentStats {
+    async fn try_new(fragment: FileFragment) -> Result<Self> {
+        let physical_rows = fragment.fragment_length().await?;
+        let num_rows = fragment.count_rows().await?;
+        Ok(Self {
+            fragment,
+            num_rows,
+            num_deleted_rows: physical_rows - num_rows,
+        })
+    }
+
+    fn needs_materialization(&self, options: &CompactionOptions) -> bool {
+        options.materialize_deletions
+            && self.num_deleted_rows as f32
+                > (self.num_rows + self.num_deleted_rows) as f32 * options.max_deletion_fraction
+    }
+
+    fn is_candidate(&self, options: &CompactionOptions) -> bool {
+        self.num_rows < options.target_rows_per_fragment || self.needs_materialization(options)
+    }
+}
+
+/// Plan the groups of adjacent fragments to rewrite together.
+///
+/// Adjacent candidates are grouped until a group reaches the target number of rows.
+/// A group with a single fragment is only rewritten to materialize its deletions.
+fn plan_compaction(
+    fragments: Vec<FragmentStats>,
+    options: &CompactionOptions,
+) -> Vec<Vec<FragmentStats>> {
+    let mut groups: Vec<Vec<FragmentStats>> = vec![];
+    let mut current: Vec<FragmentStats> = vec![];
+    let mut current_rows = 0;
+    let flush = |current: &mut Vec<FragmentStats>, groups: &mut Vec<Vec<FragmentStats>>| {
+        let group = std::mem::take(current);
+        let worth_rewriting = group.len() > 1
+            || group
+                .first()
+                .map_or(false, |f| f.needs_materialization(options));
+        if worth_rewriting {
+            groups.push(group);
+        }
+    };
+
+    for fragment in fragments {
+        if !fragment.is_candidate(options) {
+            flush(&mut current, &mut groups);
+            current_rows = 0;
+            continue;
+        }
+        if current_rows + fragment.num_rows > options.target_rows_per_fragment
+            && !current.is_empty()
+        {
+            flush(&mut current, &mut groups);
+            current_rows = 0;
+        }
+        current_rows += fragment.num_rows;
+        current.push(fragment);
+    }
+    flush(&mut current, &mut groups);
+    groups
+}
+
+/// Split the indices of the dataset into the ones covering any fragment of the plan,
+/// and the others.
+async fn partition_indices(
+    dataset: &Dataset,
+    plan: &[Vec<FragmentStats>],
+) -> Result<(Vec<Index>, Vec<Index>)> {
+    let rewritten = plan
+        .iter()
+        .flatten()
+        .map(|f| f.fragment.metadata().id)
+        .collect::<HashSet<_>>();
+    let mut covering = vec![];
+    let mut others = vec![];
+    for index in dataset.load_indices().await? {
+        let indexed = dataset.checkout_version(index.dataset_version).await?;
+        if indexed
+            .fragments()
+            .iter()
+            .any(|f| rewritten.contains(&f.id))
+        {
+            covering.push(index);
+        } else {
+            others.push(index);
+        }
+    }
+    Ok((covering, others))
+}
+
+/// Rewrite the fragments of a group into new fragments.
+///
+/// The rows are streamed from the scan into the writer.
+async fn rewrite_group(
+    dataset: &Dataset,
+    group: &[FragmentStats],
+    options: &CompactionOptions,
+) -> Result<RewriteGroup> {
+    let old_fragments = group
+        .iter()
+        .map(|f| f.fragment.metadata().clone())
+        .collect::<Vec<_>>();
+
+    let mut scanner = dataset.scan();
+    scanner.with_fragments(old_fragments.clone());
+    let batches = scanner.try_into_stream().await?;
+
+    let params = WriteParams {
+        max_rows_per_file: options.target_rows_per_fragment,
+        max_rows_per_group: options.max_rows_per_group,
+        ..Default::default()
+    };
+    let new_fragments = write_fragment_stream(
+        &dataset.object_store,
+        &dataset.base,
+        dataset.schema(),
+        batches,
+        &params,
+    )
+    .await?;
+
+    Ok(RewriteGroup {
+        old_fragments,
+        new_fragments,
+    })
+}
+
+/// Compact the files of the dataset, and commit the result as a new version.
+pub(super) async fn compact_files(
+    dataset: &mut Dataset,
+    options: CompactionOptions,
+) -> Result<CompactionMetrics> {
+    if options.target_rows_per_fragment == 0 {
+        return Err(Error::invalid_input(
+            "target_rows_per_fragment must be greater than zero",
+        ));
+    }
+
+    let mut fragments = vec![];
+    for fragment in dataset.get_fragments() {
+        fragments.push(FragmentStats::try_new(fragment).await?);
+    }
+    let plan = plan_compaction(fragments, &options);
+
+    let mut metrics = CompactionMetrics::default();
+    if plan.is_empty() {
+        return Ok(metrics);
+    }
+
+    let (covering, retained) = partition_indices(dataset, &plan).await?;
+    if !covering.is_empty() && !options.drop_indices {
+        let names = covering
+            .iter()
+            .map(|idx| idx.name.as_str())
+            .collect::<Vec<_>>();
+        return Err(Error::invalid_input(format!(
+            "Compaction would invalidate the indices {names:?}, \
+            set drop_indices to drop them"
+        )));
+    }
+    metrics.indices_removed = covering.len();
+
+    let mut groups = vec![];
+    for group in plan.iter() {
+        let group = rewrite_group(dataset, group, &options).await?;
+        metrics.fragments_removed += group.old_fragments.len();
+        metrics.files_removed += group
+            .old_fragments
+            .iter()
+            .map(|f| f.files.len() + f.deletion_file.is_some() as usize)
+            .sum::<usize>();
+        metrics.fragments_added += group.new_fragments.len();
+        metrics.files_added += group
+            .new_fragments
+            .iter()
+            .map(|f| f.files.len())
+            .sum::<usize>();
+        groups.push(group);
+    }
+
+    let operation = Operation::Rewrite {
+        groups,
+        retained_indices: retained.iter().map(|idx| idx.uuid).collect(),
+    };
+    let manifest = commit_transaction(
+        &dataset.object_store,
+        &dataset.base,
+        Some(dataset.manifest.as_ref()),
+        &operation,
+    )
+    .await?;
+    dataset.manifest = Arc::new(manifest);
+
+    Ok(metrics)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::UInt32Array;
+    use futures::TryStreamExt;
+    use tempfile::tempdir;
+
+    use crate::dataset::WriteMode;
+    use crate::index::scalar::ScalarIndexParams;
+    use crate::index::{DatasetIndexExt, IndexType};
+    use crate::utils::testing::sequence_batches;
+
+    async fn read_values(dataset: &Dataset) -> Vec<u32> {
+        read_filtered_values(dataset, None).await
+    }
+
+    async fn read_filtered_values(dataset: &Dataset, filter: Option<&str>) -> Vec<u32> {
+        let mut scanner = dataset.scan();
+        if let Some(filter) = filter {
+            scanner.filter(filter).unwrap();
+        }
+        let batches = scanner
+            .try_into_stream()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        batches
+            .iter()
+            .flat_map(|b| {
+                b["i"]
+                    .as_any()
+                    .downcast_ref::<UInt32Array>()
+                    .unwrap()
+                    .values()
+                    .to_vec()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_compact_small_fragments() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        Dataset::write(sequence_batches(0..100), test_uri, None)
+            .await
+            .unwrap();
+        for i in 1..10 {
+            let write_params = WriteParams {
+                mode: WriteMode::Append,
+                ..Default::default()
+            };
+            Dataset::write(
+                sequence_batches(i * 100..(i + 1) * 100),
+                test_uri,
+                Some(write_params),
+            )
+            .await
+            .unwrap();
+        }
+        let mut dataset = Dataset::open(test_uri).await.unwrap();
+        assert_eq!(dataset.get_fragments().len(), 10);
+
+        let options = CompactionOptions {
+            target_rows_per_fragment: 500,
+            ..Default::default()
+        };
+        let metrics = dataset.compact_files(options.clone()).await.unwrap();
+        assert_eq!(
+            metrics,
+            CompactionMetrics {
+                fragments_removed: 10,
+                fragments_added: 2,
+                files_removed: 10,
+                files_added: 2,
+                indices_removed: 0,
+            }
+        );
+        assert_eq!(dataset.version().version, 11);
+        assert_eq!(dataset.get_fragments().len(), 2);
+        dataset.validate().await.unwrap();
+        assert_eq!(read_values(&dataset).await, (0..1000).collect::<Vec<_>>());
+
+        // Nothing left to compact.
+        let metrics = dataset.compact_files(options).await.unwrap();
+        assert_eq!(metrics, CompactionMetrics::default());
+        assert_eq!(dataset.version().version, 11);
+    }
+
+    #[tokio::test]
+    async fn test_materialize_deletions() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let mut dataset = Dataset::write(sequence_batches(0..1000), test_uri, None)
+            .await
+            .unwrap();
+        dataset.delete("i < 300").await.unwrap();
+
+        // Deletions are kept if not asked to materialize them.
+        let options = CompactionOptions {
+            target_rows_per_fragment: 100,
+            materialize_deletions: false,
+            ..Default::default()
+        };
+        let metrics = dataset.compact_files(options).await.unwrap();
+        assert_eq!(metrics, CompactionMetrics::default());
+
+        let metrics = dataset
+            .compact_files(CompactionOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            metrics,
+            CompactionMetrics {
+                fragments_removed: 1,
+                fragments_added: 1,
+                files_removed: 2,
+                files_added: 1,
+                indices_removed: 0,
+            }
+        );
+        let fragments = dataset.get_fragments();
+        assert_eq!(fragments.len(), 1);
+        assert!(fragments[0].metadata().deletion_file.is_none());
+        assert_eq!(fragments[0].fragment_length().await.unwrap(), 700);
+        assert_eq!(read_values(&dataset).await, (300..1000).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn test_compact_with_index() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let dataset = Dataset::write(sequence_batches(0..1000), test_uri, None)
+            .await
+            .unwrap();
+        dataset
+            .create_index(
+                &["i"],
+                IndexType::BTree,
+                None,
+                &ScalarIndexParams::default(),
+                false,
+            )
+            .await
+            .unwrap();
+        for i in 10..12 {
+            let write_params = WriteParams {
+                mode: WriteMode::Append,
+                ..Default::default()
+            };
+            Dataset::write(
+                sequence_batches(i * 100..(i + 1) * 100),
+                test_uri,
+                Some(write_params),
+            )
+            .await
+            .unwrap();
+        }
+        let mut dataset = Dataset::open(test_uri).await.unwrap();
+
+        // The appended fragments are not indexed, so the index is kept.
+        let options = CompactionOptions {
+            target_rows_per_fragment: 500,
+            ..Default::default()
+        };
+        let metrics = dataset.compact_files(options.clone()).await.unwrap();
+        assert_eq!(metrics.fragments_removed, 2);
+        assert_eq!(metrics.indices_removed, 0);
+        assert_eq!(dataset.load_indices().await.unwrap().len(), 1);
+        let mut values = read_filtered_values(&dataset, Some("i >= 950")).await;
+        values.sort();
+        assert_eq!(values, (950..1200).collect::<Vec<_>>());
+
+        // Materializing the deletions of the indexed fragment invalidates the index.
+        dataset.delete("i < 200").await.unwrap();
+        let err = dataset.compact_files(options.clone()).await.unwrap_err();
+        assert!(matches!(err, Error::InvalidInput { .. }), "{err}");
+
+        let options = CompactionOptions {
+            drop_indices: true,
+            ..options
+        };
+        let metrics = dataset.compact_files(options).await.unwrap();
+        assert_eq!(metrics.indices_removed, 1);
+        assert!(dataset.load_indices().await.unwrap().is_empty());
+        assert_eq!(read_values(&dataset).await, (200..1200).collect::<Vec<_>>());
+    }
+}
@@ -19,25 +19,32 @@ use std::task::{Context, Poll};
 use arrow_array::{Float32Array, RecordBatch};
 use arrow_schema::DataType;
 use arrow_schema::{Field as ArrowField, Schema as ArrowSchema, SchemaRef};
+use async_recursion::async_recursion;
 use datafusion::execution::{
     context::SessionState,
     runtime_env::{RuntimeConfig, RuntimeEnv},
 };
+use datafusion::logical_expr::{BinaryExpr, Operator};
 use datafusion::physical_plan::repartition::RepartitionExec;
 use datafusion::physical_plan::{
-    filter::FilterExec, limit::GlobalLimitExec, union::UnionExec, ExecutionPlan,
+    filter::FilterExec, limit::GlobalLimitExec, union::UnionExec, ExecutionPlan, PhysicalExpr,
     SendableRecordBatchStream,
 };
 use datafusion::prelude::*;
-use futures::stream::{Stream, StreamExt};
+use futures::stream::{self, Stream, StreamExt, TryStreamExt};
 
+use super::fragment::FileFragment;
 use super::Dataset;
 use crate::datafusion::physical_expr::column_names_in_expr;
 use crate::datatypes::Schema;
 use crate::format::{Fragment, Index};
-use crate::index::vector::{MetricType, Query};
+use crate::index::inverted::{FullTextQuery, InvertedIndex};
+use crate::index::scalar::{open_scalar_index, ScalarQuery};
+use crate::index::vector::{MetricType, Query, SCORE_COL};
 use crate::io::exec::{
-    KNNFlatExec, KNNIndexExec, LanceScanExec, Planner, ProjectionExec, TakeExec,
+    FullTextSearchExec, Fusion, HybridSearchExec, KNNFlatExec, KNNIndexExec, LanceScanExec,
+    Planner, ProjectionExec, ScalarIndexExec, ScalarIndexExpr, ScalarIndexQuery, StatisticsPruner,
+    TakeExec,
 };
 use crate::io::RecordBatchStream;
 use crate::utils::sql::parse_sql_filter;
@@ -88,6 +95,15 @@ pub struct Scanner {
 
     nearest: Option<Query>,
 
+    /// Apply the filter before the vector search.
+    prefilter: bool,
+
+    /// Full text search on a string column with an inverted index.
+    full_text_query: Option<FullTextQuery>,
+
+    /// How to fuse the results of the nearest query and the full text search.
+    fusion: Fusion,
+
     /// Scan the dataset with a meta column: "_rowid"
     with_row_id: bool,
 
@@ -111,6 +127,9 @@ impl Scanner {
             limit: None,
             offset: None,
             nearest: None,
+            prefilter: false,
+            full_text_query: None,
+            fusion: Fusion::default(),
             with_row_id: false,
             ordered: true,
             fragments: None,
@@ -129,6 +148,9 @@ impl Scanner {
             limit: None,
             offset: None,
             nearest: None,
+            prefilter: false,
+            full_text_query: None,
+            fusion: Fusion::default(),
             with_row_id: false,
             ordered: true,
             fragments: Some(vec![fragment]),
@@ -264,6 +286,7 @@ impl Scanner {
             refine_factor: None,
             metric_type: MetricType::L2,
             use_index: true,
+            allow_list: None,
         });
         Ok(self)
     }
@@ -301,6 +324,51 @@ impl Scanner {
         self
     }
 
+    /// Set whether to apply the filter before the vector search, instead of after it.
+    ///
+    /// With a prefilter, the rows matching the filter are found first, and the ANN
+    /// index only searches them, so a selective filter still returns `k` rows.
+    pub fn prefilter(&mut self, should_prefilter: bool) -> &mut Self {
+        self.prefilter = should_prefilter;
+        self
+    }
+
+    /// Search the rows of a string column matching the query, with the inverted
+    /// index of the column.
+    ///
+    /// The rows are sorted by their BM25 score, emitted as the `score` column.
+    pub fn full_text_search(&mut self, column: &str, query: &str) -> Result<&mut Self> {
+        self.ensure_not_fragment_scan()?;
+
+        let field = self
+            .dataset
+            .schema()
+            .field(column)
+            .ok_or_else(|| Error::IO {
+                message: format!("Column {column} not found"),
+            })?;
+        if !matches!(field.data_type(), DataType::Utf8 | DataType::LargeUtf8) {
+            return Err(Error::IO {
+                message: format!("Full text search: column {column} is not a string column"),
+            });
+        }
+        self.full_text_query = Some(FullTextQuery {
+            column: column.to_string(),
+            query: query.to_string(),
+        });
+        Ok(self)
+    }
+
+    /// Set how to fuse the results of a hybrid search, i.e., both a nearest query and
+    /// a full text search. The default is the reciprocal rank fusion.
+    ///
+    /// A hybrid search returns the top `k` rows, `k` being the one of the nearest
+    /// query, with their fused `score`, higher is better.
+    pub fn fusion(&mut self, fusion: Fusion) -> &mut Self {
+        self.fusion = fusion;
+        self
+    }
+
     /// Instruct the scanner to return the `_rowid` meta column from the dataset.
     pub fn with_row_id(&mut self) -> &mut Self {
         self.with_row_id = true;
@@ -328,6 +396,8 @@ impl Scanner {
             })?;
             extra_columns.push(vector_field);
             extra_columns.push(ArrowField::new("score", DataType::Float32, false));
+        } else if self.full_text_query.is_some() {
+            extra_columns.push(ArrowField::new("score", DataType::Float32, false));
         };
         if self.with_row_id {
             extra_columns.push(ArrowField::new(ROW_ID, DataType::UInt64, false));
@@ -377,6 +447,31 @@ impl Scanner {
     ///     -> Take(remaining_cols) -> Projection()
     ///  ```
     ///
+    ///  - **Use scalar indices for the filter, and/or limits.**
+    ///
+    ///  ```ignore
+    ///  ScalarIndex(queries) -> Take(filtered_cols) -> Filter(expr)
+    ///     -> (*LimitExec(limit, offset))
+    ///     -> Take(remaining_cols) -> Projection()
+    ///  ```
+    ///
+    ///  - **Full text search with an inverted index (with filter and/or limits)**
+    ///
+    ///  ```ignore
+    ///  FullTextSearch(query) -> Take(filtered_cols) -> Filter(expr)
+    ///     -> (*LimitExec(limit, offset))
+    ///     -> Take(remaining_cols) -> Projection()
+    ///  ```
+    ///
+    ///  - **Hybrid search, fusing KNN and full text search (with filter and/or limits)**
+    ///
+    ///  ```ignore
+    ///  HybridSearch(KNN(), FullTextSearch(query))
+    ///     -> Take(filtered_cols) -> Filter(expr)
+    ///     -> (*LimitExec(limit, offset))
+    ///     -> Take(remaining_cols) -> Projection()
+    ///  ```
+    ///
     ///  - **Use KNN Index (with filter and/or limits)**
     ///
     /// ```ignore
@@ -386,6 +481,24 @@ impl Scanner {
     ///     -> Take(remaining_cols) -> Projection()
     /// ```
     ///
+    /// - **Use KNN Index on the prefiltered rows (with limits)**
+    ///
+    /// ```ignore
+    /// (ScalarIndex(queries) | Scan(filtered_cols)) -> Take(filtered_cols) -> Filter(expr)
+    ///     -> KNNIndex() -> Take(vector) -> FlatRefine()
+    ///     -> (*LimitExec(limit, offset))
+    ///     -> Take(remaining_cols) -> Projection()
+    /// ```
+    ///
+    /// - **Use KNN flat (brute force) on the prefiltered rows (with limits)**
+    ///
+    /// ```ignore
+    /// (ScalarIndex(queries) | Scan(filtered_cols)) -> Take(filtered_cols) -> Filter(expr)
+    ///     -> Take(vector) -> FlatKNN()
+    ///     -> (*LimitExec(limit, offset))
+    ///     -> Take(remaining_cols) -> Projection()
+    /// ```
+    ///
     /// - **Use KNN flat (brute force) with filter and/or limits**
     ///
     /// ```ignore
@@ -404,21 +517,43 @@ impl Scanner {
     async fn create_plan(&self) -> Result<Arc<dyn ExecutionPlan>> {
         // NOTE: we only support node that have one partition. So any nodes that
         // produce multiple need to be repartitioned to 1.
-        let filter_expr = if let Some(filter) = self.filter.as_ref() {
+        let (logical_filter, filter_expr) = if let Some(filter) = self.filter.as_ref() {
             let planner = Planner::new(Arc::new(self.dataset.schema().into()));
             let logical_expr = planner.parse_filter(filter)?;
-            Some(planner.create_physical_expr(&logical_expr)?)
+            let physical_expr = planner.create_physical_expr(&logical_expr)?;
+            (Some(logical_expr), Some(physical_expr))
         } else {
-            None
+            (None, None)
         };
 
         // Stage 1: source
-        let mut plan: Arc<dyn ExecutionPlan> = if self.nearest.is_some() {
-            self.knn().await?
+        //
+        // The scalar indices are only used to find the rows matching the filter before
+        // the KNN search if it is prefiltered, otherwise the results are post-filtered.
+        let index_plan = match logical_filter.as_ref() {
+            Some(filter)
+                if self.full_text_query.is_none() && (self.nearest.is_none() || self.prefilter) =>
+            {
+                self.scalar_index_search(filter).await?
+            }
+            _ => None,
+        };
+        let mut prefiltered = false;
+        let mut plan: Arc<dyn ExecutionPlan> = if let Some(q) = self.full_text_query.as_ref() {
+            self.text_search(q).await?
+        } else if self.nearest.is_some() {
+            match filter_expr.as_ref() {
+                Some(predicates) if self.prefilter => {
+                    prefiltered = true;
+                    self.knn(Some(predicates), index_plan).await?
+                }
+                _ => self.knn(None, None).await?,
+            }
+        } else if let Some(index_plan) = index_plan {
+            // The filter is applied again on the rows found by the index.
+            index_plan
         } else if let Some(expr) = filter_expr.as_ref() {
-            let columns_in_filter = column_names_in_expr(expr.as_ref());
-            let filter_schema = Arc::new(self.dataset.schema().project(&columns_in_filter)?);
-            self.scan(true, filter_schema)
+            self.filtered_scan(expr).await?
         } else {
             // Scan without filter or limits
             self.scan(self.with_row_id, self.projections.clone().into())
@@ -426,14 +561,9 @@ impl Scanner {
 
         // Stage 2: filter
         if let Some(predicates) = filter_expr.as_ref() {
-            let columns_in_filter = column_names_in_expr(predicates.as_ref());
-            let filter_schema = Arc::new(self.dataset.schema().project(&columns_in_filter)?);
-            let remaining_schema = filter_schema.exclude(plan.schema().as_ref())?;
-            if !remaining_schema.fields.is_empty() {
-                // Not all columns for filter are ready, so we need to take them first
-                plan = self.take(plan, &remaining_schema)?;
+            if !prefiltered {
+                plan = self.filter_rows(plan, predicates.clone())?;
             }
-            plan = Arc::new(FilterExec::try_new(predicates.clone(), plan)?);
         }
 
         // Stage 3: limit / offset
@@ -452,10 +582,44 @@ impl Scanner {
         Ok(plan)
     }
 
+    /// Full text search, fused with the KNN search if there is a nearest query.
+    async fn text_search(&self, text_query: &FullTextQuery) -> Result<Arc<dyn ExecutionPlan>> {
+        let text_plan = self.fts(text_query).await?;
+        let Some(q) = self.nearest.as_ref() else {
+            return Ok(text_plan);
+        };
+        let vector_plan = self.knn(None, None).await?;
+        Ok(Arc::new(HybridSearchExec::try_new(
+            vector_plan,
+            text_plan,
+            self.fusion,
+            q.k,
+        )?))
+    }
+
     // KNN search execution node.
-    async fn knn(&self) -> Result<Arc<dyn ExecutionPlan>> {
+    //
+    // With a `prefilter`, only the rows matching it are searched. The `index_plan`,
+    // if any, finds the rows which may match the prefilter with the scalar indices.
+    async fn knn(
+        &self,
+        prefilter: Option<&Arc<dyn PhysicalExpr>>,
+        index_plan: Option<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
         let Some(q) = self.nearest.as_ref() else {
-            return Err(Error::IO{message:"No nearest query".to_string()});
+            return Err(Error::IO {
+                message: "No nearest query".to_string(),
+            });
+        };
+        let allowed = match prefilter {
+            Some(predicates) => {
+                let plan = match index_plan {
+                    Some(index_plan) => index_plan,
+                    None => self.filtered_scan(predicates).await?,
+                };
+                Some(self.filter_rows(plan, predicates.clone())?)
+            }
+            None => None,
         };
 
         let column_id = self.dataset.schema().field_id(q.column.as_str())?;
@@ -477,7 +641,7 @@ impl Scanner {
                 }
             }
 
-            let knn_node = self.ann(q, index)?; // score, _rowid
+            let knn_node = self.ann(q, index, allowed)?; // score, _rowid
             let with_vector = self.dataset.schema().project(&[&q.column])?;
             let knn_node_with_vector = self.take(knn_node, &with_vector)?;
             let mut knn_node = if q.refine_factor.is_some() {
@@ -486,9 +650,14 @@ impl Scanner {
                 knn_node_with_vector
             }; // vector, score, _rowid
 
-            knn_node = self.knn_combined(&q, index, knn_node).await?;
+            knn_node = self.knn_combined(&q, index, knn_node, prefilter).await?;
 
             Ok(knn_node)
+        } else if let Some(allowed) = allowed {
+            // No index found. use flat search on the prefiltered rows.
+            let vector_schema = self.dataset.schema().project(&[&q.column])?;
+            let plan = self.take(allowed, &vector_schema)?;
+            self.flat_knn(plan, q)
         } else {
             // No index found. use flat search.
             let vector_scan_projection =
@@ -499,11 +668,14 @@ impl Scanner {
     }
 
     /// Combine ANN results with KNN results for data appended after index creation
+    ///
+    /// With a `prefilter`, only the appended rows matching it are searched.
     async fn knn_combined(
         &self,
         q: &&Query,
         index: &Index,
         knn_node: Arc<dyn ExecutionPlan>,
+        prefilter: Option<&Arc<dyn PhysicalExpr>>,
     ) -> Result<Arc<dyn ExecutionPlan>> {
         // Check if we've created new versions since the index
         let version = index.dataset_version;
@@ -527,16 +699,26 @@ impl Scanner {
                 let scan_node = self.scan_fragments(
                     true,
                     vector_scan_projection,
+                    None,
                     Arc::new(self.dataset.manifest.fragments_since(&ds.manifest)?),
                     self.ordered,
                 );
+                let scan_node = match prefilter {
+                    Some(predicates) => self.filter_rows(scan_node, predicates.clone())?,
+                    None => scan_node,
+                };
                 // first we do flat search on just the new data
                 let topk_appended = self.flat_knn(scan_node, q)?;
 
                 // To do a union, we need to make the schemas match. Right now
                 // knn_node: score, _rowid, vector
-                // topk_appended: vector, _rowid, score
-                let new_schema = Schema::try_from(&topk_appended.schema().project(&[2, 1, 0])?)?;
+                // topk_appended: vector, _rowid, (filtered_cols), score
+                let appended_schema = topk_appended.schema();
+                let indices = [SCORE_COL, ROW_ID, q.column.as_str()]
+                    .iter()
+                    .map(|name| appended_schema.index_of(name))
+                    .collect::<std::result::Result<Vec<_>, _>>()?;
+                let new_schema = Schema::try_from(&appended_schema.project(&indices)?)?;
                 let topk_appended = ProjectionExec::try_new(topk_appended, Arc::new(new_schema))?;
                 assert_eq!(topk_appended.schema(), knn_node.schema());
                 // union
@@ -553,6 +735,121 @@ impl Scanner {
         Ok(knn_node)
     }
 
+    /// Full text search execution node.
+    async fn fts(&self, q: &FullTextQuery) -> Result<Arc<dyn ExecutionPlan>> {
+        let field_id = self.dataset.schema().field_id(&q.column)?;
+        let indices = self.dataset.load_indices().await?;
+        for index in indices.iter().filter(|i| i.fields == [field_id]) {
+            let Some(inverted_index) =
+                InvertedIndex::open(self.dataset.as_ref(), &index.uuid.to_string()).await?
+            else {
+                continue;
+            };
+            return Ok(Arc::new(FullTextSearchExec::new(
+                self.dataset.clone(),
+                &index.name,
+                inverted_index,
+                q.clone(),
+                self.unindexed_fragments(index).await?,
+                self.batch_size,
+            )));
+        }
+        Err(Error::Index {
+            message: format!(
+                "Full text search requires an inverted index on column {}",
+                q.column
+            ),
+        })
+    }
+
+    /// Search the scalar indices for the rows matching the filter, if the filter,
+    /// or one of its `AND` conditions, can be answered by the indices.
+    async fn scalar_index_search(&self, filter: &Expr) -> Result<Option<Arc<dyn ExecutionPlan>>> {
+        if self.is_fragment_scan() {
+            return Ok(None);
+        }
+        let indices = self.dataset.load_indices().await?;
+        let Some(expr) = self.scalar_index_expr(filter, &indices).await? else {
+            return Ok(None);
+        };
+        Ok(Some(Arc::new(ScalarIndexExec::new(
+            self.dataset.clone(),
+            expr,
+            self.batch_size,
+        ))))
+    }
+
+    /// Map the filter to the searches of the scalar indices, which find a superset
+    /// of the matching rows.
+    #[async_recursion]
+    async fn scalar_index_expr(
+        &self,
+        filter: &Expr,
+        indices: &[Index],
+    ) -> Result<Option<ScalarIndexExpr>> {
+        match filter {
+            Expr::BinaryExpr(BinaryExpr {
+                left,
+                op: Operator::And,
+                right,
+            }) => {
+                let left = self.scalar_index_expr(left, indices).await?;
+                let right = self.scalar_index_expr(right, indices).await?;
+                Ok(match (left, right) {
+                    (Some(left), Some(right)) => {
+                        Some(ScalarIndexExpr::And(Box::new(left), Box::new(right)))
+                    }
+                    // The other condition is applied by the filter afterwards.
+                    (left, right) => left.or(right),
+                })
+            }
+            Expr::BinaryExpr(BinaryExpr {
+                left,
+                op: Operator::Or,
+                right,
+            }) => {
+                let left = self.scalar_index_expr(left, indices).await?;
+                let right = self.scalar_index_expr(right, indices).await?;
+                Ok(match (left, right) {
+                    (Some(left), Some(right)) => {
+                        Some(ScalarIndexExpr::Or(Box::new(left), Box::new(right)))
+                    }
+                    _ => None,
+                })
+            }
+            _ => {
+                let Some((column, query)) = ScalarQuery::from_expr(filter, self.dataset.schema())
+                else {
+                    return Ok(None);
+                };
+                let field_id = self.dataset.schema().field_id(&column)?;
+                for index in indices.iter().filter(|i| i.fields == [field_id]) {
+                    let Some(scalar_index) =
+                        open_scalar_index(self.dataset.as_ref(), &index.uuid.to_string()).await?
+                    else {
+                        continue;
+                    };
+                    return Ok(Some(ScalarIndexExpr::Query(ScalarIndexQuery {
+                        index_name: index.name.clone(),
+                        index: scalar_index,
+                        query,
+                        unindexed_fragments: self.unindexed_fragments(index).await?,
+                    })));
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    /// The fragments added after the index was built.
+    async fn unindexed_fragments(&self, index: &Index) -> Result<Vec<Fragment>> {
+        if index.dataset_version == self.dataset.version().version {
+            return Ok(vec![]);
+        }
+        let indexed = self.dataset.checkout_version(index.dataset_version).await?;
+        self.dataset.manifest.fragments_since(&indexed.manifest)
+    }
+
     /// Create an Execution plan with a scan node
     fn scan(&self, with_row_id: bool, projection: Arc<Schema>) -> Arc<dyn ExecutionPlan> {
         let fragments = if let Some(fragment) = self.fragments.as_ref() {
@@ -560,13 +857,56 @@ impl Scanner {
         } else {
             self.dataset.fragments().clone()
         };
-        self.scan_fragments(with_row_id, projection, fragments, self.ordered)
+        self.scan_fragments(with_row_id, projection, None, fragments, self.ordered)
+    }
+
+    /// Create a scan node of the columns in the `filter`, with their row ids.
+    ///
+    /// The page statistics of the filtered columns are used to skip the fragments that
+    /// can not match while planning, and then the batches that can not match while
+    /// scanning.
+    async fn filtered_scan(
+        &self,
+        filter: &Arc<dyn PhysicalExpr>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let columns_in_filter = column_names_in_expr(filter.as_ref());
+        let filter_schema = Arc::new(self.dataset.schema().project(&columns_in_filter)?);
+        let fragments = if let Some(fragment) = self.fragments.as_ref() {
+            fragment.clone()
+        } else {
+            self.dataset.fragments().as_ref().clone()
+        };
+
+        let pruner = StatisticsPruner::try_new(filter.clone(), self.dataset.schema())?;
+        let readers = stream::iter(fragments.iter())
+            .map(|fragment| {
+                let file_fragment = FileFragment::new(self.dataset.clone(), fragment.clone());
+                let filter_schema = filter_schema.clone();
+                async move { file_fragment.open(&filter_schema).await }
+            })
+            .buffered(self.fragment_readahead)
+            .try_collect::<Vec<_>>()
+            .await?;
+        let fragments = fragments
+            .into_iter()
+            .zip(pruner.prune_fragments(&readers)?)
+            .filter_map(|(fragment, keep)| keep.then_some(fragment))
+            .collect();
+
+        Ok(self.scan_fragments(
+            true,
+            filter_schema,
+            Some(filter.clone()),
+            Arc::new(fragments),
+            self.ordered,
+        ))
     }
 
     fn scan_fragments(
         &self,
         with_row_id: bool,
         projection: Arc<Schema>,
+        filter: Option<Arc<dyn PhysicalExpr>>,
         fragments: Arc<Vec<Fragment>>,
         ordered: bool,
     ) -> Arc<dyn ExecutionPlan> {
@@ -574,6 +914,7 @@ impl Scanner {
             self.dataset.clone(),
             fragments,
             projection,
+            filter,
             self.batch_size,
             self.batch_readahead,
             self.fragment_readahead,
@@ -588,14 +929,39 @@ impl Scanner {
     }
 
     /// Create an Execution plan to do indexed ANN search
-    fn ann(&self, q: &Query, index: &Index) -> Result<Arc<dyn ExecutionPlan>> {
+    fn ann(
+        &self,
+        q: &Query,
+        index: &Index,
+        prefilter: Option<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
         Ok(Arc::new(KNNIndexExec::try_new(
             self.dataset.clone(),
             &index.uuid.to_string(),
             q,
+            prefilter,
         )?))
     }
 
+    /// Filter the rows of the input plan, taking the columns of the filter first
+    /// if they are not in the input.
+    fn filter_rows(
+        &self,
+        input: Arc<dyn ExecutionPlan>,
+        predicates: Arc<dyn PhysicalExpr>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let columns_in_filter = column_names_in_expr(predicates.as_ref());
+        let filter_schema = Arc::new(self.dataset.schema().project(&columns_in_filter)?);
+        let remaining_schema = filter_schema.exclude(input.schema().as_ref())?;
+        let plan = if !remaining_schema.fields.is_empty() {
+            // Not all columns for filter are ready, so we need to take them first
+            self.take(input, &remaining_schema)?
+        } else {
+            input
+        };
+        Ok(Arc::new(FilterExec::try_new(predicates, plan)?))
+    }
+
     /// Take row indices produced by input plan from the dataset (with projection)
     fn take(
         &self,
@@ -665,7 +1031,7 @@ mod test {
 
     use arrow::array::as_primitive_array;
     use arrow::compute::concat_batches;
-    use arrow::datatypes::Int32Type;
+    use arrow::datatypes::{Float32Type, Int32Type};
     use arrow_array::{
         ArrayRef, FixedSizeListArray, Int32Array, Int64Array, LargeStringArray,
         RecordBatchIterator, StringArray, StructArray,
@@ -680,6 +1046,7 @@ mod test {
     use crate::arrow::*;
     use crate::dataset::WriteMode;
     use crate::dataset::WriteParams;
+    use crate::index::scalar::ScalarIndexParams;
     use crate::index::vector::diskann::DiskANNParams;
     use crate::index::{
         DatasetIndexExt,
@@ -1360,6 +1727,480 @@ mod test {
         assert_eq!(filter.schema().field_names(), ["i", "_rowid"]);
     }
 
+    /// Test filter with a scalar index
+    ///
+    /// Query:
+    ///
+    /// ```sql
+    /// SELECT s FROM dataset WHERE i > 10 and i < 20 and s != 's-12'
+    /// ```
+    ///
+    /// Expected plan:
+    ///  ScalarIndex(i) -> take(i, s) -> filter(i, s) -> projection(s)
+    #[tokio::test]
+    async fn test_scan_with_scalar_index() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("i", DataType::Int32, true),
+            ArrowField::new("s", DataType::Utf8, true),
+        ]));
+        let make_batch = |values: std::ops::Range<i32>, prefix: &str| {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int32Array::from_iter_values(values.clone())),
+                    Arc::new(StringArray::from_iter_values(
+                        values.map(|v| format!("{prefix}-{v}")),
+                    )),
+                ],
+            )
+            .unwrap()
+        };
+        let params = WriteParams {
+            max_rows_per_file: 50,
+            max_rows_per_group: 10,
+            ..Default::default()
+        };
+        let reader = RecordBatchIterator::new(vec![Ok(make_batch(0..100, "s"))], schema.clone());
+        let dataset = Dataset::write(reader, test_uri, Some(params))
+            .await
+            .unwrap();
+        let mut dataset = dataset
+            .create_index(
+                &["i"],
+                IndexType::BTree,
+                None,
+                &ScalarIndexParams::default(),
+                false,
+            )
+            .await
+            .unwrap();
+
+        // Rows deleted and appended after the index was built.
+        dataset.delete("i = 15").await.unwrap();
+        let params = WriteParams {
+            mode: WriteMode::Append,
+            ..Default::default()
+        };
+        let reader = RecordBatchIterator::new(vec![Ok(make_batch(10..20, "new"))], schema.clone());
+        let dataset = Dataset::write(reader, test_uri, Some(params))
+            .await
+            .unwrap();
+
+        let mut scan = dataset.scan();
+        scan.project(&["s"]).unwrap();
+        scan.filter("i > 10 and i < 20 and s != 's-12'").unwrap();
+        let plan = scan.create_plan().await.unwrap();
+
+        assert!(plan.as_any().is::<ProjectionExec>());
+        assert_eq!(plan.schema().field_names(), ["s"]);
+
+        let filter = &plan.children()[0];
+        assert!(filter.as_any().is::<FilterExec>());
+
+        let take = &filter.children()[0];
+        assert!(take.as_any().is::<TakeExec>());
+        assert_eq!(take.schema().field_names(), ["_rowid", "i", "s"]);
+
+        let index = &take.children()[0];
+        assert!(index.as_any().is::<ScalarIndexExec>());
+        assert_eq!(index.schema().field_names(), ["_rowid"]);
+
+        let batches = scan
+            .try_into_stream()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let batch = concat_batches(&batches[0].schema(), &batches).unwrap();
+        let expected = [11, 13, 14, 16, 17, 18, 19]
+            .iter()
+            .map(|v| format!("s-{v}"))
+            .chain((11..20).map(|v| format!("new-{v}")))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            batch.column_by_name("s").unwrap().as_ref(),
+            &StringArray::from(expected)
+        );
+
+        let mut scan = dataset.scan();
+        scan.filter("i IN (3, 60, 1000)").unwrap();
+        let batches = scan
+            .try_into_stream()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let batch = concat_batches(&batches[0].schema(), &batches).unwrap();
+        assert_eq!(
+            batch.column_by_name("s").unwrap().as_ref(),
+            &StringArray::from(vec!["s-3", "s-60"])
+        );
+    }
+
+    fn uses_scalar_index(plan: &Arc<dyn ExecutionPlan>) -> bool {
+        plan.as_any().is::<ScalarIndexExec>() || plan.children().iter().any(uses_scalar_index)
+    }
+
+    #[tokio::test]
+    async fn test_prefilter_with_bitmap_index() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("i", DataType::Int32, false),
+            ArrowField::new("split", DataType::Utf8, false),
+            ArrowField::new("label", DataType::Int32, false),
+            ArrowField::new(
+                "vec",
+                DataType::FixedSizeList(
+                    Arc::new(ArrowField::new("item", DataType::Float32, true)),
+                    4,
+                ),
+                true,
+            ),
+        ]));
+        let splits = ["train", "test", "val"];
+        let vector_values: Float32Array = (0..300 * 4).map(|v| (v / 4) as f32).collect();
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from_iter_values(0..300)),
+                Arc::new(StringArray::from_iter_values(
+                    (0..300).map(|i| splits[i % 3]),
+                )),
+                Arc::new(Int32Array::from_iter_values((0..300).map(|i| i % 5))),
+                Arc::new(FixedSizeListArray::try_new_from_values(vector_values, 4).unwrap()),
+            ],
+        )
+        .unwrap();
+        let params = WriteParams {
+            max_rows_per_file: 100,
+            max_rows_per_group: 20,
+            ..Default::default()
+        };
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+        let mut dataset = Dataset::write(reader, test_uri, Some(params))
+            .await
+            .unwrap();
+        for column in ["split", "label"] {
+            dataset = dataset
+                .create_index(
+                    &[column],
+                    IndexType::Bitmap,
+                    None,
+                    &ScalarIndexParams::default(),
+                    false,
+                )
+                .await
+                .unwrap();
+        }
+        let dataset = Arc::new(dataset);
+
+        let scan_values = |filter: &'static str| {
+            let dataset = dataset.clone();
+            async move {
+                let mut scan = dataset.scan();
+                scan.project(&["i"]).unwrap();
+                scan.filter(filter).unwrap();
+                let plan = scan.create_plan().await.unwrap();
+                assert!(uses_scalar_index(&plan));
+
+                let batches = scan
+                    .try_into_stream()
+                    .await
+                    .unwrap()
+                    .try_collect::<Vec<_>>()
+                    .await
+                    .unwrap();
+                batches
+                    .iter()
+                    .flat_map(|b| {
+                        as_primitive_array::<Int32Type>(b.column(0))
+                            .values()
+                            .to_vec()
+                    })
+                    .collect::<BTreeSet<_>>()
+            }
+        };
+        assert_eq!(
+            scan_values("split = 'val' OR label IN (1, 2)").await,
+            (0..300)
+                .filter(|i| i % 3 == 2 || i % 5 == 1 || i % 5 == 2)
+                .collect()
+        );
+        assert_eq!(
+            scan_values("split = 'val' AND (label = 1 OR i < 10)").await,
+            (0..300)
+                .filter(|i| i % 3 == 2 && (i % 5 == 1 || *i < 10))
+                .collect()
+        );
+
+        // Without prefilter, the nearest rows are found first, and none of them matches.
+        let mut scan = dataset.scan();
+        let key = Float32Array::from(vec![0.0_f32; 4]);
+        scan.nearest("vec", &key, 5).unwrap();
+        scan.project(&["i"]).unwrap();
+        scan.filter("split = 'val' AND label = 1").unwrap();
+        let plan = scan.create_plan().await.unwrap();
+        assert!(!uses_scalar_index(&plan));
+        let num_rows = scan
+            .try_into_stream()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap()
+            .iter()
+            .map(|b| b.num_rows())
+            .sum::<usize>();
+        assert_eq!(num_rows, 0);
+
+        // With prefilter, only the vectors of the rows matching the filter are searched.
+        scan.prefilter(true);
+        let plan = scan.create_plan().await.unwrap();
+        let knn = &plan.children()[0];
+        assert!(knn.as_any().is::<KNNFlatExec>());
+        let filter = &knn.children()[0].children()[0];
+        assert!(filter.as_any().is::<FilterExec>());
+        assert!(filter.children()[0].children()[0]
+            .as_any()
+            .is::<ScalarIndexExec>());
+
+        let batches = scan
+            .try_into_stream()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let batch = concat_batches(&batches[0].schema(), &batches).unwrap();
+        assert_eq!(
+            batch.column_by_name("i").unwrap().as_ref(),
+            &Int32Array::from(vec![11, 26, 41, 56, 71])
+        );
+
+        // No row matches the filter.
+        let mut scan = dataset.scan();
+        scan.nearest("vec", &key, 5).unwrap();
+        scan.prefilter(true);
+        scan.filter("split = 'val' AND label = 100").unwrap();
+        let num_rows = scan
+            .try_into_stream()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap()
+            .iter()
+            .map(|b| b.num_rows())
+            .sum::<usize>();
+        assert_eq!(num_rows, 0);
+    }
+
+    #[tokio::test]
+    async fn test_full_text_search() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("i", DataType::Int32, false),
+            ArrowField::new("caption", DataType::Utf8, true),
+        ]));
+        let make_batch = |captions: Vec<Option<&str>>, start: i32| {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int32Array::from_iter_values(
+                        start..start + captions.len() as i32,
+                    )),
+                    Arc::new(StringArray::from(captions)),
+                ],
+            )
+            .unwrap()
+        };
+        let batch = make_batch(
+            vec![
+                Some("a red car"),
+                Some("a blue car parked on a street"),
+                None,
+                Some("red, red and red flowers"),
+                Some("a cat sleeping on a red sofa"),
+                Some("two dogs"),
+            ],
+            0,
+        );
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+        let mut dataset = Dataset::write(reader, test_uri, None).await.unwrap();
+        assert!(matches!(
+            dataset
+                .scan()
+                .full_text_search("caption", "red")
+                .unwrap()
+                .try_into_stream()
+                .await,
+            Err(Error::Index { .. })
+        ));
+
+        dataset = dataset
+            .create_index(
+                &["caption"],
+                IndexType::Inverted,
+                None,
+                &ScalarIndexParams::default(),
+                false,
+            )
+            .await
+            .unwrap();
+        // Rows deleted and appended after the index was built.
+        dataset.delete("i = 4").await.unwrap();
+        let params = WriteParams {
+            mode: WriteMode::Append,
+            ..Default::default()
+        };
+        let batch = make_batch(vec![Some("red car"), Some("green car")], 6);
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+        let dataset = Dataset::write(reader, test_uri, Some(params))
+            .await
+            .unwrap();
+
+        let mut scan = dataset.scan();
+        scan.full_text_search("caption", "Red CAR").unwrap();
+        scan.project(&["i"]).unwrap();
+        let plan = scan.create_plan().await.unwrap();
+        assert_eq!(plan.schema().field_names(), ["i", "score"]);
+        let take = &plan.children()[0];
+        assert!(take.children()[0].as_any().is::<FullTextSearchExec>());
+
+        let batches = scan
+            .try_into_stream()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let batch = concat_batches(&batches[0].schema(), &batches).unwrap();
+        let ids = as_primitive_array::<Int32Type>(batch.column_by_name("i").unwrap());
+        // "red car" matches both tokens, and the shorter texts rank first.
+        assert_eq!(ids.values()[..2], [6, 0]);
+        assert_eq!(
+            ids.values().iter().copied().collect::<BTreeSet<_>>(),
+            BTreeSet::from([0, 1, 3, 6, 7])
+        );
+        let scores = as_primitive_array::<Float32Type>(batch.column_by_name("score").unwrap());
+        assert!(scores.values().windows(2).all(|w| w[0] >= w[1]));
+
+        // The filter and the limit apply on the ranked rows.
+        let mut scan = dataset.scan();
+        scan.full_text_search("caption", "red car").unwrap();
+        scan.filter("i > 0").unwrap();
+        scan.limit(Some(2), None).unwrap();
+        let batches = scan
+            .try_into_stream()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let batch = concat_batches(&batches[0].schema(), &batches).unwrap();
+        assert_eq!(
+            batch.column_by_name("i").unwrap().as_ref(),
+            &Int32Array::from(vec![6, 7])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_hybrid_search() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("i", DataType::Int32, false),
+            ArrowField::new("caption", DataType::Utf8, false),
+            ArrowField::new(
+                "vec",
+                DataType::FixedSizeList(
+                    Arc::new(ArrowField::new("item", DataType::Float32, true)),
+                    4,
+                ),
+                true,
+            ),
+        ]));
+        let vector_values: Float32Array = (0..10 * 4).map(|v| (v / 4) as f32).collect();
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from_iter_values(0..10)),
+                Arc::new(StringArray::from_iter_values((0..10).map(|i| {
+                    if [1, 7, 8, 9].contains(&i) {
+                        "a red car"
+                    } else {
+                        "a blue car"
+                    }
+                }))),
+                Arc::new(FixedSizeListArray::try_new_from_values(vector_values, 4).unwrap()),
+            ],
+        )
+        .unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+        let dataset = Dataset::write(reader, test_uri, None).await.unwrap();
+        let dataset = dataset
+            .create_index(
+                &["caption"],
+                IndexType::Inverted,
+                None,
+                &ScalarIndexParams::default(),
+                false,
+            )
+            .await
+            .unwrap();
+
+        let key = Float32Array::from(vec![0.0_f32; 4]);
+        let search = |fusion: Fusion| {
+            let mut scan = dataset.scan();
+            scan.nearest("vec", &key, 3).unwrap();
+            scan.full_text_search("caption", "red").unwrap();
+            scan.fusion(fusion);
+            scan.project(&["i"]).unwrap();
+            async move {
+                let plan = scan.create_plan().await.unwrap();
+                let schema = plan.schema();
+                let names = schema.fields().iter().map(|f| f.name()).collect::<Vec<_>>();
+                assert_eq!(names, ["i", "vec", "score"]);
+                let take = &plan.children()[0];
+                assert!(take.children()[0].as_any().is::<HybridSearchExec>());
+
+                let batches = scan
+                    .try_into_stream()
+                    .await
+                    .unwrap()
+                    .try_collect::<Vec<_>>()
+                    .await
+                    .unwrap();
+                concat_batches(&batches[0].schema(), &batches).unwrap()
+            }
+        };
+
+        // Row 1 is both the 2nd nearest vector and the 1st text match.
+        let batch = search(Fusion::ReciprocalRank { k: 60.0 }).await;
+        assert_eq!(
+            batch.column_by_name("i").unwrap().as_ref(),
+            &Int32Array::from(vec![1, 0, 7])
+        );
+        let scores = as_primitive_array::<Float32Type>(batch.column_by_name("score").unwrap());
+        assert!((scores.value(0) - (1.0 / 61.0 + 1.0 / 62.0)).abs() < 1e-6);
+
+        let batch = search(Fusion::Weighted { vector_weight: 1.0 }).await;
+        assert_eq!(
+            batch.column_by_name("i").unwrap().as_ref(),
+            &Int32Array::from(vec![0, 1, 2])
+        );
+    }
+
     /// Test KNN with index
     ///
     /// Query: nearest(vec, [...], 10) + filter(i > 10 and i < 20)
@@ -1442,6 +2283,48 @@ mod test {
         assert_eq!(knn.schema().field_names(), ["score", "_rowid"]);
     }
 
+    #[tokio::test]
+    async fn test_ann_with_prefilter() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let dataset = create_vector_dataset(test_uri, true).await;
+
+        // The nearest rows, i.e., 1, 81, 161, ..., do not match the filter.
+        let key: Float32Array = (32..64).map(|v| v as f32).collect();
+        let search = |prefilter: bool| {
+            let mut scan = dataset.scan();
+            scan.nearest("vec", &key, 5).unwrap();
+            scan.refine(2);
+            scan.filter("i >= 200 and i < 210").unwrap();
+            scan.prefilter(prefilter);
+            async move {
+                let plan = scan.create_plan().await.unwrap();
+                let mut node = plan;
+                while !node.as_any().is::<KNNIndexExec>() {
+                    node = node.children()[0].clone();
+                }
+                assert_eq!(node.children().len(), usize::from(prefilter));
+
+                scan.try_into_stream()
+                    .await
+                    .unwrap()
+                    .try_collect::<Vec<_>>()
+                    .await
+                    .unwrap()
+            }
+        };
+
+        let batches = search(false).await;
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 0);
+
+        let batches = search(true).await;
+        let batch = concat_batches(&batches[0].schema(), &batches).unwrap();
+        assert_eq!(
+            batch.column_by_name("i").unwrap().as_ref(),
+            &Int32Array::from_iter_values(200..205)
+        );
+    }
+
     /// Test KNN index with refine factor
     ///
     /// Query: nearest(vec, [...], 10, refine_factor=10) + filter(i > 10 and i < 20)
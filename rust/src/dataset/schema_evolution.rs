@@ -0,0 +1,655 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Changes to the columns of a dataset.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use arrow_array::RecordBatch;
+use arrow_cast::{can_cast_types, cast, cast_with_options, CastOptions};
+use arrow_schema::{DataType, Field as ArrowField, Schema as ArrowSchema};
+use datafusion::physical_plan::PhysicalExpr;
+use futures::stream::{self, StreamExt, TryStreamExt};
+use uuid::Uuid;
+
+use super::fragment::FileFragment;
+use super::transaction::{commit_transaction, Operation};
+use super::updater::add_blanks;
+use super::Dataset;
+use crate::datafusion::physical_expr::column_names_in_expr;
+use crate::datatypes::Schema;
+use crate::format::Fragment;
+use crate::io::deletion::read_deletion_file;
+use crate::io::exec::Planner;
+use crate::io::FileWriter;
+use crate::{Error, Result};
+
+async fn commit_schema(dataset: &mut Dataset, schema: Schema) -> Result<()> {
+    let manifest = commit_transaction(
+        &dataset.object_store,
+        &dataset.base,
+        Some(dataset.manifest.as_ref()),
+        &Operation::Project { schema },
+    )
+    .await?;
+    dataset.manifest = Arc::new(manifest);
+    Ok(())
+}
+
+pub(super) async fn add_columns(dataset: &mut Dataset, columns: Vec<(&str, &str)>) -> Result<()> {
+    if columns.is_empty() {
+        return Err(Error::invalid_input("No column to add"));
+    }
+    let arrow_schema = Arc::new(ArrowSchema::from(dataset.schema()));
+    let planner = Planner::new(arrow_schema.clone());
+
+    let mut names = HashSet::new();
+    let mut fields = vec![];
+    let mut exprs = vec![];
+    let mut input_columns = HashSet::new();
+    for (name, expr) in columns {
+        if name.is_empty() || name.contains('.') {
+            return Err(Error::invalid_input(format!(
+                "Invalid column name {name:?}"
+            )));
+        }
+        if dataset.schema().field(name).is_some() || !names.insert(name) {
+            return Err(Error::invalid_input(format!(
+                "Column {name} already exists"
+            )));
+        }
+        let expr = planner.create_physical_expr(&planner.parse_expr(expr)?)?;
+        fields.push(ArrowField::new(
+            name,
+            expr.data_type(&arrow_schema)?,
+            expr.nullable(&arrow_schema)?,
+        ));
+        input_columns.extend(column_names_in_expr(expr.as_ref()));
+        exprs.push(expr);
+    }
+    let output_schema = Arc::new(ArrowSchema::new(fields));
+
+    // The updater needs to read at least one column, to know the number of rows.
+    let input_columns = if input_columns.is_empty() {
+        vec![dataset.schema().fields[0].name.clone()]
+    } else {
+        input_columns.into_iter().collect()
+    };
+
+    let (exprs, output_schema, input_columns) = (&exprs, &output_schema, &input_columns);
+    let fragments = stream::iter(dataset.get_fragments())
+        .map(|fragment| async move {
+            add_columns_to_fragment(fragment, input_columns, exprs, output_schema.clone()).await
+        })
+        .buffered(num_cpus::get())
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    let schema = dataset.schema().merge(output_schema.as_ref())?;
+    let manifest = commit_transaction(
+        &dataset.object_store,
+        &dataset.base,
+        Some(dataset.manifest.as_ref()),
+        &Operation::Merge { fragments, schema },
+    )
+    .await?;
+    dataset.manifest = Arc::new(manifest);
+    Ok(())
+}
+
+/// Evaluate the expressions over a fragment, and write the results as a new data file.
+async fn add_columns_to_fragment(
+    fragment: FileFragment,
+    input_columns: &[String],
+    exprs: &[Arc<dyn PhysicalExpr>],
+    output_schema: Arc<ArrowSchema>,
+) -> Result<Fragment> {
+    let mut updater = fragment.updater(Some(input_columns)).await?;
+    while let Some(batch) = updater.next().await? {
+        let columns = exprs
+            .iter()
+            .zip(output_schema.fields())
+            .map(|(expr, field)| {
+                let values = expr.evaluate(batch)?.into_array(batch.num_rows());
+                Ok(cast(&values, field.data_type())?)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let new_batch = RecordBatch::try_new(output_schema.clone(), columns)?;
+        updater.update(new_batch).await?;
+    }
+    updater.finish().await
+}
+
+pub(super) async fn drop_columns(dataset: &mut Dataset, columns: &[&str]) -> Result<()> {
+    let schema = dataset.schema();
+    for column in columns {
+        if schema.field(column).is_none() {
+            return Err(Error::invalid_input(format!(
+                "Column {column} does not exist in the dataset"
+            )));
+        }
+    }
+
+    let to_drop = schema.project(columns)?;
+    let mut new_schema = schema.exclude(&to_drop)?;
+    new_schema.metadata = schema.metadata.clone();
+    if new_schema.fields.is_empty() {
+        return Err(Error::invalid_input("Can not drop all the columns"));
+    }
+
+    commit_schema(dataset, new_schema).await
+}
+
+pub(super) async fn alter_columns(dataset: &mut Dataset, renames: &[(&str, &str)]) -> Result<()> {
+    let mut new_schema = dataset.schema().clone();
+    for (column, new_name) in renames {
+        if new_name.is_empty() || new_name.contains('.') {
+            return Err(Error::invalid_input(format!(
+                "Invalid new name {new_name:?} for column {column}"
+            )));
+        }
+        let id = new_schema
+            .field(column)
+            .ok_or_else(|| {
+                Error::invalid_input(format!("Column {column} does not exist in the dataset"))
+            })?
+            .id;
+
+        let siblings = match column.rsplit_once('.') {
+            Some((parent, _)) => &new_schema.field(parent).unwrap().children,
+            None => &new_schema.fields,
+        };
+        if siblings.iter().any(|f| f.name == *new_name) {
+            return Err(Error::invalid_input(format!(
+                "Can not rename column {column}, column {new_name} already exists"
+            )));
+        }
+
+        new_schema.mut_field_by_id(id).unwrap().name = new_name.to_string();
+    }
+
+    commit_schema(dataset, new_schema).await
+}
+
+pub(super) async fn alter_column_type(
+    dataset: &mut Dataset,
+    name: &str,
+    data_type: DataType,
+) -> Result<()> {
+    let schema = dataset.schema();
+    let Some(field) = schema.fields.iter().find(|f| f.name == name) else {
+        if schema.field(name).is_some() {
+            return Err(Error::NotSupported {
+                source: format!("Can not change the type of nested column {name}").into(),
+            });
+        }
+        return Err(Error::invalid_input(format!(
+            "Column {name} does not exist in the dataset"
+        )));
+    };
+    let old_type = field.data_type();
+    if old_type == data_type {
+        return Ok(());
+    }
+    if !can_cast_types(&old_type, &data_type) {
+        return Err(Error::invalid_input(format!(
+            "Can not cast column {name} from {old_type} to {data_type}"
+        )));
+    }
+
+    let old_field_ids = schema.project(&[name])?.field_ids();
+    let new_schema = schema.with_field_type(name, &data_type)?;
+    let new_field_schema = new_schema.project(&[name])?;
+
+    let (old_field_ids, new_field_schema) = (&old_field_ids, &new_field_schema);
+    let fragments = stream::iter(dataset.get_fragments())
+        .map(|fragment| async move {
+            rewrite_column(fragment, name, old_field_ids, new_field_schema).await
+        })
+        .buffered(num_cpus::get())
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    let operation = Operation::ReplaceFields {
+        fragments,
+        schema: new_schema,
+        field_ids: old_field_ids.clone(),
+    };
+    let manifest = commit_transaction(
+        &dataset.object_store,
+        &dataset.base,
+        Some(dataset.manifest.as_ref()),
+        &operation,
+    )
+    .await?;
+    dataset.manifest = Arc::new(manifest);
+    Ok(())
+}
+
+/// Cast a column of the fragment into a new data file.
+///
+/// The old data files are kept for the other columns, but no longer list the old fields.
+async fn rewrite_column(
+    fragment: FileFragment,
+    name: &str,
+    old_field_ids: &[i32],
+    new_field_schema: &Schema,
+) -> Result<Fragment> {
+    let dataset = fragment.dataset();
+    let reader = fragment.open(&dataset.schema().project(&[name])?).await?;
+    let deletion_vector =
+        read_deletion_file(&dataset.base, fragment.metadata(), dataset.object_store())
+            .await?
+            .unwrap_or_default();
+
+    let file_name = format!("{}.lance", Uuid::new_v4());
+    let path = dataset.data_dir().child(file_name.as_str());
+    let mut writer =
+        FileWriter::try_new(dataset.object_store(), &path, new_field_schema.clone()).await?;
+    let arrow_schema = Arc::new(ArrowSchema::from(new_field_schema));
+    let data_type = arrow_schema.field(0).data_type();
+    let mut start_row_id = 0;
+    for batch_id in 0..reader.num_batches() {
+        let batch = reader.read_batch(batch_id, ..).await?;
+        // Values which do not fit into the new type are errors, instead of nulls.
+        let options = CastOptions {
+            safe: false,
+            ..Default::default()
+        };
+        let values = cast_with_options(&batch[name], data_type, &options).map_err(|e| {
+            Error::invalid_input(format!("Can not cast column {name} to {data_type}: {e}"))
+        })?;
+        let batch = RecordBatch::try_new(arrow_schema.clone(), vec![values])?;
+        // Deleted rows are not read, but they still take up rows in the data file.
+        let num_rows = reader.num_rows_in_batch(batch_id) as u32;
+        let batch = add_blanks(
+            batch,
+            start_row_id..start_row_id + num_rows,
+            &deletion_vector,
+        )?;
+        writer.write(&[batch]).await?;
+        start_row_id += num_rows;
+    }
+    writer.finish().await?;
+
+    let mut metadata = fragment.metadata().clone();
+    for file in metadata.files.iter_mut() {
+        file.fields.retain(|id| !old_field_ids.contains(id));
+    }
+    metadata.files.retain(|file| !file.fields.is_empty());
+    metadata.add_file(&file_name, new_field_schema);
+    Ok(metadata)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    use arrow_array::{
+        Array, Float32Array, Int32Array, Int64Array, RecordBatchIterator, StructArray, UInt32Array,
+    };
+    use arrow_schema::{Field, Fields};
+    use arrow_select::concat::concat_batches;
+    use tempfile::tempdir;
+
+    use crate::arrow::FixedSizeListArrayExt;
+    use crate::dataset::WriteParams;
+    use crate::index::vector::{MetricType, VectorIndexParams};
+    use crate::index::{DatasetIndexExt, IndexType};
+    use crate::utils::testing::generate_random_array;
+
+    async fn create_dataset(test_uri: &str) -> Dataset {
+        let struct_fields = Fields::from(vec![
+            Field::new("x", DataType::Int32, false),
+            Field::new("y", DataType::Int32, false),
+        ]);
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("i", DataType::UInt32, false),
+            Field::new("f", DataType::Float32, false),
+            Field::new("s", DataType::Struct(struct_fields.clone()), false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(UInt32Array::from_iter_values(0..100)),
+                Arc::new(Float32Array::from_iter_values((0..100).map(|v| v as f32))),
+                Arc::new(StructArray::new(
+                    struct_fields,
+                    vec![
+                        Arc::new(Int32Array::from_iter_values(0..100)),
+                        Arc::new(Int32Array::from_iter_values(100..200)),
+                    ],
+                    None,
+                )),
+            ],
+        )
+        .unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        Dataset::write(reader, test_uri, None).await.unwrap()
+    }
+
+    async fn scan_schema(dataset: &Dataset) -> ArrowSchema {
+        let batches = dataset
+            .scan()
+            .try_into_stream()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        batches[0].schema().as_ref().clone()
+    }
+
+    #[tokio::test]
+    async fn test_add_columns() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("width", DataType::Int32, false),
+            Field::new("height", DataType::Int32, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from_iter_values(0..100)),
+                Arc::new(Int32Array::from_iter_values(100..200)),
+            ],
+        )
+        .unwrap();
+        let write_params = WriteParams {
+            max_rows_per_file: 40,
+            max_rows_per_group: 10,
+            ..Default::default()
+        };
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        let mut dataset = Dataset::write(reader, test_uri, Some(write_params))
+            .await
+            .unwrap();
+        dataset.delete("width >= 30 and width < 50").await.unwrap();
+
+        dataset
+            .add_columns(vec![("area", "width * height"), ("one", "1")])
+            .await
+            .unwrap();
+        assert_eq!(dataset.version().version, 3);
+        assert_eq!(dataset.schema().field("area").unwrap().id, 2);
+        assert_eq!(dataset.schema().field("one").unwrap().id, 3);
+        for fragment in dataset.fragments().iter() {
+            assert_eq!(fragment.files.len(), 2);
+        }
+        dataset.validate().await.unwrap();
+
+        let batches = dataset
+            .scan()
+            .project(&["width", "area", "one"])
+            .unwrap()
+            .try_into_stream()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let mut num_rows = 0;
+        for batch in batches {
+            let width = batch["width"]
+                .as_any()
+                .downcast_ref::<Int32Array>()
+                .unwrap();
+            let area = batch["area"].as_any().downcast_ref::<Int32Array>().unwrap();
+            for (w, a) in width.values().iter().zip(area.values()) {
+                assert_eq!(*a, w * (w + 100));
+            }
+            let one = batch["one"].as_any().downcast_ref::<Int64Array>().unwrap();
+            assert!(one.values().iter().all(|v| *v == 1));
+            num_rows += batch.num_rows();
+        }
+        assert_eq!(num_rows, 80);
+
+        for columns in [
+            vec![],
+            vec![("area", "width")],
+            vec![("a", "width"), ("a", "height")],
+            vec![("a.b", "width")],
+            vec![("a", "missing + 1")],
+        ] {
+            assert!(dataset.add_columns(columns).await.is_err());
+        }
+        assert_eq!(dataset.version().version, 3);
+    }
+
+    #[tokio::test]
+    async fn test_alter_column_type() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let mut dataset = create_dataset(test_uri).await;
+        dataset.delete("i < 10").await.unwrap();
+        let field_ids = dataset.schema().field_ids();
+        let old_file = dataset.fragments()[0].files[0].clone();
+
+        dataset
+            .alter_column_type("i", DataType::Int64)
+            .await
+            .unwrap();
+        assert_eq!(dataset.version().version, 3);
+        assert_eq!(dataset.schema().field_ids(), field_ids);
+        assert_eq!(
+            dataset.schema().field("i").unwrap().data_type(),
+            DataType::Int64
+        );
+
+        // Only the altered column is rewritten.
+        let files = &dataset.fragments()[0].files;
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].path, old_file.path);
+        assert!(!files[0].fields.contains(&0));
+        assert_eq!(files[1].fields, vec![0]);
+        dataset.validate().await.unwrap();
+
+        let batches = dataset
+            .scan()
+            .project(&["i", "f"])
+            .unwrap()
+            .try_into_stream()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let batch = concat_batches(&batches[0].schema(), &batches).unwrap();
+        assert_eq!(
+            batch["i"].as_ref(),
+            &Int64Array::from_iter_values(10..100) as &dyn Array
+        );
+        assert_eq!(
+            batch["f"].as_ref(),
+            &Float32Array::from_iter_values((10..100).map(|v| v as f32)) as &dyn Array
+        );
+
+        assert!(matches!(
+            dataset.alter_column_type("s.x", DataType::Int64).await,
+            Err(Error::NotSupported { .. })
+        ));
+        assert!(matches!(
+            dataset.alter_column_type("missing", DataType::Int64).await,
+            Err(Error::InvalidInput { .. })
+        ));
+        assert!(matches!(
+            dataset.alter_column_type("s", DataType::Float64).await,
+            Err(Error::InvalidInput { .. })
+        ));
+        assert_eq!(dataset.version().version, 3);
+
+        // Narrowing fails if any value is out of range of the new type.
+        dataset
+            .update(
+                "i = 99",
+                HashMap::from([("i".to_string(), "1000".to_string())]),
+            )
+            .await
+            .unwrap();
+        assert_eq!(dataset.version().version, 4);
+        assert!(matches!(
+            dataset.alter_column_type("i", DataType::Int8).await,
+            Err(Error::InvalidInput { .. })
+        ));
+        assert_eq!(dataset.version().version, 4);
+        assert_eq!(
+            dataset.schema().field("i").unwrap().data_type(),
+            DataType::Int64
+        );
+    }
+
+    #[tokio::test]
+    async fn test_drop_columns() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let mut dataset = create_dataset(test_uri).await;
+        let data_files = dataset.fragments()[0].files.clone();
+
+        dataset.drop_columns(&["f", "s.x"]).await.unwrap();
+        assert_eq!(dataset.version().version, 2);
+        let expected = ArrowSchema::new(vec![
+            Field::new("i", DataType::UInt32, false),
+            Field::new(
+                "s",
+                DataType::Struct(Fields::from(vec![Field::new("y", DataType::Int32, false)])),
+                false,
+            ),
+        ]);
+        assert_eq!(ArrowSchema::from(dataset.schema()), expected);
+        assert_eq!(scan_schema(&dataset).await, expected);
+        assert_eq!(dataset.count_rows().await.unwrap(), 100);
+
+        // The data files are not rewritten.
+        let fragment = &dataset.fragments()[0];
+        assert_eq!(fragment.files.len(), 1);
+        assert_eq!(fragment.files[0].path, data_files[0].path);
+        assert_eq!(fragment.files[0].fields, dataset.schema().field_ids());
+
+        assert!(matches!(
+            dataset.drop_columns(&["missing"]).await,
+            Err(Error::InvalidInput { .. })
+        ));
+        assert!(matches!(
+            dataset.drop_columns(&["i", "s"]).await,
+            Err(Error::InvalidInput { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_drop_indexed_column() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let dimension = 16;
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("i", DataType::UInt32, false),
+            Field::new(
+                "vec",
+                DataType::FixedSizeList(
+                    Arc::new(Field::new("item", DataType::Float32, true)),
+                    dimension,
+                ),
+                false,
+            ),
+        ]));
+        let vectors =
+            <arrow_array::FixedSizeListArray as FixedSizeListArrayExt>::try_new_from_values(
+                generate_random_array(512 * dimension as usize),
+                dimension,
+            )
+            .unwrap();
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(UInt32Array::from_iter_values(0..512)),
+                Arc::new(vectors),
+            ],
+        )
+        .unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        let dataset = Dataset::write(reader, test_uri, None).await.unwrap();
+        let params = VectorIndexParams::ivf_pq(2, 8, 2, false, MetricType::L2, 10);
+        let mut dataset = dataset
+            .create_index(&["vec"], IndexType::Vector, None, &params, true)
+            .await
+            .unwrap();
+        assert_eq!(dataset.load_indices().await.unwrap().len(), 1);
+
+        // Renaming keeps the index.
+        dataset.alter_columns(&[("i", "id")]).await.unwrap();
+        assert_eq!(dataset.load_indices().await.unwrap().len(), 1);
+
+        dataset.drop_columns(&["vec"]).await.unwrap();
+        assert!(dataset.load_indices().await.unwrap().is_empty());
+        dataset.validate().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_alter_columns() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let mut dataset = create_dataset(test_uri).await;
+        let field_ids = dataset.schema().field_ids();
+
+        dataset
+            .alter_columns(&[("i", "id"), ("s.x", "z")])
+            .await
+            .unwrap();
+        assert_eq!(dataset.schema().field_ids(), field_ids);
+        let expected = ArrowSchema::new(vec![
+            Field::new("id", DataType::UInt32, false),
+            Field::new("f", DataType::Float32, false),
+            Field::new(
+                "s",
+                DataType::Struct(Fields::from(vec![
+                    Field::new("z", DataType::Int32, false),
+                    Field::new("y", DataType::Int32, false),
+                ])),
+                false,
+            ),
+        ]);
+        assert_eq!(scan_schema(&dataset).await, expected);
+
+        let batches = dataset
+            .scan()
+            .filter("id < 10")
+            .unwrap()
+            .try_into_stream()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 10);
+
+        for renames in [
+            vec![("missing", "a")],
+            vec![("id", "f")],
+            vec![("s.z", "y")],
+            vec![("f", "a.b")],
+        ] {
+            assert!(matches!(
+                dataset.alter_columns(&renames).await,
+                Err(Error::InvalidInput { .. })
+            ));
+        }
+        assert_eq!(dataset.version().version, 2);
+    }
+}
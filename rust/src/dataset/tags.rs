@@ -0,0 +1,305 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Named references to dataset versions.
+//!
+//! Each tag is stored as a small JSON file under `_refs/tags/`, outside of the version
+//! manifests, so tags are not affected by the removal of old versions.
+
+use std::collections::HashMap;
+
+use object_store::path::Path;
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use uuid::Uuid;
+
+use super::{manifest_path, Dataset};
+use crate::io::object_reader::ObjectReader;
+use crate::{Error, Result};
+
+const REFS_DIR: &str = "_refs";
+const TAGS_DIR: &str = "tags";
+
+/// The version a tag points to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TagContents {
+    pub version: u64,
+}
+
+/// Tags of a dataset. Created via [Dataset::tags].
+pub struct Tags<'a> {
+    dataset: &'a Dataset,
+}
+
+impl<'a> Tags<'a> {
+    pub(super) fn new(dataset: &'a Dataset) -> Self {
+        Self { dataset }
+    }
+
+    fn tags_dir(&self) -> Path {
+        self.dataset.base.child(REFS_DIR).child(TAGS_DIR)
+    }
+
+    fn tag_path(&self, tag: &str) -> Path {
+        self.tags_dir().child(format!("{tag}.json"))
+    }
+
+    /// List all the tags, and the versions they point to.
+    pub async fn list(&self) -> Result<HashMap<String, TagContents>> {
+        let object_store = self.dataset.object_store();
+        let mut tags = HashMap::new();
+        for file_name in object_store.read_dir(self.tags_dir()).await? {
+            if let Some(tag) = file_name.strip_suffix(".json") {
+                let contents = self.read(tag).await?;
+                tags.insert(tag.to_string(), contents);
+            }
+        }
+        Ok(tags)
+    }
+
+    /// Get the version a tag points to.
+    pub async fn get_version(&self, tag: &str) -> Result<u64> {
+        check_valid_tag(tag)?;
+        Ok(self.read(tag).await?.version)
+    }
+
+    /// Create a tag pointing to `version`.
+    ///
+    /// Fails if the tag already exists, or the version does not exist.
+    ///
+    /// The tag file is written to a temporary file first, and renamed to the tag only if it
+    /// does not exist, so concurrent creates of the same tag never overwrite each other.
+    /// As for commits, object stores without atomic rename, i.e., S3, fall back to checking
+    /// the existence of the tag, which is not safe against concurrent creates.
+    pub async fn create(&self, tag: &str, version: u64) -> Result<()> {
+        check_valid_tag(tag)?;
+        let object_store = self.dataset.object_store();
+
+        let tag_path = self.tag_path(tag);
+        if object_store.exists(&tag_path).await? {
+            return Err(tag_exists(tag));
+        }
+        let version_path = manifest_path(&self.dataset.base, version);
+        if !object_store.exists(&version_path).await? {
+            return Err(Error::NotFound {
+                uri: version_path.to_string(),
+            });
+        }
+
+        // The temporary name does not end with ".json", so it is never listed as a tag.
+        let tmp_path = self
+            .tags_dir()
+            .child(format!("{tag}.tmp_{}", Uuid::new_v4()));
+        let contents = serde_json::to_vec(&TagContents { version })?;
+        let mut writer = object_store.create(&tmp_path).await?;
+        writer.write_all(&contents).await?;
+        writer.shutdown().await?;
+
+        match object_store
+            .inner
+            .rename_if_not_exists(&tmp_path, &tag_path)
+            .await
+        {
+            Ok(()) => return Ok(()),
+            Err(object_store::Error::AlreadyExists { .. }) => {}
+            Err(object_store::Error::NotImplemented) => {
+                if !object_store.exists(&tag_path).await? {
+                    return Ok(object_store.inner.rename(&tmp_path, &tag_path).await?);
+                }
+            }
+            Err(e) => {
+                object_store.inner.delete(&tmp_path).await.ok();
+                return Err(e.into());
+            }
+        }
+        object_store.inner.delete(&tmp_path).await.ok();
+        Err(tag_exists(tag))
+    }
+
+    /// Delete a tag. The version it points to is not affected.
+    pub async fn delete(&self, tag: &str) -> Result<()> {
+        check_valid_tag(tag)?;
+        let tag_path = self.tag_path(tag);
+        match self.dataset.object_store().inner.delete(&tag_path).await {
+            Ok(_) => Ok(()),
+            Err(object_store::Error::NotFound { .. }) => Err(Error::NotFound {
+                uri: tag_path.to_string(),
+            }),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn read(&self, tag: &str) -> Result<TagContents> {
+        let object_store = self.dataset.object_store();
+        let tag_path = self.tag_path(tag);
+        if !object_store.exists(&tag_path).await? {
+            return Err(Error::NotFound {
+                uri: tag_path.to_string(),
+            });
+        }
+        let reader = object_store.open(&tag_path).await?;
+        let bytes = reader.get_range(0..reader.size().await?).await?;
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+}
+
+fn tag_exists(tag: &str) -> Error {
+    Error::invalid_input(format!("Tag {tag} already exists"))
+}
+
+/// Tags are used as file names, so only a conservative set of characters is allowed.
+fn check_valid_tag(tag: &str) -> Result<()> {
+    let valid = !tag.is_empty()
+        && !tag.starts_with('.')
+        && tag
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'));
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::invalid_input(format!(
+            "Tag {tag:?} is invalid, tags may only contain ASCII letters, digits, '.', '-' and '_', and must not start with '.'"
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::Duration;
+    use tempfile::tempdir;
+
+    use crate::dataset::{WriteMode, WriteParams};
+    use crate::utils::testing::sequence_batches;
+
+    #[tokio::test]
+    async fn test_tags() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let dataset = Dataset::write(sequence_batches(0..100), test_uri, None)
+            .await
+            .unwrap();
+        assert!(dataset.tags().list().await.unwrap().is_empty());
+
+        dataset.tags().create("v2023-07-release", 1).await.unwrap();
+        assert!(matches!(
+            dataset.tags().create("v2023-07-release", 1).await,
+            Err(Error::InvalidInput { .. })
+        ));
+        assert!(matches!(
+            dataset.tags().create("future", 2).await,
+            Err(Error::NotFound { .. })
+        ));
+        assert!(matches!(
+            dataset.tags().create("../escape", 1).await,
+            Err(Error::InvalidInput { .. })
+        ));
+
+        let write_params = WriteParams {
+            mode: WriteMode::Append,
+            ..Default::default()
+        };
+        let dataset = Dataset::write(sequence_batches(100..200), test_uri, Some(write_params))
+            .await
+            .unwrap();
+        dataset.tags().create("latest", 2).await.unwrap();
+
+        let tags = dataset.tags().list().await.unwrap();
+        assert_eq!(
+            tags,
+            HashMap::from([
+                ("v2023-07-release".to_string(), TagContents { version: 1 }),
+                ("latest".to_string(), TagContents { version: 2 }),
+            ])
+        );
+        assert_eq!(dataset.tags().get_version("latest").await.unwrap(), 2);
+
+        let release = dataset.checkout_tag("v2023-07-release").await.unwrap();
+        assert_eq!(release.version().version, 1);
+        assert_eq!(release.count_rows().await.unwrap(), 100);
+
+        dataset.tags().delete("latest").await.unwrap();
+        assert!(matches!(
+            dataset.tags().delete("latest").await,
+            Err(Error::NotFound { .. })
+        ));
+        assert!(matches!(
+            dataset.checkout_tag("latest").await,
+            Err(Error::NotFound { .. })
+        ));
+        assert_eq!(dataset.tags().list().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_create() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let dataset = Dataset::write(sequence_batches(0..100), test_uri, None)
+            .await
+            .unwrap();
+
+        let tags = dataset.tags();
+        let results = futures::future::join_all((0..4).map(|_| tags.create("release", 1))).await;
+        assert_eq!(results.iter().filter(|r| r.is_ok()).count(), 1);
+        assert!(results
+            .iter()
+            .filter_map(|r| r.as_ref().err())
+            .all(|e| matches!(e, Error::InvalidInput { .. })));
+        // No temporary files are left behind.
+        let files = dataset
+            .object_store()
+            .read_dir(tags.tags_dir())
+            .await
+            .unwrap();
+        assert_eq!(files, vec!["release.json"]);
+    }
+
+    #[tokio::test]
+    async fn test_tagged_versions_survive_cleanup() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let dataset = Dataset::write(sequence_batches(0..100), test_uri, None)
+            .await
+            .unwrap();
+        dataset.tags().create("release", 1).await.unwrap();
+        let write_params = WriteParams {
+            mode: WriteMode::Overwrite,
+            ..Default::default()
+        };
+        let dataset = Dataset::write(sequence_batches(0..10), test_uri, Some(write_params))
+            .await
+            .unwrap();
+
+        let stats = dataset
+            .cleanup_old_versions(Duration::zero(), true)
+            .await
+            .unwrap();
+        assert_eq!(stats.old_versions, 0);
+        let release = dataset.checkout_tag("release").await.unwrap();
+        assert_eq!(release.count_rows().await.unwrap(), 100);
+
+        let stats = dataset
+            .cleanup_old_versions(Duration::zero(), false)
+            .await
+            .unwrap();
+        assert_eq!(stats.old_versions, 1);
+        // The tag is kept, even though its version is gone.
+        assert_eq!(dataset.tags().get_version("release").await.unwrap(), 1);
+        assert!(dataset.checkout_tag("release").await.is_err());
+    }
+}
@@ -0,0 +1,482 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Optimistic concurrency control for dataset commits.
+//!
+//! Each write produces an [Operation] against the version of the dataset it has read.
+//! Committing claims the next version atomically. If another writer has committed
+//! in the meantime, the operation is checked against the changes made since the read
+//! version, and rebased onto the latest version when they are compatible, e.g., two
+//! appends, or two deletes on disjoint fragments.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use arrow_schema::Schema as ArrowSchema;
+use object_store::path::Path;
+use uuid::Uuid;
+
+use super::{latest_manifest_path, manifest_path, read_indices, write_manifest_file};
+use crate::datatypes::Schema;
+use crate::format::{Fragment, Index, Manifest};
+use crate::io::{read_manifest, ObjectStore};
+use crate::{Error, Result};
+
+/// Max number of attempts to commit an operation, before giving up on conflicts.
+const MAX_COMMIT_ATTEMPTS: usize = 5;
+
+/// A change to the dataset, to be committed as a new version.
+#[derive(Debug, Clone)]
+pub(crate) enum Operation {
+    /// Add new fragments to the dataset.
+    Append { fragments: Vec<Fragment> },
+    /// Update fragments with new deletion files, and remove fully deleted fragments.
+    Delete {
+        updated_fragments: Vec<Fragment>,
+        deleted_fragment_ids: Vec<u64>,
+    },
+    /// Update rows: the old rows are deleted from their fragments, and the new rows are
+    /// written to new fragments.
+    Update {
+        updated_fragments: Vec<Fragment>,
+        deleted_fragment_ids: Vec<u64>,
+        new_fragments: Vec<Fragment>,
+    },
+    /// Replace the content of the dataset. It is also used to create a new dataset.
+    Overwrite {
+        fragments: Vec<Fragment>,
+        schema: Schema,
+    },
+    /// Replace all the fragments and the schema, i.e., adding new columns.
+    Merge {
+        fragments: Vec<Fragment>,
+        schema: Schema,
+    },
+    /// Replace the data of some fields in all the fragments, i.e., to change their types.
+    ///
+    /// The indices on the replaced fields are dropped.
+    ReplaceFields {
+        fragments: Vec<Fragment>,
+        schema: Schema,
+        field_ids: Vec<i32>,
+    },
+    /// Add new indices. Existing indices with the same names are replaced.
+    CreateIndex { new_indices: Vec<Index> },
+    /// Rewrite groups of fragments into new fragments, i.e., compaction.
+    ///
+    /// The row ids of the rewritten fragments change, so only the indices in `retained_indices`,
+    /// which do not cover any of them, are kept.
+    Rewrite {
+        groups: Vec<RewriteGroup>,
+        retained_indices: Vec<Uuid>,
+    },
+    /// Change the schema without rewriting any data, i.e., dropping or renaming columns.
+    ///
+    /// The fields not in the new schema are removed from the data files and indices.
+    Project { schema: Schema },
+    /// Make the content of an old version the latest again.
+    Restore {
+        fragments: Vec<Fragment>,
+        schema: Schema,
+        indices: Vec<Index>,
+    },
+}
+
+/// Fragments rewritten into new fragments, which take the place of the first old fragment.
+#[derive(Debug, Clone)]
+pub(crate) struct RewriteGroup {
+    pub old_fragments: Vec<Fragment>,
+    pub new_fragments: Vec<Fragment>,
+}
+
+impl Operation {
+    fn name(&self) -> &str {
+        match self {
+            Self::Append { .. } => "Append",
+            Self::Delete { .. } => "Delete",
+            Self::Update { .. } => "Update",
+            Self::Overwrite { .. } => "Overwrite",
+            Self::Merge { .. } => "Merge",
+            Self::ReplaceFields { .. } => "ReplaceFields",
+            Self::CreateIndex { .. } => "CreateIndex",
+            Self::Rewrite { .. } => "Rewrite",
+            Self::Project { .. } => "Project",
+            Self::Restore { .. } => "Restore",
+        }
+    }
+
+    /// Check whether this operation, based on `read`, can be applied on top of `latest`.
+    fn check_conflict(&self, read: Option<&Manifest>, latest: Option<&Manifest>) -> Result<()> {
+        let (read, latest) = match (read, latest) {
+            (None, None) => return Ok(()),
+            (Some(read), Some(latest)) if read.version == latest.version => return Ok(()),
+            (Some(read), Some(latest)) => (read, latest),
+            (None, Some(latest)) => {
+                return Err(self.conflict(latest.version, "the dataset was created concurrently"))
+            }
+            (Some(read), None) => {
+                return Err(self.conflict(read.version, "the dataset no longer exists"))
+            }
+        };
+
+        let schema_changed = !schema_equals(&read.schema, &latest.schema);
+        let changed_ids = changed_fragment_ids(read, latest);
+        let has_conflict = match self {
+            // Last writer wins.
+            Self::Overwrite { .. } | Self::Restore { .. } => false,
+            Self::Append { .. } => schema_changed,
+            Self::Delete {
+                updated_fragments,
+                deleted_fragment_ids,
+            }
+            | Self::Update {
+                updated_fragments,
+                deleted_fragment_ids,
+                ..
+            } => {
+                schema_changed
+                    || updated_fragments
+                        .iter()
+                        .map(|f| &f.id)
+                        .chain(deleted_fragment_ids.iter())
+                        .any(|id| changed_ids.contains(id))
+            }
+            // Columns are (re)written for every fragment, so any new or modified
+            // fragment would miss them.
+            Self::Merge { .. } | Self::ReplaceFields { .. } => true,
+            // Fragments are only changed in the field ids, which can be applied to any
+            // fragments written with the same schema.
+            Self::Project { .. } => schema_changed,
+            Self::CreateIndex { .. } => schema_changed || !changed_ids.is_empty(),
+            Self::Rewrite { groups, .. } => {
+                schema_changed
+                    || groups
+                        .iter()
+                        .flat_map(|g| g.old_fragments.iter())
+                        .any(|f| changed_ids.contains(&f.id))
+            }
+        };
+        if has_conflict {
+            Err(self.conflict(
+                latest.version,
+                &format!(
+                    "it is incompatible with the changes committed since version {}",
+                    read.version
+                ),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn conflict(&self, version: u64, reason: &str) -> Error {
+        Error::CommitConflict {
+            version,
+            source: format!("{} operation failed: {}", self.name(), reason).into(),
+        }
+    }
+
+    /// Build the manifest of the new version, applying this operation on `latest`.
+    ///
+    /// The schema is always taken from the operation or `read`, which has the dictionaries
+    /// loaded. [Self::check_conflict] guarantees that it matches the latest schema.
+    fn build_manifest(
+        &self,
+        read: Option<&Manifest>,
+        latest: Option<&Manifest>,
+        latest_indices: Vec<Index>,
+    ) -> Result<(Manifest, Option<Vec<Index>>)> {
+        let version = latest.map_or(1, |m| m.version + 1);
+        let latest_fragments = latest.map_or(vec![], |m| m.fragments.as_ref().clone());
+        let (mut manifest, indices) = match self {
+            Self::Append { fragments } => {
+                let mut new_fragments = latest_fragments;
+                append_fragments(&mut new_fragments, fragments, latest);
+                let manifest = Manifest::new(read_schema(read)?, Arc::new(new_fragments));
+                (manifest, Some(latest_indices))
+            }
+            Self::Delete {
+                updated_fragments,
+                deleted_fragment_ids,
+            } => {
+                let new_fragments =
+                    apply_deletions(latest_fragments, updated_fragments, deleted_fragment_ids);
+                // Inherit the index, unless we deleted all the fragments.
+                let indices = if new_fragments.is_empty() {
+                    None
+                } else {
+                    Some(latest_indices)
+                };
+                let manifest = Manifest::new(read_schema(read)?, Arc::new(new_fragments));
+                (manifest, indices)
+            }
+            Self::Update {
+                updated_fragments,
+                deleted_fragment_ids,
+                new_fragments: fragments,
+            } => {
+                let mut new_fragments =
+                    apply_deletions(latest_fragments, updated_fragments, deleted_fragment_ids);
+                append_fragments(&mut new_fragments, fragments, latest);
+                // Inherit the index, unless we deleted all the fragments.
+                // The new rows are not indexed, as for appended rows.
+                let indices = if new_fragments.is_empty() {
+                    None
+                } else {
+                    Some(latest_indices)
+                };
+                let manifest = Manifest::new(read_schema(read)?, Arc::new(new_fragments));
+                (manifest, indices)
+            }
+            Self::Overwrite { fragments, schema } => {
+                (Manifest::new(schema, Arc::new(fragments.clone())), None)
+            }
+            Self::Merge { fragments, schema } => {
+                // Inherit the index, since we are just adding columns.
+                let manifest = Manifest::new(schema, Arc::new(fragments.clone()));
+                (manifest, Some(latest_indices))
+            }
+            Self::ReplaceFields {
+                fragments,
+                schema,
+                field_ids,
+            } => {
+                let indices = latest_indices
+                    .into_iter()
+                    .filter(|idx| !idx.fields.iter().any(|id| field_ids.contains(id)))
+                    .collect();
+                let manifest = Manifest::new(schema, Arc::new(fragments.clone()));
+                (manifest, Some(indices))
+            }
+            Self::CreateIndex { new_indices } => {
+                let mut manifest = latest.or(read).cloned().ok_or_else(|| Error::Index {
+                    message: "CreateIndex: dataset does not exist".to_string(),
+                })?;
+                manifest.schema = read_schema(read)?.clone();
+                // The new indices cover the new version, unless other writers have added
+                // data since they were built.
+                let covers_latest = read.map(|m| &m.fragments) == latest.map(|m| &m.fragments);
+                // Exclude the old indices with the same names.
+                let mut indices = latest_indices
+                    .into_iter()
+                    .filter(|idx| new_indices.iter().all(|new_idx| new_idx.name != idx.name))
+                    .collect::<Vec<_>>();
+                indices.extend(new_indices.iter().map(|idx| {
+                    let mut idx = idx.clone();
+                    if covers_latest {
+                        idx.dataset_version = version;
+                    }
+                    idx
+                }));
+                (manifest, Some(indices))
+            }
+            Self::Project { schema } => {
+                let field_ids = schema.field_ids().into_iter().collect::<HashSet<_>>();
+                let new_fragments = latest_fragments
+                    .into_iter()
+                    .map(|mut fragment| {
+                        for file in fragment.files.iter_mut() {
+                            file.fields.retain(|id| field_ids.contains(id));
+                        }
+                        fragment.files.retain(|file| !file.fields.is_empty());
+                        fragment
+                    })
+                    .collect::<Vec<_>>();
+                let indices = latest_indices
+                    .into_iter()
+                    .filter(|idx| idx.fields.iter().all(|id| field_ids.contains(id)))
+                    .collect();
+                let manifest = Manifest::new(schema, Arc::new(new_fragments));
+                (manifest, Some(indices))
+            }
+            Self::Restore {
+                fragments,
+                schema,
+                indices,
+            } => (
+                Manifest::new(schema, Arc::new(fragments.clone())),
+                Some(indices.clone()),
+            ),
+            Self::Rewrite {
+                groups,
+                retained_indices,
+            } => {
+                let mut fragment_id = latest
+                    .and_then(|m| m.max_fragment_id())
+                    .map_or(0, |id| id + 1);
+                let mut replaced = HashMap::new();
+                let mut removed = HashSet::new();
+                for group in groups {
+                    if let Some(first) = group.old_fragments.first() {
+                        replaced.insert(first.id, &group.new_fragments);
+                    }
+                    removed.extend(group.old_fragments.iter().map(|f| f.id));
+                }
+                let mut new_fragments = vec![];
+                for fragment in latest_fragments {
+                    if let Some(&replacements) = replaced.get(&fragment.id) {
+                        for new_fragment in replacements {
+                            let mut new_fragment = new_fragment.clone();
+                            new_fragment.id = fragment_id;
+                            fragment_id += 1;
+                            new_fragments.push(new_fragment);
+                        }
+                    }
+                    if !removed.contains(&fragment.id) {
+                        new_fragments.push(fragment);
+                    }
+                }
+                // Indices created concurrently are dropped too, they may cover the old fragments.
+                let indices = latest_indices
+                    .into_iter()
+                    .filter(|idx| retained_indices.contains(&idx.uuid))
+                    .collect();
+                let manifest = Manifest::new(read_schema(read)?, Arc::new(new_fragments));
+                (manifest, Some(indices))
+            }
+        };
+        manifest.version = version;
+        Ok((manifest, indices))
+    }
+}
+
+/// Append new fragments, numbered after the max fragment id of `latest`.
+fn append_fragments(
+    fragments: &mut Vec<Fragment>,
+    new_fragments: &[Fragment],
+    latest: Option<&Manifest>,
+) {
+    let mut fragment_id = latest
+        .and_then(|m| m.max_fragment_id())
+        .map_or(0, |id| id + 1);
+    for fragment in new_fragments {
+        let mut fragment = fragment.clone();
+        fragment.id = fragment_id;
+        fragment_id += 1;
+        fragments.push(fragment);
+    }
+}
+
+/// Replace the updated fragments, and remove the deleted ones.
+fn apply_deletions(
+    fragments: Vec<Fragment>,
+    updated_fragments: &[Fragment],
+    deleted_fragment_ids: &[u64],
+) -> Vec<Fragment> {
+    let updated = updated_fragments
+        .iter()
+        .map(|f| (f.id, f))
+        .collect::<HashMap<_, _>>();
+    fragments
+        .into_iter()
+        .filter(|f| !deleted_fragment_ids.contains(&f.id))
+        .map(|f| updated.get(&f.id).map_or(f, |&u| u.clone()))
+        .collect()
+}
+
+fn read_schema(read: Option<&Manifest>) -> Result<&Schema> {
+    read.map(|m| &m.schema).ok_or_else(|| Error::Internal {
+        message: "operation requires an existing dataset version".to_string(),
+    })
+}
+
+/// Compare the schemas by field ids and types, regardless of the loaded dictionary values.
+fn schema_equals(a: &Schema, b: &Schema) -> bool {
+    a.field_ids() == b.field_ids() && ArrowSchema::from(a) == ArrowSchema::from(b)
+}
+
+/// Ids of the fragments in `read` that have been modified or removed in `latest`.
+fn changed_fragment_ids(read: &Manifest, latest: &Manifest) -> HashSet<u64> {
+    let latest_fragments = latest
+        .fragments
+        .iter()
+        .map(|f| (f.id, f))
+        .collect::<HashMap<_, _>>();
+    read.fragments
+        .iter()
+        .filter(|f| {
+            latest_fragments
+                .get(&f.id)
+                .map_or(true, |&latest| latest != *f)
+        })
+        .map(|f| f.id)
+        .collect()
+}
+
+/// Find the latest committed version of the dataset.
+///
+/// `_latest.manifest` is updated after the version manifest is committed, so it may lag
+/// behind; the following versions are probed until one does not exist.
+async fn load_latest_manifest(object_store: &ObjectStore, base: &Path) -> Result<Option<Manifest>> {
+    let latest_path = latest_manifest_path(base);
+    let mut manifest = if object_store.exists(&latest_path).await? {
+        Some(read_manifest(object_store, &latest_path).await?)
+    } else {
+        None
+    };
+    loop {
+        let next_version = manifest.as_ref().map_or(1, |m| m.version + 1);
+        let path = manifest_path(base, next_version);
+        if !object_store.exists(&path).await? {
+            return Ok(manifest);
+        }
+        manifest = Some(read_manifest(object_store, &path).await?);
+    }
+}
+
+/// Commit the operation as a new version of the dataset at `base`.
+///
+/// `read` is the manifest of the version the operation was based on, or `None` if the dataset
+/// did not exist. If other versions have been committed since then, the operation is rebased on
+/// top of the latest version, or fails with [Error::CommitConflict] if it is incompatible with
+/// them.
+///
+/// Returns the manifest of the new version.
+pub(crate) async fn commit_transaction(
+    object_store: &ObjectStore,
+    base: &Path,
+    read: Option<&Manifest>,
+    operation: &Operation,
+) -> Result<Manifest> {
+    let mut last_error = None;
+    for _ in 0..MAX_COMMIT_ATTEMPTS {
+        let latest = load_latest_manifest(object_store, base).await?;
+        operation.check_conflict(read, latest.as_ref())?;
+
+        let latest_indices = match latest.as_ref() {
+            Some(m) => read_indices(object_store, base, m).await?,
+            None => vec![],
+        };
+        let (mut manifest, indices) =
+            operation.build_manifest(read, latest.as_ref(), latest_indices)?;
+        match write_manifest_file(
+            object_store,
+            base,
+            &mut manifest,
+            indices,
+            Default::default(),
+        )
+        .await
+        {
+            Ok(()) => return Ok(manifest),
+            // Another writer has claimed this version, try again on top of it.
+            Err(e @ Error::CommitConflict { .. }) => last_error = Some(e),
+            Err(e) => return Err(e),
+        }
+    }
+    Err(last_error.unwrap())
+}
@@ -29,7 +29,7 @@ use async_recursion::async_recursion;
 use super::{Dictionary, LogicalType};
 use crate::{
     arrow::*,
-    encodings::Encoding,
+    encodings::{rle::is_rle_supported, Encoding},
     format::pb,
     io::object_reader::{read_binary_array, read_fixed_stride_array, ObjectReader},
     Error, Result,
@@ -68,6 +68,38 @@ impl Field {
         }
     }
 
+    /// The encoding used to store the field on disk.
+    pub fn encoding(&self) -> Option<&Encoding> {
+        self.encoding.as_ref()
+    }
+
+    /// Set the encoding used to store the field.
+    ///
+    /// Returns [Error::Schema] if the field's data type can not be stored with the encoding.
+    pub fn set_encoding(&mut self, encoding: Encoding) -> Result<()> {
+        let data_type = self.data_type();
+        let supported = match encoding {
+            // The offsets of a list are plain encoded, same as the default encoding.
+            Encoding::Plain => {
+                data_type.is_fixed_stride()
+                    || matches!(data_type, DataType::List(_) | DataType::LargeList(_))
+            }
+            Encoding::VarBinary => data_type.is_binary_like(),
+            Encoding::Dictionary => data_type.is_dictionary(),
+            Encoding::RLE => is_rle_supported(&data_type),
+        };
+        if !supported {
+            return Err(Error::Schema {
+                message: format!(
+                    "Field {} of type {} does not support {:?} encoding",
+                    self.name, data_type, encoding
+                ),
+            });
+        }
+        self.encoding = Some(encoding);
+        Ok(())
+    }
+
     pub fn child(&self, name: &str) -> Option<&Self> {
         self.children.iter().find(|f| f.name == name)
     }
@@ -617,6 +649,21 @@ mod tests {
 
     use arrow_schema::{DataType, Fields, TimeUnit};
 
+    #[test]
+    fn test_set_plain_encoding() {
+        let mut field = Field::try_from(&ArrowField::new(
+            "l",
+            DataType::List(Arc::new(ArrowField::new("item", DataType::Int32, true))),
+            true,
+        ))
+        .unwrap();
+        field.set_encoding(Encoding::Plain).unwrap();
+        assert_eq!(field.encoding, Some(Encoding::Plain));
+
+        let mut field = Field::try_from(&ArrowField::new("s", DataType::Utf8, true)).unwrap();
+        assert!(field.set_encoding(Encoding::Plain).is_err());
+    }
+
     #[test]
     fn arrow_field_to_field() {
         for (name, data_type) in [
@@ -20,7 +20,7 @@ use std::{
 };
 
 use arrow_array::RecordBatch;
-use arrow_schema::{Field as ArrowField, Schema as ArrowSchema};
+use arrow_schema::{DataType, Field as ArrowField, Schema as ArrowSchema};
 
 use super::field::Field;
 use crate::arrow::*;
@@ -191,6 +191,32 @@ impl Schema {
         None
     }
 
+    /// Change the data type of a top-level field, keeping its field id.
+    ///
+    /// The children of the new field, if any, are assigned new field ids.
+    pub(crate) fn with_field_type(&self, name: &str, data_type: &DataType) -> Result<Self> {
+        let index = self
+            .fields
+            .iter()
+            .position(|f| f.name == name)
+            .ok_or_else(|| Error::Schema {
+                message: format!("Field {name} not found"),
+            })?;
+        let old_field = &self.fields[index];
+        let mut field = Field::try_from(&ArrowField::new(
+            name,
+            data_type.clone(),
+            old_field.nullable,
+        ))?;
+        field.id = old_field.id;
+        let mut id_seed = self.max_field_id().unwrap_or(-1) + 1;
+        field.set_id(-1, &mut id_seed);
+
+        let mut schema = self.clone();
+        schema.fields[index] = field;
+        Ok(schema)
+    }
+
     pub(crate) fn max_field_id(&self) -> Option<i32> {
         self.fields.iter().map(|f| f.max_id()).max()
     }
@@ -747,4 +773,25 @@ mod tests {
         let result = schema1.merge(&schema2).unwrap();
         assert_eq!(result, expected_schema);
     }
+
+    #[test]
+    fn test_with_field_type() {
+        let arrow_schema = ArrowSchema::new(vec![
+            ArrowField::new("a", DataType::Int32, false),
+            ArrowField::new("b", DataType::Float32, true),
+        ]);
+        let schema = Schema::try_from(&arrow_schema).unwrap();
+
+        let altered = schema.with_field_type("a", &DataType::Int64).unwrap();
+        assert_eq!(altered.field("a").unwrap().data_type(), DataType::Int64);
+        assert_eq!(altered.field_ids(), vec![0, 1]);
+
+        // Children are assigned new ids.
+        let list_type = DataType::List(Arc::new(ArrowField::new("item", DataType::Float64, true)));
+        let altered = schema.with_field_type("b", &list_type).unwrap();
+        assert_eq!(altered.field("b").unwrap().data_type(), list_type);
+        assert_eq!(altered.field_ids(), vec![0, 1, 2]);
+
+        assert!(schema.with_field_type("c", &DataType::Int64).is_err());
+    }
 }
@@ -22,7 +22,7 @@ pub enum Encoding {
     VarBinary,
     /// Dictionary encoding.
     Dictionary,
-    /// RLE encoding.
+    /// Run-length encoding, see [rle].
     RLE,
 }
 
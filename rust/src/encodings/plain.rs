@@ -16,19 +16,22 @@
 //!
 //! Plain encoding works with fixed stride types, i.e., `boolean`, `i8...i64`, `f16...f64`,
 //! it stores the array directly in the file. It offers O(1) read access.
+//!
+//! For nullable columns, a bit-packed validity bitmap of `ceil(length / 8)` bytes is
+//! written in front of the values of each page.
 
 use std::ops::{Range, RangeFrom, RangeFull, RangeTo};
 use std::slice::from_raw_parts;
 use std::sync::Arc;
 
-use arrow::array::{as_boolean_array, BooleanBuilder};
+use arrow::array::{as_boolean_array, BooleanBufferBuilder, BooleanBuilder};
 use arrow_arith::arithmetic::subtract_scalar;
 use arrow_array::cast::as_primitive_array;
 use arrow_array::{
     make_array, new_empty_array, Array, ArrayRef, BooleanArray, FixedSizeBinaryArray,
     FixedSizeListArray, UInt32Array, UInt8Array,
 };
-use arrow_buffer::{bit_util, Buffer};
+use arrow_buffer::{bit_util, BooleanBuffer, Buffer, NullBuffer};
 use arrow_data::ArrayDataBuilder;
 use arrow_schema::{DataType, Field};
 use arrow_select::concat::concat;
@@ -58,17 +61,66 @@ const PARALLELISM_FACTOR: usize = 4;
 pub struct PlainEncoder<'a> {
     writer: &'a mut ObjectWriter,
     data_type: &'a DataType,
+    /// Write the validity bitmap in front of the values.
+    nullable: bool,
 }
 
 impl<'a> PlainEncoder<'a> {
     pub fn new(writer: &'a mut ObjectWriter, data_type: &'a DataType) -> PlainEncoder<'a> {
-        PlainEncoder { writer, data_type }
+        PlainEncoder {
+            writer,
+            data_type,
+            nullable: false,
+        }
+    }
+
+    /// Create a [PlainEncoder] that persists the validity bitmap of the arrays.
+    ///
+    /// The page must be read back with [`PlainDecoder::new_nullable()`].
+    pub fn new_nullable(writer: &'a mut ObjectWriter, data_type: &'a DataType) -> PlainEncoder<'a> {
+        PlainEncoder {
+            writer,
+            data_type,
+            nullable: true,
+        }
     }
 
     /// Encode an slice of an Array of a batch.
     /// Returns the offset of the metadata
     pub async fn encode(&mut self, arrays: &[&dyn Array]) -> Result<usize> {
-        self.encode_internal(arrays, self.data_type).await
+        let offset = self.writer.tell();
+        if self.nullable {
+            self.encode_validity(arrays).await?;
+        }
+        self.encode_internal(arrays, self.data_type).await?;
+        Ok(offset)
+    }
+
+    /// Write the validity of the top-level arrays as one bit-packed bitmap.
+    ///
+    /// Only the top-level validity is persisted, so fixed size lists with null items are
+    /// rejected, instead of losing their nulls.
+    async fn encode_validity(&mut self, arrays: &[&dyn Array]) -> Result<()> {
+        if arrays.iter().any(|a| has_null_items(*a)) {
+            return Err(Error::NotSupported {
+                source: format!(
+                    "Null items in the valid lists of {} are not supported by plain encoding",
+                    self.data_type
+                )
+                .into(),
+            });
+        }
+        let capacity: usize = arrays.iter().map(|a| a.len()).sum();
+        let mut builder = BooleanBufferBuilder::new(capacity);
+        for array in arrays {
+            if array.null_count() == 0 {
+                builder.append_n(array.len(), true);
+            } else {
+                (0..array.len()).for_each(|i| builder.append(array.is_valid(i)));
+            }
+        }
+        self.writer.write_all(builder.as_slice()).await?;
+        Ok(())
     }
 
     #[async_recursion]
@@ -163,6 +215,23 @@ impl<'a> PlainEncoder<'a> {
     }
 }
 
+/// Returns true if a valid list of the fixed size list array has null items, at any depth.
+///
+/// The items of null lists are not checked, as they are never read.
+fn has_null_items(array: &dyn Array) -> bool {
+    let Some(list) = array.as_any().downcast_ref::<FixedSizeListArray>() else {
+        return false;
+    };
+    if list.values().null_count() == 0 && !matches!(list.value_type(), DataType::FixedSizeList(..))
+    {
+        return false;
+    }
+    (0..list.len()).filter(|&i| list.is_valid(i)).any(|i| {
+        let items = list.value(i);
+        items.null_count() > 0 || has_null_items(items.as_ref())
+    })
+}
+
 /// Decoder for plain encoding.
 pub struct PlainDecoder<'a> {
     reader: &'a dyn ObjectReader,
@@ -171,6 +240,8 @@ pub struct PlainDecoder<'a> {
     position: usize,
     /// Number of the rows in this batch.
     length: usize,
+    /// Whether the page starts with a validity bitmap.
+    nullable: bool,
 }
 
 /// Get byte range from the row offset range.
@@ -194,9 +265,57 @@ impl<'a> PlainDecoder<'a> {
             data_type,
             position,
             length,
+            nullable: false,
+        })
+    }
+
+    /// Create a [PlainDecoder] for a page written by [`PlainEncoder::new_nullable()`].
+    pub fn new_nullable(
+        reader: &'a dyn ObjectReader,
+        data_type: &'a DataType,
+        position: usize,
+        length: usize,
+    ) -> Result<PlainDecoder<'a>> {
+        Ok(PlainDecoder {
+            reader,
+            data_type,
+            position,
+            length,
+            nullable: true,
         })
     }
 
+    /// Decode values from "start" to "end", and apply the validity bitmap
+    /// stored in front of the values.
+    async fn decode_nullable(&self, start: usize, end: usize) -> Result<ArrayRef> {
+        if end > self.length {
+            return Err(Error::IO {
+                message: format!(
+                    "PlainDecoder: request([{}..{}]) out of range: [0..{}]",
+                    start, end, self.length
+                ),
+            });
+        }
+        let values_decoder = PlainDecoder::new(
+            self.reader,
+            self.data_type,
+            self.position + bit_util::ceil(self.length, 8),
+            self.length,
+        )?;
+        let validity_range = self.position + start / 8..self.position + bit_util::ceil(end, 8);
+        let (values, validity) = futures::try_join!(
+            values_decoder.get(start..end),
+            self.reader.get_range(validity_range)
+        )?;
+
+        let nulls = NullBuffer::new(BooleanBuffer::new(validity.into(), start % 8, end - start));
+        if nulls.null_count() == 0 {
+            return Ok(values);
+        }
+        let array_data = values.to_data().into_builder().nulls(Some(nulls)).build()?;
+        Ok(make_array(array_data))
+    }
+
     /// Decode primitive values, from "offset" to "offset + length".
     ///
     async fn decode_primitive(&self, start: usize, end: usize) -> Result<ArrayRef> {
@@ -409,6 +528,9 @@ impl AsyncIndex<Range<usize>> for PlainDecoder<'_> {
         if index.is_empty() {
             return Ok(new_empty_array(self.data_type));
         }
+        if self.nullable {
+            return self.decode_nullable(index.start, index.end).await;
+        }
         match self.data_type {
             DataType::FixedSizeList(items, list_size) => {
                 self.decode_fixed_size_list(items, *list_size, index.start, index.end)
@@ -643,6 +765,155 @@ mod tests {
         )
     }
 
+    async fn test_nullable_round_trip(expected: &[ArrayRef], data_type: DataType) {
+        let store = ObjectStore::memory();
+        let path = Path::from("/foo");
+        let mut object_writer = ObjectWriter::new(&store, &path).await.unwrap();
+        let mut encoder = PlainEncoder::new_nullable(&mut object_writer, &data_type);
+
+        let expected_as_array = expected
+            .iter()
+            .map(|e| e.as_ref())
+            .collect::<Vec<&dyn Array>>();
+        assert_eq!(
+            encoder.encode(expected_as_array.as_slice()).await.unwrap(),
+            0
+        );
+        object_writer.shutdown().await.unwrap();
+
+        let reader = store.open(&path).await.unwrap();
+        let expected_size = expected.iter().map(|e| e.len()).sum();
+        let decoder =
+            PlainDecoder::new_nullable(reader.as_ref(), &data_type, 0, expected_size).unwrap();
+        let expected_merged = concat(expected_as_array.as_slice()).unwrap();
+        let actual = decoder.decode().await.unwrap();
+        assert_eq!(expected_merged.as_ref(), actual.as_ref());
+
+        // Ranges that are not aligned to the bytes of the validity bitmap.
+        let actual = decoder.get(3..expected_size - 5).await.unwrap();
+        assert_eq!(
+            expected_merged.slice(3, expected_size - 8).as_ref(),
+            actual.as_ref()
+        );
+
+        let indices = UInt32Array::from_iter_values((0..expected_size as u32).step_by(3));
+        let actual = decoder.take(&indices).await.unwrap();
+        assert_eq!(
+            take(&expected_merged, &indices, None).unwrap().as_ref(),
+            actual.as_ref()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_encode_decode_nullable_arrays() {
+        let arrs: Vec<ArrayRef> = (0..5)
+            .map(|i| {
+                Arc::new(Int32Array::from_iter((0..13).map(|v| {
+                    if (v + i) % 4 == 0 {
+                        None
+                    } else {
+                        Some(v)
+                    }
+                }))) as ArrayRef
+            })
+            .collect();
+        test_nullable_round_trip(arrs.as_slice(), DataType::Int32).await;
+
+        let arrs: Vec<ArrayRef> = (0..5)
+            .map(|_| {
+                Arc::new(Float64Array::from(vec![Some(1.0), None, None, Some(2.5)])) as ArrayRef
+            })
+            .collect();
+        test_nullable_round_trip(arrs.as_slice(), DataType::Float64).await;
+
+        let arrs: Vec<ArrayRef> = (0..5)
+            .map(|_| Arc::new(BooleanArray::from(vec![Some(true), None, Some(false)])) as ArrayRef)
+            .collect();
+        test_nullable_round_trip(arrs.as_slice(), DataType::Boolean).await;
+
+        // Arrays without nulls round trip through the nullable path as well.
+        let arrs: Vec<ArrayRef> = (0..5)
+            .map(|_| Arc::new(Int64Array::from_iter_values(0..10)) as ArrayRef)
+            .collect();
+        test_nullable_round_trip(arrs.as_slice(), DataType::Int64).await;
+    }
+
+    #[tokio::test]
+    async fn test_encode_decode_nullable_fixed_size_arrays() {
+        let list_type =
+            DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Int32, true)), 3);
+        let arrs: Vec<ArrayRef> = (0..5)
+            .map(|i| {
+                Arc::new(FixedSizeListArray::from_iter_primitive::<
+                    types::Int32Type,
+                    _,
+                    _,
+                >(
+                    (0..11).map(|v| {
+                        if (v + i) % 3 == 0 {
+                            None
+                        } else {
+                            Some(vec![Some(v), Some(v + 1), Some(v + 2)])
+                        }
+                    }),
+                    3,
+                )) as ArrayRef
+            })
+            .collect();
+        test_nullable_round_trip(arrs.as_slice(), list_type).await;
+
+        let arrs: Vec<ArrayRef> = (0..5)
+            .map(|_| {
+                Arc::new(
+                    FixedSizeBinaryArray::try_from_sparse_iter_with_size(
+                        vec![Some(b"abc"), None, Some(b"def"), None, Some(b"ghi")].into_iter(),
+                        3,
+                    )
+                    .unwrap(),
+                ) as ArrayRef
+            })
+            .collect();
+        test_nullable_round_trip(arrs.as_slice(), DataType::FixedSizeBinary(3)).await;
+    }
+
+    #[tokio::test]
+    async fn test_encode_fixed_size_list_with_null_items() {
+        let list_type =
+            DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Int32, true)), 2);
+        let list = |items: Vec<Option<Vec<Option<i32>>>>| {
+            Arc::new(FixedSizeListArray::from_iter_primitive::<
+                types::Int32Type,
+                _,
+                _,
+            >(items, 2)) as ArrayRef
+        };
+
+        // Null items of null lists are never read, so they round trip.
+        let arrs = vec![list(vec![Some(vec![Some(1), Some(2)]), None, None]); 3];
+        test_nullable_round_trip(arrs.as_slice(), list_type.clone()).await;
+
+        // Null items of valid lists can not be persisted.
+        let store = ObjectStore::memory();
+        let path = Path::from("/foo");
+        let mut object_writer = ObjectWriter::new(&store, &path).await.unwrap();
+        let mut encoder = PlainEncoder::new_nullable(&mut object_writer, &list_type);
+        let arr = list(vec![Some(vec![Some(1), None]), None]);
+        assert!(matches!(
+            encoder.encode(&[arr.as_ref()]).await,
+            Err(Error::NotSupported { .. })
+        ));
+
+        // Nor can null inner lists of valid outer lists.
+        let nested_type = DataType::FixedSizeList(Arc::new(Field::new("item", list_type, true)), 1);
+        let mut encoder = PlainEncoder::new_nullable(&mut object_writer, &nested_type);
+        let inner = list(vec![Some(vec![Some(1), Some(2)]), None]);
+        let arr = FixedSizeListArray::try_new_from_values(inner, 1).unwrap();
+        assert!(matches!(
+            encoder.encode(&[&arr]).await,
+            Err(Error::NotSupported { .. })
+        ));
+    }
+
     #[tokio::test]
     async fn test_decode_by_range() {
         let store = ObjectStore::memory();
@@ -795,11 +1066,62 @@ mod tests {
         file_writer.finish().await.unwrap();
         let batch = read_file_as_one_batch(&store, &path).await;
 
-        // None default to Some(false), since we don't support null values yet.
-        let expected = BooleanArray::from(vec![Some(true), Some(false), Some(false)]);
+        // Nulls are preserved for the nullable field.
+        let expected = BooleanArray::from(vec![Some(true), Some(false), None]);
         assert_eq!(batch.column_by_name("b").unwrap().as_ref(), &expected);
     }
 
+    #[tokio::test]
+    async fn test_nullable_fixed_stride_in_file() {
+        let store = ObjectStore::memory();
+        let path = Path::from("/nullable");
+
+        let ints = Int64Array::from_iter((0..100).map(|v| if v % 3 == 0 { None } else { Some(v) }));
+        let vectors = FixedSizeListArray::from_iter_primitive::<types::Float32Type, _, _>(
+            (0..100).map(|v| {
+                if v % 7 == 0 {
+                    None
+                } else {
+                    Some(vec![Some(v as f32), Some(v as f32 + 0.5)])
+                }
+            }),
+            2,
+        );
+        let arrow_schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("i", DataType::Int64, true),
+            Field::new("vec", vectors.data_type().clone(), true),
+        ]));
+        let schema = Schema::try_from(arrow_schema.as_ref()).unwrap();
+        let mut file_writer = FileWriter::try_new(&store, &path, schema.clone())
+            .await
+            .unwrap();
+        for i in (0..100).step_by(25) {
+            let batch = RecordBatch::try_new(
+                arrow_schema.clone(),
+                vec![Arc::new(ints.slice(i, 25)), Arc::new(vectors.slice(i, 25))],
+            )
+            .unwrap();
+            file_writer.write(&[batch]).await.unwrap();
+        }
+        file_writer.finish().await.unwrap();
+
+        let batch = read_file_as_one_batch(&store, &path).await;
+        assert_eq!(batch.column_by_name("i").unwrap().as_ref(), &ints);
+        assert_eq!(batch.column_by_name("vec").unwrap().as_ref(), &vectors);
+
+        let reader = FileReader::try_new(&store, &path).await.unwrap();
+        let actual = reader.take(&[0, 1, 7, 30, 63, 99], &schema).await.unwrap();
+        let indices = UInt32Array::from(vec![0, 1, 7, 30, 63, 99]);
+        assert_eq!(
+            actual.column_by_name("i").unwrap().as_ref(),
+            take(&ints, &indices, None).unwrap().as_ref()
+        );
+        assert_eq!(
+            actual.column_by_name("vec").unwrap().as_ref(),
+            take(&vectors, &indices, None).unwrap().as_ref()
+        );
+    }
+
     #[tokio::test]
     async fn test_encode_fixed_size_list_slice() {
         let store = ObjectStore::memory();
@@ -18,3 +18,547 @@
 //! Run-length encoding
 //!
 //! <https://en.wikipedia.org/wiki/Run-length_encoding>
+//!
+//! Run-length encoding works with fixed width primitive types, i.e., `i8...i64`,
+//! `f16...f64`, temporal types, decimals and fixed size binary. Consecutive equal
+//! values, including consecutive nulls, are stored once as a run.
+//!
+//! The layout of one page is:
+//!
+//! ```text
+//! +-----------------+-------------------------+--------------------------+----------------+
+//! | num_runs: u32   | run ends (u32 x N)      | run validity (N bits)    | run values     |
+//! +-----------------+-------------------------+--------------------------+----------------+
+//! ```
+//!
+//! `run ends` are the exclusive end row offsets of each run within the page, so the
+//! runs covering any row range can be located via binary search.
+
+use std::ops::{Range, RangeFrom, RangeFull, RangeTo};
+
+use arrow_array::builder::UInt32Builder;
+use arrow_array::cast::{as_boolean_array, as_primitive_array};
+use arrow_array::types::UInt32Type;
+use arrow_array::{make_array, new_empty_array, Array, ArrayRef, BooleanArray, UInt32Array};
+use arrow_buffer::{bit_util, NullBuffer};
+use arrow_schema::DataType;
+use arrow_select::concat::concat;
+use arrow_select::take::take;
+use async_trait::async_trait;
+use byteorder::{ByteOrder, LittleEndian};
+use tokio::io::AsyncWriteExt;
+
+use super::plain::{PlainDecoder, PlainEncoder};
+use super::{AsyncIndex, Decoder, Encoder};
+use crate::arrow::*;
+use crate::error::{Error, Result};
+use crate::io::object_reader::ObjectReader;
+use crate::io::object_writer::ObjectWriter;
+use crate::io::ReadBatchParams;
+
+/// Size of the `num_runs` header, in bytes.
+const HEADER_SIZE: usize = 4;
+
+/// Returns true if run-length encoding supports the data type.
+pub fn is_rle_supported(data_type: &DataType) -> bool {
+    data_type.is_fixed_stride()
+        && !matches!(data_type, DataType::Boolean | DataType::FixedSizeList(_, _))
+}
+
+/// Encoder for run-length encoding.
+pub struct RLEEncoder<'a> {
+    writer: &'a mut ObjectWriter,
+}
+
+impl<'a> RLEEncoder<'a> {
+    pub fn new(writer: &'a mut ObjectWriter) -> Self {
+        Self { writer }
+    }
+}
+
+/// Row `idx` of `array` as raw bytes, or `None` if the row is null.
+fn value_bytes<'b>(
+    buffer: &'b [u8],
+    offset: usize,
+    byte_width: usize,
+    array: &dyn Array,
+    idx: usize,
+) -> Option<&'b [u8]> {
+    if array.is_null(idx) {
+        None
+    } else {
+        let start = (offset + idx) * byte_width;
+        Some(&buffer[start..start + byte_width])
+    }
+}
+
+#[async_trait]
+impl<'a> Encoder for RLEEncoder<'a> {
+    /// Encode the arrays as one page, and returns the file position of the page.
+    async fn encode(&mut self, arrays: &[&dyn Array]) -> Result<usize> {
+        assert!(!arrays.is_empty());
+        let data_type = arrays[0].data_type();
+        if !is_rle_supported(data_type) {
+            return Err(Error::Schema {
+                message: format!("RLEEncoder: unsupported data type: {data_type}"),
+            });
+        }
+        let byte_width = data_type.byte_width();
+
+        // The run ends (exclusive) of the whole page.
+        let mut run_ends = UInt32Builder::new();
+        // The values of each run, taken from each of the input arrays.
+        let mut run_values: Vec<ArrayRef> = Vec::with_capacity(arrays.len());
+        let mut last_value: Option<Option<Vec<u8>>> = None;
+        let mut num_rows: u32 = 0;
+
+        for array in arrays {
+            let data = array.to_data();
+            let buffer = data.buffers()[0].as_slice();
+            let mut run_starts = UInt32Builder::new();
+            for idx in 0..array.len() {
+                let value = value_bytes(buffer, data.offset(), byte_width, *array, idx);
+                let continues_run = matches!(&last_value, Some(last) if last.as_deref() == value);
+                if continues_run {
+                    let last = run_ends.values_slice_mut().last_mut().unwrap();
+                    *last += 1;
+                } else {
+                    run_starts.append_value(idx as u32);
+                    run_ends.append_value(num_rows + 1);
+                    last_value = Some(value.map(|v| v.to_vec()));
+                }
+                num_rows += 1;
+            }
+            let run_starts = run_starts.finish();
+            if !run_starts.is_empty() {
+                run_values.push(take(*array, &run_starts, None)?);
+            }
+        }
+
+        let run_ends = run_ends.finish();
+        let run_values = if run_values.is_empty() {
+            new_empty_array(data_type)
+        } else {
+            concat(
+                run_values
+                    .iter()
+                    .map(|a| a.as_ref())
+                    .collect::<Vec<_>>()
+                    .as_slice(),
+            )?
+        };
+        let run_validity = BooleanArray::from(
+            (0..run_values.len())
+                .map(|i| run_values.is_valid(i))
+                .collect::<Vec<_>>(),
+        );
+
+        let pos = self.writer.tell();
+        let mut header = [0_u8; HEADER_SIZE];
+        LittleEndian::write_u32(&mut header, run_ends.len() as u32);
+        self.writer.write_all(&header).await?;
+        PlainEncoder::new(self.writer, &DataType::UInt32)
+            .encode(&[&run_ends])
+            .await?;
+        PlainEncoder::new(self.writer, &DataType::Boolean)
+            .encode(&[&run_validity])
+            .await?;
+        PlainEncoder::new(self.writer, data_type)
+            .encode(&[run_values.as_ref()])
+            .await?;
+        Ok(pos)
+    }
+}
+
+/// Decoder for run-length encoding.
+pub struct RLEDecoder<'a> {
+    reader: &'a dyn ObjectReader,
+    data_type: &'a DataType,
+    /// The start position of the page in the file.
+    position: usize,
+    /// Number of the rows in this page.
+    length: usize,
+}
+
+/// The runs of one page, loaded from the page header.
+struct Runs {
+    /// The exclusive end row of each run.
+    ends: UInt32Array,
+}
+
+impl Runs {
+    fn len(&self) -> usize {
+        self.ends.len()
+    }
+
+    /// Index of the run that contains row `row`.
+    fn find(&self, row: u32) -> usize {
+        self.ends.values().partition_point(|end| *end <= row)
+    }
+}
+
+impl<'a> RLEDecoder<'a> {
+    pub fn new(
+        reader: &'a dyn ObjectReader,
+        data_type: &'a DataType,
+        position: usize,
+        length: usize,
+    ) -> Self {
+        Self {
+            reader,
+            data_type,
+            position,
+            length,
+        }
+    }
+
+    /// Load the run ends of the page.
+    async fn load_runs(&self) -> Result<Runs> {
+        let header = self
+            .reader
+            .get_range(self.position..self.position + HEADER_SIZE)
+            .await?;
+        let num_runs = LittleEndian::read_u32(&header) as usize;
+        let ends_decoder = PlainDecoder::new(
+            self.reader,
+            &DataType::UInt32,
+            self.position + HEADER_SIZE,
+            num_runs,
+        )?;
+        let ends = ends_decoder.decode().await?;
+        Ok(Runs {
+            ends: as_primitive_array::<UInt32Type>(ends.as_ref()).clone(),
+        })
+    }
+
+    /// Read the values of the runs in `run_range`, with their validity applied.
+    async fn read_run_values(&self, runs: &Runs, run_range: Range<usize>) -> Result<ArrayRef> {
+        let num_runs = runs.len();
+        let validity_position = self.position + HEADER_SIZE + num_runs * 4;
+        let values_position = validity_position + bit_util::ceil(num_runs, 8);
+
+        let validity_decoder =
+            PlainDecoder::new(self.reader, &DataType::Boolean, validity_position, num_runs)?;
+        let values_decoder =
+            PlainDecoder::new(self.reader, self.data_type, values_position, num_runs)?;
+        let (validity, values) = futures::try_join!(
+            validity_decoder.get(run_range.clone()),
+            values_decoder.get(run_range)
+        )?;
+
+        let nulls = NullBuffer::new(as_boolean_array(validity.as_ref()).values().clone());
+        if nulls.null_count() == 0 {
+            return Ok(values);
+        }
+        let array_data = values.to_data().into_builder().nulls(Some(nulls)).build()?;
+        Ok(make_array(array_data))
+    }
+
+    /// Decode rows from "start" to "end".
+    async fn decode_range(&self, start: usize, end: usize) -> Result<ArrayRef> {
+        if end > self.length {
+            return Err(Error::IO {
+                message: format!(
+                    "RLEDecoder: request([{}..{}]) out of range: [0..{}]",
+                    start, end, self.length
+                ),
+            });
+        }
+        if start >= end {
+            return Ok(new_empty_array(self.data_type));
+        }
+        let runs = self.load_runs().await?;
+        let first_run = runs.find(start as u32);
+        let last_run = runs.find(end as u32 - 1);
+        let values = self.read_run_values(&runs, first_run..last_run + 1).await?;
+
+        // Expand each run to the rows it covers.
+        let mut indices = UInt32Builder::with_capacity(end - start);
+        let mut row = start as u32;
+        for run in first_run..=last_run {
+            let run_end = std::cmp::min(runs.ends.value(run), end as u32);
+            (row..run_end).for_each(|_| indices.append_value((run - first_run) as u32));
+            row = run_end;
+        }
+        Ok(take(values.as_ref(), &indices.finish(), None)?)
+    }
+}
+
+#[async_trait]
+impl<'a> Decoder for RLEDecoder<'a> {
+    async fn decode(&self) -> Result<ArrayRef> {
+        self.decode_range(0, self.length).await
+    }
+
+    async fn take(&self, indices: &UInt32Array) -> Result<ArrayRef> {
+        if indices.is_empty() {
+            return Ok(new_empty_array(self.data_type));
+        }
+        if let Some(idx) = indices
+            .values()
+            .iter()
+            .find(|i| **i as usize >= self.length)
+        {
+            return Err(Error::IO {
+                message: format!(
+                    "RLEDecoder: take index {} out of range: [0..{}]",
+                    idx, self.length
+                ),
+            });
+        }
+
+        let runs = self.load_runs().await?;
+        let run_ids = indices
+            .values()
+            .iter()
+            .map(|i| runs.find(*i) as u32)
+            .collect::<Vec<_>>();
+        let first_run = *run_ids.iter().min().unwrap();
+        let last_run = *run_ids.iter().max().unwrap();
+        let values = self
+            .read_run_values(&runs, first_run as usize..last_run as usize + 1)
+            .await?;
+
+        let run_indices = UInt32Array::from_iter_values(run_ids.iter().map(|r| r - first_run));
+        Ok(take(values.as_ref(), &run_indices, None)?)
+    }
+}
+
+#[async_trait]
+impl AsyncIndex<usize> for RLEDecoder<'_> {
+    type Output = Result<ArrayRef>;
+
+    async fn get(&self, index: usize) -> Self::Output {
+        self.get(index..index + 1).await
+    }
+}
+
+#[async_trait]
+impl AsyncIndex<Range<usize>> for RLEDecoder<'_> {
+    type Output = Result<ArrayRef>;
+
+    async fn get(&self, index: Range<usize>) -> Self::Output {
+        self.decode_range(index.start, index.end).await
+    }
+}
+
+#[async_trait]
+impl AsyncIndex<RangeFrom<usize>> for RLEDecoder<'_> {
+    type Output = Result<ArrayRef>;
+
+    async fn get(&self, index: RangeFrom<usize>) -> Self::Output {
+        self.get(index.start..self.length).await
+    }
+}
+
+#[async_trait]
+impl AsyncIndex<RangeTo<usize>> for RLEDecoder<'_> {
+    type Output = Result<ArrayRef>;
+
+    async fn get(&self, index: RangeTo<usize>) -> Self::Output {
+        self.get(0..index.end).await
+    }
+}
+
+#[async_trait]
+impl AsyncIndex<RangeFull> for RLEDecoder<'_> {
+    type Output = Result<ArrayRef>;
+
+    async fn get(&self, _: RangeFull) -> Self::Output {
+        self.get(0..self.length).await
+    }
+}
+
+#[async_trait]
+impl AsyncIndex<ReadBatchParams> for RLEDecoder<'_> {
+    type Output = Result<ArrayRef>;
+
+    async fn get(&self, params: ReadBatchParams) -> Self::Output {
+        match params {
+            ReadBatchParams::Range(r) => self.get(r).await,
+            ReadBatchParams::RangeFull => self.get(..).await,
+            ReadBatchParams::RangeTo(r) => self.get(r).await,
+            ReadBatchParams::RangeFrom(r) => self.get(r).await,
+            ReadBatchParams::Indices(indices) => self.take(&indices).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use arrow_array::{
+        FixedSizeBinaryArray, Float32Array, Int32Array, RecordBatch, TimestampSecondArray,
+    };
+    use arrow_schema::{Field as ArrowField, Schema as ArrowSchema};
+    use object_store::path::Path;
+
+    use crate::datatypes::Schema;
+    use crate::encodings::Encoding;
+    use crate::io::{FileReader, FileWriter, ObjectStore};
+
+    async fn test_round_trip(expected: &[ArrayRef]) {
+        let data_type = expected[0].data_type().clone();
+        let store = ObjectStore::memory();
+        let path = Path::from("/rle");
+        let mut object_writer = ObjectWriter::new(&store, &path).await.unwrap();
+        let mut encoder = RLEEncoder::new(&mut object_writer);
+        let arrays = expected.iter().map(|a| a.as_ref()).collect::<Vec<_>>();
+        assert_eq!(encoder.encode(arrays.as_slice()).await.unwrap(), 0);
+        object_writer.shutdown().await.unwrap();
+
+        let reader = store.open(&path).await.unwrap();
+        let length = expected.iter().map(|a| a.len()).sum::<usize>();
+        let decoder = RLEDecoder::new(reader.as_ref(), &data_type, 0, length);
+        let expected_merged = concat(arrays.as_slice()).unwrap();
+        assert_eq!(
+            decoder.decode().await.unwrap().as_ref(),
+            expected_merged.as_ref()
+        );
+
+        for range in [0..1, 1..length - 1, 3..7, length - 2..length] {
+            assert_eq!(
+                decoder.get(range.clone()).await.unwrap().as_ref(),
+                expected_merged.slice(range.start, range.len()).as_ref()
+            );
+        }
+        assert_eq!(
+            decoder.get(2..).await.unwrap().as_ref(),
+            expected_merged.slice(2, length - 2).as_ref()
+        );
+
+        let indices = UInt32Array::from_iter_values([length as u32 - 1, 0, 4, 5, 6]);
+        assert_eq!(
+            decoder.take(&indices).await.unwrap().as_ref(),
+            take(expected_merged.as_ref(), &indices, None)
+                .unwrap()
+                .as_ref()
+        );
+
+        assert!(decoder.get(0..length + 1).await.is_err());
+        assert!(decoder
+            .take(&UInt32Array::from_iter_values([length as u32]))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_encode_decode_runs() {
+        // Runs span across the input arrays.
+        test_round_trip(&[
+            Arc::new(Int32Array::from(vec![1, 1, 1, 2, 2])) as ArrayRef,
+            Arc::new(Int32Array::from(vec![2, 2, 3, 1, 1, 1, 1])) as ArrayRef,
+        ])
+        .await;
+
+        // No repeated values at all.
+        test_round_trip(&[
+            Arc::new(Float32Array::from_iter_values((0..20).map(|v| v as f32))) as ArrayRef,
+        ])
+        .await;
+
+        // Sliced input.
+        let values = Int32Array::from_iter_values((0..100).map(|v| v / 10));
+        test_round_trip(&[
+            Arc::new(values.slice(5, 30)) as ArrayRef,
+            Arc::new(values.slice(50, 30)) as ArrayRef,
+        ])
+        .await;
+
+        test_round_trip(&[Arc::new(
+            TimestampSecondArray::from(vec![10, 10, 10, 20, 20, 30, 30, 30, 30])
+                .with_timezone("UTC"),
+        ) as ArrayRef])
+        .await;
+
+        test_round_trip(&[Arc::new(
+            FixedSizeBinaryArray::try_from_iter(
+                vec![b"ab", b"ab", b"cd", b"cd", b"cd", b"ab", b"ef", b"ef"].into_iter(),
+            )
+            .unwrap(),
+        ) as ArrayRef])
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_encode_decode_null_runs() {
+        test_round_trip(&[
+            Arc::new(Int32Array::from(vec![
+                None,
+                None,
+                Some(1),
+                Some(1),
+                None,
+                Some(2),
+                Some(2),
+                Some(2),
+            ])) as ArrayRef,
+            Arc::new(Int32Array::from(vec![Some(2), None, None, Some(3)])) as ArrayRef,
+        ])
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_type() {
+        let store = ObjectStore::memory();
+        let path = Path::from("/rle");
+        let mut object_writer = ObjectWriter::new(&store, &path).await.unwrap();
+        let mut encoder = RLEEncoder::new(&mut object_writer);
+        let arr = BooleanArray::from(vec![true, true]);
+        assert!(encoder.encode(&[&arr]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_write_rle_field() {
+        let arrow_schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("label", DataType::Int32, true),
+            ArrowField::new("score", DataType::Float32, false),
+        ]));
+        let mut schema = Schema::try_from(arrow_schema.as_ref()).unwrap();
+        schema.fields[0].set_encoding(Encoding::RLE).unwrap();
+        assert!(schema.fields[1].set_encoding(Encoding::VarBinary).is_err());
+
+        let store = ObjectStore::memory();
+        let path = Path::from("/rle_file");
+        let mut file_writer = FileWriter::try_new(&store, &path, schema.clone())
+            .await
+            .unwrap();
+        let mut batches = vec![];
+        for i in 0..4 {
+            let batch = RecordBatch::try_new(
+                arrow_schema.clone(),
+                vec![
+                    Arc::new(Int32Array::from_iter((0..50).map(|v| {
+                        if v % 20 == 19 {
+                            None
+                        } else {
+                            Some(i * 10 + v / 20)
+                        }
+                    }))),
+                    Arc::new(Float32Array::from_iter_values((0..50).map(|v| v as f32))),
+                ],
+            )
+            .unwrap();
+            file_writer.write(&[batch.clone()]).await.unwrap();
+            batches.push(batch);
+        }
+        file_writer.finish().await.unwrap();
+
+        let reader = FileReader::try_new(&store, &path).await.unwrap();
+        assert_eq!(reader.schema().fields[0].encoding, Some(Encoding::RLE));
+        for (i, expected) in batches.iter().enumerate() {
+            let actual = reader.read_batch(i as i32, .., &schema).await.unwrap();
+            assert_eq!(&actual, expected);
+            let actual = reader.read_batch(i as i32, 15..42, &schema).await.unwrap();
+            assert_eq!(actual, expected.slice(15, 27));
+        }
+        let actual = reader.take(&[1, 19, 70, 139], &schema).await.unwrap();
+        assert_eq!(
+            actual.column(0).as_ref(),
+            &Int32Array::from(vec![Some(0), None, Some(11), None])
+        );
+    }
+}
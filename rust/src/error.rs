@@ -35,6 +35,8 @@ pub enum Error {
     DatasetAlreadyExists { uri: String },
     #[snafu(display("Append with different schema: original={original} new={new}"))]
     SchemaMismatch { original: Schema, new: Schema },
+    #[snafu(display("Commit conflict for version {version}: {source}"))]
+    CommitConflict { version: u64, source: BoxedError },
     #[snafu(display("Dataset at path {path} was not found: {source}"))]
     DatasetNotFound { path: String, source: BoxedError },
     #[snafu(display("Encountered corrupt file {path}: {source}"))]
@@ -13,7 +13,13 @@
 // limitations under the License.
 
 use std::collections::BTreeMap;
+use std::io::Cursor;
 use std::ops::Range;
+use std::sync::Arc;
+
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_ipc::{reader::StreamReader, writer::StreamWriter};
+use arrow_schema::{Field as ArrowField, Schema as ArrowSchema};
 
 use crate::format::{pb, ProtoStruct};
 use crate::{Error, Result};
@@ -29,6 +35,69 @@ pub struct Metadata {
 
     /// The file position of the manifest block in the file.
     pub manifest_position: Option<usize>,
+
+    /// Ids of the fields whose pages are prefixed with a validity bitmap.
+    pub fields_with_validity: Vec<i32>,
+
+    /// Statistics of the pages, by field id.
+    pub page_statistics: BTreeMap<i32, PageStatistics>,
+}
+
+/// Statistics of the pages of one field, with one value per batch.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageStatistics {
+    /// Min value of each page, ignoring nulls.
+    pub min_values: ArrayRef,
+
+    /// Max value of each page, ignoring nulls.
+    pub max_values: ArrayRef,
+
+    /// Number of nulls in each page.
+    pub null_counts: Vec<i64>,
+}
+
+impl PageStatistics {
+    fn min_max_schema(values: &ArrayRef) -> Arc<ArrowSchema> {
+        Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("min", values.data_type().clone(), true),
+            ArrowField::new("max", values.data_type().clone(), true),
+        ]))
+    }
+
+    fn to_pb(&self, field_id: i32) -> Result<pb::PageStatistics> {
+        let schema = Self::min_max_schema(&self.min_values);
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![self.min_values.clone(), self.max_values.clone()],
+        )?;
+        let mut min_max = vec![];
+        let mut writer = StreamWriter::try_new(&mut min_max, &schema)?;
+        writer.write(&batch)?;
+        writer.finish()?;
+        drop(writer);
+        Ok(pb::PageStatistics {
+            field_id,
+            null_counts: self.null_counts.clone(),
+            min_max,
+        })
+    }
+
+    fn try_from_pb(p: &pb::PageStatistics) -> Result<Self> {
+        let mut reader = StreamReader::try_new(Cursor::new(p.min_max.as_slice()), None)?;
+        let batch = reader.next().transpose()?.ok_or_else(|| Error::IO {
+            message: format!("Page statistics of field {} are empty", p.field_id),
+        })?;
+        if batch.num_columns() != 2 || batch.num_rows() != p.null_counts.len() {
+            return Err(Error::IO {
+                message: format!("Page statistics of field {} are malformed", p.field_id),
+            });
+        }
+        Ok(Self {
+            min_values: batch.column(0).clone(),
+            max_values: batch.column(1).clone(),
+            null_counts: p.null_counts.clone(),
+        })
+    }
 }
 
 impl ProtoStruct for Metadata {
@@ -41,6 +110,20 @@ impl From<&Metadata> for pb::Metadata {
             batch_offsets: m.batch_offsets.clone(),
             page_table_position: m.page_table_position as u64,
             manifest_position: m.manifest_position.unwrap_or(0) as u64,
+            fields_with_validity: m.fields_with_validity.clone(),
+            // Statistics are only an optimization, so the ones that can not be
+            // encoded are left out rather than failing the write.
+            page_statistics: m
+                .page_statistics
+                .iter()
+                .filter_map(|(field_id, stats)| match stats.to_pb(*field_id) {
+                    Ok(p) => Some(p),
+                    Err(e) => {
+                        log::warn!("Failed to encode page statistics of field {field_id}: {e}");
+                        None
+                    }
+                })
+                .collect(),
         }
     }
 }
@@ -51,6 +134,21 @@ impl From<pb::Metadata> for Metadata {
             batch_offsets: m.batch_offsets.clone(),
             page_table_position: m.page_table_position as usize,
             manifest_position: Some(m.manifest_position as usize),
+            fields_with_validity: m.fields_with_validity,
+            page_statistics: m
+                .page_statistics
+                .iter()
+                .filter_map(|p| match PageStatistics::try_from_pb(p) {
+                    Ok(stats) => Some((p.field_id, stats)),
+                    Err(e) => {
+                        log::warn!(
+                            "Failed to decode page statistics of field {}: {e}",
+                            p.field_id
+                        );
+                        None
+                    }
+                })
+                .collect(),
         }
     }
 }
@@ -89,6 +187,18 @@ impl Metadata {
             .push(batch_len + self.batch_offsets.last().unwrap())
     }
 
+    /// Record that the pages of the field carry a validity bitmap.
+    pub fn set_has_validity(&mut self, field_id: i32) {
+        if !self.has_validity(field_id) {
+            self.fields_with_validity.push(field_id);
+        }
+    }
+
+    /// Returns true if the pages of the field carry a validity bitmap.
+    pub fn has_validity(&self, field_id: i32) -> bool {
+        self.fields_with_validity.contains(&field_id)
+    }
+
     /// Get the starting offset of the batch.
     pub fn get_offset(&self, batch_id: i32) -> Option<i32> {
         self.batch_offsets.get(batch_id as usize).copied()
@@ -218,4 +328,27 @@ mod tests {
         let batches = metadata.range_to_batches(14..33).unwrap();
         assert_eq!(batches, vec![(1, 9..10), (2, 0..15), (3, 0..3)]);
     }
+
+    #[test]
+    fn test_page_statistics_roundtrip() {
+        use arrow_array::Int32Array;
+
+        let mut metadata = Metadata::default();
+        metadata.push_batch_length(10);
+        metadata.push_batch_length(10);
+        metadata.page_statistics.insert(
+            3,
+            PageStatistics {
+                min_values: Arc::new(Int32Array::from(vec![Some(1), None])),
+                max_values: Arc::new(Int32Array::from(vec![Some(8), None])),
+                null_counts: vec![0, 10],
+            },
+        );
+
+        let pb_metadata = pb::Metadata::from(&metadata);
+        assert_eq!(pb_metadata.page_statistics.len(), 1);
+        assert_eq!(pb_metadata.page_statistics[0].field_id, 3);
+        let actual = Metadata::from(pb_metadata);
+        assert_eq!(actual.page_statistics, metadata.page_statistics);
+    }
 }
@@ -29,14 +29,23 @@ pub mod pb {
 }
 
 pub(crate) mod cache;
+pub mod inverted;
+pub mod scalar;
 pub mod vector;
 
-use crate::dataset::write_manifest_file;
+use crate::dataset::transaction::{commit_transaction, Operation};
 use crate::format::Index as IndexMetadata;
+use crate::io::object_reader::{read_message, ObjectReader};
+use crate::io::{read_message_from_buf, read_metadata_offset};
 use crate::session::Session;
 use crate::{dataset::Dataset, Error, Result};
 
-use self::vector::{build_vector_index, VectorIndexParams};
+use self::inverted::build_inverted_index;
+use self::scalar::{bitmap::build_bitmap_index, btree::build_btree_index, ScalarIndexParams};
+use self::vector::{build_vector_index, ivf::optimize_ivf_index, VectorIndexParams};
+
+/// Name of the file with the protobuf metadata of an index, in the index directory.
+pub(crate) const INDEX_FILE_NAME: &str = "index.idx";
 
 /// Trait of a secondary index.
 pub(crate) trait Index: Send + Sync {
@@ -47,6 +56,14 @@ pub(crate) trait Index: Send + Sync {
 /// Index Type
 pub enum IndexType {
     // Preserve 0-100 for simple indices.
+    /// B-tree index over the sorted values of a scalar column.
+    BTree = 0,
+
+    /// Bitmap index of the row ids of each distinct value of a scalar column.
+    Bitmap = 1,
+
+    /// Full-text inverted index of the tokens of a string column.
+    Inverted = 2,
 
     // 100+ and up for vector index.
     /// Flat vector index.
@@ -56,6 +73,9 @@ pub enum IndexType {
 impl fmt::Display for IndexType {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
+            Self::BTree => write!(f, "BTree"),
+            Self::Bitmap => write!(f, "Bitmap"),
+            Self::Inverted => write!(f, "Inverted"),
             Self::Vector => write!(f, "Vector"),
         }
     }
@@ -96,6 +116,16 @@ pub trait DatasetIndexExt {
         params: &dyn IndexParams,
         replace: bool,
     ) -> Result<Dataset>;
+
+    /// Optimize the indices: add the rows appended since an index was built to it.
+    ///
+    /// The rows of the new fragments are assigned to the IVF partitions and encoded
+    /// with the product quantizer of an IVF_PQ index, without retraining them.
+    /// The other indices are left as they are, and the new fragments keep being
+    /// searched without the index.
+    ///
+    /// Upon finish, a new dataset version is generated if any index was updated.
+    async fn optimize_indices(&self) -> Result<Dataset>;
 }
 
 #[async_trait]
@@ -115,9 +145,9 @@ impl DatasetIndexExt for Dataset {
         }
         let column = columns[0];
         let Some(field) = self.schema().field(column) else {
-            return Err(Error::Index{message: format!(
-                "CreateIndex: column '{column}' does not exist"
-            )});
+            return Err(Error::Index {
+                message: format!("CreateIndex: column '{column}' does not exist"),
+            });
         };
 
         // Load indices from the disk.
@@ -156,39 +186,192 @@ impl DatasetIndexExt for Dataset {
                 build_vector_index(self, column, &index_name, &index_id.to_string(), vec_params)
                     .await?;
             }
-        }
+            IndexType::BTree => {
+                let scalar_params = params
+                    .as_any()
+                    .downcast_ref::<ScalarIndexParams>()
+                    .ok_or_else(|| Error::Index {
+                        message: "BTree index type must take a ScalarIndexParams".to_string(),
+                    })?;
 
-        let latest_manifest = self.latest_manifest().await?;
-        let mut new_manifest = self.manifest.as_ref().clone();
-        new_manifest.version = latest_manifest.version + 1;
+                build_btree_index(
+                    self,
+                    column,
+                    &index_name,
+                    &index_id.to_string(),
+                    scalar_params,
+                )
+                .await?;
+            }
+            IndexType::Bitmap => {
+                build_bitmap_index(self, column, &index_name, &index_id.to_string()).await?;
+            }
+            IndexType::Inverted => {
+                let scalar_params = params
+                    .as_any()
+                    .downcast_ref::<ScalarIndexParams>()
+                    .ok_or_else(|| Error::Index {
+                        message: "Inverted index type must take a ScalarIndexParams".to_string(),
+                    })?;
+
+                build_inverted_index(
+                    self,
+                    column,
+                    &index_name,
+                    &index_id.to_string(),
+                    scalar_params,
+                )
+                .await?;
+            }
+        }
 
-        // Write index metadata down
-        let new_idx = IndexMetadata::new(index_id, &index_name, &[field.id], new_manifest.version);
-        // Exclude the old index if it exists.
+        // Write index metadata down.
+        // The old index with the same name is replaced on commit.
         // We already checked that there is no index with the same name but on different fields.
-        let mut indices = indices
-            .iter()
-            .filter(|idx| idx.name != index_name)
-            .cloned()
-            .collect::<Vec<_>>();
-        indices.push(new_idx);
-
-        write_manifest_file(
-            &self.object_store,
-            &self.base,
-            &mut new_manifest,
-            Some(indices),
-            Default::default(),
-        )
-        .await?;
-
-        Ok(Self {
-            object_store: self.object_store.clone(),
-            base: self.base.clone(),
-            manifest: Arc::new(new_manifest),
-            session: Arc::new(Session::default()),
-        })
+        let new_idx =
+            IndexMetadata::new(index_id, &index_name, &[field.id], self.version().version);
+        commit_indices(self, vec![new_idx]).await
     }
+
+    async fn optimize_indices(&self) -> Result<Self> {
+        let mut new_indices = vec![];
+        for index in self.load_indices().await? {
+            if index.dataset_version == self.version().version {
+                continue;
+            }
+            // Cleaning up old versions keeps the versions the indices were built on.
+            let indexed = self.checkout_version(index.dataset_version).await?;
+            let fragments = self.manifest.fragments_since(&indexed.manifest)?;
+            if fragments.is_empty() {
+                continue;
+            }
+
+            let (reader, proto) = read_index_file(self, &index.uuid.to_string()).await?;
+            let Some(pb::index::Implementation::VectorIndex(vec_idx)) =
+                proto.implementation.as_ref()
+            else {
+                continue;
+            };
+            // Only the IVF_PQ and IVF_SQ indices can be optimized, the other indices
+            // are left as they are.
+            let has_stage = |f: fn(&pb::vector_index_stage::Stage) -> bool| {
+                vec_idx
+                    .stages
+                    .iter()
+                    .any(|stage| stage.stage.as_ref().map_or(false, f))
+            };
+            let is_ivf = has_stage(|stage| matches!(stage, pb::vector_index_stage::Stage::Ivf(_)));
+            let is_quantized = has_stage(|stage| {
+                matches!(
+                    stage,
+                    pb::vector_index_stage::Stage::Pq(_) | pb::vector_index_stage::Stage::Sq(_)
+                )
+            });
+            if !is_ivf || !is_quantized {
+                continue;
+            }
+
+            let index_id = Uuid::new_v4();
+            optimize_ivf_index(
+                self,
+                &proto.columns[0],
+                &index.name,
+                &index_id.to_string(),
+                reader.as_ref(),
+                vec_idx,
+                fragments,
+            )
+            .await?;
+            new_indices.push(IndexMetadata::new(
+                index_id,
+                &index.name,
+                &index.fields,
+                self.version().version,
+            ));
+        }
+
+        if new_indices.is_empty() {
+            return Ok(self.clone());
+        }
+        commit_indices(self, new_indices).await
+    }
+}
+
+/// Commit the new indices, replacing the old indices with the same names.
+async fn commit_indices(dataset: &Dataset, new_indices: Vec<IndexMetadata>) -> Result<Dataset> {
+    let operation = Operation::CreateIndex { new_indices };
+    let new_manifest = commit_transaction(
+        &dataset.object_store,
+        &dataset.base,
+        Some(dataset.manifest.as_ref()),
+        &operation,
+    )
+    .await?;
+
+    Ok(Dataset {
+        object_store: dataset.object_store.clone(),
+        base: dataset.base.clone(),
+        manifest: Arc::new(new_manifest),
+        session: Arc::new(Session::default()),
+    })
+}
+
+/// Read the index file of the index `uuid`.
+///
+/// Returns the reader of the file and the protobuf metadata of the index.
+pub(crate) async fn read_index_file(
+    dataset: &Dataset,
+    uuid: &str,
+) -> Result<(Arc<dyn ObjectReader>, pb::Index)> {
+    let index_file = dataset.indices_dir().child(uuid).child(INDEX_FILE_NAME);
+
+    let object_store = dataset.object_store();
+    let reader: Arc<dyn ObjectReader> = object_store.open(&index_file).await?.into();
+
+    let file_size = reader.size().await?;
+    let block_size = object_store.block_size();
+    let begin = if file_size < block_size {
+        0
+    } else {
+        file_size - block_size
+    };
+    let tail_bytes = reader.get_range(begin..file_size).await?;
+    let metadata_pos = read_metadata_offset(&tail_bytes)?;
+    let proto: pb::Index = if metadata_pos < file_size - tail_bytes.len() {
+        // We have not read the metadata bytes yet.
+        read_message(reader.as_ref(), metadata_pos).await?
+    } else {
+        let offset = tail_bytes.len() - (file_size - metadata_pos);
+        read_message_from_buf(&tail_bytes.slice(offset..))?
+    };
+    Ok((reader, proto))
+}
+
+/// Write the index file of the index `uuid`, with its protobuf metadata.
+pub(crate) async fn write_index_file(
+    dataset: &Dataset,
+    column: &str,
+    name: &str,
+    uuid: &str,
+    index_type: pb::IndexType,
+    implementation: pb::index::Implementation,
+) -> Result<()> {
+    let path = dataset.indices_dir().child(uuid).child(INDEX_FILE_NAME);
+    let mut writer = dataset.object_store().create(&path).await?;
+
+    let metadata = pb::Index {
+        name: name.to_string(),
+        columns: vec![column.to_string()],
+        dataset_version: dataset.version().version,
+        index_type: index_type.into(),
+        implementation: Some(implementation),
+    };
+
+    let pos = writer.write_protobuf(&metadata).await?;
+    writer.write_magics(pos).await?;
+    writer.shutdown().await?;
+
+    Ok(())
 }
 
 #[cfg(test)]
@@ -16,6 +16,8 @@ use std::sync::{Arc, Mutex};
 
 use lru_time_cache::LruCache;
 
+use super::inverted::InvertedIndex;
+use super::scalar::ScalarIndex;
 use super::vector::VectorIndex;
 
 #[derive(Clone)]
@@ -24,6 +26,12 @@ pub struct IndexCache {
     capacity: usize,
 
     cache: Arc<Mutex<LruCache<String, Arc<dyn VectorIndex>>>>,
+
+    /// Opened scalar indices, by the UUID of the index.
+    scalar_cache: Arc<Mutex<LruCache<String, Arc<dyn ScalarIndex>>>>,
+
+    /// Opened inverted indices, by the UUID of the index.
+    inverted_cache: Arc<Mutex<LruCache<String, Arc<InvertedIndex>>>>,
 }
 
 impl IndexCache {
@@ -31,6 +39,8 @@ impl IndexCache {
         Self {
             capacity,
             cache: Arc::new(Mutex::new(LruCache::with_capacity(capacity))),
+            scalar_cache: Arc::new(Mutex::new(LruCache::with_capacity(capacity))),
+            inverted_cache: Arc::new(Mutex::new(LruCache::with_capacity(capacity))),
         }
     }
 
@@ -56,4 +66,34 @@ impl IndexCache {
         let mut cache = self.cache.lock().unwrap();
         cache.insert(key.to_string(), index);
     }
+
+    /// Get a scalar index if present. Otherwise returns [None].
+    pub(crate) fn get_scalar(&self, key: &str) -> Option<Arc<dyn ScalarIndex>> {
+        let mut cache = self.scalar_cache.lock().unwrap();
+        cache.get(key).cloned()
+    }
+
+    /// Insert a new scalar index into the cache.
+    pub(crate) fn insert_scalar(&self, key: &str, index: Arc<dyn ScalarIndex>) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut cache = self.scalar_cache.lock().unwrap();
+        cache.insert(key.to_string(), index);
+    }
+
+    /// Get an inverted index if present. Otherwise returns [None].
+    pub(crate) fn get_inverted(&self, key: &str) -> Option<Arc<InvertedIndex>> {
+        let mut cache = self.inverted_cache.lock().unwrap();
+        cache.get(key).cloned()
+    }
+
+    /// Insert a new inverted index into the cache.
+    pub(crate) fn insert_inverted(&self, key: &str, index: Arc<InvertedIndex>) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut cache = self.inverted_cache.lock().unwrap();
+        cache.insert(key.to_string(), index);
+    }
 }
@@ -0,0 +1,718 @@
+// Copyright 2023 Lance Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Full-text Inverted Index
+//!
+//! The text of a string column is split into tokens, and the index keeps the
+//! posting list of each token: the row ids of the rows containing the token, with
+//! the number of its occurrences. The rows matching a query are ranked by their
+//! [BM25](https://en.wikipedia.org/wiki/Okapi_BM25) score.
+//!
+//! The posting lists, sorted by token, are written as the pages of a Lance file,
+//! and the page statistics of the tokens are used to find the page of a token.
+//! The number of tokens of each row, used by BM25, is kept in the posting lists,
+//! so opening the index only reads its metadata.
+//!
+//! The posting lists of large columns are built in chunks, which are written as
+//! sorted runs and merged, so the posting lists of the whole column are never in
+//! memory.
+
+use std::cmp::Reverse;
+use std::collections::binary_heap::PeekMut;
+use std::collections::{BinaryHeap, HashMap};
+use std::sync::Arc;
+
+use arrow_array::{
+    cast::AsArray,
+    types::{UInt32Type, UInt64Type},
+    ListArray, RecordBatch, StringArray,
+};
+use arrow_cast::cast;
+use arrow_schema::{DataType, Field as ArrowField, Schema as ArrowSchema, SchemaRef};
+use datafusion::scalar::ScalarValue;
+use futures::TryStreamExt;
+use object_store::path::Path;
+
+use super::scalar::ScalarIndexParams;
+use super::{pb, read_index_file, write_index_file};
+use crate::dataset::{Dataset, ROW_ID};
+use crate::datatypes::Schema;
+use crate::io::{FileReader, FileWriter};
+use crate::{Error, Result};
+
+/// Name of the file of the posting lists, in the index directory.
+const TOKENS_FILE_NAME: &str = "tokens.lance";
+
+const TOKEN_COLUMN: &str = "token";
+const ROW_IDS_COLUMN: &str = "row_ids";
+const FREQUENCIES_COLUMN: &str = "frequencies";
+const NUM_TOKENS_COLUMN: &str = "num_tokens";
+
+/// Number of tokens in each page of the posting lists.
+const TOKENS_PAGE_SIZE: usize = 1024;
+
+/// BM25 parameter of the saturation of the term frequency.
+const K1: f32 = 1.2;
+
+/// BM25 parameter of the normalization by the length of the text.
+const B: f32 = 0.75;
+
+/// Full text search query on a string column.
+#[derive(Debug, Clone)]
+pub struct FullTextQuery {
+    /// The name of the indexed column.
+    pub column: String,
+
+    /// The text of the query.
+    pub query: String,
+}
+
+/// Split the text into lower case tokens, separated by the non-alphanumeric characters.
+pub fn tokenize(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(|token| token.to_lowercase())
+}
+
+/// The statistics of the indexed rows, to compute the BM25 scores.
+#[derive(Debug, Clone)]
+pub struct Bm25 {
+    /// Number of indexed rows.
+    num_docs: usize,
+
+    /// Average number of tokens of the indexed rows.
+    avg_num_tokens: f32,
+}
+
+impl Bm25 {
+    /// Inverse document frequency of a token contained in `doc_freq` rows.
+    pub fn idf(&self, doc_freq: usize) -> f32 {
+        let num_docs = self.num_docs.max(doc_freq) as f32;
+        let doc_freq = doc_freq as f32;
+        ((num_docs - doc_freq + 0.5) / (doc_freq + 0.5) + 1.0).ln()
+    }
+
+    /// Score of a token, found `freq` times in a text of `num_tokens` tokens.
+    pub fn score(&self, idf: f32, freq: u32, num_tokens: u32) -> f32 {
+        let freq = freq as f32;
+        let norm = 1.0 - B + B * num_tokens as f32 / self.avg_num_tokens.max(1.0);
+        idf * freq * (K1 + 1.0) / (freq + K1 * norm)
+    }
+
+    /// Score a text which is not indexed, with the `idfs` of the query `tokens`.
+    pub fn score_text(&self, tokens: &[String], idfs: &[f32], text: &str) -> f32 {
+        let mut num_tokens = 0;
+        let mut freqs = vec![0; tokens.len()];
+        for token in tokenize(text) {
+            num_tokens += 1;
+            if let Some(i) = tokens.iter().position(|t| t == &token) {
+                freqs[i] += 1;
+            }
+        }
+        freqs
+            .iter()
+            .zip(idfs)
+            .filter(|(freq, _)| **freq > 0)
+            .map(|(freq, idf)| self.score(*idf, *freq, num_tokens))
+            .sum()
+    }
+}
+
+/// The rows containing a token.
+#[derive(Debug, Default)]
+struct PostingList {
+    row_ids: Vec<u64>,
+
+    /// Number of occurrences of the token in each row.
+    frequencies: Vec<u32>,
+
+    /// Number of tokens of each row.
+    num_tokens: Vec<u32>,
+}
+
+impl PostingList {
+    /// Read the posting list of the `i`-th token of a page.
+    fn read(page: &RecordBatch, i: usize) -> Self {
+        let list = |name: &str| page.column_by_name(name).unwrap().as_list::<i32>().value(i);
+        Self {
+            row_ids: list(ROW_IDS_COLUMN)
+                .as_primitive::<UInt64Type>()
+                .values()
+                .to_vec(),
+            frequencies: list(FREQUENCIES_COLUMN)
+                .as_primitive::<UInt32Type>()
+                .values()
+                .to_vec(),
+            num_tokens: list(NUM_TOKENS_COLUMN)
+                .as_primitive::<UInt32Type>()
+                .values()
+                .to_vec(),
+        }
+    }
+
+    fn push(&mut self, row_id: u64, frequency: u32, num_tokens: u32) {
+        self.row_ids.push(row_id);
+        self.frequencies.push(frequency);
+        self.num_tokens.push(num_tokens);
+    }
+
+    fn extend(&mut self, other: Self) {
+        self.row_ids.extend(other.row_ids);
+        self.frequencies.extend(other.frequencies);
+        self.num_tokens.extend(other.num_tokens);
+    }
+}
+
+/// Full-text inverted index over a string column.
+#[derive(Debug)]
+pub struct InvertedIndex {
+    /// Reader of the posting lists.
+    reader: FileReader,
+
+    /// Bounds of the first and the last token of each page, from the page statistics.
+    ///
+    /// Long tokens are truncated in the statistics, and the upper bound is `None`
+    /// if it could not be rounded up.
+    pages: Vec<(String, Option<String>)>,
+
+    bm25: Bm25,
+}
+
+impl InvertedIndex {
+    /// Open the inverted index on dataset, specified by the `uuid`.
+    ///
+    /// Returns `None` if the index is not an inverted index. The opened indices are
+    /// cached in the session of the dataset.
+    pub(crate) async fn open(dataset: &Dataset, uuid: &str) -> Result<Option<Arc<Self>>> {
+        if let Some(index) = dataset.session.index_cache.get_inverted(uuid) {
+            return Ok(Some(index));
+        }
+
+        let (_, proto) = read_index_file(dataset, uuid).await?;
+        let Some(pb::index::Implementation::InvertedIndex(inverted)) = proto.implementation else {
+            return Ok(None);
+        };
+        let index_dir = dataset.indices_dir().child(uuid);
+        let bm25 = Bm25 {
+            num_docs: inverted.num_docs as usize,
+            avg_num_tokens: inverted.total_tokens as f32 / inverted.num_docs.max(1) as f32,
+        };
+
+        let path = index_dir.child(inverted.tokens_filename.as_str());
+        let reader = FileReader::try_new(dataset.object_store(), &path).await?;
+        let pages = if reader.num_batches() == 0 {
+            vec![]
+        } else {
+            let field = reader
+                .schema()
+                .field(TOKEN_COLUMN)
+                .ok_or_else(|| Error::Index {
+                    message: format!("Inverted index does not have a '{TOKEN_COLUMN}' column"),
+                })?;
+            let stats = reader
+                .page_statistics(field.id)
+                .ok_or_else(|| Error::Index {
+                    message: "Inverted index does not have page statistics".to_string(),
+                })?;
+            (0..stats.min_values.len())
+                .map(|i| {
+                    match (
+                        ScalarValue::try_from_array(&stats.min_values, i)?,
+                        ScalarValue::try_from_array(&stats.max_values, i)?,
+                    ) {
+                        (ScalarValue::Utf8(Some(min)), ScalarValue::Utf8(max)) => Ok((min, max)),
+                        _ => Err(Error::Index {
+                            message: format!("Inverted index: invalid statistics of page {i}"),
+                        }),
+                    }
+                })
+                .collect::<Result<Vec<_>>>()?
+        };
+
+        let index = Arc::new(Self {
+            reader,
+            pages,
+            bm25,
+        });
+        dataset
+            .session
+            .index_cache
+            .insert_inverted(uuid, index.clone());
+        Ok(Some(index))
+    }
+
+    /// The statistics of the indexed rows.
+    pub fn bm25(&self) -> &Bm25 {
+        &self.bm25
+    }
+
+    /// Read the posting list of the token.
+    async fn posting_list(&self, token: &str) -> Result<Option<PostingList>> {
+        let start = self
+            .pages
+            .partition_point(|(_, last)| last.as_deref().map_or(false, |last| last < token));
+        // The bounds of the pages may overlap once truncated, so the token may be in any
+        // of the pages whose bounds contain it.
+        for page in start..self.pages.len() {
+            if self.pages[page].0.as_str() > token {
+                break;
+            }
+            if let Some(posting_list) = self.search_page(page, token).await? {
+                return Ok(Some(posting_list));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Read the posting list of the token in the page, if it is there.
+    async fn search_page(&self, page: usize, token: &str) -> Result<Option<PostingList>> {
+        let batch = self
+            .reader
+            .read_batch(page as i32, .., self.reader.schema())
+            .await?;
+        let tokens = batch
+            .column_by_name(TOKEN_COLUMN)
+            .unwrap()
+            .as_string::<i32>();
+        let (mut low, mut high) = (0, tokens.len());
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if tokens.value(mid) < token {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        if low == tokens.len() || tokens.value(low) != token {
+            return Ok(None);
+        }
+
+        Ok(Some(PostingList::read(&batch, low)))
+    }
+
+    /// Search the rows containing any of the `tokens`.
+    ///
+    /// Returns the BM25 scores of the rows, which may include deleted rows, and the
+    /// inverse document frequency of each token.
+    pub async fn search(&self, tokens: &[String]) -> Result<(HashMap<u64, f32>, Vec<f32>)> {
+        let mut scores = HashMap::new();
+        let mut idfs = Vec::with_capacity(tokens.len());
+        for token in tokens {
+            let Some(posting_list) = self.posting_list(token).await? else {
+                idfs.push(self.bm25.idf(0));
+                continue;
+            };
+            let idf = self.bm25.idf(posting_list.row_ids.len());
+            for ((row_id, freq), num_tokens) in posting_list
+                .row_ids
+                .iter()
+                .zip(posting_list.frequencies)
+                .zip(posting_list.num_tokens)
+            {
+                *scores.entry(*row_id).or_default() += self.bm25.score(idf, freq, num_tokens);
+            }
+            idfs.push(idf);
+        }
+        Ok((scores, idfs))
+    }
+}
+
+/// Build an inverted index on the string `column` of the dataset.
+///
+/// At most `sort_chunk_size` postings, i.e., pairs of a token and a row, are kept in
+/// memory. Larger columns are written as sorted runs to the index directory, and merged.
+pub(crate) async fn build_inverted_index(
+    dataset: &Dataset,
+    column: &str,
+    name: &str,
+    uuid: &str,
+    params: &ScalarIndexParams,
+) -> Result<()> {
+    let field = dataset.schema().field(column).ok_or_else(|| Error::Index {
+        message: format!("Inverted index: column '{column}' does not exist"),
+    })?;
+    let data_type = field.data_type();
+    if !matches!(data_type, DataType::Utf8 | DataType::LargeUtf8) {
+        return Err(Error::Index {
+            message: format!(
+                "Inverted index does not support column '{column}' of type {data_type}"
+            ),
+        });
+    }
+    if params.sort_chunk_size == 0 {
+        return Err(Error::Index {
+            message: "Inverted index: sort chunk size must be greater than 0".to_string(),
+        });
+    }
+
+    let mut scanner = dataset.scan();
+    scanner.project(&[column])?;
+    scanner.with_row_id();
+    let mut stream = scanner.try_into_stream().await?;
+
+    let index_dir = dataset.indices_dir().child(uuid);
+    let schema = postings_schema();
+
+    // Nulls are not indexed.
+    let mut postings: HashMap<String, PostingList> = HashMap::new();
+    let mut num_postings = 0;
+    let mut runs = vec![];
+    let mut num_docs = 0_u64;
+    let mut total_tokens = 0_u64;
+    while let Some(batch) = stream.try_next().await? {
+        let texts = cast(batch.column_by_name(column).unwrap(), &DataType::Utf8)?;
+        let row_ids = batch
+            .column_by_name(ROW_ID)
+            .unwrap()
+            .as_primitive::<UInt64Type>();
+        for (text, row_id) in texts.as_string::<i32>().iter().zip(row_ids.values()) {
+            let Some(text) = text else {
+                continue;
+            };
+            let mut freqs: HashMap<String, u32> = HashMap::new();
+            let mut num_tokens = 0;
+            for token in tokenize(text) {
+                *freqs.entry(token).or_default() += 1;
+                num_tokens += 1;
+            }
+            num_docs += 1;
+            total_tokens += num_tokens as u64;
+            num_postings += freqs.len();
+            for (token, freq) in freqs {
+                postings
+                    .entry(token)
+                    .or_default()
+                    .push(*row_id, freq, num_tokens);
+            }
+        }
+
+        if num_postings >= params.sort_chunk_size {
+            let run = index_dir.child(format!("postings_run_{}.lance", runs.len()));
+            write_postings(dataset, &run, &schema, std::mem::take(&mut postings)).await?;
+            runs.push(run);
+            num_postings = 0;
+        }
+    }
+
+    let path = index_dir.child(TOKENS_FILE_NAME);
+    if runs.is_empty() {
+        write_postings(dataset, &path, &schema, postings).await?;
+    } else {
+        if !postings.is_empty() {
+            let run = index_dir.child(format!("postings_run_{}.lance", runs.len()));
+            write_postings(dataset, &run, &schema, postings).await?;
+            runs.push(run);
+        }
+        merge_postings(dataset, &runs, &path, &schema).await?;
+        for run in runs.iter() {
+            dataset.object_store().inner.delete(run).await?;
+        }
+    }
+
+    write_index_file(
+        dataset,
+        column,
+        name,
+        uuid,
+        pb::IndexType::Inverted,
+        pb::index::Implementation::InvertedIndex(pb::InvertedIndex {
+            tokens_filename: TOKENS_FILE_NAME.to_string(),
+            num_docs,
+            total_tokens,
+        }),
+    )
+    .await
+}
+
+fn postings_schema() -> SchemaRef {
+    let list = |data_type| DataType::List(Arc::new(ArrowField::new("item", data_type, true)));
+    Arc::new(ArrowSchema::new(vec![
+        ArrowField::new(TOKEN_COLUMN, DataType::Utf8, false),
+        ArrowField::new(ROW_IDS_COLUMN, list(DataType::UInt64), false),
+        ArrowField::new(FREQUENCIES_COLUMN, list(DataType::UInt32), false),
+        ArrowField::new(NUM_TOKENS_COLUMN, list(DataType::UInt32), false),
+    ]))
+}
+
+/// One page of the posting lists of the sorted tokens.
+fn postings_page(schema: &SchemaRef, page: &[(String, PostingList)]) -> Result<RecordBatch> {
+    let tokens = StringArray::from_iter_values(page.iter().map(|(token, _)| token));
+    let row_ids = ListArray::from_iter_primitive::<UInt64Type, _, _>(
+        page.iter()
+            .map(|(_, list)| Some(list.row_ids.iter().map(|r| Some(*r)))),
+    );
+    let frequencies = ListArray::from_iter_primitive::<UInt32Type, _, _>(
+        page.iter()
+            .map(|(_, list)| Some(list.frequencies.iter().map(|f| Some(*f)))),
+    );
+    let num_tokens = ListArray::from_iter_primitive::<UInt32Type, _, _>(
+        page.iter()
+            .map(|(_, list)| Some(list.num_tokens.iter().map(|n| Some(*n)))),
+    );
+    Ok(RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            Arc::new(tokens),
+            Arc::new(row_ids),
+            Arc::new(frequencies),
+            Arc::new(num_tokens),
+        ],
+    )?)
+}
+
+/// Write the posting lists, sorted by token.
+async fn write_postings(
+    dataset: &Dataset,
+    path: &Path,
+    schema: &SchemaRef,
+    postings: HashMap<String, PostingList>,
+) -> Result<()> {
+    let mut postings = postings.into_iter().collect::<Vec<_>>();
+    postings.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let mut writer = FileWriter::try_new(
+        dataset.object_store(),
+        path,
+        Schema::try_from(schema.as_ref())?,
+    )
+    .await?;
+    for page in postings.chunks(TOKENS_PAGE_SIZE) {
+        writer.write(&[postings_page(schema, page)?]).await?;
+    }
+    writer.finish().await?;
+    Ok(())
+}
+
+/// A sorted run of posting lists, read page by page while merging.
+struct PostingsRun {
+    reader: FileReader,
+
+    /// The next page to read.
+    next_page: usize,
+
+    page: RecordBatch,
+
+    /// Position of the current token in the page.
+    position: usize,
+}
+
+impl PostingsRun {
+    /// Move to the next token, reading the next page if needed.
+    ///
+    /// Returns the token, or `None` at the end of the run.
+    async fn advance(&mut self) -> Result<Option<String>> {
+        self.position += 1;
+        while self.position >= self.page.num_rows() {
+            if self.next_page >= self.reader.num_batches() {
+                return Ok(None);
+            }
+            self.page = self
+                .reader
+                .read_batch(self.next_page as i32, .., self.reader.schema())
+                .await?;
+            self.next_page += 1;
+            self.position = 0;
+        }
+        let tokens = self
+            .page
+            .column_by_name(TOKEN_COLUMN)
+            .unwrap()
+            .as_string::<i32>();
+        Ok(Some(tokens.value(self.position).to_string()))
+    }
+}
+
+/// Merge the sorted runs into the posting lists file at `path`.
+///
+/// The posting lists of a token found in several runs are concatenated, in the order
+/// of the runs. Only the current page of each run is kept in memory.
+async fn merge_postings(
+    dataset: &Dataset,
+    runs: &[Path],
+    path: &Path,
+    schema: &SchemaRef,
+) -> Result<()> {
+    let mut writer = FileWriter::try_new(
+        dataset.object_store(),
+        path,
+        Schema::try_from(schema.as_ref())?,
+    )
+    .await?;
+
+    let mut postings_runs = vec![];
+    // The smallest current token of the runs is on the top.
+    let mut heap = BinaryHeap::new();
+    for run in runs {
+        let mut postings_run = PostingsRun {
+            reader: FileReader::try_new(dataset.object_store(), run).await?,
+            next_page: 0,
+            page: RecordBatch::new_empty(schema.clone()),
+            position: 0,
+        };
+        if let Some(token) = postings_run.advance().await? {
+            heap.push(Reverse((token, postings_runs.len())));
+        }
+        postings_runs.push(postings_run);
+    }
+
+    let mut page = Vec::with_capacity(TOKENS_PAGE_SIZE);
+    while let Some(Reverse((token, run_id))) = heap.pop() {
+        let mut run_ids = vec![run_id];
+        while let Some(top) = heap.peek_mut() {
+            if top.0 .0 != token {
+                break;
+            }
+            let Reverse((_, run_id)) = PeekMut::pop(top);
+            run_ids.push(run_id);
+        }
+        run_ids.sort();
+
+        let mut posting_list = PostingList::default();
+        for run_id in run_ids {
+            let postings_run = &mut postings_runs[run_id];
+            posting_list.extend(PostingList::read(&postings_run.page, postings_run.position));
+            if let Some(next) = postings_run.advance().await? {
+                heap.push(Reverse((next, run_id)));
+            }
+        }
+        page.push((token, posting_list));
+
+        if page.len() == TOKENS_PAGE_SIZE || heap.is_empty() {
+            writer.write(&[postings_page(schema, &page)?]).await?;
+            page.clear();
+        }
+    }
+    writer.finish().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::RecordBatchIterator;
+    use tempfile::tempdir;
+
+    use crate::dataset::WriteParams;
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokenize("A red car, parked on the road-side (2023).").collect::<Vec<_>>(),
+            ["a", "red", "car", "parked", "on", "the", "road", "side", "2023"]
+        );
+        assert_eq!(tokenize(" ... ").count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_inverted_search() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        // Each row has a unique token, so the posting lists span many pages.
+        let texts = (0..3000)
+            .map(|i| match i % 3 {
+                0 => format!("red car w{i} {}{i}", "a".repeat(100)),
+                1 => format!("blue car on a long and winding road w{i}"),
+                _ => format!("red red boat w{i}"),
+            })
+            .collect::<Vec<_>>();
+        let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "text",
+            DataType::Utf8,
+            true,
+        )]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(StringArray::from_iter_values(texts))],
+        )
+        .unwrap();
+        let write_params = WriteParams {
+            max_rows_per_group: 500,
+            ..Default::default()
+        };
+        let batches = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+        let dataset = Dataset::write(batches, test_uri, Some(write_params))
+            .await
+            .unwrap();
+
+        build_inverted_index(
+            &dataset,
+            "text",
+            "text_idx",
+            "inverted_text",
+            &ScalarIndexParams::default(),
+        )
+        .await
+        .unwrap();
+        let index = InvertedIndex::open(&dataset, "inverted_text")
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(index.pages.len() > 1);
+        assert_eq!(index.bm25().num_docs, 3000);
+        assert!(dataset
+            .session
+            .index_cache
+            .get_inverted("inverted_text")
+            .is_some());
+
+        let tokens = |query: &str| tokenize(query).collect::<Vec<_>>();
+        let (scores, idfs) = index.search(&tokens("w1234 w42 fish")).await.unwrap();
+        let mut row_ids = scores.keys().copied().collect::<Vec<_>>();
+        row_ids.sort();
+        assert_eq!(row_ids, [42, 1234]);
+        assert!(idfs[0] > 0.0 && idfs[2] > idfs[0]);
+
+        // Long tokens are truncated in the page statistics.
+        let long_token = format!("{}42", "a".repeat(100));
+        let (scores, _) = index.search(&[long_token]).await.unwrap();
+        assert_eq!(scores.keys().copied().collect::<Vec<_>>(), [42]);
+
+        let (scores, _) = index.search(&tokens("red")).await.unwrap();
+        assert_eq!(scores.len(), 2000);
+        // More occurrences of the token in a text of similar length score higher.
+        assert!(scores[&2] > scores[&0]);
+
+        let (scores, _) = index.search(&tokens("car")).await.unwrap();
+        assert_eq!(scores.len(), 2000);
+        // The same occurrences in a shorter text score higher.
+        assert!(scores[&0] > scores[&1]);
+
+        // Spill the postings to sorted runs, and merge them.
+        let params = ScalarIndexParams {
+            sort_chunk_size: 1000,
+            ..Default::default()
+        };
+        build_inverted_index(&dataset, "text", "text_idx", "inverted_spill", &params)
+            .await
+            .unwrap();
+        let spilled = InvertedIndex::open(&dataset, "inverted_spill")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(spilled.bm25().num_docs, 3000);
+        assert_eq!(spilled.bm25().avg_num_tokens, index.bm25().avg_num_tokens);
+        for query in ["red", "car", "w1234 w42 fish", "road boat"] {
+            let (expected, expected_idfs) = index.search(&tokens(query)).await.unwrap();
+            let (scores, idfs) = spilled.search(&tokens(query)).await.unwrap();
+            assert_eq!(scores, expected);
+            assert_eq!(idfs, expected_idfs);
+        }
+
+        let index_dir = dataset.indices_dir().child("inverted_spill");
+        let files = dataset.object_store().read_dir(index_dir).await.unwrap();
+        assert!(files.iter().all(|f| !f.contains("postings_run")));
+    }
+}
@@ -0,0 +1,335 @@
+// Copyright 2023 Lance Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Scalar Index
+//!
+//! Indices over the values of a numeric, boolean or string column, which find the rows
+//! matching a filter without scanning the column.
+
+use std::any::Any;
+use std::ops::Bound;
+use std::sync::Arc;
+
+use arrow_schema::DataType;
+use async_trait::async_trait;
+use datafusion::logical_expr::expr::InList;
+use datafusion::logical_expr::{Between, BinaryExpr, Cast, Expr, Operator};
+use datafusion::scalar::ScalarValue;
+use roaring::RoaringTreemap;
+
+pub mod bitmap;
+pub mod btree;
+
+use super::{pb, read_index_file, write_index_file, IndexParams};
+use crate::dataset::Dataset;
+use crate::datatypes::Schema;
+use crate::io::FileReader;
+use crate::{Error, Result};
+
+use self::bitmap::BitmapIndex;
+use self::btree::BTreeIndex;
+
+/// Column of the indexed values.
+const VALUES_COLUMN: &str = "values";
+
+/// Column of the row ids of the values.
+const ROW_IDS_COLUMN: &str = "row_ids";
+
+/// Default number of values in each page of a scalar index.
+const DEFAULT_PAGE_SIZE: usize = 4096;
+
+/// Default number of values sorted in memory at once while building a scalar index.
+const DEFAULT_SORT_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// A query on the values of the indexed column.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScalarQuery {
+    /// `column = value`
+    Equals(ScalarValue),
+
+    /// `column IN (values)`
+    IsIn(Vec<ScalarValue>),
+
+    /// `column >= / > lower AND column <= / < upper`, i.e., `BETWEEN`.
+    Range(Bound<ScalarValue>, Bound<ScalarValue>),
+}
+
+impl ScalarQuery {
+    /// Build a query from a filter on one column, i.e., `id = 42`.
+    ///
+    /// Returns the column name and the query, or `None` if the filter can not
+    /// be answered by a scalar index. The values are cast to the type of the column.
+    pub(crate) fn from_expr(expr: &Expr, schema: &Schema) -> Option<(String, Self)> {
+        match expr {
+            Expr::BinaryExpr(BinaryExpr { left, op, right }) => {
+                let (column, value, op) = match (left.as_ref(), right.as_ref()) {
+                    (Expr::Column(column), value) => (column, value, *op),
+                    (value, Expr::Column(column)) => (column, value, op.swap()?),
+                    _ => return None,
+                };
+                let column = column.flat_name();
+                let value = literal_value(value, &column_type(&column, schema)?)?;
+                let query = match op {
+                    Operator::Eq => Self::Equals(value),
+                    Operator::Lt => Self::Range(Bound::Unbounded, Bound::Excluded(value)),
+                    Operator::LtEq => Self::Range(Bound::Unbounded, Bound::Included(value)),
+                    Operator::Gt => Self::Range(Bound::Excluded(value), Bound::Unbounded),
+                    Operator::GtEq => Self::Range(Bound::Included(value), Bound::Unbounded),
+                    _ => return None,
+                };
+                Some((column, query))
+            }
+            Expr::InList(InList {
+                expr,
+                list,
+                negated: false,
+            }) => {
+                let Expr::Column(column) = expr.as_ref() else {
+                    return None;
+                };
+                let column = column.flat_name();
+                let data_type = column_type(&column, schema)?;
+                let values = list
+                    .iter()
+                    .map(|value| literal_value(value, &data_type))
+                    .collect::<Option<Vec<_>>>()?;
+                Some((column, Self::IsIn(values)))
+            }
+            Expr::Between(Between {
+                expr,
+                negated: false,
+                low,
+                high,
+            }) => {
+                let Expr::Column(column) = expr.as_ref() else {
+                    return None;
+                };
+                let column = column.flat_name();
+                let data_type = column_type(&column, schema)?;
+                let low = literal_value(low, &data_type)?;
+                let high = literal_value(high, &data_type)?;
+                Some((
+                    column,
+                    Self::Range(Bound::Included(low), Bound::Included(high)),
+                ))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Returns true if the value is below the lower bound.
+fn below(value: &ScalarValue, lower: &Bound<ScalarValue>) -> bool {
+    match lower {
+        Bound::Included(l) => value < l,
+        Bound::Excluded(l) => value <= l,
+        Bound::Unbounded => false,
+    }
+}
+
+/// Returns true if the value is above the upper bound.
+fn above(value: &ScalarValue, upper: &Bound<ScalarValue>) -> bool {
+    match upper {
+        Bound::Included(u) => value > u,
+        Bound::Excluded(u) => value >= u,
+        Bound::Unbounded => false,
+    }
+}
+
+fn column_type(column: &str, schema: &Schema) -> Option<DataType> {
+    schema.field(column).map(|f| f.data_type())
+}
+
+/// The non-null value of a literal, or of a cast literal, as `data_type`.
+fn literal_value(expr: &Expr, data_type: &DataType) -> Option<ScalarValue> {
+    let value = match expr {
+        Expr::Literal(value) => value.clone(),
+        Expr::Cast(Cast {
+            expr,
+            data_type: cast_type,
+        }) => match expr.as_ref() {
+            Expr::Literal(value) => value.cast_to(cast_type).ok()?,
+            _ => return None,
+        },
+        _ => return None,
+    };
+    let value = value.cast_to(data_type).ok()?;
+    (!value.is_null()).then_some(value)
+}
+
+/// Scalar index.
+#[async_trait]
+pub trait ScalarIndex: Send + Sync + std::fmt::Debug {
+    /// Search the index for the row ids matching the query.
+    ///
+    /// The row ids of deleted rows may be returned.
+    async fn search(&self, query: &ScalarQuery) -> Result<RoaringTreemap>;
+}
+
+/// The parameters to build a scalar index.
+#[derive(Debug, Clone)]
+pub struct ScalarIndexParams {
+    /// Number of values in each page of the index.
+    pub page_size: usize,
+
+    /// Number of values sorted in memory at once while building a B-tree index,
+    /// or number of postings kept in memory while building an inverted index.
+    ///
+    /// Larger columns are sorted in chunks, which are written to the index directory
+    /// and merged.
+    pub sort_chunk_size: usize,
+}
+
+impl Default for ScalarIndexParams {
+    fn default() -> Self {
+        Self {
+            page_size: DEFAULT_PAGE_SIZE,
+            sort_chunk_size: DEFAULT_SORT_CHUNK_SIZE,
+        }
+    }
+}
+
+impl IndexParams for ScalarIndexParams {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Write the index file of a scalar index, with its protobuf metadata.
+async fn write_scalar_index_file(
+    dataset: &Dataset,
+    column: &str,
+    name: &str,
+    uuid: &str,
+    index: pb::scalar_index::Index,
+) -> Result<()> {
+    write_index_file(
+        dataset,
+        column,
+        name,
+        uuid,
+        pb::IndexType::Scalar,
+        pb::index::Implementation::ScalarIndex(pb::ScalarIndex { index: Some(index) }),
+    )
+    .await
+}
+
+/// Open the scalar index on dataset, specified by the `uuid`.
+///
+/// Returns `None` if the index is not a scalar index. The opened indices are cached
+/// in the session of the dataset.
+pub(crate) async fn open_scalar_index(
+    dataset: &Dataset,
+    uuid: &str,
+) -> Result<Option<Arc<dyn ScalarIndex>>> {
+    if let Some(index) = dataset.session.index_cache.get_scalar(uuid) {
+        return Ok(Some(index));
+    }
+
+    let (_, proto) = read_index_file(dataset, uuid).await?;
+    let Some(pb::index::Implementation::ScalarIndex(scalar_index)) = proto.implementation else {
+        return Ok(None);
+    };
+
+    let index_dir = dataset.indices_dir().child(uuid);
+    let index: Arc<dyn ScalarIndex> = match scalar_index.index {
+        Some(pb::scalar_index::Index::Btree(btree)) => {
+            let path = index_dir.child(btree.filename.as_str());
+            let reader = FileReader::try_new(dataset.object_store(), &path).await?;
+            Arc::new(BTreeIndex::try_new(reader)?)
+        }
+        Some(pb::scalar_index::Index::Bitmap(bitmap)) => {
+            let path = index_dir.child(bitmap.filename.as_str());
+            let reader = FileReader::try_new(dataset.object_store(), &path).await?;
+            Arc::new(BitmapIndex::load(&reader).await?)
+        }
+        None => {
+            return Err(Error::Index {
+                message: format!(
+                    "Invalid protobuf for ScalarIndex metadata: {:?}",
+                    scalar_index
+                ),
+            })
+        }
+    };
+    dataset
+        .session
+        .index_cache
+        .insert_scalar(uuid, index.clone());
+    Ok(Some(index))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_schema::{Field as ArrowField, Schema as ArrowSchema};
+
+    use crate::io::exec::Planner;
+
+    #[test]
+    fn test_query_from_expr() {
+        let arrow_schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("i", DataType::Int32, false),
+            ArrowField::new("s", DataType::Utf8, true),
+            ArrowField::new("d", DataType::Date32, true),
+        ]));
+        let schema = Schema::try_from(arrow_schema.as_ref()).unwrap();
+        let planner = Planner::new(arrow_schema);
+        let query =
+            |filter: &str| ScalarQuery::from_expr(&planner.parse_filter(filter).unwrap(), &schema);
+
+        assert_eq!(
+            query("i = 42"),
+            Some((
+                "i".to_string(),
+                ScalarQuery::Equals(ScalarValue::Int32(Some(42)))
+            ))
+        );
+        assert_eq!(
+            query("i > 10"),
+            Some((
+                "i".to_string(),
+                ScalarQuery::Range(
+                    Bound::Excluded(ScalarValue::Int32(Some(10))),
+                    Bound::Unbounded
+                )
+            ))
+        );
+        assert_eq!(
+            query("s IN ('a', 'b')"),
+            Some((
+                "s".to_string(),
+                ScalarQuery::IsIn(vec![
+                    ScalarValue::Utf8(Some("a".to_string())),
+                    ScalarValue::Utf8(Some("b".to_string()))
+                ])
+            ))
+        );
+        assert_eq!(
+            query("d BETWEEN DATE '2020-01-01' AND DATE '2020-01-31'"),
+            Some((
+                "d".to_string(),
+                ScalarQuery::Range(
+                    Bound::Included(ScalarValue::Date32(Some(18262))),
+                    Bound::Included(ScalarValue::Date32(Some(18292)))
+                )
+            ))
+        );
+        assert_eq!(query("i != 42"), None);
+        assert_eq!(query("s NOT IN ('a')"), None);
+        assert_eq!(query("i % 7 = 0"), None);
+        assert_eq!(query("i = 1 OR i = 2"), None);
+    }
+}
@@ -0,0 +1,469 @@
+// Copyright 2023 Lance Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Bitmap index.
+//!
+//! For the columns with few distinct values, i.e., labels or splits, the index
+//! keeps one bitmap of the row ids of each distinct value. The sorted distinct
+//! values and their serialized bitmaps are written as a Lance file, which is
+//! loaded in memory when the index is opened. Opened indices are cached in the
+//! session, so the file is read once.
+//!
+//! Floats are ordered by their total order, where NaN is greater than all the
+//! other values. `-0.0` is indexed as `0.0`, and all NaNs as one canonical NaN,
+//! so the values equal in SQL share one bitmap.
+
+use std::collections::HashMap;
+use std::ops::Bound;
+use std::sync::Arc;
+
+use arrow_array::{
+    cast::{as_primitive_array, AsArray},
+    Array, BinaryArray, RecordBatch, UInt64Array,
+};
+use arrow_cast::cast;
+use arrow_ord::sort::sort_to_indices;
+use arrow_schema::{DataType, Field as ArrowField, Schema as ArrowSchema};
+use arrow_select::take::take;
+use async_trait::async_trait;
+use datafusion::scalar::ScalarValue;
+use futures::TryStreamExt;
+use roaring::RoaringTreemap;
+
+use super::{above, below, write_scalar_index_file, ScalarIndex, ScalarQuery, VALUES_COLUMN};
+use crate::dataset::{Dataset, ROW_ID};
+use crate::datatypes::Schema;
+use crate::index::pb;
+use crate::io::{supports_statistics, FileReader, FileWriter};
+use crate::{Error, Result};
+
+/// Name of the file of the distinct values and their bitmaps, in the index directory.
+const BITMAP_FILE_NAME: &str = "bitmap.lance";
+
+/// Column of the serialized bitmaps of the values.
+const BITMAPS_COLUMN: &str = "bitmaps";
+
+/// Bitmap index over a column.
+#[derive(Debug)]
+pub struct BitmapIndex {
+    /// Type of the indexed values.
+    data_type: DataType,
+
+    /// The sorted distinct values.
+    values: Vec<ScalarValue>,
+
+    /// The row ids of each value.
+    bitmaps: Vec<RoaringTreemap>,
+}
+
+impl BitmapIndex {
+    /// Load the values and the bitmaps of the index file.
+    pub(super) async fn load(reader: &FileReader) -> Result<Self> {
+        let field = reader
+            .schema()
+            .field(VALUES_COLUMN)
+            .ok_or_else(|| Error::Index {
+                message: format!("Bitmap index does not have a '{VALUES_COLUMN}' column"),
+            })?;
+        let data_type = field.data_type();
+
+        let mut values = vec![];
+        let mut bitmaps = vec![];
+        for batch_id in 0..reader.num_batches() {
+            let batch = reader
+                .read_batch(batch_id as i32, .., reader.schema())
+                .await?;
+            let batch_values = batch.column_by_name(VALUES_COLUMN).unwrap();
+            let batch_bitmaps = batch
+                .column_by_name(BITMAPS_COLUMN)
+                .ok_or_else(|| Error::Index {
+                    message: format!("Bitmap index does not have a '{BITMAPS_COLUMN}' column"),
+                })?
+                .as_binary::<i32>();
+            for i in 0..batch.num_rows() {
+                values.push(ScalarValue::try_from_array(batch_values, i)?);
+                bitmaps.push(RoaringTreemap::deserialize_from(batch_bitmaps.value(i))?);
+            }
+        }
+        Ok(Self {
+            data_type,
+            values,
+            bitmaps,
+        })
+    }
+
+    /// Union the bitmaps of the values within the range.
+    fn search_range(
+        &self,
+        lower: &Bound<ScalarValue>,
+        upper: &Bound<ScalarValue>,
+        row_ids: &mut RoaringTreemap,
+    ) {
+        let start = self.values.partition_point(|v| below(v, lower));
+        let end = self.values.partition_point(|v| !above(v, upper));
+        for bitmap in &self.bitmaps[start..end.max(start)] {
+            *row_ids |= bitmap;
+        }
+    }
+
+    fn cast(&self, value: &ScalarValue) -> Result<ScalarValue> {
+        Ok(normalize(value.cast_to(&self.data_type)?))
+    }
+
+    fn cast_bound(&self, bound: &Bound<ScalarValue>) -> Result<Bound<ScalarValue>> {
+        Ok(match bound {
+            Bound::Included(v) => Bound::Included(self.cast(v)?),
+            Bound::Excluded(v) => Bound::Excluded(self.cast(v)?),
+            Bound::Unbounded => Bound::Unbounded,
+        })
+    }
+}
+
+#[async_trait]
+impl ScalarIndex for BitmapIndex {
+    async fn search(&self, query: &ScalarQuery) -> Result<RoaringTreemap> {
+        let mut row_ids = RoaringTreemap::new();
+        match query {
+            ScalarQuery::Equals(value) => {
+                let value = Bound::Included(self.cast(value)?);
+                self.search_range(&value, &value, &mut row_ids);
+            }
+            ScalarQuery::IsIn(values) => {
+                for value in values {
+                    let value = Bound::Included(self.cast(value)?);
+                    self.search_range(&value, &value, &mut row_ids);
+                }
+            }
+            ScalarQuery::Range(lower, upper) => {
+                let lower = self.cast_bound(lower)?;
+                let upper = self.cast_bound(upper)?;
+                self.search_range(&lower, &upper, &mut row_ids);
+            }
+        }
+        Ok(row_ids)
+    }
+}
+
+/// Index `-0.0` as `0.0`, and all NaNs as one canonical NaN.
+fn normalize(value: ScalarValue) -> ScalarValue {
+    match value {
+        ScalarValue::Float32(Some(v)) if v.is_nan() => ScalarValue::Float32(Some(f32::NAN)),
+        ScalarValue::Float32(Some(v)) if v == 0.0 => ScalarValue::Float32(Some(0.0)),
+        ScalarValue::Float64(Some(v)) if v.is_nan() => ScalarValue::Float64(Some(f64::NAN)),
+        ScalarValue::Float64(Some(v)) if v == 0.0 => ScalarValue::Float64(Some(0.0)),
+        value => value,
+    }
+}
+
+/// Build a bitmap index on the `column` of the dataset.
+///
+/// The values of a dictionary column are indexed as the values of the dictionary.
+pub(crate) async fn build_bitmap_index(
+    dataset: &Dataset,
+    column: &str,
+    name: &str,
+    uuid: &str,
+) -> Result<()> {
+    let field = dataset.schema().field(column).ok_or_else(|| Error::Index {
+        message: format!("Bitmap index: column '{column}' does not exist"),
+    })?;
+    let data_type = match field.data_type() {
+        DataType::Dictionary(_, value_type) => value_type.as_ref().clone(),
+        data_type => data_type,
+    };
+    if !(data_type == DataType::Boolean || supports_statistics(&data_type)) {
+        return Err(Error::Index {
+            message: format!("Bitmap index does not support column '{column}' of type {data_type}"),
+        });
+    }
+
+    let mut scanner = dataset.scan();
+    scanner.project(&[column])?;
+    scanner.with_row_id();
+    let mut stream = scanner.try_into_stream().await?;
+
+    // Nulls are not indexed.
+    let mut bitmaps: HashMap<ScalarValue, RoaringTreemap> = HashMap::new();
+    while let Some(batch) = stream.try_next().await? {
+        let values = cast(batch.column_by_name(column).unwrap(), &data_type)?;
+        let row_ids: &UInt64Array = as_primitive_array(batch.column_by_name(ROW_ID).unwrap());
+        for (i, row_id) in row_ids.values().iter().enumerate() {
+            if values.is_null(i) {
+                continue;
+            }
+            let value = normalize(ScalarValue::try_from_array(&values, i)?);
+            bitmaps.entry(value).or_default().insert(*row_id);
+        }
+    }
+
+    let schema = Arc::new(ArrowSchema::new(vec![
+        ArrowField::new(VALUES_COLUMN, data_type.clone(), false),
+        ArrowField::new(BITMAPS_COLUMN, DataType::Binary, false),
+    ]));
+    let path = dataset.indices_dir().child(uuid).child(BITMAP_FILE_NAME);
+    let mut writer = FileWriter::try_new(
+        dataset.object_store(),
+        &path,
+        Schema::try_from(schema.as_ref())?,
+    )
+    .await?;
+    if !bitmaps.is_empty() {
+        let (values, bitmaps): (Vec<_>, Vec<_>) = bitmaps.into_iter().unzip();
+        // Sorted by Arrow, which orders floats by their total order, as `ScalarValue` does.
+        let values = ScalarValue::iter_to_array(values)?;
+        let indices = sort_to_indices(&values, None, None)?;
+        let values = take(&values, &indices, None)?;
+        let serialized = indices
+            .values()
+            .iter()
+            .map(|i| {
+                let bitmap = &bitmaps[*i as usize];
+                let mut buf = Vec::with_capacity(bitmap.serialized_size());
+                bitmap.serialize_into(&mut buf)?;
+                Ok(buf)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let serialized = BinaryArray::from_iter_values(serialized);
+        let batch = RecordBatch::try_new(schema.clone(), vec![values, Arc::new(serialized)])?;
+        writer.write(&[batch]).await?;
+    }
+    writer.finish().await?;
+
+    write_scalar_index_file(
+        dataset,
+        column,
+        name,
+        uuid,
+        pb::scalar_index::Index::Bitmap(pb::Bitmap {
+            filename: BITMAP_FILE_NAME.to_string(),
+        }),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::types::Int32Type;
+    use arrow_array::{
+        BooleanArray, DictionaryArray, Float64Array, Int32Array, RecordBatchIterator,
+    };
+    use tempfile::tempdir;
+
+    use crate::dataset::WriteParams;
+    use crate::index::scalar::open_scalar_index;
+
+    #[tokio::test]
+    async fn test_bitmap_search() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let labels = ["cat", "dog", "bird"];
+        let label_values = (0..100)
+            .map(|i| (i % 7 != 6).then_some(labels[i % 3]))
+            .collect::<Vec<_>>();
+        let label_array: DictionaryArray<Int32Type> = label_values.iter().copied().collect();
+        let flag_array = BooleanArray::from_iter((0..100).map(|i| Some(i % 4 == 0)));
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("label", label_array.data_type().clone(), true),
+            ArrowField::new("flag", DataType::Boolean, true),
+            ArrowField::new("i", DataType::Int32, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(label_array),
+                Arc::new(flag_array),
+                Arc::new(Int32Array::from_iter_values(0..100)),
+            ],
+        )
+        .unwrap();
+        let write_params = WriteParams {
+            max_rows_per_group: 30,
+            ..Default::default()
+        };
+        let batches = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+        let dataset = Dataset::write(batches, test_uri, Some(write_params))
+            .await
+            .unwrap();
+
+        build_bitmap_index(&dataset, "label", "label_idx", "bitmap_label")
+            .await
+            .unwrap();
+        build_bitmap_index(&dataset, "flag", "flag_idx", "bitmap_flag")
+            .await
+            .unwrap();
+
+        let expected = |pred: &dyn Fn(&str) -> bool| {
+            label_values
+                .iter()
+                .enumerate()
+                .filter(|(_, v)| v.map(pred).unwrap_or(false))
+                .map(|(row_id, _)| row_id as u64)
+                .collect::<Vec<_>>()
+        };
+        let label = |v: &str| ScalarValue::Utf8(Some(v.to_string()));
+
+        let index = open_scalar_index(&dataset, "bitmap_label")
+            .await
+            .unwrap()
+            .unwrap();
+        let search = |query: ScalarQuery| {
+            let index = index.clone();
+            async move {
+                index
+                    .search(&query)
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .collect::<Vec<_>>()
+            }
+        };
+        assert_eq!(
+            search(ScalarQuery::Equals(label("dog"))).await,
+            expected(&|v| v == "dog")
+        );
+        assert_eq!(
+            search(ScalarQuery::Equals(label("fish"))).await,
+            Vec::<u64>::new()
+        );
+        assert_eq!(
+            search(ScalarQuery::IsIn(vec![
+                label("cat"),
+                label("bird"),
+                label("fish")
+            ]))
+            .await,
+            expected(&|v| v == "cat" || v == "bird")
+        );
+        assert_eq!(
+            search(ScalarQuery::Range(
+                Bound::Included(label("bird")),
+                Bound::Excluded(label("dog")),
+            ))
+            .await,
+            expected(&|v| v == "bird" || v == "cat")
+        );
+
+        let index = open_scalar_index(&dataset, "bitmap_flag")
+            .await
+            .unwrap()
+            .unwrap();
+        let row_ids = index
+            .search(&ScalarQuery::Equals(ScalarValue::Boolean(Some(true))))
+            .await
+            .unwrap();
+        assert_eq!(
+            row_ids.into_iter().collect::<Vec<_>>(),
+            (0..100).step_by(4).collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bitmap_float_values() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let values = [
+            1.5,
+            f64::NAN,
+            -0.0,
+            0.0,
+            -f64::NAN,
+            -1.0,
+            f64::INFINITY,
+            1.5,
+        ];
+        let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "f",
+            DataType::Float64,
+            false,
+        )]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Float64Array::from(values.to_vec()))],
+        )
+        .unwrap();
+        let batches = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+        let dataset = Dataset::write(batches, test_uri, None).await.unwrap();
+        build_bitmap_index(&dataset, "f", "f_idx", "bitmap_f")
+            .await
+            .unwrap();
+        let index = open_scalar_index(&dataset, "bitmap_f")
+            .await
+            .unwrap()
+            .unwrap();
+        // The index is loaded once, and then found in the session.
+        assert!(dataset.session.index_cache.get_scalar("bitmap_f").is_some());
+
+        let search = |query: ScalarQuery| {
+            let index = index.clone();
+            async move {
+                index
+                    .search(&query)
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .collect::<Vec<_>>()
+            }
+        };
+        let value = |v: f64| ScalarValue::Float64(Some(v));
+        assert_eq!(search(ScalarQuery::Equals(value(0.0))).await, vec![2, 3]);
+        assert_eq!(search(ScalarQuery::Equals(value(-0.0))).await, vec![2, 3]);
+        assert_eq!(
+            search(ScalarQuery::Equals(value(f64::NAN))).await,
+            vec![1, 4]
+        );
+        assert_eq!(
+            search(ScalarQuery::Range(
+                Bound::Excluded(value(-1.0)),
+                Bound::Included(value(1.5)),
+            ))
+            .await,
+            vec![0, 2, 3, 7]
+        );
+        // NaN is greater than all the other values.
+        assert_eq!(
+            search(ScalarQuery::Range(
+                Bound::Excluded(value(f64::INFINITY)),
+                Bound::Unbounded,
+            ))
+            .await,
+            vec![1, 4]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bitmap_unsupported_type() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "b",
+            DataType::Binary,
+            false,
+        )]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(BinaryArray::from_iter_values([b"a", b"b"]))],
+        )
+        .unwrap();
+        let batches = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+        let dataset = Dataset::write(batches, test_uri, None).await.unwrap();
+
+        let result = build_bitmap_index(&dataset, "b", "b_idx", "bitmap_b").await;
+        assert!(matches!(result, Err(Error::Index { .. })));
+    }
+}
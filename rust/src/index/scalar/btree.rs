@@ -0,0 +1,715 @@
+// Copyright 2023 Lance Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! B-tree index.
+//!
+//! The values of the column are sorted, with their row ids, and written as the
+//! pages of a Lance file. The min / max values of the pages, kept in the page
+//! statistics of the file, are the inner nodes: a lookup only reads the pages
+//! whose range overlaps with the query.
+//!
+//! Large columns are sorted in chunks, which are merged page by page, so the
+//! whole column is never in memory.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::ops::Bound;
+use std::sync::Arc;
+
+use arrow_array::{cast::as_primitive_array, ArrayRef, RecordBatch, UInt64Array};
+use arrow_row::{RowConverter, Rows, SortField};
+use arrow_schema::{DataType, Field as ArrowField, Schema as ArrowSchema, SchemaRef, SortOptions};
+use arrow_select::{concat::concat, interleave::interleave, take::take};
+use async_trait::async_trait;
+use datafusion::scalar::ScalarValue;
+use futures::TryStreamExt;
+use object_store::path::Path;
+use roaring::RoaringTreemap;
+
+use super::{
+    above, below, write_scalar_index_file, ScalarIndex, ScalarIndexParams, ScalarQuery,
+    ROW_IDS_COLUMN, VALUES_COLUMN,
+};
+use crate::dataset::{Dataset, ROW_ID};
+use crate::datatypes::Schema;
+use crate::index::pb;
+use crate::io::{supports_statistics, FileReader, FileWriter};
+use crate::{Error, Result};
+
+/// Name of the file of the sorted values, in the index directory.
+const BTREE_FILE_NAME: &str = "btree.lance";
+
+/// B-tree index over a column.
+#[derive(Debug)]
+pub struct BTreeIndex {
+    reader: FileReader,
+
+    /// Type of the indexed values.
+    data_type: DataType,
+
+    /// The min and max values of each page.
+    pages: Vec<(ScalarValue, ScalarValue)>,
+}
+
+impl BTreeIndex {
+    pub(super) fn try_new(reader: FileReader) -> Result<Self> {
+        let field = reader
+            .schema()
+            .field(VALUES_COLUMN)
+            .ok_or_else(|| Error::Index {
+                message: format!("BTree index does not have a '{VALUES_COLUMN}' column"),
+            })?;
+        let data_type = field.data_type();
+        let pages = if reader.num_batches() == 0 {
+            vec![]
+        } else {
+            let stats = reader
+                .page_statistics(field.id)
+                .ok_or_else(|| Error::Index {
+                    message: "BTree index does not have page statistics".to_string(),
+                })?;
+            (0..stats.min_values.len())
+                .map(|i| {
+                    Ok((
+                        ScalarValue::try_from_array(&stats.min_values, i)?,
+                        ScalarValue::try_from_array(&stats.max_values, i)?,
+                    ))
+                })
+                .collect::<Result<Vec<_>>>()?
+        };
+        if pages.len() != reader.num_batches() {
+            return Err(Error::Index {
+                message: format!(
+                    "BTree index has {} pages but statistics of {} pages",
+                    reader.num_batches(),
+                    pages.len()
+                ),
+            });
+        }
+        Ok(Self {
+            reader,
+            data_type,
+            pages,
+        })
+    }
+
+    /// Search the row ids of the values within the range.
+    async fn search_range(
+        &self,
+        lower: &Bound<ScalarValue>,
+        upper: &Bound<ScalarValue>,
+        row_ids: &mut RoaringTreemap,
+    ) -> Result<()> {
+        // Values are sorted across the pages, so are the min and the max values. A null
+        // max is a string which could not be rounded up once truncated, it is unbounded.
+        let start = self
+            .pages
+            .partition_point(|(_, max)| !max.is_null() && below(max, lower));
+        let end = self.pages.partition_point(|(min, _)| !above(min, upper));
+
+        for page in start..end.max(start) {
+            let batch = self
+                .reader
+                .read_batch(page as i32, .., self.reader.schema())
+                .await?;
+            let values = batch.column_by_name(VALUES_COLUMN).unwrap();
+            let page_row_ids: &UInt64Array =
+                as_primitive_array(batch.column_by_name(ROW_IDS_COLUMN).unwrap());
+
+            let begin = partition_point(values, |v| below(v, lower))?;
+            let end = partition_point(values, |v| !above(v, upper))?;
+            row_ids.extend(page_row_ids.values()[begin..end.max(begin)].iter().copied());
+        }
+        Ok(())
+    }
+
+    fn cast(&self, value: &ScalarValue) -> Result<ScalarValue> {
+        Ok(value.cast_to(&self.data_type)?)
+    }
+
+    fn cast_bound(&self, bound: &Bound<ScalarValue>) -> Result<Bound<ScalarValue>> {
+        Ok(match bound {
+            Bound::Included(v) => Bound::Included(self.cast(v)?),
+            Bound::Excluded(v) => Bound::Excluded(self.cast(v)?),
+            Bound::Unbounded => Bound::Unbounded,
+        })
+    }
+}
+
+/// Binary search the index of the first value of the sorted array that does not
+/// satisfy the predicate.
+fn partition_point(values: &ArrayRef, pred: impl Fn(&ScalarValue) -> bool) -> Result<usize> {
+    let (mut low, mut high) = (0, values.len());
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if pred(&ScalarValue::try_from_array(values, mid)?) {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+    Ok(low)
+}
+
+#[async_trait]
+impl ScalarIndex for BTreeIndex {
+    async fn search(&self, query: &ScalarQuery) -> Result<RoaringTreemap> {
+        let mut row_ids = RoaringTreemap::new();
+        match query {
+            ScalarQuery::Equals(value) => {
+                let value = Bound::Included(self.cast(value)?);
+                self.search_range(&value, &value, &mut row_ids).await?;
+            }
+            ScalarQuery::IsIn(values) => {
+                for value in values {
+                    let value = Bound::Included(self.cast(value)?);
+                    self.search_range(&value, &value, &mut row_ids).await?;
+                }
+            }
+            ScalarQuery::Range(lower, upper) => {
+                let lower = self.cast_bound(lower)?;
+                let upper = self.cast_bound(upper)?;
+                self.search_range(&lower, &upper, &mut row_ids).await?;
+            }
+        }
+        Ok(row_ids)
+    }
+}
+
+/// Build a B-tree index on the `column` of the dataset.
+pub(crate) async fn build_btree_index(
+    dataset: &Dataset,
+    column: &str,
+    name: &str,
+    uuid: &str,
+    params: &ScalarIndexParams,
+) -> Result<()> {
+    let field = dataset.schema().field(column).ok_or_else(|| Error::Index {
+        message: format!("BTree index: column '{column}' does not exist"),
+    })?;
+    let data_type = field.data_type();
+    if !supports_statistics(&data_type) {
+        return Err(Error::Index {
+            message: format!("BTree index does not support column '{column}' of type {data_type}"),
+        });
+    }
+    if params.page_size == 0 {
+        return Err(Error::Index {
+            message: "BTree index: page size must be greater than 0".to_string(),
+        });
+    }
+    if params.sort_chunk_size == 0 {
+        return Err(Error::Index {
+            message: "BTree index: sort chunk size must be greater than 0".to_string(),
+        });
+    }
+
+    let mut scanner = dataset.scan();
+    scanner.project(&[column])?;
+    scanner.with_row_id();
+    let mut stream = scanner.try_into_stream().await?;
+
+    let schema = Arc::new(ArrowSchema::new(vec![
+        ArrowField::new(VALUES_COLUMN, data_type.clone(), false),
+        ArrowField::new(ROW_IDS_COLUMN, DataType::UInt64, false),
+    ]));
+    let index_dir = dataset.indices_dir().child(uuid);
+    let path = index_dir.child(BTREE_FILE_NAME);
+
+    // The column is sorted in chunks of `sort_chunk_size` values. If it does not fit
+    // in one chunk, the sorted chunks are written as runs to the index directory, and
+    // merged into the index file.
+    let mut chunk = vec![];
+    let mut chunk_size = 0;
+    let mut runs = vec![];
+    while let Some(batch) = stream.try_next().await? {
+        chunk_size += batch.num_rows();
+        chunk.push(batch);
+        if chunk_size >= params.sort_chunk_size {
+            let run = index_dir.child(format!("sort_run_{}.lance", runs.len()));
+            let sorted = sort_chunk(&schema, column, &chunk)?;
+            write_sorted(dataset, &run, &schema, &sorted, params.page_size).await?;
+            runs.push(run);
+            chunk.clear();
+            chunk_size = 0;
+        }
+    }
+
+    if runs.is_empty() {
+        let sorted = sort_chunk(&schema, column, &chunk)?;
+        write_sorted(dataset, &path, &schema, &sorted, params.page_size).await?;
+    } else {
+        if !chunk.is_empty() {
+            let run = index_dir.child(format!("sort_run_{}.lance", runs.len()));
+            let sorted = sort_chunk(&schema, column, &chunk)?;
+            write_sorted(dataset, &run, &schema, &sorted, params.page_size).await?;
+            runs.push(run);
+        }
+        merge_runs(dataset, &runs, &path, &schema, params.page_size).await?;
+        for run in runs.iter() {
+            dataset.object_store().inner.delete(run).await?;
+        }
+    }
+
+    write_scalar_index_file(
+        dataset,
+        column,
+        name,
+        uuid,
+        pb::scalar_index::Index::Btree(pb::BTree {
+            filename: BTREE_FILE_NAME.to_string(),
+        }),
+    )
+    .await
+}
+
+/// Sort the values of the `column` of the batches, with their row ids.
+///
+/// Nulls are not indexed, so they are left out.
+fn sort_chunk(schema: &SchemaRef, column: &str, batches: &[RecordBatch]) -> Result<RecordBatch> {
+    if batches.is_empty() {
+        return Ok(RecordBatch::new_empty(schema.clone()));
+    }
+    let values = batches
+        .iter()
+        .map(|b| b.column_by_name(column).unwrap().as_ref())
+        .collect::<Vec<_>>();
+    let values = concat(&values)?;
+    let row_ids = batches
+        .iter()
+        .map(|b| b.column_by_name(ROW_ID).unwrap().as_ref())
+        .collect::<Vec<_>>();
+    let row_ids = concat(&row_ids)?;
+
+    // Nulls are sorted last.
+    let indices = arrow_ord::sort::sort_to_indices(
+        &values,
+        Some(SortOptions {
+            descending: false,
+            nulls_first: false,
+        }),
+        None,
+    )?;
+    let indices = indices.slice(0, values.len() - values.null_count());
+    Ok(RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            take(&values, &indices, None)?,
+            take(&row_ids, &indices, None)?,
+        ],
+    )?)
+}
+
+/// Write the sorted values, in pages of `page_size` values.
+async fn write_sorted(
+    dataset: &Dataset,
+    path: &Path,
+    schema: &SchemaRef,
+    sorted: &RecordBatch,
+    page_size: usize,
+) -> Result<()> {
+    let mut writer = FileWriter::try_new(
+        dataset.object_store(),
+        path,
+        Schema::try_from(schema.as_ref())?,
+    )
+    .await?;
+    for offset in (0..sorted.num_rows()).step_by(page_size) {
+        let len = page_size.min(sorted.num_rows() - offset);
+        writer.write(&[sorted.slice(offset, len)]).await?;
+    }
+    writer.finish().await?;
+    Ok(())
+}
+
+/// A sorted run, read page by page while merging.
+struct SortedRun {
+    reader: FileReader,
+
+    /// The next page to read.
+    next_page: usize,
+
+    /// The current page, and its values in the row format.
+    page: RecordBatch,
+    rows: Rows,
+
+    /// Position of the current value in the page.
+    position: usize,
+
+    /// Index of the current page in the pages of the output page being merged.
+    source: usize,
+}
+
+impl SortedRun {
+    /// Read the next non-empty page. Returns false at the end of the run.
+    async fn read_next_page(&mut self, converter: &mut RowConverter) -> Result<bool> {
+        while self.next_page < self.reader.num_batches() {
+            let page = self
+                .reader
+                .read_batch(self.next_page as i32, .., self.reader.schema())
+                .await?;
+            self.next_page += 1;
+            if page.num_rows() > 0 {
+                self.rows = converter.convert_columns(&[page.column(0).clone()])?;
+                self.page = page;
+                self.position = 0;
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+}
+
+/// Merge the sorted runs into the index file at `path`.
+///
+/// Only the current page of each run is kept in memory.
+async fn merge_runs(
+    dataset: &Dataset,
+    runs: &[Path],
+    path: &Path,
+    schema: &SchemaRef,
+    page_size: usize,
+) -> Result<()> {
+    let mut converter =
+        RowConverter::new(vec![SortField::new(schema.field(0).data_type().clone())])?;
+    let mut writer = FileWriter::try_new(
+        dataset.object_store(),
+        path,
+        Schema::try_from(schema.as_ref())?,
+    )
+    .await?;
+
+    let empty_page = RecordBatch::new_empty(schema.clone());
+
+    // The pages the values of the output page are taken from.
+    let mut sources = vec![];
+    let mut sorted_runs = vec![];
+    // The smallest current value of the runs is on the top.
+    let mut heap = BinaryHeap::new();
+    for run in runs {
+        let mut sorted_run = SortedRun {
+            reader: FileReader::try_new(dataset.object_store(), run).await?,
+            next_page: 0,
+            page: empty_page.clone(),
+            rows: converter.convert_columns(&[empty_page.column(0).clone()])?,
+            position: 0,
+            source: 0,
+        };
+        if sorted_run.read_next_page(&mut converter).await? {
+            sorted_run.source = sources.len();
+            sources.push(sorted_run.page.clone());
+            heap.push(Reverse((sorted_run.rows.row(0).owned(), sorted_runs.len())));
+        }
+        sorted_runs.push(sorted_run);
+    }
+
+    let mut indices = Vec::with_capacity(page_size);
+    while let Some(Reverse((_, run_id))) = heap.pop() {
+        let sorted_run = &mut sorted_runs[run_id];
+        indices.push((sorted_run.source, sorted_run.position));
+        sorted_run.position += 1;
+        let has_next = if sorted_run.position < sorted_run.page.num_rows() {
+            true
+        } else if sorted_run.read_next_page(&mut converter).await? {
+            sorted_run.source = sources.len();
+            sources.push(sorted_run.page.clone());
+            true
+        } else {
+            false
+        };
+        if has_next {
+            let row = sorted_run.rows.row(sorted_run.position).owned();
+            heap.push(Reverse((row, run_id)));
+        }
+
+        if indices.len() == page_size || heap.is_empty() {
+            let columns = (0..schema.fields().len())
+                .map(|i| {
+                    let arrays = sources
+                        .iter()
+                        .map(|page| page.column(i).as_ref())
+                        .collect::<Vec<_>>();
+                    interleave(&arrays, &indices)
+                })
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            writer
+                .write(&[RecordBatch::try_new(schema.clone(), columns)?])
+                .await?;
+            indices.clear();
+
+            // Only the current pages are needed for the next output page.
+            sources.clear();
+            for sorted_run in sorted_runs.iter_mut() {
+                sorted_run.source = sources.len();
+                sources.push(sorted_run.page.clone());
+            }
+        }
+    }
+    writer.finish().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::{Int32Array, RecordBatchIterator, StringArray};
+    use tempfile::tempdir;
+
+    use crate::dataset::WriteParams;
+    use crate::index::scalar::open_scalar_index;
+
+    #[tokio::test]
+    async fn test_btree_search() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("i", DataType::Int32, true),
+            ArrowField::new("s", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                // Values are not sorted, and some are duplicated or null.
+                Arc::new(Int32Array::from_iter(
+                    (0..100).map(|i| (i % 10 != 9).then_some((i * 7) % 50)),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    (0..100).map(|i| format!("s-{:03}", i)),
+                )),
+            ],
+        )
+        .unwrap();
+        let write_params = WriteParams {
+            max_rows_per_group: 30,
+            ..Default::default()
+        };
+        let batches = RecordBatchIterator::new(vec![Ok(batch.clone())], schema.clone());
+        let dataset = Dataset::write(batches, test_uri, Some(write_params))
+            .await
+            .unwrap();
+
+        let params = ScalarIndexParams {
+            page_size: 16,
+            ..Default::default()
+        };
+        build_btree_index(&dataset, "i", "i_idx", "btree_i", &params)
+            .await
+            .unwrap();
+        build_btree_index(&dataset, "s", "s_idx", "btree_s", &params)
+            .await
+            .unwrap();
+        let index = open_scalar_index(&dataset, "btree_i")
+            .await
+            .unwrap()
+            .unwrap();
+        // The opened index is cached in the session, so it is not read again.
+        assert!(dataset.session.index_cache.get_scalar("btree_i").is_some());
+
+        // Expected row ids computed from the values.
+        let values = batch.column(0);
+        let values: &Int32Array = as_primitive_array(values);
+        let expected = |pred: &dyn Fn(i32) -> bool| {
+            values
+                .iter()
+                .enumerate()
+                .filter(|(_, v)| v.map(pred).unwrap_or(false))
+                .map(|(row_id, _)| row_id as u64)
+                .collect::<Vec<_>>()
+        };
+        let search = |query: ScalarQuery| {
+            let index = index.clone();
+            async move {
+                index
+                    .search(&query)
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .collect::<Vec<_>>()
+            }
+        };
+
+        assert_eq!(
+            search(ScalarQuery::Equals(ScalarValue::Int32(Some(21)))).await,
+            expected(&|v| v == 21)
+        );
+        assert_eq!(
+            search(ScalarQuery::Equals(ScalarValue::Int32(Some(1000)))).await,
+            Vec::<u64>::new()
+        );
+        assert_eq!(
+            search(ScalarQuery::IsIn(vec![
+                ScalarValue::Int32(Some(0)),
+                ScalarValue::Int32(Some(49)),
+                ScalarValue::Int32(Some(-3)),
+            ]))
+            .await,
+            expected(&|v| v == 0 || v == 49)
+        );
+        assert_eq!(
+            search(ScalarQuery::Range(
+                Bound::Excluded(ScalarValue::Int32(Some(10))),
+                Bound::Included(ScalarValue::Int32(Some(20))),
+            ))
+            .await,
+            expected(&|v| v > 10 && v <= 20)
+        );
+        assert_eq!(
+            search(ScalarQuery::Range(
+                Bound::Unbounded,
+                Bound::Excluded(ScalarValue::Int32(Some(7)))
+            ))
+            .await,
+            expected(&|v| v < 7)
+        );
+        // The values of the query are cast to the type of the column.
+        assert_eq!(
+            search(ScalarQuery::Range(
+                Bound::Included(ScalarValue::Int64(Some(45))),
+                Bound::Unbounded,
+            ))
+            .await,
+            expected(&|v| v >= 45)
+        );
+
+        let index = open_scalar_index(&dataset, "btree_s")
+            .await
+            .unwrap()
+            .unwrap();
+        let row_ids = index
+            .search(&ScalarQuery::Range(
+                Bound::Included(ScalarValue::Utf8(Some("s-042".to_string()))),
+                Bound::Excluded(ScalarValue::Utf8(Some("s-045".to_string()))),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(row_ids.into_iter().collect::<Vec<_>>(), vec![42, 43, 44]);
+    }
+
+    #[tokio::test]
+    async fn test_btree_merge_sorted_runs() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "i",
+            DataType::Int32,
+            true,
+        )]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter(
+                (0..1000).map(|i| (i % 10 != 9).then_some((i * 37) % 500)),
+            ))],
+        )
+        .unwrap();
+        let write_params = WriteParams {
+            max_rows_per_group: 30,
+            ..Default::default()
+        };
+        let batches = RecordBatchIterator::new(vec![Ok(batch.clone())], schema.clone());
+        let dataset = Dataset::write(batches, test_uri, Some(write_params))
+            .await
+            .unwrap();
+
+        // The column is sorted in chunks of about 100 values, which are merged.
+        let params = ScalarIndexParams {
+            page_size: 16,
+            sort_chunk_size: 100,
+        };
+        build_btree_index(&dataset, "i", "i_idx", "btree_i", &params)
+            .await
+            .unwrap();
+        let index_dir = dataset.indices_dir().child("btree_i");
+        let files = dataset
+            .object_store()
+            .read_dir(index_dir.clone())
+            .await
+            .unwrap();
+        assert!(files.iter().all(|f| !f.starts_with("sort_run_")));
+
+        let reader = FileReader::try_new(dataset.object_store(), &index_dir.child(BTREE_FILE_NAME))
+            .await
+            .unwrap();
+        let mut values = vec![];
+        let mut row_ids = vec![];
+        for page in 0..reader.num_batches() {
+            let batch = reader
+                .read_batch(page as i32, .., reader.schema())
+                .await
+                .unwrap();
+            assert!(batch.num_rows() <= 16);
+            let page_values: &Int32Array = as_primitive_array(batch.column(0));
+            values.extend(page_values.values().iter().copied());
+            let page_row_ids: &UInt64Array = as_primitive_array(batch.column(1));
+            row_ids.extend(page_row_ids.values().iter().copied());
+        }
+        let mut expected = (0..1000)
+            .filter(|i| i % 10 != 9)
+            .map(|i| ((i * 37) % 500, i as u64))
+            .collect::<Vec<_>>();
+        expected.sort();
+        assert_eq!(values, expected.iter().map(|(v, _)| *v).collect::<Vec<_>>());
+        // Row ids of the same value may be in any order.
+        row_ids.sort();
+        let mut expected_row_ids = expected.iter().map(|(_, r)| *r).collect::<Vec<_>>();
+        expected_row_ids.sort();
+        assert_eq!(row_ids, expected_row_ids);
+
+        let index = open_scalar_index(&dataset, "btree_i")
+            .await
+            .unwrap()
+            .unwrap();
+        let found = index
+            .search(&ScalarQuery::Equals(ScalarValue::Int32(Some(37))))
+            .await
+            .unwrap();
+        let expected = expected
+            .iter()
+            .filter(|(v, _)| *v == 37)
+            .map(|(_, r)| *r)
+            .collect::<Vec<_>>();
+        assert_eq!(found.into_iter().collect::<Vec<_>>(), expected);
+    }
+
+    #[tokio::test]
+    async fn test_btree_unsupported_type() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "b",
+            DataType::Boolean,
+            true,
+        )]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(arrow_array::BooleanArray::from(vec![true, false]))],
+        )
+        .unwrap();
+        let batches = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+        let dataset = Dataset::write(batches, test_uri, None).await.unwrap();
+
+        let result = build_btree_index(
+            &dataset,
+            "b",
+            "b_idx",
+            "btree_b",
+            &ScalarIndexParams::default(),
+        )
+        .await;
+        assert!(matches!(result, Err(Error::Index { .. })));
+    }
+}
@@ -19,25 +19,30 @@ use std::any::Any;
 use std::sync::Arc;
 
 use arrow_array::Float32Array;
+use roaring::RoaringTreemap;
 
 pub mod diskann;
 pub mod flat;
 #[allow(dead_code)]
 mod graph;
+pub mod hnsw;
 pub mod ivf;
 mod kmeans;
 #[cfg(feature = "opq")]
 pub mod opq;
 pub mod pq;
+pub mod sq;
 mod traits;
 mod utils;
 
 use self::{
-    ivf::{build_ivf_pq_index, IVFIndex, IvfBuildParams},
+    ivf::{build_ivf_pq_index, build_ivf_sq_index, IVFIndex, IvfBuildParams},
     pq::{PQBuildParams, PQIndex},
+    sq::{SQBuildParams, SQIndex},
 };
 
-use super::{pb, IndexParams};
+pub(crate) use super::INDEX_FILE_NAME;
+use super::{pb, read_index_file, IndexParams};
 #[cfg(feature = "opq")]
 use crate::index::vector::opq::{OPQIndex, OptimizedProductQuantizer};
 use crate::{
@@ -46,15 +51,13 @@ use crate::{
         pb::vector_index_stage::Stage,
         vector::{
             diskann::{DiskANNIndex, DiskANNParams},
+            hnsw::{HNSWIndex, HNSWParams, IvfHNSWIndex},
             ivf::Ivf,
             pq::ProductQuantizer,
+            sq::ScalarQuantizer,
         },
     },
-    io::{
-        deletion::LruDeletionVectorStore,
-        object_reader::{read_message, ObjectReader},
-        read_message_from_buf, read_metadata_offset,
-    },
+    io::deletion::LruDeletionVectorStore,
     linalg::{
         cosine::{cosine_distance, cosine_distance_batch},
         dot::{dot_distance, dot_distance_batch},
@@ -65,7 +68,6 @@ use crate::{
 pub use traits::*;
 
 pub(crate) const SCORE_COL: &str = "score";
-const INDEX_FILE_NAME: &str = "index.idx";
 
 /// Query parameters for the vector indices
 #[derive(Debug, Clone)]
@@ -91,6 +93,10 @@ pub struct Query {
 
     /// Whether to use an ANN index if available
     pub use_index: bool,
+
+    /// If presented, only the rows with these row ids are searched, i.e., the
+    /// rows matching a prefilter.
+    pub allow_list: Option<Arc<RoaringTreemap>>,
 }
 
 /// Distance metrics type.
@@ -180,7 +186,11 @@ pub enum StageParams {
 
     PQ(PQBuildParams),
 
+    SQ(SQBuildParams),
+
     DiskANN(DiskANNParams),
+
+    HNSW(HNSWParams),
 }
 
 /// The parameters to build vector index.
@@ -197,7 +207,9 @@ impl VectorIndexParams {
     /// Parameters
     ///
     ///  - `num_partitions`: the number of IVF partitions.
-    ///  - `num_bits`: the number of bits to present the centroids used in PQ. Can only be `8` for now.
+    ///  - `num_bits`: the number of bits to present the centroids used in PQ, either `4` or `8`.
+    ///    The 4-bit codes of two sub-vectors are packed into one byte, and scanned with SIMD
+    ///    lookup tables, so `num_sub_vectors` must be even.
     ///  - `num_sub_vectors`: the number of sub vectors used in PQ.
     ///  - `metric_type`: how to compute distance, i.e., `L2` or `Cosine`.
     pub fn ivf_pq(
@@ -241,6 +253,34 @@ impl VectorIndexParams {
         }
     }
 
+    /// Create index parameters for `IVF_SQ` index.
+    ///
+    /// Parameters
+    ///
+    ///  - `num_partitions`: the number of IVF partitions.
+    ///  - `metric_type`: how to compute distance, i.e., `L2` or `Cosine`.
+    ///  - `max_iterations`: the max iterations to train the IVF partitions.
+    pub fn ivf_sq(num_partitions: usize, metric_type: MetricType, max_iterations: usize) -> Self {
+        let ivf_params = IvfBuildParams {
+            max_iters: max_iterations,
+            ..IvfBuildParams::new(num_partitions)
+        };
+        Self::with_ivf_sq_params(metric_type, ivf_params, SQBuildParams::default())
+    }
+
+    /// Create index parameters with `IVF` and `SQ` parameters, respectively.
+    pub fn with_ivf_sq_params(
+        metric_type: MetricType,
+        ivf: IvfBuildParams,
+        sq: SQBuildParams,
+    ) -> Self {
+        let stages = vec![StageParams::Ivf(ivf), StageParams::SQ(sq)];
+        Self {
+            stages,
+            metric_type,
+        }
+    }
+
     pub fn with_diskann_params(metric_type: MetricType, diskann: DiskANNParams) -> Self {
         let stages = vec![StageParams::DiskANN(diskann)];
         Self {
@@ -248,6 +288,31 @@ impl VectorIndexParams {
             metric_type,
         }
     }
+
+    /// Create index parameters for `HNSW` index.
+    ///
+    /// The `m`, `ef_construction` and `ef_search` parameters are set in `hnsw`.
+    pub fn with_hnsw_params(metric_type: MetricType, hnsw: HNSWParams) -> Self {
+        let stages = vec![StageParams::HNSW(hnsw)];
+        Self {
+            stages,
+            metric_type,
+        }
+    }
+
+    /// Create index parameters for `IVF_HNSW` index, i.e., an HNSW graph in each
+    /// IVF partition, with `IVF` and `HNSW` parameters, respectively.
+    pub fn with_ivf_hnsw_params(
+        metric_type: MetricType,
+        ivf: IvfBuildParams,
+        hnsw: HNSWParams,
+    ) -> Self {
+        let stages = vec![StageParams::Ivf(ivf), StageParams::HNSW(hnsw)];
+        Self {
+            stages,
+            metric_type,
+        }
+    }
 }
 
 impl IndexParams for VectorIndexParams {
@@ -266,6 +331,12 @@ fn is_ivf_pq(stages: &[StageParams]) -> bool {
         && matches!(&stages[len - 2], StageParams::Ivf(_))
 }
 
+fn is_ivf_sq(stages: &[StageParams]) -> bool {
+    stages.len() == 2
+        && matches!(&stages[0], StageParams::Ivf(_))
+        && matches!(&stages[1], StageParams::SQ(_))
+}
+
 fn is_diskann(stages: &[StageParams]) -> bool {
     if stages.is_empty() {
         return false;
@@ -274,6 +345,16 @@ fn is_diskann(stages: &[StageParams]) -> bool {
     matches!(last, StageParams::DiskANN(_))
 }
 
+fn is_hnsw(stages: &[StageParams]) -> bool {
+    stages.len() == 1 && matches!(&stages[0], StageParams::HNSW(_))
+}
+
+fn is_ivf_hnsw(stages: &[StageParams]) -> bool {
+    stages.len() == 2
+        && matches!(&stages[0], StageParams::Ivf(_))
+        && matches!(&stages[1], StageParams::HNSW(_))
+}
+
 /// Build a Vector Index
 pub(crate) async fn build_vector_index(
     dataset: &Dataset,
@@ -313,6 +394,28 @@ pub(crate) async fn build_vector_index(
             pq_params,
         )
         .await?
+    } else if is_ivf_sq(stages) {
+        // This is a IVF SQ index.
+        let StageParams::Ivf(ivf_params) = &stages[0] else {
+            return Err(Error::Index{message:
+                format!("Build Vector Index: invalid stages: {:?}", stages),
+            });
+        };
+        let StageParams::SQ(sq_params) = &stages[1] else {
+            return Err(Error::Index{message:
+                format!("Build Vector Index: invalid stages: {:?}", stages),
+            });
+        };
+        build_ivf_sq_index(
+            dataset,
+            column,
+            name,
+            uuid,
+            params.metric_type,
+            ivf_params,
+            sq_params,
+        )
+        .await?
     } else if is_diskann(stages) {
         // This is DiskANN index.
         use self::diskann::build_diskann_index;
@@ -322,6 +425,38 @@ pub(crate) async fn build_vector_index(
             });
         };
         build_diskann_index(dataset, column, name, uuid, params.clone()).await?;
+    } else if is_hnsw(stages) {
+        use self::hnsw::build_hnsw_index;
+        let StageParams::HNSW(hnsw_params) = &stages[0] else {
+            return Err(Error::Index{message:
+                format!("Build Vector Index: invalid stages: {:?}", stages),
+            });
+        };
+        let mut hnsw_params = hnsw_params.clone();
+        hnsw_params.metric_type(params.metric_type);
+        build_hnsw_index(dataset, column, name, uuid, hnsw_params).await?;
+    } else if is_ivf_hnsw(stages) {
+        use self::hnsw::build_ivf_hnsw_index;
+        let StageParams::Ivf(ivf_params) = &stages[0] else {
+            return Err(Error::Index{message:
+                format!("Build Vector Index: invalid stages: {:?}", stages),
+            });
+        };
+        let StageParams::HNSW(hnsw_params) = &stages[1] else {
+            return Err(Error::Index{message:
+                format!("Build Vector Index: invalid stages: {:?}", stages),
+            });
+        };
+        build_ivf_hnsw_index(
+            dataset,
+            column,
+            name,
+            uuid,
+            params.metric_type,
+            ivf_params,
+            hnsw_params.clone(),
+        )
+        .await?;
     } else {
         return Err(Error::Index {
             message: format!("Build Vector Index: invalid stages: {:?}", stages),
@@ -342,27 +477,8 @@ pub(crate) async fn open_index(
     }
 
     let index_dir = dataset.indices_dir().child(uuid);
-    let index_file = index_dir.child(INDEX_FILE_NAME);
-
     let object_store = dataset.object_store();
-    let reader: Arc<dyn ObjectReader> = object_store.open(&index_file).await?.into();
-
-    let file_size = reader.size().await?;
-    let block_size = object_store.block_size();
-    let begin = if file_size < block_size {
-        0
-    } else {
-        file_size - block_size
-    };
-    let tail_bytes = reader.get_range(begin..file_size).await?;
-    let metadata_pos = read_metadata_offset(&tail_bytes)?;
-    let proto: pb::Index = if metadata_pos < file_size - tail_bytes.len() {
-        // We have not read the metadata bytes yet.
-        read_message(reader.as_ref(), metadata_pos).await?
-    } else {
-        let offset = tail_bytes.len() - (file_size - metadata_pos);
-        read_message_from_buf(&tail_bytes.slice(offset..))?
-    };
+    let (reader, proto) = read_index_file(&dataset, uuid).await?;
 
     if proto.columns.len() != 1 {
         return Err(Error::Index {
@@ -371,12 +487,13 @@ pub(crate) async fn open_index(
     }
     assert_eq!(proto.index_type, pb::IndexType::Vector as i32);
 
-    let Some(idx_impl) = proto.implementation.as_ref() else {
-        return Err(Error::Index{message:"Invalid protobuf for VectorIndex metadata".to_string()});
+    let Some(pb::index::Implementation::VectorIndex(vec_idx)) = proto.implementation.as_ref()
+    else {
+        return Err(Error::Index {
+            message: "Invalid protobuf for VectorIndex metadata".to_string(),
+        });
     };
 
-    let pb::index::Implementation::VectorIndex(vec_idx) = idx_impl;
-
     let metric_type = pb::VectorMetricType::from_i32(vec_idx.metric_type)
         .ok_or(Error::Index {
             message: format!("Unsupported metric type value: {}", vec_idx.metric_type),
@@ -392,6 +509,26 @@ pub(crate) async fn open_index(
         100_usize,
     ));
 
+    // The HNSW graphs of the partitions of an IVF_HNSW index are opened by the
+    // index itself, when the partitions are searched.
+    if let [ivf_stage, hnsw_stage] = vec_idx.stages.as_slice() {
+        if let (Some(Stage::Ivf(ivf_pb)), Some(Stage::Hnsw(hnsw_proto))) =
+            (ivf_stage.stage.as_ref(), hnsw_stage.stage.as_ref())
+        {
+            let idx: Arc<dyn VectorIndex> = Arc::new(IvfHNSWIndex::try_new(
+                dataset.clone(),
+                column,
+                uuid,
+                Ivf::try_from(ivf_pb)?,
+                hnsw_proto,
+                metric_type,
+                deletion_cache,
+            )?);
+            dataset.session.index_cache.insert(uuid, idx.clone());
+            return Ok(idx);
+        }
+    }
+
     for stg in vec_idx.stages.iter().rev() {
         match stg.stage.as_ref() {
             #[allow(unused_variables)]
@@ -443,13 +580,26 @@ pub(crate) async fn open_index(
                         message: format!("Invalid vector index stages: {:?}", vec_idx.stages),
                     });
                 };
-                let pq = Arc::new(ProductQuantizer::try_from(pq_proto).unwrap());
+                let pq = Arc::new(ProductQuantizer::try_from(pq_proto)?);
                 last_stage = Some(Arc::new(PQIndex::new(
                     pq,
                     metric_type,
                     deletion_cache.clone(),
                 )));
             }
+            Some(Stage::Sq(sq_proto)) => {
+                if last_stage.is_some() {
+                    return Err(Error::Index {
+                        message: format!("Invalid vector index stages: {:?}", vec_idx.stages),
+                    });
+                };
+                let sq = Arc::new(ScalarQuantizer::from(sq_proto));
+                last_stage = Some(Arc::new(SQIndex::new(
+                    sq,
+                    metric_type,
+                    deletion_cache.clone(),
+                )));
+            }
             Some(Stage::Diskann(diskann_proto)) => {
                 if last_stage.is_some() {
                     return Err(Error::Index {
@@ -471,6 +621,28 @@ pub(crate) async fn open_index(
                 );
                 last_stage = Some(diskann);
             }
+            Some(Stage::Hnsw(hnsw_proto)) => {
+                if last_stage.is_some() {
+                    return Err(Error::Index {
+                        message: format!(
+                            "HNSW should be the only stage, but we got stages: {:?}",
+                            vec_idx.stages
+                        ),
+                    });
+                };
+                let hnsw = Arc::new(
+                    HNSWIndex::try_new(
+                        dataset.clone(),
+                        column,
+                        &index_dir,
+                        hnsw_proto,
+                        metric_type,
+                        deletion_cache.clone(),
+                    )
+                    .await?,
+                );
+                last_stage = Some(hnsw);
+            }
             _ => {}
         }
     }
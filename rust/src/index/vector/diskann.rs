@@ -26,7 +26,7 @@ use super::{
 use crate::index::vector::pq::PQBuildParams;
 pub(crate) use builder::build_diskann_index;
 pub(crate) use row_vertex::RowVertex;
-pub(crate) use search::DiskANNIndex;
+pub(crate) use search::{greedy_search, DiskANNIndex};
 
 #[derive(Clone, Debug)]
 pub struct DiskANNParams {
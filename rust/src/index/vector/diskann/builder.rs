@@ -278,7 +278,7 @@ async fn index_once<V: Vertex + Clone + Sync + Send>(
             message: format!("Cannot find vector with id {}", id),
         })?;
 
-        let state = greedy_search(graph, medoid, vector, 1, l).await?;
+        let state = greedy_search(graph, medoid, vector, 1, l, None).await?;
 
         let neighbors = robust_prune(graph, id, state.visited, alpha, r).await?;
         graph.set_neighbors(
@@ -24,6 +24,7 @@ use arrow_schema::{DataType, Field, Schema};
 use async_trait::async_trait;
 use object_store::path::Path;
 use ordered_float::OrderedFloat;
+use roaring::RoaringTreemap;
 
 use super::row_vertex::{RowVertex, RowVertexSerDe};
 use crate::{
@@ -60,7 +61,7 @@ pub struct SearchState {
     ///
     /// Different to the heap is that, candidates might contain visited vertices
     /// and unvisited vertices.
-    candidates: BTreeMap<OrderedFloat<f32>, usize>,
+    pub(crate) candidates: BTreeMap<OrderedFloat<f32>, usize>,
 
     /// Heap maintains the unvisited vertices, ordered by the distance.
     heap: BinaryHeap<Reverse<VertexWithDistance>>,
@@ -80,6 +81,11 @@ pub struct SearchState {
     //TODO: used during search.
     #[allow(dead_code)]
     k: usize,
+
+    /// If presented, only the vertices in the allow list can be candidates.
+    ///
+    /// The other vertices are still visited, to reach the allowed vertices through them.
+    allow_list: Option<Arc<RoaringTreemap>>,
 }
 
 impl SearchState {
@@ -92,13 +98,30 @@ impl SearchState {
             heap_visisted: HashSet::new(),
             k,
             l,
+            allow_list: None,
         }
     }
 
+    /// Only the vertices in the allow list can be candidates.
+    pub(crate) fn with_allow_list(mut self, allow_list: Arc<RoaringTreemap>) -> Self {
+        self.allow_list = Some(allow_list);
+        self
+    }
+
     /// Return the next unvisited vertex.
     fn pop(&mut self) -> Option<usize> {
         while let Some(vertex) = self.heap.pop() {
-            if !self.candidates.contains_key(&vertex.0.distance) {
+            if self.allow_list.is_some() {
+                // The vertex is farther than all the candidates.
+                if self.candidates.len() >= self.l
+                    && self
+                        .candidates
+                        .last_key_value()
+                        .map_or(false, |(distance, _)| vertex.0.distance > *distance)
+                {
+                    continue;
+                }
+            } else if !self.candidates.contains_key(&vertex.0.distance) {
                 // The vertex has been removed from the candidate lists,
                 // from [`push()`].
                 continue;
@@ -117,12 +140,22 @@ impl SearchState {
         self.heap_visisted.insert(vertex_id);
         self.heap
             .push(Reverse(VertexWithDistance::new(vertex_id, distance)));
+        if !self.is_allowed(vertex_id) {
+            return;
+        }
         self.candidates.insert(OrderedFloat(distance), vertex_id);
         if self.candidates.len() > self.l {
             self.candidates.pop_last();
         }
     }
 
+    /// Returns true if the vertex can be a candidate.
+    fn is_allowed(&self, vertex_id: usize) -> bool {
+        self.allow_list
+            .as_ref()
+            .map_or(true, |allow_list| allow_list.contains(vertex_id as u64))
+    }
+
     /// Mark a vertex as visited.
     fn visit(&mut self, vertex_id: usize) {
         self.visited.insert(vertex_id);
@@ -143,16 +176,21 @@ impl SearchState {
 /// - query: The query vector.
 /// - k: The number of nearest neighbors to return.
 /// - search_size: Search list size, L in the paper.
+/// - allow_list: If presented, only the vertices in it are returned as candidates.
 pub async fn greedy_search(
     graph: &(dyn Graph + Send + Sync),
     start: usize,
     query: &[f32],
     k: usize,
     search_size: usize, // L in the paper.
+    allow_list: Option<Arc<RoaringTreemap>>,
 ) -> Result<SearchState> {
     // L in the paper.
     // A map from distance to vertex id.
     let mut state = SearchState::new(k, search_size);
+    if let Some(allow_list) = allow_list {
+        state = state.with_allow_list(allow_list);
+    }
 
     let dist = graph.distance_to(query, start).await?;
     state.push(start, dist);
@@ -215,7 +253,15 @@ impl Index for DiskANNIndex {
 #[async_trait]
 impl VectorIndex for DiskANNIndex {
     async fn search(&self, query: &Query) -> Result<RecordBatch> {
-        let state = greedy_search(&self.graph, 0, query.key.values(), query.k, query.k * 2).await?;
+        let state = greedy_search(
+            &self.graph,
+            0,
+            query.key.values(),
+            query.k,
+            query.k * 2,
+            query.allow_list.clone(),
+        )
+        .await?;
         let schema = Arc::new(Schema::new(vec![
             Field::new(ROW_ID, DataType::UInt64, false),
             Field::new(SCORE_COL, DataType::Float32, false),
@@ -291,4 +337,30 @@ mod test {
         assert!(state.heap.is_empty());
         assert_eq!(state.candidates.len(), 20);
     }
+
+    #[test]
+    fn test_search_state_with_allow_list() {
+        let k: usize = 5;
+        let l: usize = 5;
+
+        // Only the even vertices can be candidates.
+        let allow_list = Arc::new((0..40).step_by(2).collect::<RoaringTreemap>());
+        let mut state = SearchState::new(k, l).with_allow_list(allow_list);
+        for i in (0..40).rev() {
+            state.push(i, i as f32);
+        }
+        assert_eq!(state.heap.len(), 40);
+        assert_eq!(
+            state.candidates.values().copied().collect::<Vec<_>>(),
+            [0, 2, 4, 6, 8]
+        );
+
+        // The odd vertices closer than the farthest candidate are visited too.
+        let mut visited = vec![];
+        while let Some(next) = state.pop() {
+            visited.push(next);
+        }
+        assert_eq!(visited, (0..=8).collect::<Vec<_>>());
+        assert!(state.heap.is_empty());
+    }
 }
@@ -15,23 +15,24 @@
 //! Flat Vector Index.
 //!
 
+use std::sync::Arc;
+
 use arrow::array::as_primitive_array;
 use arrow::datatypes::Float32Type;
 use arrow_array::{cast::as_struct_array, ArrayRef, RecordBatch, StructArray};
 use arrow_ord::sort::sort_to_indices;
-use arrow_schema::{DataType, Field as ArrowField};
+use arrow_schema::{DataType, Field as ArrowField, Schema as ArrowSchema};
 use arrow_select::{concat::concat_batches, take::take};
 use futures::future;
-use futures::stream::{repeat_with, Stream, StreamExt, TryStreamExt};
+use futures::stream::{repeat_with, StreamExt, TryStreamExt};
 
 use super::{Query, SCORE_COL};
 use crate::arrow::*;
+use crate::io::RecordBatchStream;
 use crate::{Error, Result};
 
-pub async fn flat_search(
-    stream: impl Stream<Item = Result<RecordBatch>>,
-    query: &Query,
-) -> Result<RecordBatch> {
+pub async fn flat_search(stream: impl RecordBatchStream, query: &Query) -> Result<RecordBatch> {
+    let schema = stream.schema();
     let batches = stream
         .filter(|batch| {
             let pred = batch.as_ref().map(|b| b.num_rows() > 0).unwrap_or(false);
@@ -72,6 +73,21 @@ pub async fn flat_search(
         .buffer_unordered(16)
         .try_collect::<Vec<_>>()
         .await?;
+    if batches.is_empty() {
+        // No vector to search, i.e., no row matches the prefilter.
+        let mut fields = schema
+            .fields()
+            .iter()
+            .filter(|f| f.name() != SCORE_COL)
+            .cloned()
+            .collect::<Vec<_>>();
+        fields.push(Arc::new(ArrowField::new(
+            SCORE_COL,
+            DataType::Float32,
+            false,
+        )));
+        return Ok(RecordBatch::new_empty(Arc::new(ArrowSchema::new(fields))));
+    }
     let batch = concat_batches(&batches[0].schema(), &batches)?;
     let scores = batch.column_by_name(SCORE_COL).unwrap();
     let indices = sort_to_indices(scores, None, Some(query.k))?;
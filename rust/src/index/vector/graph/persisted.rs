@@ -29,12 +29,13 @@ use object_store::path::Path;
 
 use super::{builder::GraphBuilder, Graph};
 use super::{Vertex, VertexSerDe};
+use crate::arrow::as_fixed_size_binary_array;
 use crate::arrow::as_fixed_size_list_array;
 use crate::dataset::Dataset;
 use crate::datatypes::Schema;
 use crate::index::vector::diskann::RowVertex;
+use crate::index::vector::{DistanceFunc, MetricType};
 use crate::io::{FileReader, FileWriter, ObjectStore};
-use crate::{arrow::as_fixed_size_binary_array, linalg::l2::L2};
 use crate::{Error, Result};
 
 const NEIGHBORS_COL: &str = "neighbors";
@@ -47,6 +48,9 @@ pub struct GraphReadParams {
     pub vertex_cache_size: usize,
 
     pub neighbors_cache_size: usize,
+
+    /// Metric type to compute the distances between vertices.
+    pub metric_type: MetricType,
 }
 
 impl Default for GraphReadParams {
@@ -55,6 +59,7 @@ impl Default for GraphReadParams {
             prefetch_byte_size: 8 * 1024,
             vertex_cache_size: 100_000,
             neighbors_cache_size: 1024,
+            metric_type: MetricType::L2,
         }
     }
 }
@@ -89,6 +94,9 @@ pub struct PersistedGraph<V: Vertex + Debug> {
 
     /// SerDe for vertex.
     serde: Arc<dyn VertexSerDe<V> + Send + Sync>,
+
+    /// Distance function.
+    distance_func: Arc<DistanceFunc>,
 }
 
 impl<V: Vertex + Debug> PersistedGraph<V> {
@@ -125,6 +133,7 @@ impl<V: Vertex + Debug> PersistedGraph<V> {
         let neighbors_projection = schema.project(&[NEIGHBORS_COL])?;
 
         let vector_column_projection = dataset.schema().project(&[vector_column])?;
+        let distance_func = params.metric_type.func();
 
         Ok(Self {
             dataset,
@@ -141,6 +150,7 @@ impl<V: Vertex + Debug> PersistedGraph<V> {
             neighbors_projection,
             params,
             serde,
+            distance_func,
         })
     }
 
@@ -149,6 +159,19 @@ impl<V: Vertex + Debug> PersistedGraph<V> {
         self.reader.len()
     }
 
+    /// Read the metadata of all the vertices, without their vectors.
+    pub async fn vertices(&self) -> Result<Vec<V>> {
+        let batch = self
+            .reader
+            .read_range(0..self.len(), &self.vertex_projection)
+            .await?;
+        let array = as_fixed_size_binary_array(batch.column(0));
+        array
+            .iter()
+            .map(|vertex_bytes| self.serde.deserialize(vertex_bytes.unwrap()))
+            .collect()
+    }
+
     /// Get the vertex specified by its id.
     pub async fn vertex(&self, id: u32) -> Result<Arc<V>> {
         {
@@ -230,7 +253,7 @@ impl<V: Vertex + Send + Sync + Debug> Graph for PersistedGraph<V> {
 
     async fn distance_to(&self, query: &[f32], idx: usize) -> Result<f32> {
         let vertex = self.vertex(idx as u32).await?;
-        Ok(vertex.vector().l2(query))
+        Ok((self.distance_func)(query, vertex.vector()))
     }
 
     /// Get the neighbors of a vertex, specified by its id.
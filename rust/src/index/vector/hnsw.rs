@@ -0,0 +1,247 @@
+// Copyright 2023 Lance Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+/// HNSW: Efficient and robust approximate nearest neighbor search using
+/// Hierarchical Navigable Small World graphs.
+///
+/// Each level of the graph is persisted as a graph file. The vectors are read from
+/// the `lance` dataset.
+///
+/// A standalone HNSW graph is built in memory, one vertex after another. An IVF_HNSW
+/// index builds a smaller graph in each IVF partition, so the graphs are built in
+/// parallel, and only the graphs being built are in memory.
+mod builder;
+mod search;
+
+use super::MetricType;
+pub(crate) use builder::{build_hnsw_index, build_ivf_hnsw_index};
+pub(crate) use search::{HNSWIndex, IvfHNSWIndex};
+
+#[derive(Clone, Debug)]
+pub struct HNSWParams {
+    /// The number of neighbors of each vertex on the upper levels.
+    /// The vertices on the bottom level have at most `2 * m` neighbors.
+    pub m: usize,
+
+    /// The size of the dynamic candidate list to build the graph.
+    pub ef_construction: usize,
+
+    /// The size of the dynamic candidate list to search the graph.
+    pub ef_search: usize,
+
+    /// Metric type.
+    pub metric_type: MetricType,
+}
+
+impl Default for HNSWParams {
+    fn default() -> Self {
+        Self {
+            m: 16,
+            ef_construction: 100,
+            ef_search: 50,
+            metric_type: MetricType::L2,
+        }
+    }
+}
+
+impl HNSWParams {
+    pub fn new(m: usize, ef_construction: usize, ef_search: usize) -> Self {
+        Self {
+            m,
+            ef_construction,
+            ef_search,
+            metric_type: MetricType::L2,
+        }
+    }
+
+    pub fn m(&mut self, m: usize) -> &mut Self {
+        self.m = m;
+        self
+    }
+
+    pub fn ef_construction(&mut self, ef_construction: usize) -> &mut Self {
+        self.ef_construction = ef_construction;
+        self
+    }
+
+    pub fn ef_search(&mut self, ef_search: usize) -> &mut Self {
+        self.ef_search = ef_search;
+        self
+    }
+
+    pub fn metric_type(&mut self, metric_type: MetricType) -> &mut Self {
+        self.metric_type = metric_type;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{
+        cast::AsArray, types::UInt64Type, FixedSizeListArray, RecordBatch, RecordBatchIterator,
+    };
+    use arrow_schema::{DataType, Field, Schema as ArrowSchema};
+    use futures::TryStreamExt;
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::{
+        arrow::*,
+        dataset::{Dataset, WriteMode, WriteParams, ROW_ID},
+        index::{
+            vector::{ivf::IvfBuildParams, VectorIndexParams},
+            DatasetIndexExt, IndexType,
+        },
+        utils::testing::generate_random_array,
+    };
+
+    #[tokio::test]
+    async fn test_create_and_search_index() {
+        let test_dir = tempdir().unwrap();
+
+        let dimension = 16;
+        let schema = Arc::new(ArrowSchema::new(vec![Field::new(
+            "embeddings",
+            DataType::FixedSizeList(
+                Arc::new(Field::new("item", DataType::Float32, true)),
+                dimension,
+            ),
+            false,
+        )]));
+
+        let float_arr = generate_random_array(512 * dimension as usize);
+        let vectors =
+            Arc::new(FixedSizeListArray::try_new_from_values(float_arr, dimension).unwrap());
+        let batches = vec![RecordBatch::try_new(schema.clone(), vec![vectors.clone()]).unwrap()];
+
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let reader = RecordBatchIterator::new(batches.into_iter().map(Ok), schema.clone());
+        let dataset = Dataset::write(reader, test_uri, None).await.unwrap();
+
+        let params = VectorIndexParams::with_hnsw_params(MetricType::L2, HNSWParams::default());
+        let dataset = dataset
+            .create_index(&["embeddings"], IndexType::Vector, None, &params, false)
+            .await
+            .unwrap();
+
+        let indices = dataset.load_indices().await.unwrap();
+        let actual = indices.first().unwrap().dataset_version;
+        let expected = dataset.manifest.version;
+        assert_eq!(actual, expected);
+
+        // Every vector is the nearest neighbor of itself.
+        for row in [0, 77, 300, 511] {
+            let key = vectors.value(row);
+            let mut scanner = dataset.scan();
+            scanner
+                .nearest("embeddings", key.as_primitive(), 5)
+                .unwrap()
+                .with_row_id();
+            let batches = scanner
+                .try_into_stream()
+                .await
+                .unwrap()
+                .try_collect::<Vec<_>>()
+                .await
+                .unwrap();
+            let row_ids = batches[0]
+                .column_by_name(ROW_ID)
+                .unwrap()
+                .as_primitive::<UInt64Type>();
+            assert_eq!(batches[0].num_rows(), 5);
+            assert_eq!(row_ids.value(0), row as u64);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_create_and_search_ivf_hnsw_index() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let dimension = 16;
+        let schema = Arc::new(ArrowSchema::new(vec![Field::new(
+            "embeddings",
+            DataType::FixedSizeList(
+                Arc::new(Field::new("item", DataType::Float32, true)),
+                dimension,
+            ),
+            false,
+        )]));
+        let make_batch = |num_rows: usize| {
+            let float_arr = generate_random_array(num_rows * dimension as usize);
+            let vectors = FixedSizeListArray::try_new_from_values(float_arr, dimension).unwrap();
+            RecordBatch::try_new(schema.clone(), vec![Arc::new(vectors)]).unwrap()
+        };
+        let batch = make_batch(1000);
+        let vectors = batch.column(0).as_fixed_size_list().clone();
+
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+        let dataset = Dataset::write(reader, test_uri, None).await.unwrap();
+
+        let params = VectorIndexParams::with_ivf_hnsw_params(
+            MetricType::L2,
+            IvfBuildParams::new(4),
+            HNSWParams::new(8, 50, 50),
+        );
+        let dataset = dataset
+            .create_index(&["embeddings"], IndexType::Vector, None, &params, false)
+            .await
+            .unwrap();
+
+        // Every vector is the nearest neighbor of itself, found in its own partition.
+        for row in [0, 77, 300, 999] {
+            let key = vectors.value(row);
+            let mut scanner = dataset.scan();
+            scanner
+                .nearest("embeddings", key.as_primitive(), 5)
+                .unwrap()
+                .nprobs(2)
+                .with_row_id();
+            let batches = scanner
+                .try_into_stream()
+                .await
+                .unwrap()
+                .try_collect::<Vec<_>>()
+                .await
+                .unwrap();
+            let row_ids = batches[0]
+                .column_by_name(ROW_ID)
+                .unwrap()
+                .as_primitive::<UInt64Type>();
+            assert_eq!(batches[0].num_rows(), 5);
+            assert_eq!(row_ids.value(0), row as u64);
+        }
+
+        // IVF_HNSW indices are not optimized, and do not stop the other indices
+        // from being optimized.
+        let old_index = dataset.load_indices().await.unwrap()[0].clone();
+        let reader = RecordBatchIterator::new(vec![Ok(make_batch(100))], schema.clone());
+        let write_params = WriteParams {
+            mode: WriteMode::Append,
+            ..Default::default()
+        };
+        let dataset = Dataset::write(reader, test_uri, Some(write_params))
+            .await
+            .unwrap();
+        let optimized = dataset.optimize_indices().await.unwrap();
+        assert_eq!(optimized.version().version, dataset.version().version);
+        assert_eq!(
+            optimized.load_indices().await.unwrap()[0].uuid,
+            old_index.uuid
+        );
+    }
+}
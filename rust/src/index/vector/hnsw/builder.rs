@@ -0,0 +1,428 @@
+// Copyright 2023 Lance Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::cmp::Reverse;
+use std::sync::Arc;
+
+use arrow_array::{cast::AsArray, types::UInt64Type, Float32Array, UInt32Array};
+use arrow_schema::DataType;
+use arrow_select::concat::concat_batches;
+use futures::{stream, StreamExt, TryStreamExt};
+use object_store::path::Path;
+use rand::{Rng, SeedableRng};
+
+use super::HNSWParams;
+use crate::arrow::{linalg::matrix::MatrixView, *};
+use crate::dataset::{Dataset, ROW_ID};
+use crate::index::vector::diskann::{greedy_search, row_vertex::RowVertexSerDe, RowVertex};
+use crate::index::vector::graph::{builder::GraphBuilder, write_graph, Graph, WriteGraphParams};
+use crate::index::vector::ivf::{sanity_check, train_ivf_model, Ivf, IvfBuildParams};
+use crate::index::vector::utils::maybe_sample_training_data;
+use crate::index::vector::MetricType;
+use crate::index::{pb, write_index_file};
+use crate::{Error, Result};
+
+/// The maximum level of the graph.
+const MAX_LEVEL: usize = 16;
+
+pub async fn build_hnsw_index(
+    dataset: &Dataset,
+    column: &str,
+    name: &str,
+    uuid: &str,
+    params: HNSWParams,
+) -> Result<()> {
+    let (row_ids, vectors) = load_vectors(dataset, column).await?;
+
+    let mut rng = rand::rngs::SmallRng::from_entropy();
+    let levels = (0..row_ids.len())
+        .map(|_| random_level(params.m, &mut rng))
+        .collect::<Vec<_>>();
+    let graphs = build_graphs(&row_ids, &vectors, &levels, &params).await?;
+
+    let index_dir = dataset.indices_dir().child(uuid);
+    let filenames = write_graphs(dataset, &index_dir, "hnsw", &graphs).await?;
+
+    let stages = vec![pb::VectorIndexStage {
+        stage: Some(pb::vector_index_stage::Stage::Hnsw(pb::Hnsw {
+            spec: 1,
+            filenames,
+            m: params.m as u32,
+            ef_construction: params.ef_construction as u32,
+            ef_search: params.ef_search as u32,
+            partition_num_levels: vec![],
+        })),
+    }];
+    write_index_file(
+        dataset,
+        column,
+        name,
+        uuid,
+        pb::IndexType::Vector,
+        pb::index::Implementation::VectorIndex(pb::VectorIndex {
+            spec_version: 1,
+            dimension: vectors.num_columns() as u32,
+            stages,
+            metric_type: pb::VectorMetricType::from(params.metric_type).into(),
+        }),
+    )
+    .await
+}
+
+/// Build an IVF_HNSW index, i.e., an HNSW graph in each IVF partition.
+///
+/// The vectors are assigned to the IVF partitions while scanning the dataset. The
+/// graphs of the partitions are built in parallel, and each graph is written and
+/// dropped once it is built, so only the graphs being built are kept in memory.
+pub async fn build_ivf_hnsw_index(
+    dataset: &Dataset,
+    column: &str,
+    name: &str,
+    uuid: &str,
+    metric_type: MetricType,
+    ivf_params: &IvfBuildParams,
+    params: HNSWParams,
+) -> Result<()> {
+    let field = sanity_check(dataset, column)?;
+    let DataType::FixedSizeList(_, dim) = field.data_type() else {
+        return Err(Error::Index {
+            message: format!(
+                "VectorIndex requires the column data type to be fixed size list of floats, got {}",
+                field.data_type()
+            ),
+        });
+    };
+    let dim = dim as usize;
+
+    // Train IVF partitions, with at most 256 vectors per centroid.
+    let mut ivf = if let Some(centroids) = &ivf_params.centroids {
+        if centroids.values().len() != ivf_params.num_partitions * dim {
+            return Err(Error::Index {
+                message: format!(
+                    "IVF centroids length mismatch: {} != {}",
+                    centroids.len(),
+                    ivf_params.num_partitions * dim,
+                ),
+            });
+        }
+        Ivf::new(centroids.clone())
+    } else {
+        let sample_size_hint = ivf_params.num_partitions * 256;
+        let training_data = maybe_sample_training_data(dataset, column, sample_size_hint).await?;
+        train_ivf_model(&training_data, metric_type, ivf_params).await?
+    };
+
+    // The row ids and the vector values of each partition.
+    let mut partitions = vec![(Vec::<u64>::new(), Vec::<f32>::new()); ivf.num_partitions()];
+    let mut stream = dataset
+        .scan()
+        .project(&[column])?
+        .with_row_id()
+        .try_into_stream()
+        .await?;
+    while let Some(batch) = stream.try_next().await? {
+        let row_ids = batch
+            .column_by_name(ROW_ID)
+            .ok_or_else(|| Error::Index {
+                message: "row_id not found".to_string(),
+            })?
+            .as_primitive::<UInt64Type>();
+        let vectors: MatrixView =
+            as_fixed_size_list_array(batch.column_by_name(column).ok_or_else(|| Error::Index {
+                message: format!("column {} not found", column),
+            })?)
+            .try_into()?;
+        let part_ids = ivf.compute_partitions(&vectors, metric_type)?;
+        for (i, part_id) in part_ids.values().iter().enumerate() {
+            let (part_row_ids, part_values) = &mut partitions[*part_id as usize];
+            part_row_ids.push(row_ids.value(i));
+            part_values.extend_from_slice(vectors.row(i).unwrap());
+        }
+    }
+    for (row_ids, _) in partitions.iter() {
+        // The partitions are in the graph files, not at an offset of the index file.
+        ivf.add_partition(0, row_ids.len() as u32);
+    }
+
+    let index_dir = dataset.indices_dir().child(uuid);
+    let params = HNSWParams {
+        metric_type,
+        ..params
+    };
+    let partition_filenames = stream::iter(partitions.into_iter().enumerate())
+        .map(|(part_id, (row_ids, values))| {
+            build_partition(
+                dataset,
+                &index_dir,
+                part_id,
+                row_ids,
+                MatrixView::new(Arc::new(Float32Array::from(values)), dim),
+                params.clone(),
+            )
+        })
+        .buffered(num_cpus::get())
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    let stages = vec![
+        pb::VectorIndexStage {
+            stage: Some(pb::vector_index_stage::Stage::Ivf(pb::Ivf::try_from(&ivf)?)),
+        },
+        pb::VectorIndexStage {
+            stage: Some(pb::vector_index_stage::Stage::Hnsw(pb::Hnsw {
+                spec: 1,
+                partition_num_levels: partition_filenames
+                    .iter()
+                    .map(|filenames| filenames.len() as u32)
+                    .collect(),
+                filenames: partition_filenames.into_iter().flatten().collect(),
+                m: params.m as u32,
+                ef_construction: params.ef_construction as u32,
+                ef_search: params.ef_search as u32,
+            })),
+        },
+    ];
+    write_index_file(
+        dataset,
+        column,
+        name,
+        uuid,
+        pb::IndexType::Vector,
+        pb::index::Implementation::VectorIndex(pb::VectorIndex {
+            spec_version: 1,
+            dimension: dim as u32,
+            stages,
+            metric_type: pb::VectorMetricType::from(metric_type).into(),
+        }),
+    )
+    .await
+}
+
+/// Build and write the HNSW graph of one IVF partition.
+///
+/// Returns the file names of the levels, or no file names if the partition is empty.
+async fn build_partition(
+    dataset: &Dataset,
+    index_dir: &Path,
+    part_id: usize,
+    row_ids: Vec<u64>,
+    vectors: MatrixView,
+    params: HNSWParams,
+) -> Result<Vec<String>> {
+    if row_ids.is_empty() {
+        return Ok(vec![]);
+    }
+    // Build the graphs on the thread pool, so the partitions are built in parallel.
+    let graphs = tokio::spawn(async move {
+        let mut rng = rand::rngs::SmallRng::from_entropy();
+        let levels = (0..row_ids.len())
+            .map(|_| random_level(params.m, &mut rng))
+            .collect::<Vec<_>>();
+        build_graphs(&row_ids, &vectors, &levels, &params).await
+    })
+    .await??;
+    write_graphs(dataset, index_dir, &format!("hnsw_part_{part_id}"), &graphs).await
+}
+
+/// Write the graphs of the levels as `{prefix}_level_{level}.lance` in the index directory.
+///
+/// Returns the file names of the levels, from the bottom level 0 to the top level.
+async fn write_graphs(
+    dataset: &Dataset,
+    index_dir: &Path,
+    prefix: &str,
+    graphs: &[GraphBuilder<RowVertex>],
+) -> Result<Vec<String>> {
+    let serde = RowVertexSerDe::new();
+    let mut filenames = vec![];
+    for (level, graph) in graphs.iter().enumerate() {
+        let filename = format!("{prefix}_level_{level}.lance");
+        write_graph(
+            graph,
+            dataset.object_store(),
+            &index_dir.child(filename.as_str()),
+            &WriteGraphParams::default(),
+            &serde,
+        )
+        .await?;
+        filenames.push(filename);
+    }
+    Ok(filenames)
+}
+
+/// Read the row ids and the vectors of the column.
+async fn load_vectors(dataset: &Dataset, column: &str) -> Result<(Vec<u64>, MatrixView)> {
+    let stream = dataset
+        .scan()
+        .project(&[column])?
+        .with_row_id()
+        .try_into_stream()
+        .await?;
+    let batches = stream.try_collect::<Vec<_>>().await?;
+    if batches.is_empty() {
+        return Err(Error::Index {
+            message: "HNSW: cannot build the index of an empty dataset".to_string(),
+        });
+    }
+    let batch = concat_batches(&batches[0].schema(), &batches)?;
+
+    let row_ids = batch
+        .column_by_name(ROW_ID)
+        .ok_or_else(|| Error::Index {
+            message: "row_id not found".to_string(),
+        })?
+        .as_primitive::<UInt64Type>();
+    let vectors =
+        as_fixed_size_list_array(batch.column_by_name(column).ok_or_else(|| Error::Index {
+            message: format!("column {} not found", column),
+        })?);
+    Ok((row_ids.values().to_vec(), vectors.try_into()?))
+}
+
+/// Draw the top level of a new vertex, from an exponentially decaying distribution.
+fn random_level(m: usize, rng: &mut impl Rng) -> usize {
+    let ml = 1.0 / (m.max(2) as f64).ln();
+    let level = -(1.0 - rng.gen::<f64>()).ln() * ml;
+    (level as usize).min(MAX_LEVEL)
+}
+
+/// Build the graph of each level, from the bottom level 0 to the top level.
+///
+/// The vertices are sorted by their top level, so the vertices of a level are
+/// the first vertices of the level below it, and share the same ids. The vertex 0
+/// on the top level is the entry point of the search.
+async fn build_graphs(
+    row_ids: &[u64],
+    vectors: &MatrixView,
+    levels: &[usize],
+    params: &HNSWParams,
+) -> Result<Vec<GraphBuilder<RowVertex>>> {
+    let mut order = (0..row_ids.len()).collect::<Vec<_>>();
+    order.sort_by_key(|&i| Reverse(levels[i]));
+    let levels = order.iter().map(|&i| levels[i]).collect::<Vec<_>>();
+    let top = levels[0];
+
+    let dim = vectors.num_columns();
+    let mut values = Vec::with_capacity(row_ids.len() * dim);
+    for &i in order.iter() {
+        values.extend_from_slice(vectors.row(i).unwrap());
+    }
+    let data = MatrixView::new(Arc::new(Float32Array::from(values)), dim);
+    let vertices = order
+        .iter()
+        .map(|&i| RowVertex::new(row_ids[i], None))
+        .collect::<Vec<_>>();
+
+    let mut graphs = (0..=top)
+        .map(|level| {
+            let num_vertices = levels.iter().take_while(|&&l| l >= level).count();
+            GraphBuilder::new(&vertices[..num_vertices], data.clone(), params.metric_type)
+        })
+        .collect::<Vec<_>>();
+
+    for (id, &level) in levels.iter().enumerate().skip(1) {
+        let query = data.row(id).unwrap();
+
+        // Greedily find the nearest vertex on the levels above the vertex.
+        let mut entry = 0;
+        for graph in graphs[level + 1..].iter().rev() {
+            let state = greedy_search(graph, entry, query, 1, 1, None).await?;
+            entry = state.candidates.values().next().copied().unwrap_or(entry);
+        }
+
+        for (lc, graph) in graphs[..=level].iter_mut().enumerate().rev() {
+            let state = greedy_search(
+                &*graph,
+                entry,
+                query,
+                params.m,
+                params.ef_construction.max(params.m),
+                None,
+            )
+            .await?;
+            entry = state.candidates.values().next().copied().unwrap_or(entry);
+
+            let neighbors = state
+                .candidates
+                .values()
+                .take(params.m)
+                .map(|&v| v as u32)
+                .collect::<Vec<_>>();
+            graph.set_neighbors(id, Arc::new(UInt32Array::from(neighbors.clone())));
+
+            // Connect the neighbors back, and shrink their neighbors to the nearest ones.
+            let max_degree = if lc == 0 { params.m * 2 } else { params.m };
+            for neighbor in neighbors {
+                let neighbor = neighbor as usize;
+                let mut neighbor_ids = graph.neighbors(neighbor).await?.values().to_vec();
+                neighbor_ids.push(id as u32);
+                if neighbor_ids.len() > max_degree {
+                    let mut with_distances = Vec::with_capacity(neighbor_ids.len());
+                    for &n in neighbor_ids.iter() {
+                        with_distances.push((graph.distance(neighbor, n as usize).await?, n));
+                    }
+                    with_distances.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+                    neighbor_ids = with_distances
+                        .into_iter()
+                        .take(max_degree)
+                        .map(|(_, n)| n)
+                        .collect();
+                }
+                graph.set_neighbors(neighbor, Arc::new(UInt32Array::from(neighbor_ids)));
+            }
+        }
+    }
+
+    Ok(graphs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_build_graphs() {
+        let n = 300;
+        let params = HNSWParams::new(8, 32, 32);
+        let mut rng = rand::thread_rng();
+        let levels = (0..n)
+            .map(|_| random_level(params.m, &mut rng))
+            .collect::<Vec<_>>();
+        let row_ids = (0..n as u64).collect::<Vec<_>>();
+        let vectors = MatrixView::random(n, 8);
+
+        let graphs = build_graphs(&row_ids, &vectors, &levels, &params)
+            .await
+            .unwrap();
+        assert_eq!(graphs.len(), levels.iter().max().unwrap() + 1);
+        assert_eq!(graphs[0].len(), n);
+        for (level, graph) in graphs.iter().enumerate() {
+            assert_eq!(graph.len(), levels.iter().filter(|&&l| l >= level).count());
+            let max_degree = if level == 0 { 16 } else { 8 };
+            for id in 0..graph.len() {
+                let neighbors = graph.neighbors(id).await.unwrap();
+                assert!(neighbors.len() <= max_degree);
+                assert!(neighbors
+                    .values()
+                    .iter()
+                    .all(|&v| (v as usize) < graph.len()));
+                if level > 0 {
+                    // The vertices of the upper levels are on the levels below.
+                    assert_eq!(graph.vertex(id).row_id, graphs[0].vertex(id).row_id);
+                }
+            }
+        }
+        assert!(!graphs[0].neighbors(n - 1).await.unwrap().is_empty());
+    }
+}
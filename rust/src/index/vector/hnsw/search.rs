@@ -0,0 +1,390 @@
+// Copyright 2023 Lance Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{any::Any, sync::Arc};
+
+use arrow_array::{
+    cast::as_struct_array, ArrayRef, Float32Array, RecordBatch, StructArray, UInt64Array,
+};
+use arrow_ord::sort::sort_to_indices;
+use arrow_schema::{DataType, Field, Schema};
+use arrow_select::{concat::concat_batches, take::take};
+use async_trait::async_trait;
+use futures::{stream, StreamExt, TryStreamExt};
+use object_store::path::Path;
+use roaring::RoaringTreemap;
+
+use crate::{
+    dataset::{Dataset, ROW_ID},
+    index::{
+        pb,
+        vector::{
+            diskann::{greedy_search, row_vertex::RowVertexSerDe, RowVertex},
+            graph::{GraphReadParams, PersistedGraph},
+            ivf::Ivf,
+            MetricType, Query, VectorIndex, SCORE_COL,
+        },
+        Index,
+    },
+    io::{deletion::LruDeletionVectorStore, object_reader::ObjectReader},
+    Error, Result,
+};
+
+pub struct HNSWIndex {
+    /// The graphs of the levels, from the bottom level 0 to the top level.
+    graphs: Vec<PersistedGraph<RowVertex>>,
+
+    /// The row ids of the vertices, indexed by the vertex ids.
+    row_ids: Vec<u64>,
+
+    /// The size of the dynamic candidate list to search the bottom level.
+    ef_search: usize,
+
+    deletion_cache: Arc<LruDeletionVectorStore>,
+}
+
+impl std::fmt::Debug for HNSWIndex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "HNSWIndex(levels={}, ef_search={})",
+            self.graphs.len(),
+            self.ef_search
+        )
+    }
+}
+
+impl HNSWIndex {
+    /// Open the HNSW index, whose graph files are in `index_dir`.
+    pub async fn try_new(
+        dataset: Arc<Dataset>,
+        index_column: &str,
+        index_dir: &Path,
+        proto: &pb::Hnsw,
+        metric_type: MetricType,
+        deletion_cache: Arc<LruDeletionVectorStore>,
+    ) -> Result<Self> {
+        if proto.filenames.is_empty() {
+            return Err(Error::Index {
+                message: "HNSW index must have at least one level".to_string(),
+            });
+        }
+        let serde = Arc::new(RowVertexSerDe::new());
+        let mut graphs = Vec::with_capacity(proto.filenames.len());
+        for filename in proto.filenames.iter() {
+            let params = GraphReadParams {
+                metric_type,
+                ..Default::default()
+            };
+            let graph = PersistedGraph::try_new(
+                dataset.clone(),
+                index_column,
+                &index_dir.child(filename.as_str()),
+                params,
+                serde.clone(),
+            )
+            .await?;
+            graphs.push(graph);
+        }
+        let row_ids = graphs[0]
+            .vertices()
+            .await?
+            .iter()
+            .map(|v| v.row_id)
+            .collect();
+
+        Ok(Self {
+            graphs,
+            row_ids,
+            ef_search: proto.ef_search as usize,
+            deletion_cache,
+        })
+    }
+
+    /// Search from the entry point on the top level down to the bottom level.
+    ///
+    /// Returns the ids of the nearest vertices on the bottom level, with their
+    /// distances to the query, sorted by distance.
+    async fn search_levels(
+        &self,
+        key: &[f32],
+        k: usize,
+        allow_list: Option<Arc<RoaringTreemap>>,
+    ) -> Result<Vec<(f32, usize)>> {
+        let mut entry = 0;
+        for graph in self.graphs[1..].iter().rev() {
+            let state = greedy_search(graph, entry, key, 1, 1, None).await?;
+            entry = state.candidates.values().next().copied().unwrap_or(entry);
+        }
+        let state = greedy_search(
+            &self.graphs[0],
+            entry,
+            key,
+            k,
+            self.ef_search.max(k),
+            allow_list,
+        )
+        .await?;
+        Ok(state
+            .candidates
+            .into_iter()
+            .map(|(distance, id)| (distance.0, id))
+            .collect())
+    }
+}
+
+impl Index for HNSWIndex {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[async_trait]
+impl VectorIndex for HNSWIndex {
+    async fn search(&self, query: &Query) -> Result<RecordBatch> {
+        // The graph is searched by vertex ids, so translate the allowed row ids.
+        let allow_list = query.allow_list.as_ref().map(|allow_list| {
+            Arc::new(
+                self.row_ids
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, row_id)| allow_list.contains(**row_id))
+                    .map(|(id, _)| id as u64)
+                    .collect::<RoaringTreemap>(),
+            )
+        });
+        let candidates = self
+            .search_levels(query.key.values(), query.k, allow_list)
+            .await?;
+
+        let mut results = Vec::with_capacity(query.k);
+        for (distance, id) in candidates {
+            if results.len() == query.k {
+                break;
+            }
+            let row_id = self.row_ids[id];
+            if !self.deletion_cache.as_ref().is_deleted(row_id).await? {
+                results.push((row_id, distance));
+            }
+        }
+
+        results_batch(&results)
+    }
+
+    fn is_loadable(&self) -> bool {
+        false
+    }
+
+    async fn load(
+        &self,
+        _reader: &dyn ObjectReader,
+        _offset: usize,
+        _length: usize,
+    ) -> Result<Arc<dyn VectorIndex>> {
+        Err(Error::Index {
+            message: "HNSWIndex is not loadable".to_string(),
+        })
+    }
+}
+
+/// The batch of the row ids and the distances of the search results.
+fn results_batch(results: &[(u64, f32)]) -> Result<RecordBatch> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new(ROW_ID, DataType::UInt64, false),
+        Field::new(SCORE_COL, DataType::Float32, false),
+    ]));
+    let row_ids: UInt64Array = results.iter().map(|(row_id, _)| *row_id).collect();
+    let scores: Float32Array = results.iter().map(|(_, distance)| *distance).collect();
+    Ok(RecordBatch::try_new(
+        schema,
+        vec![Arc::new(row_ids) as ArrayRef, Arc::new(scores) as ArrayRef],
+    )?)
+}
+
+/// IVF_HNSW index: an HNSW graph in each IVF partition.
+///
+/// The graphs of a partition are opened when the partition is first searched, and
+/// cached in the session.
+pub struct IvfHNSWIndex {
+    uuid: String,
+
+    ivf: Ivf,
+
+    /// The HNSW stage, with the graph files of all the partitions.
+    proto: pb::Hnsw,
+
+    /// Range of the graph files of each partition in `proto.filenames`.
+    partitions: Vec<std::ops::Range<usize>>,
+
+    dataset: Arc<Dataset>,
+
+    column: String,
+
+    metric_type: MetricType,
+
+    deletion_cache: Arc<LruDeletionVectorStore>,
+}
+
+impl std::fmt::Debug for IvfHNSWIndex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Ivf({}) -> HNSW(ef_search={})",
+            self.metric_type, self.proto.ef_search
+        )
+    }
+}
+
+impl IvfHNSWIndex {
+    pub fn try_new(
+        dataset: Arc<Dataset>,
+        index_column: &str,
+        uuid: &str,
+        ivf: Ivf,
+        proto: &pb::Hnsw,
+        metric_type: MetricType,
+        deletion_cache: Arc<LruDeletionVectorStore>,
+    ) -> Result<Self> {
+        let num_files = proto.partition_num_levels.iter().sum::<u32>() as usize;
+        if proto.partition_num_levels.len() != ivf.num_partitions()
+            || num_files != proto.filenames.len()
+        {
+            return Err(Error::Index {
+                message: format!(
+                    "IVF_HNSW index: {} partitions with {} graph files, got {} partitions and {} files",
+                    proto.partition_num_levels.len(),
+                    num_files,
+                    ivf.num_partitions(),
+                    proto.filenames.len()
+                ),
+            });
+        }
+        let mut start = 0;
+        let partitions = proto
+            .partition_num_levels
+            .iter()
+            .map(|&num_levels| {
+                let range = start..start + num_levels as usize;
+                start = range.end;
+                range
+            })
+            .collect();
+        Ok(Self {
+            uuid: uuid.to_owned(),
+            ivf,
+            proto: proto.clone(),
+            partitions,
+            dataset,
+            column: index_column.to_owned(),
+            metric_type,
+            deletion_cache,
+        })
+    }
+
+    async fn search_in_partition(&self, partition_id: usize, query: &Query) -> Result<RecordBatch> {
+        let files = self.partitions[partition_id].clone();
+        if files.is_empty() {
+            return results_batch(&[]);
+        }
+        let cache_key = format!("{}-hnsw-{}", self.uuid, partition_id);
+        let session = self.dataset.session.clone();
+        let part_index = if let Some(part_idx) = session.index_cache.get(&cache_key) {
+            part_idx
+        } else {
+            let proto = pb::Hnsw {
+                filenames: self.proto.filenames[files].to_vec(),
+                partition_num_levels: vec![],
+                ..self.proto.clone()
+            };
+            let idx: Arc<dyn VectorIndex> = Arc::new(
+                HNSWIndex::try_new(
+                    self.dataset.clone(),
+                    &self.column,
+                    &self.dataset.indices_dir().child(self.uuid.as_str()),
+                    &proto,
+                    self.metric_type,
+                    self.deletion_cache.clone(),
+                )
+                .await?,
+            );
+            session.index_cache.insert(&cache_key, idx.clone());
+            idx
+        };
+        // The graphs hold the original vectors, so the query is not a residual.
+        part_index.search(query).await
+    }
+}
+
+impl Index for IvfHNSWIndex {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[async_trait]
+impl VectorIndex for IvfHNSWIndex {
+    async fn search(&self, query: &Query) -> Result<RecordBatch> {
+        // With an allow list, the partitions are probed `nprobes` at a time, in the
+        // order of their distances, until enough allowed rows are found.
+        let nprobes = if query.allow_list.is_some() {
+            self.ivf.num_partitions()
+        } else {
+            query.nprobes
+        };
+        let partition_ids = self
+            .ivf
+            .find_partitions(&query.key, nprobes, self.metric_type)?;
+        let mut batches = vec![];
+        let mut num_rows = 0;
+        for part_ids in partition_ids.values().chunks(query.nprobes.max(1)) {
+            let part_batches = stream::iter(part_ids.to_vec())
+                .map(|part_id| self.search_in_partition(part_id as usize, query))
+                .buffer_unordered(num_cpus::get())
+                .try_collect::<Vec<_>>()
+                .await?;
+            num_rows += part_batches.iter().map(|b| b.num_rows()).sum::<usize>();
+            batches.extend(part_batches);
+            if query.allow_list.is_none() || num_rows >= query.k {
+                break;
+            }
+        }
+        if batches.is_empty() {
+            return results_batch(&[]);
+        }
+        let batch = concat_batches(&batches[0].schema(), &batches)?;
+
+        let scores = batch.column_by_name(SCORE_COL).ok_or_else(|| Error::IO {
+            message: format!("score column does not exist in batch: {}", batch.schema()),
+        })?;
+        let selection = sort_to_indices(scores, None, Some(query.k))?;
+        let struct_arr = StructArray::from(batch);
+        let taken_scores = take(&struct_arr, &selection, None)?;
+        Ok(as_struct_array(&taken_scores).into())
+    }
+
+    fn is_loadable(&self) -> bool {
+        false
+    }
+
+    async fn load(
+        &self,
+        _reader: &dyn ObjectReader,
+        _offset: usize,
+        _length: usize,
+    ) -> Result<Arc<dyn VectorIndex>> {
+        Err(Error::Index {
+            message: "IvfHNSWIndex is not loadable".to_string(),
+        })
+    }
+}
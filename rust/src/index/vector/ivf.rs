@@ -16,7 +16,7 @@
 
 use std::{any::Any, sync::Arc};
 
-use arrow::datatypes::Float32Type;
+use arrow::datatypes::{Float32Type, UInt8Type};
 use arrow_arith::arithmetic::subtract_dyn;
 use arrow_array::{
     builder::{Float32Builder, UInt32Builder},
@@ -36,24 +36,29 @@ use log::info;
 use rand::{rngs::SmallRng, SeedableRng};
 
 #[cfg(feature = "opq")]
-use super::opq::train_opq;
+use super::opq::{train_opq, OptimizedProductQuantizer};
 use super::{
     pq::{train_pq, PQBuildParams, ProductQuantizer},
+    sq::{train_sq, SQBuildParams, ScalarQuantizer},
     utils::maybe_sample_training_data,
     MetricType, Query, VectorIndex, INDEX_FILE_NAME,
 };
 use crate::{
     arrow::{linalg::matrix::MatrixView, *},
-    dataset::{Dataset, ROW_ID},
+    dataset::{scanner::Scanner, Dataset, ROW_ID},
     datatypes::Field,
     index::{pb, vector::Transformer, Index},
 };
-use crate::{io::object_reader::ObjectReader, session::Session};
+use crate::{
+    format::Fragment,
+    io::object_reader::{read_fixed_stride_array, ObjectReader},
+    session::Session,
+};
 use crate::{Error, Result};
 
 const PARTITION_ID_COLUMN: &str = "__ivf_part_id";
 const RESIDUAL_COLUMN: &str = "__residual_vector";
-const PQ_CODE_COLUMN: &str = "__pq_code";
+const CODE_COLUMN: &str = "__code";
 
 /// IVF Index.
 pub struct IVFIndex {
@@ -137,16 +142,34 @@ impl Index for IVFIndex {
 #[async_trait]
 impl VectorIndex for IVFIndex {
     async fn search(&self, query: &Query) -> Result<RecordBatch> {
-        let partition_ids =
-            self.ivf
-                .find_partitions(&query.key, query.nprobes, self.metric_type)?;
-        assert!(partition_ids.len() <= query.nprobes);
-        let part_ids = partition_ids.values().to_vec();
-        let batches = stream::iter(part_ids)
-            .map(|part_id| async move { self.search_in_partition(part_id as usize, query).await })
-            .buffer_unordered(num_cpus::get())
-            .try_collect::<Vec<_>>()
-            .await?;
+        // With an allow list, the partitions are probed `nprobes` at a time, in the
+        // order of their distances, until enough allowed rows are found.
+        let nprobes = if query.allow_list.is_some() {
+            self.ivf.num_partitions()
+        } else {
+            query.nprobes
+        };
+        let partition_ids = self
+            .ivf
+            .find_partitions(&query.key, nprobes, self.metric_type)?;
+        let limit = query.k * query.refine_factor.unwrap_or(1) as usize;
+        let mut batches = vec![];
+        let mut num_rows = 0;
+        for part_ids in partition_ids.values().chunks(query.nprobes.max(1)) {
+            let part_batches =
+                stream::iter(part_ids.to_vec())
+                    .map(|part_id| async move {
+                        self.search_in_partition(part_id as usize, query).await
+                    })
+                    .buffer_unordered(num_cpus::get())
+                    .try_collect::<Vec<_>>()
+                    .await?;
+            num_rows += part_batches.iter().map(|b| b.num_rows()).sum::<usize>();
+            batches.extend(part_batches);
+            if query.allow_list.is_none() || num_rows >= limit {
+                break;
+            }
+        }
         let batch = concat_batches(&batches[0].schema(), &batches)?;
 
         let score_col = batch.column_by_name("score").ok_or_else(|| Error::IO {
@@ -154,7 +177,6 @@ impl VectorIndex for IVFIndex {
         })?;
 
         // TODO: Use a heap sort to get the top-k.
-        let limit = query.k * query.refine_factor.unwrap_or(1) as usize;
         let selection = sort_to_indices(score_col, None, Some(limit))?;
         let struct_arr = StructArray::from(batch);
         let taken_scores = take(&struct_arr, &selection, None)?;
@@ -177,9 +199,57 @@ impl VectorIndex for IVFIndex {
     }
 }
 
+/// The quantizer to encode the residual vectors in the IVF partitions.
+#[derive(Debug)]
+pub(crate) enum Quantizer {
+    Product(ProductQuantizer),
+
+    Scalar(ScalarQuantizer),
+}
+
+impl Quantizer {
+    /// Vector dimension.
+    fn dimension(&self) -> usize {
+        match self {
+            Self::Product(pq) => pq.dimension,
+            Self::Scalar(sq) => sq.dimension,
+        }
+    }
+
+    /// The number of bytes of the code of one vector.
+    fn code_length(&self) -> usize {
+        match self {
+            Self::Product(pq) => pq.code_length(),
+            Self::Scalar(sq) => sq.dimension,
+        }
+    }
+
+    /// Transform the vectors to the code array.
+    async fn transform(
+        &self,
+        data: &MatrixView,
+        metric_type: MetricType,
+    ) -> Result<FixedSizeListArray> {
+        match self {
+            Self::Product(pq) => pq.transform(data, metric_type).await,
+            Self::Scalar(sq) => sq.transform(data),
+        }
+    }
+}
+
+impl From<&Quantizer> for pb::VectorIndexStage {
+    fn from(quantizer: &Quantizer) -> Self {
+        let stage = match quantizer {
+            Quantizer::Product(pq) => pb::vector_index_stage::Stage::Pq(pq.into()),
+            Quantizer::Scalar(sq) => pb::vector_index_stage::Stage::Sq(sq.into()),
+        };
+        Self { stage: Some(stage) }
+    }
+}
+
 /// Ivf PQ index metadata.
 ///
-/// It contains the on-disk data for a IVF PQ index.
+/// It contains the on-disk data for a IVF PQ (or IVF SQ) index.
 #[derive(Debug)]
 pub struct IvfPQIndexMetadata {
     /// Index name
@@ -200,8 +270,8 @@ pub struct IvfPQIndexMetadata {
     /// IVF model
     pub(crate) ivf: Ivf,
 
-    /// Product Quantizer
-    pub(crate) pq: Arc<ProductQuantizer>,
+    /// Product (or scalar) quantizer
+    pub(crate) quantizer: Quantizer,
 
     /// Transforms to be applied before search.
     transforms: Vec<pb::Transform>,
@@ -228,9 +298,7 @@ impl TryFrom<&IvfPQIndexMetadata> for pb::Index {
                     &idx.ivf,
                 )?)),
             },
-            pb::VectorIndexStage {
-                stage: Some(pb::vector_index_stage::Stage::Pq(idx.pq.as_ref().into())),
-            },
+            (&idx.quantizer).into(),
         ]);
 
         Ok(Self {
@@ -269,7 +337,7 @@ pub(crate) struct Ivf {
 }
 
 impl Ivf {
-    fn new(centroids: Arc<FixedSizeListArray>) -> Self {
+    pub(crate) fn new(centroids: Arc<FixedSizeListArray>) -> Self {
         Self {
             centroids,
             offsets: vec![],
@@ -283,12 +351,12 @@ impl Ivf {
     }
 
     /// Number of IVF partitions.
-    fn num_partitions(&self) -> usize {
+    pub(crate) fn num_partitions(&self) -> usize {
         self.centroids.len()
     }
 
     /// Use the query vector to find `nprobes` closest partitions.
-    fn find_partitions(
+    pub(crate) fn find_partitions(
         &self,
         query: &Float32Array,
         nprobes: usize,
@@ -315,11 +383,35 @@ impl Ivf {
     }
 
     /// Add the offset and length of one partition.
-    fn add_partition(&mut self, offset: usize, len: u32) {
+    pub(crate) fn add_partition(&mut self, offset: usize, len: u32) {
         self.offsets.push(offset);
         self.lengths.push(len);
     }
 
+    /// Compute the partition ID of each vector.
+    pub(crate) fn compute_partitions(
+        &self,
+        data: &MatrixView,
+        metric_type: MetricType,
+    ) -> Result<UInt32Array> {
+        let dim = data.num_columns();
+        let dist_func = metric_type.batch_func();
+        let centroids: MatrixView = self.centroids.as_ref().try_into()?;
+        let part_ids = (0..data.num_rows())
+            .map(|i| {
+                let vector = data.row(i).unwrap();
+                let distances = dist_func(vector, centroids.data().values(), dim);
+                argmin(distances.as_ref()).ok_or_else(|| Error::Index {
+                    message: format!(
+                        "Ivf::compute_partitions: failed to find the minimum of {:?}",
+                        distances
+                    ),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(UInt32Array::from(part_ids))
+    }
+
     /// Compute the partition ID and residual vectors.
     ///
     /// Parameters
@@ -418,7 +510,7 @@ impl TryFrom<&pb::Ivf> for Ivf {
     }
 }
 
-fn sanity_check<'a>(dataset: &'a Dataset, column: &str) -> Result<&'a Field> {
+pub(crate) fn sanity_check<'a>(dataset: &'a Dataset, column: &str) -> Result<&'a Field> {
     let Some(field) = dataset.schema().field(column) else {
         return Err(Error::IO{message:format!(
             "Building index: column {} does not exist in dataset: {:?}",
@@ -558,6 +650,8 @@ pub async fn build_ivf_pq_index(
         metric_type,
     );
 
+    pq_params.validate()?;
+
     let field = sanity_check(dataset, column)?;
     let dim = if let DataType::FixedSizeList(_, d) = field.data_type() {
         d as usize
@@ -622,7 +716,7 @@ pub async fn build_ivf_pq_index(
             pq_params.num_bits as u32,
             dim,
             codebook.clone(),
-        )
+        )?
     } else {
         // Compute the residual vector for training PQ
         let ivf_centroids = ivf_model.centroids.as_ref().try_into()?;
@@ -637,80 +731,300 @@ pub async fn build_ivf_pq_index(
     scanner.project(&[column])?;
     scanner.with_row_id();
 
-    let ivf = &ivf_model;
-    let pq_ref = &pq;
+    let quantizer = Quantizer::Product(pq);
     let metric_type = pq_params.metric_type;
-    let transform_ref = &transforms;
+    let batches = scan_and_encode(
+        scanner,
+        column,
+        &ivf_model,
+        &quantizer,
+        metric_type,
+        &transforms,
+    )
+    .await?;
 
-    // Scan the dataset and compute residual, pq with with partition ID.
-    // For now, it loads all data into memory.
-    let batches = scanner
-        .try_into_stream()
-        .await?
-        .map(|b| async move {
-            let batch = b?;
-            let arr = batch.column_by_name(column).ok_or_else(|| Error::IO {
-                message: format!("Dataset does not have column {column}"),
-            })?;
-            let mut vectors: MatrixView = as_fixed_size_list_array(arr).try_into()?;
-
-            // Transform the vectors if pre-transforms are used.
-            for transform in transform_ref.iter() {
-                vectors = transform.transform(&vectors).await?;
-            }
+    write_index_file(
+        dataset,
+        column,
+        index_name,
+        uuid,
+        &transforms,
+        ivf_model,
+        quantizer,
+        metric_type,
+        &batches,
+    )
+    .await
+}
 
-            let i = ivf.clone();
-            let part_id_and_residual = tokio::task::spawn_blocking(move || {
-                i.compute_partition_and_residual(&vectors, metric_type)
-            })
-            .await??;
+/// Build IVF(SQ) index
+pub async fn build_ivf_sq_index(
+    dataset: &Dataset,
+    column: &str,
+    index_name: &str,
+    uuid: &str,
+    metric_type: MetricType,
+    ivf_params: &IvfBuildParams,
+    sq_params: &SQBuildParams,
+) -> Result<()> {
+    info!(
+        "Building vector index: IVF{},SQ{}, metric={}",
+        ivf_params.num_partitions, sq_params.num_bits, metric_type,
+    );
 
-            let residual_col = part_id_and_residual
-                .column_by_name(RESIDUAL_COLUMN)
-                .unwrap();
-            let residual_data = as_fixed_size_list_array(&residual_col);
-            let pq_code = pq_ref
-                .transform(&residual_data.try_into()?, metric_type)
-                .await?;
+    let field = sanity_check(dataset, column)?;
+    let dim = if let DataType::FixedSizeList(_, d) = field.data_type() {
+        d as usize
+    } else {
+        return Err(Error::Index {
+            message: format!(
+                "VectorIndex requires the column data type to be fixed size list of floats, got {}",
+                field.data_type()
+            ),
+        });
+    };
 
-            let row_ids = batch
-                .column_by_name(ROW_ID)
-                .expect("Expect row id column")
-                .clone();
-            let part_ids = part_id_and_residual
-                .column_by_name(PARTITION_ID_COLUMN)
-                .expect("Expect partition ids column")
-                .clone();
-
-            let schema = Arc::new(ArrowSchema::new(vec![
-                ArrowField::new(ROW_ID, DataType::UInt64, false),
-                ArrowField::new(PARTITION_ID_COLUMN, DataType::UInt32, false),
-                ArrowField::new(
-                    PQ_CODE_COLUMN,
-                    DataType::FixedSizeList(
-                        Arc::new(ArrowField::new("item", DataType::UInt8, true)),
-                        pq_params.num_sub_vectors as i32,
-                    ),
-                    false,
+    // Maximum to train 256 vectors per centroids, see Faiss.
+    let sample_size_hint = ivf_params.num_partitions * 256;
+    let training_data = maybe_sample_training_data(dataset, column, sample_size_hint).await?;
+
+    // Train IVF partitions.
+    let ivf_model = if let Some(centroids) = &ivf_params.centroids {
+        if centroids.values().len() != ivf_params.num_partitions * dim {
+            return Err(Error::Index {
+                message: format!(
+                    "IVF centroids length mismatch: {} != {}",
+                    centroids.len(),
+                    ivf_params.num_partitions * dim,
                 ),
-            ]));
-            Ok::<RecordBatch, Error>(RecordBatch::try_new(
-                schema,
-                vec![row_ids, part_ids, Arc::new(pq_code)],
-            )?)
+            });
+        }
+        Ivf::new(centroids.clone())
+    } else {
+        train_ivf_model(&training_data, metric_type, ivf_params).await?
+    };
+
+    // The scalar quantizer encodes the residual vectors, same as PQ.
+    let ivf_centroids = ivf_model.centroids.as_ref().try_into()?;
+    let residual_data = compute_residual_matrix(&training_data, &ivf_centroids, metric_type)?;
+    let sq = train_sq(
+        &MatrixView::new(residual_data, training_data.num_columns()),
+        sq_params,
+    )?;
+
+    let mut scanner = dataset.scan();
+    scanner.project(&[column])?;
+    scanner.with_row_id();
+
+    let quantizer = Quantizer::Scalar(sq);
+    let batches =
+        scan_and_encode(scanner, column, &ivf_model, &quantizer, metric_type, &[]).await?;
+
+    write_index_file(
+        dataset,
+        column,
+        index_name,
+        uuid,
+        &[],
+        ivf_model,
+        quantizer,
+        metric_type,
+        &batches,
+    )
+    .await
+}
+
+/// Scan the dataset, and compute the partition ids and the codes of the residuals
+/// of the vectors.
+///
+/// For now, it loads all data into memory.
+async fn scan_and_encode(
+    scanner: Scanner,
+    column: &str,
+    ivf: &Ivf,
+    quantizer: &Quantizer,
+    metric_type: MetricType,
+    transforms: &[Box<dyn Transformer>],
+) -> Result<Vec<RecordBatch>> {
+    scanner
+        .try_into_stream()
+        .await?
+        .map(|b| async move {
+            partition_and_encode(b?, column, ivf, quantizer, metric_type, transforms).await
         })
         .buffered(num_cpus::get())
         .try_collect::<Vec<_>>()
+        .await
+}
+
+/// Assign the vectors of the `column` of the batch to the IVF partitions, and
+/// encode their residuals with the quantizer.
+///
+/// Returns a `RecordBatch` with schema `{_rowid: u64, __ivf_part_id: u32, __code: FixedSizeList}`
+async fn partition_and_encode(
+    batch: RecordBatch,
+    column: &str,
+    ivf: &Ivf,
+    quantizer: &Quantizer,
+    metric_type: MetricType,
+    transforms: &[Box<dyn Transformer>],
+) -> Result<RecordBatch> {
+    let arr = batch.column_by_name(column).ok_or_else(|| Error::IO {
+        message: format!("Dataset does not have column {column}"),
+    })?;
+    let mut vectors: MatrixView = as_fixed_size_list_array(arr).try_into()?;
+
+    // Transform the vectors if pre-transforms are used.
+    for transform in transforms.iter() {
+        vectors = transform.transform(&vectors).await?;
+    }
+
+    let i = ivf.clone();
+    let part_id_and_residual = tokio::task::spawn_blocking(move || {
+        i.compute_partition_and_residual(&vectors, metric_type)
+    })
+    .await??;
+
+    let residual_col = part_id_and_residual
+        .column_by_name(RESIDUAL_COLUMN)
+        .unwrap();
+    let residual_data = as_fixed_size_list_array(&residual_col);
+    let code = quantizer
+        .transform(&residual_data.try_into()?, metric_type)
         .await?;
 
+    let row_ids = batch
+        .column_by_name(ROW_ID)
+        .expect("Expect row id column")
+        .clone();
+    let part_ids = part_id_and_residual
+        .column_by_name(PARTITION_ID_COLUMN)
+        .expect("Expect partition ids column")
+        .clone();
+    code_batch(row_ids, part_ids, code)
+}
+
+/// Make the `RecordBatch` of the codes of some rows and their partition ids.
+fn code_batch(
+    row_ids: ArrayRef,
+    part_ids: ArrayRef,
+    code: FixedSizeListArray,
+) -> Result<RecordBatch> {
+    let schema = Arc::new(ArrowSchema::new(vec![
+        ArrowField::new(ROW_ID, DataType::UInt64, false),
+        ArrowField::new(PARTITION_ID_COLUMN, DataType::UInt32, false),
+        ArrowField::new(CODE_COLUMN, code.data_type().clone(), false),
+    ]));
+    Ok(RecordBatch::try_new(
+        schema,
+        vec![row_ids, part_ids, Arc::new(code)],
+    )?)
+}
+
+/// Add the rows of the `fragments` to the IVF_PQ (or IVF_SQ) index read from
+/// `reader`, and write the updated index as the index `new_uuid`.
+///
+/// The new rows are assigned to the existing IVF partitions, and encoded with the
+/// existing quantizer, i.e., neither of them is retrained.
+pub(crate) async fn optimize_ivf_index(
+    dataset: &Dataset,
+    column: &str,
+    index_name: &str,
+    new_uuid: &str,
+    reader: &dyn ObjectReader,
+    vec_idx: &pb::VectorIndex,
+    fragments: Vec<Fragment>,
+) -> Result<()> {
+    let metric_type: MetricType = pb::VectorMetricType::from_i32(vec_idx.metric_type)
+        .ok_or(Error::Index {
+            message: format!("Unsupported metric type value: {}", vec_idx.metric_type),
+        })?
+        .into();
+
+    #[cfg(feature = "opq")]
+    let mut transforms: Vec<Box<dyn Transformer>> = vec![];
+    #[cfg(not(feature = "opq"))]
+    let transforms: Vec<Box<dyn Transformer>> = vec![];
+    let mut ivf = None;
+    let mut quantizer = None;
+    for stage in vec_idx.stages.iter() {
+        match stage.stage.as_ref() {
+            #[allow(unused_variables)]
+            Some(pb::vector_index_stage::Stage::Transform(tf)) => {
+                #[cfg(not(feature = "opq"))]
+                return Err(Error::Index {
+                    message: "Feature 'opq' is not installed.".to_string(),
+                });
+                #[cfg(feature = "opq")]
+                match tf.r#type() {
+                    pb::TransformType::Opq => {
+                        let shape = tf.shape.iter().map(|s| *s as usize).collect::<Vec<_>>();
+                        let opq =
+                            OptimizedProductQuantizer::load(reader, tf.position as usize, &shape)
+                                .await?;
+                        transforms.push(Box::new(opq));
+                    }
+                }
+            }
+            Some(pb::vector_index_stage::Stage::Ivf(ivf_pb)) => {
+                ivf = Some(Ivf::try_from(ivf_pb)?);
+            }
+            Some(pb::vector_index_stage::Stage::Pq(pq_pb)) => {
+                quantizer = Some(Quantizer::Product(ProductQuantizer::try_from(pq_pb)?));
+            }
+            Some(pb::vector_index_stage::Stage::Sq(sq_pb)) => {
+                quantizer = Some(Quantizer::Scalar(ScalarQuantizer::from(sq_pb)));
+            }
+            _ => {}
+        }
+    }
+    let (Some(ivf), Some(quantizer)) = (ivf, quantizer) else {
+        return Err(Error::Index {
+            message: format!("Index {index_name} is not an IVF_PQ or IVF_SQ index"),
+        });
+    };
+
+    // The codes and row ids of the partitions of the existing index.
+    let code_width = quantizer.code_length();
+    let mut batches = vec![];
+    for part_id in 0..ivf.num_partitions() {
+        let length = ivf.lengths[part_id] as usize;
+        if length == 0 {
+            continue;
+        }
+        let offset = ivf.offsets[part_id];
+        let code_length = code_width * length;
+        let code =
+            read_fixed_stride_array(reader, &DataType::UInt8, offset, code_length, ..).await?;
+        let row_ids =
+            read_fixed_stride_array(reader, &DataType::UInt64, offset + code_length, length, ..)
+                .await?;
+        let part_ids = Arc::new(UInt32Array::from(vec![part_id as u32; length]));
+        let code = FixedSizeListArray::try_new_from_values(
+            as_primitive_array::<UInt8Type>(code.as_ref()).clone(),
+            code_width as i32,
+        )?;
+        batches.push(code_batch(row_ids, part_ids, code)?);
+    }
+
+    // Encode the new rows.
+    let mut scanner = dataset.scan();
+    scanner.with_fragments(fragments);
+    scanner.project(&[column])?;
+    scanner.with_row_id();
+    let ivf = Ivf::new(ivf.centroids);
+    let new_batches =
+        scan_and_encode(scanner, column, &ivf, &quantizer, metric_type, &transforms).await?;
+    batches.extend(new_batches);
+
     write_index_file(
         dataset,
         column,
         index_name,
-        uuid,
+        new_uuid,
         &transforms,
-        ivf_model,
-        pq,
+        ivf,
+        quantizer,
         metric_type,
         &batches,
     )
@@ -727,7 +1041,7 @@ async fn write_index_file(
     uuid: &str,
     transformers: &[Box<dyn Transformer>],
     mut ivf: Ivf,
-    pq: ProductQuantizer,
+    quantizer: Quantizer,
     metric_type: MetricType,
     batches: &[RecordBatch],
 ) -> Result<()> {
@@ -751,8 +1065,8 @@ async fn write_index_file(
         ivf.add_partition(writer.tell(), parted_batch.num_rows() as u32);
         if parted_batch.num_rows() > 0 {
             // Write one partition.
-            let pq_code = &parted_batch[PQ_CODE_COLUMN];
-            writer.write_plain_encoded_array(pq_code.as_ref()).await?;
+            let code = &parted_batch[CODE_COLUMN];
+            writer.write_plain_encoded_array(code.as_ref()).await?;
             let row_ids = &parted_batch[ROW_ID];
             writer.write_plain_encoded_array(row_ids.as_ref()).await?;
         }
@@ -768,11 +1082,11 @@ async fn write_index_file(
     let metadata = IvfPQIndexMetadata {
         name: index_name.to_string(),
         column: column.to_string(),
-        dimension: pq.dimension as u32,
+        dimension: quantizer.dimension() as u32,
         dataset_version: dataset.version().version,
         metric_type,
         ivf,
-        pq: pq.into(),
+        quantizer,
         transforms,
     };
 
@@ -785,7 +1099,7 @@ async fn write_index_file(
 }
 
 /// Train IVF partitions using kmeans.
-async fn train_ivf_model(
+pub(crate) async fn train_ivf_model(
     data: &MatrixView,
     metric_type: MetricType,
     params: &IvfBuildParams,
@@ -813,12 +1127,13 @@ async fn train_ivf_model(
 mod tests {
     use super::*;
 
-    use arrow_array::{cast::AsArray, RecordBatchIterator};
+    use arrow_array::{cast::AsArray, types::UInt64Type, RecordBatchIterator};
     use arrow_schema::{DataType, Field, Schema};
     use tempfile::tempdir;
 
     use crate::{
-        index::{vector::VectorIndexParams, DatasetIndexExt, IndexType},
+        dataset::{WriteMode, WriteParams},
+        index::{read_index_file, vector::VectorIndexParams, DatasetIndexExt, IndexType},
         utils::testing::generate_random_array,
     };
 
@@ -873,4 +1188,219 @@ mod tests {
         assert_eq!(1, results.len());
         assert_eq!(5, results[0].num_rows());
     }
+
+    #[tokio::test]
+    async fn test_create_ivf_pq_4bit() {
+        const DIM: usize = 16;
+        let vectors = generate_random_array(1000 * DIM);
+
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "vector",
+            DataType::FixedSizeList(
+                Arc::new(Field::new("item", DataType::Float32, true)),
+                DIM as i32,
+            ),
+            true,
+        )]));
+        let array = Arc::new(FixedSizeListArray::try_new_from_values(vectors, DIM as i32).unwrap());
+        let batch = RecordBatch::try_new(schema.clone(), vec![array.clone()]).unwrap();
+
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let batches = RecordBatchIterator::new(vec![batch].into_iter().map(Ok), schema.clone());
+        let dataset = Dataset::write(batches, test_uri, None).await.unwrap();
+
+        // An odd number of sub-vectors can not be packed.
+        let params = VectorIndexParams::ivf_pq(2, 4, 3, false, MetricType::L2, 10);
+        assert!(dataset
+            .create_index(&["vector"], IndexType::Vector, None, &params, false)
+            .await
+            .is_err());
+
+        let params = VectorIndexParams::ivf_pq(2, 4, 8, false, MetricType::L2, 10);
+        let dataset = dataset
+            .create_index(&["vector"], IndexType::Vector, None, &params, false)
+            .await
+            .unwrap();
+
+        for row in [0, 10, 500, 999] {
+            let elem = array.value(row);
+            let query = elem.as_primitive::<Float32Type>();
+            let results = dataset
+                .scan()
+                .nearest("vector", query, 5)
+                .unwrap()
+                .nprobs(2)
+                .refine(20)
+                .with_row_id()
+                .try_into_stream()
+                .await
+                .unwrap()
+                .try_collect::<Vec<_>>()
+                .await
+                .unwrap();
+            assert_eq!(5, results[0].num_rows());
+            let row_ids = results[0]
+                .column_by_name(ROW_ID)
+                .unwrap()
+                .as_primitive::<UInt64Type>();
+            assert_eq!(row_ids.value(0), row as u64);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_create_ivf_sq_index() {
+        const DIM: usize = 16;
+        let vectors = generate_random_array(1000 * DIM);
+
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "vector",
+            DataType::FixedSizeList(
+                Arc::new(Field::new("item", DataType::Float32, true)),
+                DIM as i32,
+            ),
+            true,
+        )]));
+        let array = Arc::new(FixedSizeListArray::try_new_from_values(vectors, DIM as i32).unwrap());
+        let batch = RecordBatch::try_new(schema.clone(), vec![array.clone()]).unwrap();
+
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let batches = RecordBatchIterator::new(vec![batch].into_iter().map(Ok), schema.clone());
+        let dataset = Dataset::write(batches, test_uri, None).await.unwrap();
+
+        let params = VectorIndexParams::ivf_sq(2, MetricType::L2, 10);
+        let dataset = dataset
+            .create_index(&["vector"], IndexType::Vector, None, &params, false)
+            .await
+            .unwrap();
+
+        let indices = dataset.load_indices().await.unwrap();
+        let (_, proto) = read_index_file(&dataset, &indices[0].uuid.to_string())
+            .await
+            .unwrap();
+        let Some(pb::index::Implementation::VectorIndex(vec_idx)) = proto.implementation else {
+            panic!("Expect a vector index");
+        };
+        assert!(matches!(
+            vec_idx.stages.last().unwrap().stage,
+            Some(pb::vector_index_stage::Stage::Sq(_))
+        ));
+
+        // SQ8 is precise enough to find the vector itself.
+        for row in [0, 10, 500, 999] {
+            let elem = array.value(row);
+            let query = elem.as_primitive::<Float32Type>();
+            let results = dataset
+                .scan()
+                .nearest("vector", query, 5)
+                .unwrap()
+                .nprobs(2)
+                .with_row_id()
+                .try_into_stream()
+                .await
+                .unwrap()
+                .try_collect::<Vec<_>>()
+                .await
+                .unwrap();
+            assert_eq!(5, results[0].num_rows());
+            let row_ids = results[0]
+                .column_by_name(ROW_ID)
+                .unwrap()
+                .as_primitive::<UInt64Type>();
+            assert_eq!(row_ids.value(0), row as u64);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_optimize_ivf_pq_index() {
+        const DIM: usize = 16;
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "vector",
+            DataType::FixedSizeList(
+                Arc::new(Field::new("item", DataType::Float32, true)),
+                DIM as i32,
+            ),
+            true,
+        )]));
+        let make_batch = |num_rows: usize| {
+            let vectors = generate_random_array(num_rows * DIM);
+            let array = FixedSizeListArray::try_new_from_values(vectors, DIM as i32).unwrap();
+            RecordBatch::try_new(schema.clone(), vec![Arc::new(array)]).unwrap()
+        };
+
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let batches = RecordBatchIterator::new(vec![Ok(make_batch(1000))], schema.clone());
+        let dataset = Dataset::write(batches, test_uri, None).await.unwrap();
+        let params = VectorIndexParams::ivf_pq(2, 8, 4, false, MetricType::L2, 2);
+        let dataset = dataset
+            .create_index(&["vector"], IndexType::Vector, None, &params, false)
+            .await
+            .unwrap();
+        let old_index = dataset.load_indices().await.unwrap()[0].clone();
+
+        let new_batch = make_batch(500);
+        let batches = RecordBatchIterator::new(vec![Ok(new_batch.clone())], schema.clone());
+        let params = WriteParams {
+            mode: WriteMode::Append,
+            ..Default::default()
+        };
+        let dataset = Dataset::write(batches, test_uri, Some(params))
+            .await
+            .unwrap();
+
+        let dataset = dataset.optimize_indices().await.unwrap();
+        let indices = dataset.load_indices().await.unwrap();
+        assert_eq!(indices.len(), 1);
+        assert_eq!(indices[0].name, old_index.name);
+        assert_ne!(indices[0].uuid, old_index.uuid);
+        assert_eq!(indices[0].dataset_version, dataset.version().version - 1);
+
+        // The index covers all the rows.
+        let (_, proto) = read_index_file(&dataset, &indices[0].uuid.to_string())
+            .await
+            .unwrap();
+        let Some(pb::index::Implementation::VectorIndex(vec_idx)) = proto.implementation else {
+            panic!("Expect a vector index");
+        };
+        let num_rows = vec_idx
+            .stages
+            .iter()
+            .find_map(|stage| match &stage.stage {
+                Some(pb::vector_index_stage::Stage::Ivf(ivf)) => Some(ivf.lengths.iter().sum()),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(1500_u32, num_rows);
+
+        // Nothing to optimize anymore.
+        let optimized = dataset.optimize_indices().await.unwrap();
+        assert_eq!(optimized.version().version, dataset.version().version);
+
+        // An appended row is found by the index.
+        let vectors = as_fixed_size_list_array(new_batch.column(0));
+        let elem = vectors.value(10);
+        let query = elem.as_primitive::<Float32Type>();
+        let results = dataset
+            .scan()
+            .nearest("vector", query, 5)
+            .unwrap()
+            .nprobs(2)
+            .refine(10)
+            .with_row_id()
+            .try_into_stream()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let row_ids = results[0]
+            .column_by_name(ROW_ID)
+            .unwrap()
+            .as_primitive::<UInt64Type>();
+        assert_eq!(row_ids.value(0), (1_u64 << 32) + 10);
+    }
 }
@@ -187,7 +187,7 @@ impl Transformer for OptimizedProductQuantizer {
         };
 
         // Run a few iterations to get the initialized centroids.
-        let mut pq = ProductQuantizer::new(self.num_sub_vectors, self.num_bits, dim);
+        let mut pq = ProductQuantizer::new(self.num_sub_vectors, self.num_bits, dim)?;
         pq.train(&train, self.metric_type, OPQ_PQ_INIT_ITERATIONS)
             .await?;
         let mut pq_code = pq.transform(&train, self.metric_type).await?;
@@ -413,6 +413,7 @@ mod tests {
             metric_type: MetricType::L2,
             use_index: true,
             key: Float32Array::from_iter_values((0..64).map(|x| x as f32 + 640.0)).into(),
+            allow_list: None,
         };
         let results = index.search(&query).await.unwrap();
         let row_ids: &UInt64Array = as_primitive_array(&results[ROW_ID]);
@@ -17,11 +17,10 @@ use std::sync::Arc;
 
 use arrow::datatypes::Float32Type;
 use arrow_arith::aggregate::min;
-use arrow_array::builder::UInt64Builder;
-use arrow_array::types::UInt64Type;
+use arrow_array::types::{UInt64Type, UInt8Type};
 use arrow_array::{
     builder::Float32Builder, cast::as_primitive_array, Array, ArrayRef, FixedSizeListArray,
-    Float32Array, RecordBatch, UInt64Array, UInt8Array,
+    Float32Array, RecordBatch, UInt32Array, UInt64Array, UInt8Array,
 };
 use arrow_ord::sort::sort_to_indices;
 use arrow_schema::{DataType, Field as ArrowField, Schema as ArrowSchema};
@@ -41,12 +40,16 @@ use crate::io::object_reader::{read_fixed_stride_array, ObjectReader};
 use crate::linalg::{l2::l2_distance_batch, norm_l2::norm_l2};
 use crate::{Error, Result};
 
+mod fast_scan;
+
+use fast_scan::QuantizedTable;
+
 /// Product Quantization Index.
 ///
 pub struct PQIndex {
     /// Number of bits for the centroids.
     ///
-    /// Only support 8, as one of `u8` byte now.
+    /// Either 8, as one `u8` byte, or 4, as two codes packed in one byte.
     pub nbits: u32,
 
     /// Number of sub-vectors.
@@ -59,6 +62,9 @@ pub struct PQIndex {
     pub pq: Arc<ProductQuantizer>,
 
     /// PQ code
+    ///
+    /// The 4-bit codes are transposed to blocks for the fast scan of L2 distances,
+    /// or unpacked to one byte for each sub-vector for the other metrics.
     pub code: Option<Arc<UInt8Array>>,
 
     /// ROW Id used to refer to the actual row in dataset.
@@ -120,6 +126,17 @@ impl PQIndex {
             distance_table.extend(distances.values());
         }
 
+        if self.nbits == 4 {
+            // Look up the quantized distances of 4-bit codes with SIMD shuffles.
+            let table = QuantizedTable::new(&distance_table);
+            let code = self.code.as_ref().unwrap().values();
+            let sums = fast_scan::scan(code, self.pq.code_length(), &table.table);
+            let num_vectors = self.row_ids.as_ref().unwrap().len();
+            return Ok(Arc::new(Float32Array::from_iter_values(
+                sums[..num_vectors].iter().map(|s| table.distance(*s)),
+            )));
+        }
+
         Ok(Arc::new(unsafe {
             Float32Array::from_trusted_len_iter(
                 self.code
@@ -151,6 +168,7 @@ impl PQIndex {
 
         let x_norm = norm_l2(key.values()).powi(2);
 
+        let num_centroids = ProductQuantizer::num_centroids(self.nbits);
         let sub_vector_length = self.dimension / self.num_sub_vectors;
         for i in 0..self.num_sub_vectors {
             let key_sub_vector: Float32Array = key.slice(i * sub_vector_length, sub_vector_length);
@@ -192,7 +210,7 @@ impl PQIndex {
                         .iter()
                         .enumerate()
                         .map(|(sub_vec_idx, centroid)| {
-                            let idx = sub_vec_idx * 256 + *centroid as usize;
+                            let idx = sub_vec_idx * num_centroids + *centroid as usize;
                             xy_table[idx]
                         })
                         .sum::<f32>();
@@ -200,7 +218,7 @@ impl PQIndex {
                         .iter()
                         .enumerate()
                         .map(|(sub_vec_idx, centroid)| {
-                            let idx = sub_vec_idx * 256 + *centroid as usize;
+                            let idx = sub_vec_idx * num_centroids + *centroid as usize;
                             y_norm_table[idx]
                         })
                         .sum::<f32>();
@@ -237,6 +255,24 @@ impl VectorIndex for PQIndex {
             self.cosine_scores(&query.key)?
         };
 
+        // Skip the rows not in the allow list.
+        let (scores, row_ids): (ArrayRef, ArrayRef) = if let Some(allow_list) = &query.allow_list {
+            let allowed = UInt32Array::from_iter_values(
+                row_ids
+                    .values()
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, row_id)| allow_list.contains(**row_id))
+                    .map(|(i, _)| i as u32),
+            );
+            (
+                take(&scores, &allowed, None)?,
+                take(row_ids.as_ref(), &allowed, None)?,
+            )
+        } else {
+            (scores, row_ids.clone() as ArrayRef)
+        };
+
         let limit = query.k * query.refine_factor.unwrap_or(1) as usize;
         let indices = sort_to_indices(&scores, None, Some(limit))?;
         let scores = take(&scores, &indices, None)?;
@@ -260,31 +296,49 @@ impl VectorIndex for PQIndex {
         offset: usize,
         length: usize,
     ) -> Result<Arc<dyn VectorIndex>> {
-        let pq_code_length = self.pq.num_sub_vectors * length;
+        let code_length = self.pq.code_length();
+        let pq_code_length = code_length * length;
         let pq_code =
             read_fixed_stride_array(reader, &DataType::UInt8, offset, pq_code_length, ..).await?;
 
-        let row_id_offset = offset + pq_code_length /* *1 */;
+        let row_id_offset = offset + pq_code_length;
         let row_ids =
             read_fixed_stride_array(reader, &DataType::UInt64, row_id_offset, length, ..).await?;
 
-        let mut filtered_row_id_builder = UInt64Builder::new();
+        // Skip the deleted rows, along with their codes.
+        let pq_code = as_primitive_array::<UInt8Type>(pq_code.as_ref()).values();
+        let mut filtered_code = Vec::with_capacity(pq_code_length);
+        let mut filtered_row_ids = Vec::with_capacity(length);
         let deletion_checker = self.deletion_lookup_cache.as_ref();
         // TODO: consider a more optimized way of reading
         // group by frag_id and check per frag in one go
-        for row_id in as_primitive_array::<UInt64Type>(row_ids.as_ref()) {
-            let row = row_id.expect("Found null row id.");
-            if !deletion_checker.is_deleted(row).await? {
-                filtered_row_id_builder.append_value(row);
+        for (i, row_id) in as_primitive_array::<UInt64Type>(row_ids.as_ref())
+            .values()
+            .iter()
+            .enumerate()
+        {
+            if !deletion_checker.is_deleted(*row_id).await? {
+                filtered_code.extend_from_slice(&pq_code[i * code_length..(i + 1) * code_length]);
+                filtered_row_ids.push(*row_id);
             }
         }
+        if self.pq.num_bits == 4 {
+            filtered_code = if self.metric_type == MetricType::L2 {
+                fast_scan::to_blocks(&filtered_code, code_length)
+            } else {
+                filtered_code
+                    .iter()
+                    .flat_map(|c| [c & 0x0F, c >> 4])
+                    .collect()
+            };
+        }
 
         Ok(Arc::new(Self {
             nbits: self.pq.num_bits,
             num_sub_vectors: self.pq.num_sub_vectors,
             dimension: self.pq.dimension,
-            code: Some(Arc::new(as_primitive_array(&pq_code).clone())),
-            row_ids: Some(Arc::new(filtered_row_id_builder.finish())),
+            code: Some(Arc::new(UInt8Array::from(filtered_code))),
+            row_ids: Some(Arc::new(UInt64Array::from(filtered_row_ids))),
             pq: self.pq.clone(),
             metric_type: self.metric_type,
             deletion_lookup_cache: self.deletion_lookup_cache.clone(),
@@ -298,7 +352,7 @@ impl VectorIndex for PQIndex {
 pub struct ProductQuantizer {
     /// Number of bits for the centroids.
     ///
-    /// Only support 8, as one of `u8` byte now.
+    /// Either 8, as one `u8` byte, or 4, as two codes packed in one byte.
     pub num_bits: u32,
 
     /// Number of sub-vectors.
@@ -329,14 +383,14 @@ pub struct ProductQuantizer {
 
 impl ProductQuantizer {
     /// Build a Product quantizer with `m` sub-vectors, and `nbits` to present centroids.
-    pub fn new(m: usize, nbits: u32, dimension: usize) -> Self {
-        assert!(nbits == 8, "nbits can only be 8");
-        Self {
+    pub fn new(m: usize, nbits: u32, dimension: usize) -> Result<Self> {
+        Self::check_num_bits(m, nbits)?;
+        Ok(Self {
             num_bits: nbits,
             num_sub_vectors: m,
             dimension,
             codebook: None,
-        }
+        })
     }
 
     /// Create a [`ProductQuantizer`] with pre-trained codebook.
@@ -345,14 +399,34 @@ impl ProductQuantizer {
         nbits: u32,
         dimension: usize,
         codebook: Arc<Float32Array>,
-    ) -> Self {
-        assert!(nbits == 8, "nbits can only be 8");
-        Self {
+    ) -> Result<Self> {
+        Self::check_num_bits(m, nbits)?;
+        Ok(Self {
             num_bits: nbits,
             num_sub_vectors: m,
             dimension,
             codebook: Some(codebook),
+        })
+    }
+
+    /// Check the number of bits and sub-vectors, given by users or read from an index file.
+    fn check_num_bits(m: usize, nbits: u32) -> Result<()> {
+        if nbits != 4 && nbits != 8 {
+            return Err(Error::Index {
+                message: format!("PQ num_bits can only be 4 or 8, got {nbits}"),
+            });
+        }
+        if m == 0 {
+            return Err(Error::Index {
+                message: "PQ requires at least one sub-vector".to_string(),
+            });
         }
+        if nbits == 4 && m % 2 != 0 {
+            return Err(Error::Index {
+                message: format!("4-bit PQ requires an even number of sub-vectors, got {m}"),
+            });
+        }
+        Ok(())
     }
 
     pub fn num_centroids(num_bits: u32) -> usize {
@@ -364,6 +438,22 @@ impl ProductQuantizer {
         Self::num_centroids(num_bits) * num_sub_vectors
     }
 
+    /// The number of bytes of the code of one vector.
+    ///
+    /// Two 4-bit codes are packed in one byte, the lower 4 bits for the even sub-vector.
+    pub fn code_length(&self) -> usize {
+        self.num_sub_vectors * self.num_bits as usize / 8
+    }
+
+    /// Unpack the code of a vector to one centroid index for each sub-vector.
+    pub fn unpack_code(&self, code: &[u8]) -> Vec<u8> {
+        if self.num_bits == 4 {
+            code.iter().flat_map(|c| [c & 0x0F, c >> 4]).collect()
+        } else {
+            code.to_vec()
+        }
+    }
+
     /// Get the centroids for one sub-vector.
     ///
     /// Returns a flatten `num_centroids * sub_vector_width` f32 array.
@@ -382,12 +472,11 @@ impl ProductQuantizer {
     }
 
     /// Reconstruct a vector from its PQ code.
-    /// It only supports U8 PQ code for now.
     pub fn reconstruct(&self, code: &[u8]) -> Arc<Float32Array> {
-        assert_eq!(code.len(), self.num_sub_vectors);
+        assert_eq!(code.len(), self.code_length());
         let mut builder = Float32Builder::with_capacity(self.dimension);
         let sub_vector_dim = self.dimension / self.num_sub_vectors;
-        for (i, sub_code) in code.iter().enumerate() {
+        for (i, sub_code) in self.unpack_code(code).iter().enumerate() {
             let centroids = self.centroids(i).unwrap();
             builder.append_slice(
                 &centroids.values()[*sub_code as usize * sub_vector_dim
@@ -447,6 +536,7 @@ impl ProductQuantizer {
 
         let flatten_data = data.data();
         let num_sub_vectors = self.num_sub_vectors;
+        let num_bits = self.num_bits;
         let dim = self.dimension;
         let num_rows = data.num_rows();
         let values = tokio::task::spawn_blocking(move || {
@@ -467,11 +557,18 @@ impl ProductQuantizer {
                     builder[i * num_sub_vectors + sub_idx] = code as u8;
                 }
             }
+            if num_bits == 4 {
+                // Pack the codes of two sub-vectors into one byte.
+                builder = builder
+                    .chunks_exact(2)
+                    .map(|c| c[0] | (c[1] << 4))
+                    .collect();
+            }
             Ok::<UInt8Array, Error>(UInt8Array::from(builder))
         })
         .await??;
 
-        FixedSizeListArray::try_new_from_values(values, self.num_sub_vectors as i32)
+        FixedSizeListArray::try_new_from_values(values, self.code_length() as i32)
     }
 
     /// Train [`ProductQuantizer`] using vectors.
@@ -542,8 +639,8 @@ impl ProductQuantizer {
         for i in 0..data.num_rows() {
             let code_arr = pq_code.value(i);
             let code: &UInt8Array = as_primitive_array(code_arr.as_ref());
-            for sub_vec_id in 0..code.len() {
-                let centroid = code.value(sub_vec_id) as usize;
+            for (sub_vec_id, centroid) in self.unpack_code(code.values()).iter().enumerate() {
+                let centroid = *centroid as usize;
                 let sub_vector: Float32Array = data.data().slice(
                     i * self.dimension + sub_vec_id * sub_vector_dim,
                     sub_vector_dim,
@@ -574,16 +671,18 @@ impl ProductQuantizer {
     }
 }
 
-impl From<&pb::Pq> for ProductQuantizer {
-    fn from(proto: &pb::Pq) -> Self {
-        Self {
-            num_bits: proto.num_bits,
-            num_sub_vectors: proto.num_sub_vectors as usize,
-            dimension: proto.dimension as usize,
-            codebook: Some(Arc::new(Float32Array::from_iter_values(
+impl TryFrom<&pb::Pq> for ProductQuantizer {
+    type Error = Error;
+
+    fn try_from(proto: &pb::Pq) -> Result<Self> {
+        Self::new_with_codebook(
+            proto.num_sub_vectors as usize,
+            proto.num_bits,
+            proto.dimension as usize,
+            Arc::new(Float32Array::from_iter_values(
                 proto.codebook.iter().copied(),
-            ))),
-        }
+            )),
+        )
     }
 }
 
@@ -634,7 +733,7 @@ pub struct PQBuildParams {
     /// Number of subvectors to build PQ code
     pub num_sub_vectors: usize,
 
-    /// The number of bits to present one PQ centroid.
+    /// The number of bits to present one PQ centroid, either 4 or 8.
     pub num_bits: usize,
 
     /// Metric type, L2 or Cosine.
@@ -688,6 +787,13 @@ impl PQBuildParams {
             ..Default::default()
         }
     }
+
+    /// Check the parameters, before they reach the [`ProductQuantizer`], which only
+    /// accepts valid ones.
+    pub fn validate(&self) -> Result<()> {
+        let num_bits = u32::try_from(self.num_bits).unwrap_or(u32::MAX);
+        ProductQuantizer::check_num_bits(self.num_sub_vectors, num_bits)
+    }
 }
 
 /// Train product quantization over (OPQ-rotated) residual vectors.
@@ -695,11 +801,12 @@ pub(crate) async fn train_pq(
     data: &MatrixView,
     params: &PQBuildParams,
 ) -> Result<ProductQuantizer> {
+    params.validate()?;
     let mut pq = ProductQuantizer::new(
         params.num_sub_vectors,
         params.num_bits as u32,
         data.num_columns(),
-    );
+    )?;
     pq.train(data, params.metric_type, params.max_iters).await?;
     Ok(pq)
 }
@@ -709,8 +816,16 @@ mod tests {
 
     use super::*;
     use approx::relative_eq;
+    use arrow_array::cast::AsArray;
     use arrow_array::types::Float32Type;
 
+    use arrow_array::RecordBatchIterator;
+    use tempfile::tempdir;
+
+    use crate::dataset::Dataset;
+    use crate::index::{vector::VectorIndexParams, DatasetIndexExt, IndexType};
+    use crate::utils::testing::generate_random_array;
+
     #[test]
     fn test_divide_to_subvectors() {
         let values = Float32Array::from_iter((0..320).map(|v| v as f32));
@@ -738,6 +853,101 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_invalid_num_bits() {
+        assert!(ProductQuantizer::new(4, 8, 16).is_ok());
+        assert!(ProductQuantizer::new(4, 4, 16).is_ok());
+        assert!(matches!(
+            ProductQuantizer::new(4, 5, 16),
+            Err(Error::Index { .. })
+        ));
+        assert!(matches!(
+            ProductQuantizer::new(3, 4, 12),
+            Err(Error::Index { .. })
+        ));
+        assert!(matches!(
+            ProductQuantizer::new(0, 8, 16),
+            Err(Error::Index { .. })
+        ));
+
+        // A corrupted index file is rejected when it is loaded.
+        let mut proto = pb::Pq::from(
+            &ProductQuantizer::new_with_codebook(
+                2,
+                8,
+                4,
+                Arc::new(Float32Array::from(vec![0.0; 256 * 4])),
+            )
+            .unwrap(),
+        );
+        assert!(ProductQuantizer::try_from(&proto).is_ok());
+        proto.num_bits = 16;
+        assert!(matches!(
+            ProductQuantizer::try_from(&proto),
+            Err(Error::Index { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_4bit_pq_index() {
+        const DIM: usize = 16;
+        let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "vector",
+            DataType::FixedSizeList(
+                Arc::new(ArrowField::new("item", DataType::Float32, true)),
+                DIM as i32,
+            ),
+            true,
+        )]));
+        let vectors =
+            FixedSizeListArray::try_new_from_values(generate_random_array(1000 * DIM), DIM as i32)
+                .unwrap();
+        let batch = RecordBatch::try_new(schema.clone(), vec![Arc::new(vectors.clone())]).unwrap();
+
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let batches = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+        let mut dataset = Dataset::write(batches, test_uri, None).await.unwrap();
+
+        // Invalid parameters are errors, instead of panics.
+        for (num_bits, num_sub_vectors) in [(5, 4), (4, 3), (8, 0)] {
+            let params =
+                VectorIndexParams::ivf_pq(1, num_bits, num_sub_vectors, false, MetricType::L2, 10);
+            let result = dataset
+                .create_index(&["vector"], IndexType::Vector, None, &params, true)
+                .await;
+            assert!(matches!(result, Err(Error::Index { .. })));
+        }
+
+        // The L2 distances are scanned from the packed codes, and the cosine
+        // distances from the codes unpacked at load.
+        for metric_type in [MetricType::L2, MetricType::Cosine] {
+            let params = VectorIndexParams::ivf_pq(1, 4, 8, false, metric_type, 10);
+            dataset = dataset
+                .create_index(&["vector"], IndexType::Vector, None, &params, true)
+                .await
+                .unwrap();
+            for row in [0, 7, 500, 999] {
+                let key = vectors.value(row);
+                let results = dataset
+                    .scan()
+                    .nearest("vector", key.as_primitive::<Float32Type>(), 5)
+                    .unwrap()
+                    .refine(20)
+                    .with_row_id()
+                    .try_into_stream()
+                    .await
+                    .unwrap()
+                    .try_collect::<Vec<_>>()
+                    .await
+                    .unwrap();
+                let row_ids = results[0][ROW_ID].as_primitive::<UInt64Type>();
+                assert_eq!(row_ids.len(), 5);
+                assert_eq!(row_ids.value(0), row as u64, "{metric_type}");
+            }
+        }
+    }
+
     #[ignore]
     #[tokio::test]
     async fn test_train_pq_iteratively() {
@@ -745,7 +955,7 @@ mod tests {
         // A 16-dim array.
         let dim = 16;
         let mat = MatrixView::new(values.into(), dim);
-        let mut pq = ProductQuantizer::new(2, 8, dim);
+        let mut pq = ProductQuantizer::new(2, 8, dim).unwrap();
         pq.train(&mat, MetricType::L2, 1).await.unwrap();
 
         // Init centroids
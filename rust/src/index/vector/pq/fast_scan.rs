@@ -0,0 +1,307 @@
+// Copyright 2023 Lance Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Fast scan of 4-bit PQ codes.
+//!
+//! The distances from the query to the 16 centroids of a sub-vector are quantized
+//! to `u8`, so the lookup table of a sub-vector fits in one SIMD register, and the
+//! distances of a block of vectors are looked up with one shuffle instruction.
+//!
+//! See "Cache locality is not enough: High-Performance Nearest Neighbor Search with
+//! Product Quantization Fast Scan", VLDB 2015.
+
+/// The number of vectors in one block.
+pub const BLOCK_SIZE: usize = 32;
+
+/// The number of centroids of a 4-bit sub-vector.
+const NUM_CENTROIDS: usize = 16;
+
+/// Transpose the packed 4-bit codes, `code_length` bytes for each vector, into
+/// blocks of [`BLOCK_SIZE`] vectors.
+///
+/// In a block, the j-th bytes of the codes of all the vectors are contiguous.
+/// The last block is padded with zeros.
+pub fn to_blocks(codes: &[u8], code_length: usize) -> Vec<u8> {
+    let num_vectors = codes.len() / code_length;
+    let num_blocks = (num_vectors + BLOCK_SIZE - 1) / BLOCK_SIZE;
+    let block_length = BLOCK_SIZE * code_length;
+    let mut blocks = vec![0; num_blocks * block_length];
+    for (i, code) in codes.chunks_exact(code_length).enumerate() {
+        let block = &mut blocks[(i / BLOCK_SIZE) * block_length..];
+        for (j, byte) in code.iter().enumerate() {
+            block[j * BLOCK_SIZE + i % BLOCK_SIZE] = *byte;
+        }
+    }
+    blocks
+}
+
+/// Read the packed code of the `i`-th vector back from the blocks.
+#[cfg(test)]
+pub fn code_of(blocks: &[u8], code_length: usize, i: usize) -> impl Iterator<Item = u8> + '_ {
+    let block = &blocks[(i / BLOCK_SIZE) * BLOCK_SIZE * code_length..];
+    (0..code_length).map(move |j| block[j * BLOCK_SIZE + i % BLOCK_SIZE])
+}
+
+/// The distance table of a query, quantized to `u8`.
+pub struct QuantizedTable {
+    /// `16` quantized distances for each sub-vector.
+    pub table: Vec<u8>,
+
+    /// The sum of the minimal distance of each sub-vector.
+    bias: f32,
+
+    /// The distance of one quantization step.
+    scale: f32,
+}
+
+impl QuantizedTable {
+    /// Quantize the `num_sub_vectors * 16` distance table.
+    ///
+    /// The distances of a sub-vector are offset by their minimum, and all the
+    /// sub-vectors share the same scale, so the quantized distances can be summed up.
+    pub fn new(distance_table: &[f32]) -> Self {
+        let num_sub_vectors = distance_table.len() / NUM_CENTROIDS;
+        // Keep the sum of the quantized distances of all sub-vectors within `u16`.
+        let levels = (u16::MAX as usize / num_sub_vectors.max(1)).min(u8::MAX as usize) as f32;
+
+        let mins = distance_table
+            .chunks_exact(NUM_CENTROIDS)
+            .map(|t| t.iter().copied().fold(f32::INFINITY, f32::min))
+            .collect::<Vec<_>>();
+        let max_range = distance_table
+            .chunks_exact(NUM_CENTROIDS)
+            .zip(mins.iter())
+            .map(|(t, min)| t.iter().map(|d| d - min).fold(0.0, f32::max))
+            .fold(0.0, f32::max);
+        let scale = if max_range > 0.0 {
+            max_range / levels
+        } else {
+            1.0
+        };
+
+        let table = distance_table
+            .chunks_exact(NUM_CENTROIDS)
+            .zip(mins.iter())
+            .flat_map(|(t, min)| t.iter().map(move |d| ((d - min) / scale).round() as u8))
+            .collect();
+        Self {
+            table,
+            bias: mins.iter().sum(),
+            scale,
+        }
+    }
+
+    /// Convert the sum of the quantized distances back to the distance.
+    #[inline]
+    pub fn distance(&self, sum: u16) -> f32 {
+        self.bias + sum as f32 * self.scale
+    }
+}
+
+/// Sum up the quantized distances of all the vectors in the blocks.
+///
+/// Returns one sum for each vector of the blocks, including the padding of the
+/// last block.
+pub fn scan(blocks: &[u8], code_length: usize, table: &[u8]) -> Vec<u16> {
+    debug_assert_eq!(blocks.len() % (code_length * BLOCK_SIZE), 0);
+    debug_assert_eq!(table.len(), code_length * 2 * NUM_CENTROIDS);
+
+    #[cfg(target_arch = "aarch64")]
+    {
+        // Neon is the lowest aarch64 CPU requirement (available in all Apple Silicon / Arm V7+).
+        aarch64::neon::scan(blocks, code_length, table)
+    }
+
+    #[cfg(not(target_arch = "aarch64"))]
+    {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if is_x86_feature_detected!("avx2") {
+                return unsafe { x86_64::avx::scan(blocks, code_length, table) };
+            }
+        }
+
+        // Fallback on x86_64 without AVX2, or other platforms.
+        scan_scalar(blocks, code_length, table)
+    }
+}
+
+#[cfg_attr(target_arch = "aarch64", allow(dead_code))]
+fn scan_scalar(blocks: &[u8], code_length: usize, table: &[u8]) -> Vec<u16> {
+    let mut sums = vec![0_u16; blocks.len() / code_length];
+    for (b, block) in blocks.chunks_exact(code_length * BLOCK_SIZE).enumerate() {
+        let sums = &mut sums[b * BLOCK_SIZE..(b + 1) * BLOCK_SIZE];
+        for (j, codes) in block.chunks_exact(BLOCK_SIZE).enumerate() {
+            // The low 4 bits are the code of the sub-vector `2j`, the high 4 bits `2j + 1`.
+            let low_table = &table[2 * j * NUM_CENTROIDS..(2 * j + 1) * NUM_CENTROIDS];
+            let high_table = &table[(2 * j + 1) * NUM_CENTROIDS..(2 * j + 2) * NUM_CENTROIDS];
+            for (sum, code) in sums.iter_mut().zip(codes.iter()) {
+                *sum += low_table[(code & 0x0F) as usize] as u16;
+                *sum += high_table[(code >> 4) as usize] as u16;
+            }
+        }
+    }
+    sums
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86_64 {
+    pub mod avx {
+        use super::super::{BLOCK_SIZE, NUM_CENTROIDS};
+        use std::arch::x86_64::*;
+
+        #[target_feature(enable = "avx2")]
+        pub unsafe fn scan(blocks: &[u8], code_length: usize, table: &[u8]) -> Vec<u16> {
+            let mut sums = vec![0_u16; blocks.len() / code_length];
+            let low_mask = _mm256_set1_epi8(0x0F);
+            let even_mask = _mm256_set1_epi16(0x00FF);
+            for (b, block) in blocks.chunks_exact(code_length * BLOCK_SIZE).enumerate() {
+                // The sums of the vectors at the even and odd positions of the block.
+                let mut even = _mm256_setzero_si256();
+                let mut odd = _mm256_setzero_si256();
+                for j in 0..code_length {
+                    let codes =
+                        _mm256_loadu_si256(block.as_ptr().add(j * BLOCK_SIZE) as *const __m256i);
+                    // Shuffle works within 128-bit lanes, so repeat the table in both lanes.
+                    let low_table = _mm256_broadcastsi128_si256(_mm_loadu_si128(
+                        table.as_ptr().add(2 * j * NUM_CENTROIDS) as *const __m128i,
+                    ));
+                    let high_table = _mm256_broadcastsi128_si256(_mm_loadu_si128(
+                        table.as_ptr().add((2 * j + 1) * NUM_CENTROIDS) as *const __m128i,
+                    ));
+                    let low = _mm256_shuffle_epi8(low_table, _mm256_and_si256(codes, low_mask));
+                    let high = _mm256_shuffle_epi8(
+                        high_table,
+                        _mm256_and_si256(_mm256_srli_epi16(codes, 4), low_mask),
+                    );
+
+                    // Widen to u16 before summing up.
+                    even = _mm256_add_epi16(even, _mm256_and_si256(low, even_mask));
+                    even = _mm256_add_epi16(even, _mm256_and_si256(high, even_mask));
+                    odd = _mm256_add_epi16(odd, _mm256_srli_epi16(low, 8));
+                    odd = _mm256_add_epi16(odd, _mm256_srli_epi16(high, 8));
+                }
+
+                let mut even_sums = [0_u16; BLOCK_SIZE / 2];
+                let mut odd_sums = [0_u16; BLOCK_SIZE / 2];
+                _mm256_storeu_si256(even_sums.as_mut_ptr() as *mut __m256i, even);
+                _mm256_storeu_si256(odd_sums.as_mut_ptr() as *mut __m256i, odd);
+                let sums = &mut sums[b * BLOCK_SIZE..(b + 1) * BLOCK_SIZE];
+                for k in 0..BLOCK_SIZE / 2 {
+                    sums[2 * k] = even_sums[k];
+                    sums[2 * k + 1] = odd_sums[k];
+                }
+            }
+            sums
+        }
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod aarch64 {
+    pub mod neon {
+        use super::super::{BLOCK_SIZE, NUM_CENTROIDS};
+        use std::arch::aarch64::*;
+
+        const WIDTH: usize = 16;
+
+        pub fn scan(blocks: &[u8], code_length: usize, table: &[u8]) -> Vec<u16> {
+            let mut sums = vec![0_u16; blocks.len() / code_length];
+            unsafe {
+                let low_mask = vdupq_n_u8(0x0F);
+                for (b, block) in blocks.chunks_exact(code_length * BLOCK_SIZE).enumerate() {
+                    // A 128-bit register holds the codes of half of the block.
+                    for half in 0..BLOCK_SIZE / WIDTH {
+                        let mut low_sums = vdupq_n_u16(0);
+                        let mut high_sums = vdupq_n_u16(0);
+                        for j in 0..code_length {
+                            let codes = vld1q_u8(block.as_ptr().add(j * BLOCK_SIZE + half * WIDTH));
+                            let low_table = vld1q_u8(table.as_ptr().add(2 * j * NUM_CENTROIDS));
+                            let high_table =
+                                vld1q_u8(table.as_ptr().add((2 * j + 1) * NUM_CENTROIDS));
+                            let low = vqtbl1q_u8(low_table, vandq_u8(codes, low_mask));
+                            let high = vqtbl1q_u8(high_table, vshrq_n_u8(codes, 4));
+
+                            // Widen to u16 before summing up.
+                            low_sums = vaddw_u8(low_sums, vget_low_u8(low));
+                            low_sums = vaddw_u8(low_sums, vget_low_u8(high));
+                            high_sums = vaddw_high_u8(high_sums, low);
+                            high_sums = vaddw_high_u8(high_sums, high);
+                        }
+                        let out = sums.as_mut_ptr().add(b * BLOCK_SIZE + half * WIDTH);
+                        vst1q_u16(out, low_sums);
+                        vst1q_u16(out.add(WIDTH / 2), high_sums);
+                    }
+                }
+            }
+            sums
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::Rng;
+
+    #[test]
+    fn test_to_blocks() {
+        let code_length = 3;
+        let codes = (0..40 * code_length).map(|v| v as u8).collect::<Vec<_>>();
+        let blocks = to_blocks(&codes, code_length);
+        assert_eq!(blocks.len(), 2 * BLOCK_SIZE * code_length);
+        for i in 0..40 {
+            assert_eq!(
+                code_of(&blocks, code_length, i).collect::<Vec<_>>(),
+                codes[i * code_length..(i + 1) * code_length]
+            );
+        }
+        // Padding of the last block.
+        assert!(code_of(&blocks, code_length, 50).all(|c| c == 0));
+    }
+
+    #[test]
+    fn test_scan() {
+        let mut rng = rand::thread_rng();
+        let code_length = 8;
+        let num_vectors = 100;
+        let codes = (0..num_vectors * code_length)
+            .map(|_| rng.gen::<u8>())
+            .collect::<Vec<_>>();
+        let distance_table = (0..code_length * 2 * NUM_CENTROIDS)
+            .map(|_| rng.gen_range(0.0..10.0))
+            .collect::<Vec<f32>>();
+
+        let table = QuantizedTable::new(&distance_table);
+        let blocks = to_blocks(&codes, code_length);
+        let sums = scan(&blocks, code_length, &table.table);
+        assert_eq!(sums, scan_scalar(&blocks, code_length, &table.table));
+        assert_eq!(sums.len(), 4 * BLOCK_SIZE);
+
+        // The quantization error of a sub-vector is at most half a step.
+        let max_error = code_length as f32 * 2.0 * table.scale / 2.0 + 1e-3;
+        for (i, code) in codes.chunks_exact(code_length).enumerate() {
+            let expected = code
+                .iter()
+                .enumerate()
+                .map(|(j, c)| {
+                    distance_table[2 * j * NUM_CENTROIDS + (c & 0x0F) as usize]
+                        + distance_table[(2 * j + 1) * NUM_CENTROIDS + (c >> 4) as usize]
+                })
+                .sum::<f32>();
+            assert!((table.distance(sums[i]) - expected).abs() <= max_error);
+        }
+    }
+}
@@ -0,0 +1,521 @@
+// Copyright 2023 Lance Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Scalar Quantization (SQ).
+//!
+//! Each dimension of a vector is encoded into one `u8` code, uniformly over the
+//! `[min, max]` range of the dimension in the training data.
+
+use std::any::Any;
+use std::sync::Arc;
+
+use arrow_array::{
+    cast::AsArray,
+    types::{UInt64Type, UInt8Type},
+    ArrayRef, FixedSizeListArray, Float32Array, RecordBatch, UInt32Array, UInt64Array, UInt8Array,
+};
+use arrow_ord::sort::sort_to_indices;
+use arrow_schema::{DataType, Field as ArrowField, Schema as ArrowSchema};
+use arrow_select::take::take;
+use async_trait::async_trait;
+
+use super::{MetricType, Query, VectorIndex};
+use crate::arrow::linalg::matrix::MatrixView;
+use crate::arrow::*;
+use crate::dataset::ROW_ID;
+use crate::index::Index;
+use crate::index::{pb, vector::SCORE_COL};
+use crate::io::deletion::LruDeletionVectorStore;
+use crate::io::object_reader::{read_fixed_stride_array, ObjectReader};
+use crate::linalg::{
+    norm_l2::norm_l2,
+    uint8::{dot_uint8_batch, l2_distance_uint8_batch},
+};
+use crate::{Error, Result};
+
+/// The largest `u8` code.
+const MAX_CODE: f32 = u8::MAX as f32;
+
+/// Scalar Quantization Index.
+///
+/// It holds the SQ codes of the vectors of one IVF partition.
+pub struct SQIndex {
+    /// Scalar quantizer.
+    pub sq: Arc<ScalarQuantizer>,
+
+    /// SQ code, `dimension` bytes for each vector.
+    pub code: Option<Arc<UInt8Array>>,
+
+    /// ROW Id used to refer to the actual row in dataset.
+    pub row_ids: Option<Arc<UInt64Array>>,
+
+    /// Metric type.
+    metric_type: MetricType,
+
+    /// Deletion vector cache.
+    deletion_lookup_cache: Arc<LruDeletionVectorStore>,
+}
+
+impl std::fmt::Debug for SQIndex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "SQ(nbits={}, dim={}, {})",
+            self.sq.num_bits, self.sq.dimension, self.metric_type
+        )
+    }
+}
+
+impl SQIndex {
+    pub(crate) fn new(
+        sq: Arc<ScalarQuantizer>,
+        metric_type: MetricType,
+        deletion_cache: Arc<LruDeletionVectorStore>,
+    ) -> Self {
+        Self {
+            sq,
+            code: None,
+            row_ids: None,
+            metric_type,
+            deletion_lookup_cache: deletion_cache,
+        }
+    }
+
+    /// Compute the distances from the key to all the vectors in the index.
+    fn scores(&self, key: &[f32]) -> Result<ArrayRef> {
+        let code = self.code.as_ref().unwrap().values();
+        let scores = self.sq.distances(key, code, self.metric_type)?;
+        Ok(scores)
+    }
+}
+
+impl Index for SQIndex {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[async_trait]
+impl VectorIndex for SQIndex {
+    /// Search top-k nearest neighbors for `key` within one SQ partition.
+    async fn search(&self, query: &Query) -> Result<RecordBatch> {
+        if self.code.is_none() || self.row_ids.is_none() {
+            return Err(Error::Index {
+                message: "SQIndex::search: SQ is not initialized".to_string(),
+            });
+        }
+        let row_ids = self.row_ids.as_ref().unwrap();
+        let scores = self.scores(query.key.values())?;
+
+        // Skip the rows not in the allow list.
+        let (scores, row_ids): (ArrayRef, ArrayRef) = if let Some(allow_list) = &query.allow_list {
+            let allowed = UInt32Array::from_iter_values(
+                row_ids
+                    .values()
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, row_id)| allow_list.contains(**row_id))
+                    .map(|(i, _)| i as u32),
+            );
+            (
+                take(&scores, &allowed, None)?,
+                take(row_ids.as_ref(), &allowed, None)?,
+            )
+        } else {
+            (scores, row_ids.clone() as ArrayRef)
+        };
+
+        let limit = query.k * query.refine_factor.unwrap_or(1) as usize;
+        let indices = sort_to_indices(&scores, None, Some(limit))?;
+        let scores = take(&scores, &indices, None)?;
+        let row_ids = take(row_ids.as_ref(), &indices, None)?;
+
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new(SCORE_COL, DataType::Float32, false),
+            ArrowField::new(ROW_ID, DataType::UInt64, false),
+        ]));
+        Ok(RecordBatch::try_new(schema, vec![scores, row_ids])?)
+    }
+
+    fn is_loadable(&self) -> bool {
+        true
+    }
+
+    /// Load a SQ index (page) from the disk.
+    async fn load(
+        &self,
+        reader: &dyn ObjectReader,
+        offset: usize,
+        length: usize,
+    ) -> Result<Arc<dyn VectorIndex>> {
+        let dimension = self.sq.dimension;
+        let code_length = dimension * length;
+        let code =
+            read_fixed_stride_array(reader, &DataType::UInt8, offset, code_length, ..).await?;
+        let row_ids =
+            read_fixed_stride_array(reader, &DataType::UInt64, offset + code_length, length, ..)
+                .await?;
+
+        // Skip the deleted rows, along with their codes.
+        let code = code.as_primitive::<UInt8Type>().values();
+        let mut filtered_code = Vec::with_capacity(code_length);
+        let mut filtered_row_ids = Vec::with_capacity(length);
+        let deletion_checker = self.deletion_lookup_cache.as_ref();
+        for (i, row_id) in row_ids
+            .as_primitive::<UInt64Type>()
+            .values()
+            .iter()
+            .enumerate()
+        {
+            if !deletion_checker.is_deleted(*row_id).await? {
+                filtered_code.extend_from_slice(&code[i * dimension..(i + 1) * dimension]);
+                filtered_row_ids.push(*row_id);
+            }
+        }
+
+        Ok(Arc::new(Self {
+            sq: self.sq.clone(),
+            code: Some(Arc::new(UInt8Array::from(filtered_code))),
+            row_ids: Some(Arc::new(UInt64Array::from(filtered_row_ids))),
+            metric_type: self.metric_type,
+            deletion_lookup_cache: self.deletion_lookup_cache.clone(),
+        }))
+    }
+}
+
+/// Scalar Quantization.
+///
+/// A value `x` of the i-th dimension is encoded as
+/// `round((x - min[i]) / (max[i] - min[i]) * 255)`, clamped to `[0, 255]`.
+#[derive(Debug, Clone)]
+pub struct ScalarQuantizer {
+    /// Number of bits of a code.
+    ///
+    /// Only support 8, as one of `u8` byte now.
+    pub num_bits: u32,
+
+    /// Vector dimension.
+    pub dimension: usize,
+
+    /// The minimum value of each dimension.
+    pub min: Vec<f32>,
+
+    /// The maximum value of each dimension.
+    pub max: Vec<f32>,
+}
+
+impl ScalarQuantizer {
+    /// Create a [`ScalarQuantizer`] to be trained.
+    pub fn new(num_bits: u32, dimension: usize) -> Self {
+        assert!(num_bits == 8, "nbits can only be 8");
+        Self {
+            num_bits,
+            dimension,
+            min: vec![],
+            max: vec![],
+        }
+    }
+
+    /// The width of the range of the values of each code.
+    fn steps(&self) -> Vec<f32> {
+        self.min
+            .iter()
+            .zip(self.max.iter())
+            .map(|(min, max)| {
+                let step = (max - min) / MAX_CODE;
+                // All the values of the dimension are the same.
+                if step > 0.0 {
+                    step
+                } else {
+                    1.0
+                }
+            })
+            .collect()
+    }
+
+    /// Train [`ScalarQuantizer`], i.e., find the range of each dimension of the vectors.
+    pub fn train(&mut self, data: &MatrixView) -> Result<()> {
+        if data.num_columns() != self.dimension {
+            return Err(Error::Index {
+                message: format!(
+                    "ScalarQuantizer::train: dimension mismatch: {} != {}",
+                    data.num_columns(),
+                    self.dimension
+                ),
+            });
+        }
+        let mut min = vec![f32::MAX; self.dimension];
+        let mut max = vec![f32::MIN; self.dimension];
+        for i in 0..data.num_rows() {
+            let row = data.row(i).unwrap();
+            for (d, v) in row.iter().enumerate() {
+                min[d] = min[d].min(*v);
+                max[d] = max[d].max(*v);
+            }
+        }
+        if data.num_rows() == 0 {
+            min.fill(0.0);
+            max.fill(0.0);
+        }
+        self.min = min;
+        self.max = max;
+        Ok(())
+    }
+
+    /// Transform the vectors to SQ code array.
+    pub fn transform(&self, data: &MatrixView) -> Result<FixedSizeListArray> {
+        if self.min.len() != self.dimension {
+            return Err(Error::Index {
+                message: "ScalarQuantizer::transform: SQ is not trained".to_string(),
+            });
+        }
+        let steps = self.steps();
+        let mut codes = Vec::with_capacity(data.num_rows() * self.dimension);
+        for i in 0..data.num_rows() {
+            let row = data.row(i).unwrap();
+            codes.extend(
+                row.iter()
+                    .zip(self.min.iter())
+                    .zip(steps.iter())
+                    .map(|((v, min), step)| ((v - min) / step).round().clamp(0.0, MAX_CODE) as u8),
+            );
+        }
+        FixedSizeListArray::try_new_from_values(UInt8Array::from(codes), self.dimension as i32)
+    }
+
+    /// Reconstruct a vector from its SQ code.
+    pub fn reconstruct(&self, code: &[u8]) -> Vec<f32> {
+        assert_eq!(code.len(), self.dimension);
+        code.iter()
+            .zip(self.min.iter())
+            .zip(self.steps())
+            .map(|((c, min), step)| min + *c as f32 * step)
+            .collect()
+    }
+
+    /// Compute the distances from the key to the vectors of the codes.
+    ///
+    /// The key is mapped into the code space, so the codes are never decoded.
+    pub fn distances(&self, key: &[f32], code: &[u8], metric_type: MetricType) -> Result<ArrayRef> {
+        if key.len() != self.dimension {
+            return Err(Error::Index {
+                message: format!(
+                    "ScalarQuantizer::distances: dimension mismatch: {} != {}",
+                    key.len(),
+                    self.dimension
+                ),
+            });
+        }
+        let steps = self.steps();
+        // `(x - min) ^ 2 = step ^ 2 * ((x - min) / step) ^ 2`
+        let weights = steps.iter().map(|s| s * s).collect::<Vec<_>>();
+        let scores: ArrayRef = match metric_type {
+            MetricType::L2 => {
+                let scaled_key = key
+                    .iter()
+                    .zip(self.min.iter())
+                    .zip(steps.iter())
+                    .map(|((x, min), step)| (x - min) / step)
+                    .collect::<Vec<_>>();
+                l2_distance_uint8_batch(&scaled_key, code, &weights)
+            }
+            MetricType::Dot | MetricType::Cosine => {
+                // `x . y = x . min + (x * step) . code`
+                let offset = key
+                    .iter()
+                    .zip(self.min.iter())
+                    .map(|(x, min)| x * min)
+                    .sum::<f32>();
+                let stepped_key = key
+                    .iter()
+                    .zip(steps.iter())
+                    .map(|(x, step)| x * step)
+                    .collect::<Vec<_>>();
+                let dots = dot_uint8_batch(&stepped_key, code);
+                if metric_type == MetricType::Dot {
+                    Arc::new(Float32Array::from_iter_values(
+                        dots.values().iter().map(|d| d + offset),
+                    ))
+                } else {
+                    // `|y| ^ 2` is the weighted L2 distance from the origin, at `-min / step`
+                    // in the code space.
+                    let origin = self
+                        .min
+                        .iter()
+                        .zip(steps.iter())
+                        .map(|(min, step)| -min / step)
+                        .collect::<Vec<_>>();
+                    let y_norms = l2_distance_uint8_batch(&origin, code, &weights);
+                    let x_norm = norm_l2(key);
+                    Arc::new(Float32Array::from_iter_values(
+                        dots.values()
+                            .iter()
+                            .zip(y_norms.values().iter())
+                            .map(|(d, y_norm)| {
+                                let norm = x_norm * y_norm.sqrt();
+                                // A zero vector is not similar to any vector.
+                                if norm == 0.0 {
+                                    1.0
+                                } else {
+                                    1.0 - (d + offset) / norm
+                                }
+                            }),
+                    ))
+                }
+            }
+        };
+        Ok(scores)
+    }
+}
+
+impl From<&pb::Sq> for ScalarQuantizer {
+    fn from(proto: &pb::Sq) -> Self {
+        Self {
+            num_bits: proto.num_bits,
+            dimension: proto.dimension as usize,
+            min: proto.min.clone(),
+            max: proto.max.clone(),
+        }
+    }
+}
+
+impl From<&ScalarQuantizer> for pb::Sq {
+    fn from(sq: &ScalarQuantizer) -> Self {
+        Self {
+            num_bits: sq.num_bits,
+            dimension: sq.dimension as u32,
+            min: sq.min.clone(),
+            max: sq.max.clone(),
+        }
+    }
+}
+
+/// Parameters for building scalar quantization.
+#[derive(Debug, Clone)]
+pub struct SQBuildParams {
+    /// The number of bits of a code. Only `8` is supported for now.
+    pub num_bits: usize,
+}
+
+impl Default for SQBuildParams {
+    fn default() -> Self {
+        Self { num_bits: 8 }
+    }
+}
+
+/// Train scalar quantization over residual vectors.
+pub(crate) fn train_sq(data: &MatrixView, params: &SQBuildParams) -> Result<ScalarQuantizer> {
+    if params.num_bits != 8 {
+        return Err(Error::Index {
+            message: format!(
+                "SQ: the number of bits can only be 8, got {}",
+                params.num_bits
+            ),
+        });
+    }
+    let mut sq = ScalarQuantizer::new(params.num_bits as u32, data.num_columns());
+    sq.train(data)?;
+    Ok(sq)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use approx::assert_relative_eq;
+    use arrow_array::types::Float32Type;
+
+    use crate::linalg::{cosine::cosine_distance, l2::l2_distance};
+    use crate::utils::testing::generate_random_array;
+
+    #[test]
+    fn test_train_and_transform() {
+        // Dimension 1 is constant.
+        let values = Float32Array::from_iter_values(
+            (0..256).flat_map(|v| [v as f32 / 10.0 - 5.0, 1.0, -(v as f32)]),
+        );
+        let mat = MatrixView::new(values.into(), 3);
+        let sq = train_sq(&mat, &SQBuildParams::default()).unwrap();
+        assert_eq!(sq.min, [-5.0, 1.0, -255.0]);
+        assert_eq!(sq.max, [20.5, 1.0, 0.0]);
+
+        let code = sq.transform(&mat).unwrap();
+        assert_eq!(code.len(), 256);
+        let code = code.values();
+        let code = code.as_primitive::<UInt8Type>().values();
+        assert_eq!(&code[..3], &[0, 0, 255]);
+        assert_eq!(&code[255 * 3..], &[255, 0, 0]);
+
+        for i in [0, 17, 128, 255] {
+            let decoded = sq.reconstruct(&code[i * 3..(i + 1) * 3]);
+            let expected = mat.row(i).unwrap();
+            for (d, e) in decoded.iter().zip(expected.iter()) {
+                assert!((d - e).abs() <= 0.5, "{d} != {e}");
+            }
+        }
+
+        assert!(train_sq(&mat, &SQBuildParams { num_bits: 4 }).is_err());
+    }
+
+    #[test]
+    fn test_distances() {
+        let dim = 16;
+        let mat = MatrixView::new(generate_random_array(100 * dim).into(), dim);
+        let sq = train_sq(&mat, &SQBuildParams::default()).unwrap();
+        let code = sq.transform(&mat).unwrap();
+        let code = code.values();
+        let code = code.as_primitive::<UInt8Type>().values();
+
+        let key = generate_random_array(dim);
+        for (metric_type, func) in [
+            (MetricType::L2, l2_distance as fn(&[f32], &[f32]) -> f32),
+            (MetricType::Cosine, cosine_distance),
+        ] {
+            let scores = sq.distances(key.values(), code, metric_type).unwrap();
+            let scores = scores.as_primitive::<Float32Type>();
+            assert_eq!(scores.len(), 100);
+            for i in 0..100 {
+                // The same as the distance to the decoded vector.
+                let decoded = sq.reconstruct(&code[i * dim..(i + 1) * dim]);
+                assert_relative_eq!(
+                    scores.value(i),
+                    func(key.values(), &decoded),
+                    epsilon = 1e-4
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_cosine_distances_of_zero_vectors() {
+        let dim = 8;
+        // The code 0 is the zero vector.
+        let sq = ScalarQuantizer {
+            num_bits: 8,
+            dimension: dim,
+            min: vec![0.0; dim],
+            max: vec![255.0; dim],
+        };
+        let code = [[0_u8; 8], [1_u8; 8]].concat();
+
+        let scores = sq.distances(&[1.0; 8], &code, MetricType::Cosine).unwrap();
+        let scores = scores.as_primitive::<Float32Type>();
+        assert_eq!(scores.value(0), 1.0);
+        assert_relative_eq!(scores.value(1), 0.0, epsilon = 1e-6);
+
+        let scores = sq.distances(&[0.0; 8], &code, MetricType::Cosine).unwrap();
+        let scores = scores.as_primitive::<Float32Type>();
+        assert_eq!(scores.values(), &[1.0, 1.0]);
+    }
+}
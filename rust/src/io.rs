@@ -24,6 +24,7 @@ use prost::bytes::Bytes;
 use prost::Message;
 use tokio::io::{AsyncWrite, AsyncWriteExt};
 
+pub mod commit;
 pub(crate) mod deletion;
 pub(crate) mod exec;
 pub mod local;
@@ -41,6 +42,7 @@ pub use deletion::deletion_file_path;
 pub use reader::read_manifest;
 pub use reader::FileReader;
 pub use stream::RecordBatchStream;
+pub(crate) use writer::supports_statistics;
 pub use writer::*;
 
 #[async_trait]
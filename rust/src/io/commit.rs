@@ -0,0 +1,636 @@
+// Copyright 2023 Lance Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Commit handlers.
+//!
+//! Committing a new version of a dataset must fail if the version already exists,
+//! so that concurrent writers never overwrite each other. A [CommitHandler] implements
+//! this "create if not exists" write of the version manifest.
+//!
+//! * [RenameCommitHandler] (default) relies on the atomic rename of the object store.
+//!   It is safe on local file systems, GCS and Azure.
+//! * [UnsafeCommitHandler] (default on S3) checks that the version does not exist before
+//!   writing it. S3 has no atomic rename or put-if-absent, so concurrent writers can
+//!   overwrite each other's versions. Pass a [CommitLock] to write to S3 concurrently.
+//! * Any [CommitLock] is a [CommitHandler] that holds an external lock while committing.
+//!   [MutexCommitHandler] and [LockFileCommitHandler] are provided to synchronize
+//!   writers in the same process or on the same machine. Other lock services can be
+//!   used by implementing [CommitLock], i.e., [InMemoryCommitLock].
+
+use std::collections::HashSet;
+use std::fmt::Debug;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use log::warn;
+use object_store::path::Path;
+use tokio::fs::{create_dir_all, metadata, remove_file, OpenOptions};
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
+use uuid::Uuid;
+
+use super::{write_manifest, ObjectStore};
+use crate::format::{Index, Manifest};
+use crate::{Error, Result};
+
+/// Default time to wait for a lock before giving up.
+const DEFAULT_LOCK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Default age after which a lock file is considered left over by a crashed writer.
+const DEFAULT_LOCK_FILE_EXPIRY: Duration = Duration::from_secs(300);
+
+/// Interval to poll a lock that is held by another writer.
+const LOCK_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Handles the commit of a new dataset version.
+#[async_trait]
+pub trait CommitHandler: Debug + Send + Sync {
+    /// Write the manifest to `path`, the manifest file of its version, if and only if
+    /// the file does not exist yet.
+    ///
+    /// Returns [Error::CommitConflict] if the version has already been committed.
+    async fn commit(
+        &self,
+        manifest: &mut Manifest,
+        indices: Option<Vec<Index>>,
+        path: &Path,
+        object_store: &ObjectStore,
+    ) -> Result<()>;
+}
+
+fn commit_conflict(manifest: &Manifest) -> Error {
+    Error::CommitConflict {
+        version: manifest.version,
+        source: format!("version {} already exists", manifest.version).into(),
+    }
+}
+
+/// Write the manifest, with the indices, to a file.
+pub(crate) async fn write_manifest_file_to_path(
+    object_store: &ObjectStore,
+    manifest: &mut Manifest,
+    indices: Option<Vec<Index>>,
+    path: &Path,
+) -> Result<()> {
+    let mut object_writer = object_store.create(path).await?;
+    let pos = write_manifest(&mut object_writer, manifest, indices).await?;
+    object_writer.write_magics(pos).await?;
+    object_writer.shutdown().await?;
+    Ok(())
+}
+
+/// Commit by writing a temporary file, and renaming it to the version manifest only if
+/// it does not exist yet.
+///
+/// Object stores without atomic rename, i.e., S3, are not supported.
+#[derive(Debug, Default)]
+pub struct RenameCommitHandler;
+
+#[async_trait]
+impl CommitHandler for RenameCommitHandler {
+    async fn commit(
+        &self,
+        manifest: &mut Manifest,
+        indices: Option<Vec<Index>>,
+        path: &Path,
+        object_store: &ObjectStore,
+    ) -> Result<()> {
+        // The temporary name does not end with ".manifest", so it is never listed as a version.
+        let tmp_path = Path::parse(format!(
+            "{}.tmp_{}",
+            path.as_ref().trim_end_matches(".manifest"),
+            Uuid::new_v4()
+        ))?;
+        write_manifest_file_to_path(object_store, manifest, indices, &tmp_path).await?;
+
+        match object_store
+            .inner
+            .rename_if_not_exists(&tmp_path, path)
+            .await
+        {
+            Ok(()) => Ok(()),
+            Err(object_store::Error::AlreadyExists { .. }) => {
+                object_store.inner.delete(&tmp_path).await?;
+                Err(commit_conflict(manifest))
+            }
+            Err(object_store::Error::NotImplemented) => {
+                object_store.inner.delete(&tmp_path).await.ok();
+                Err(Error::NotSupported {
+                    source: format!(
+                        "{object_store} does not support atomic rename, use a CommitLock to commit"
+                    )
+                    .into(),
+                })
+            }
+            Err(e) => {
+                object_store.inner.delete(&tmp_path).await.ok();
+                Err(e.into())
+            }
+        }
+    }
+}
+
+/// Commit by checking that the version does not exist, and then writing it.
+///
+/// It is NOT safe against concurrent writers: two writers may both find the version missing,
+/// and the last one overwrites the other's commit. It is the default on S3, which has no
+/// atomic rename or put-if-absent; use a [CommitLock] if datasets on S3 are written
+/// concurrently.
+#[derive(Debug, Default)]
+pub struct UnsafeCommitHandler;
+
+#[async_trait]
+impl CommitHandler for UnsafeCommitHandler {
+    async fn commit(
+        &self,
+        manifest: &mut Manifest,
+        indices: Option<Vec<Index>>,
+        path: &Path,
+        object_store: &ObjectStore,
+    ) -> Result<()> {
+        if object_store.exists(path).await? {
+            return Err(commit_conflict(manifest));
+        }
+        warn!("Committing {path} without a commit lock, concurrent writers may overwrite it");
+        write_manifest_file_to_path(object_store, manifest, indices, path).await
+    }
+}
+
+/// A lock held while committing a version.
+#[async_trait]
+pub trait CommitLease: Send + Sync {
+    /// Release the lock. `success` is whether the version has been committed.
+    async fn release(&self, success: bool) -> Result<()>;
+}
+
+/// An external lock to synchronize commits, i.e., backed by a lock service.
+///
+/// Every [CommitLock] is a [CommitHandler]: the version manifest is only written
+/// while holding the lock, after checking that the version does not exist yet.
+#[async_trait]
+pub trait CommitLock: Debug + Send + Sync {
+    type Lease: CommitLease;
+
+    /// Acquire the lock to commit the version manifest at `path`, waiting until it is available.
+    ///
+    /// The lock must be exclusive for all writers of the same path.
+    async fn lock(&self, path: &Path) -> Result<Self::Lease>;
+}
+
+#[async_trait]
+impl<T: CommitLock> CommitHandler for T {
+    async fn commit(
+        &self,
+        manifest: &mut Manifest,
+        indices: Option<Vec<Index>>,
+        path: &Path,
+        object_store: &ObjectStore,
+    ) -> Result<()> {
+        let lease = self.lock(path).await?;
+        let result = async {
+            if object_store.exists(path).await? {
+                return Err(commit_conflict(manifest));
+            }
+            write_manifest_file_to_path(object_store, manifest, indices, path).await
+        }
+        .await;
+        // The lock is released whether the commit succeeded or not. Once the manifest is
+        // written the version is committed, so a failure to release does not fail it.
+        if let Err(e) = lease.release(result.is_ok()).await {
+            warn!("Failed to release the commit lock of {path}: {e}");
+        }
+        result
+    }
+}
+
+/// Commit while holding an in-process mutex.
+///
+/// It is only safe if all writers are in the same process, and share the same handler.
+#[derive(Debug, Default)]
+pub struct MutexCommitHandler {
+    mutex: Arc<AsyncMutex<()>>,
+}
+
+pub struct MutexLease {
+    _guard: OwnedMutexGuard<()>,
+}
+
+#[async_trait]
+impl CommitLease for MutexLease {
+    async fn release(&self, _success: bool) -> Result<()> {
+        // The mutex is unlocked when the lease is dropped.
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl CommitLock for MutexCommitHandler {
+    type Lease = MutexLease;
+
+    async fn lock(&self, _path: &Path) -> Result<Self::Lease> {
+        Ok(MutexLease {
+            _guard: self.mutex.clone().lock_owned().await,
+        })
+    }
+}
+
+/// Commit while holding a lock file on the local file system.
+///
+/// The lock file is created exclusively in `lock_dir`, which must be shared by all
+/// writers, i.e., processes on the same machine.
+///
+/// A writer that crashes while committing leaves its lock file behind. Lock files older
+/// than the expiry (5 minutes by default) are removed by the next writer, so the expiry
+/// must be longer than any commit takes. Stale lock files can also be deleted by hand,
+/// once no writer is running.
+#[derive(Debug)]
+pub struct LockFileCommitHandler {
+    lock_dir: PathBuf,
+    timeout: Duration,
+    expiry: Duration,
+}
+
+impl LockFileCommitHandler {
+    pub fn new(lock_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            lock_dir: lock_dir.into(),
+            timeout: DEFAULT_LOCK_TIMEOUT,
+            expiry: DEFAULT_LOCK_FILE_EXPIRY,
+        }
+    }
+
+    /// Set the time to wait for the lock file, before failing the commit.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Set the age after which a lock file is considered stale and removed.
+    pub fn with_expiry(mut self, expiry: Duration) -> Self {
+        self.expiry = expiry;
+        self
+    }
+
+    /// Returns true if the lock file exists and is older than the expiry.
+    async fn is_expired(&self, lock_path: &std::path::Path) -> Result<bool> {
+        let modified = match metadata(lock_path).await {
+            Ok(metadata) => metadata.modified()?,
+            // Released in the meantime.
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(modified.elapsed().map_or(false, |age| age > self.expiry))
+    }
+}
+
+pub struct LockFileLease {
+    path: PathBuf,
+}
+
+#[async_trait]
+impl CommitLease for LockFileLease {
+    async fn release(&self, _success: bool) -> Result<()> {
+        Ok(remove_file(&self.path).await?)
+    }
+}
+
+#[async_trait]
+impl CommitLock for LockFileCommitHandler {
+    type Lease = LockFileLease;
+
+    async fn lock(&self, path: &Path) -> Result<Self::Lease> {
+        create_dir_all(&self.lock_dir).await?;
+        let lock_path = self
+            .lock_dir
+            .join(format!("{}.lock", path.as_ref().replace('/', "_")));
+        let start = Instant::now();
+        loop {
+            match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&lock_path)
+                .await
+            {
+                Ok(_) => return Ok(LockFileLease { path: lock_path }),
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                    if self.is_expired(&lock_path).await? {
+                        warn!("Removing stale lock file {}", lock_path.display());
+                        match remove_file(&lock_path).await {
+                            Ok(()) => continue,
+                            Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                            Err(e) => return Err(e.into()),
+                        }
+                    }
+                    if start.elapsed() > self.timeout {
+                        return Err(Error::IO {
+                            message: format!(
+                                "Timed out waiting for lock file {}",
+                                lock_path.display()
+                            ),
+                        });
+                    }
+                    tokio::time::sleep(LOCK_POLL_INTERVAL).await;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+/// A fake [CommitLock] that keeps the locks in memory.
+///
+/// It behaves like an external lock service, and can be used to test [CommitLock]
+/// without one.
+#[derive(Debug, Clone)]
+pub struct InMemoryCommitLock {
+    locked: Arc<Mutex<HashSet<String>>>,
+    timeout: Duration,
+}
+
+impl Default for InMemoryCommitLock {
+    fn default() -> Self {
+        Self {
+            locked: Arc::new(Mutex::new(HashSet::new())),
+            timeout: DEFAULT_LOCK_TIMEOUT,
+        }
+    }
+}
+
+impl InMemoryCommitLock {
+    /// Set the time to wait for the lock, before failing the commit.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Returns true if the lock for `path` is currently held.
+    pub fn is_locked(&self, path: &Path) -> bool {
+        self.locked.lock().unwrap().contains(path.as_ref())
+    }
+}
+
+pub struct InMemoryLease {
+    locked: Arc<Mutex<HashSet<String>>>,
+    key: String,
+}
+
+#[async_trait]
+impl CommitLease for InMemoryLease {
+    async fn release(&self, _success: bool) -> Result<()> {
+        self.locked.lock().unwrap().remove(&self.key);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl CommitLock for InMemoryCommitLock {
+    type Lease = InMemoryLease;
+
+    async fn lock(&self, path: &Path) -> Result<Self::Lease> {
+        let key = path.to_string();
+        let start = Instant::now();
+        while !self.locked.lock().unwrap().insert(key.clone()) {
+            if start.elapsed() > self.timeout {
+                return Err(Error::IO {
+                    message: format!("Timed out waiting for commit lock of {key}"),
+                });
+            }
+            tokio::time::sleep(LOCK_POLL_INTERVAL).await;
+        }
+        Ok(InMemoryLease {
+            locked: self.locked.clone(),
+            key,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use arrow_array::{Int32Array, RecordBatch, RecordBatchIterator};
+    use arrow_schema::{DataType, Field as ArrowField, Schema as ArrowSchema};
+    use futures::future::join_all;
+    use tempfile::tempdir;
+
+    use crate::dataset::{Dataset, ReadParams, WriteMode, WriteParams};
+    use crate::datatypes::Schema;
+    use crate::io::object_store::ObjectStoreParams;
+
+    async fn test_commit_handler(handler: &dyn CommitHandler) {
+        let object_store = ObjectStore::memory();
+        let arrow_schema = ArrowSchema::new(vec![ArrowField::new("i", DataType::Int32, false)]);
+        let schema = Schema::try_from(&arrow_schema).unwrap();
+        let mut manifest = Manifest::new(&schema, Arc::new(vec![]));
+        let path = Path::from("dataset/_versions/1.manifest");
+
+        handler
+            .commit(&mut manifest, None, &path, &object_store)
+            .await
+            .unwrap();
+        assert!(object_store.exists(&path).await.unwrap());
+
+        let err = handler
+            .commit(&mut manifest, None, &path, &object_store)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::CommitConflict { version: 1, .. }));
+
+        // Only the committed version is left in the directory.
+        let files = object_store.read_dir("dataset/_versions").await.unwrap();
+        assert_eq!(files, vec!["1.manifest"]);
+    }
+
+    #[tokio::test]
+    async fn test_rename_commit_handler() {
+        test_commit_handler(&RenameCommitHandler).await;
+    }
+
+    #[tokio::test]
+    async fn test_mutex_commit_handler() {
+        test_commit_handler(&MutexCommitHandler::default()).await;
+    }
+
+    #[tokio::test]
+    async fn test_lock_file_commit_handler() {
+        let lock_dir = tempdir().unwrap();
+        test_commit_handler(&LockFileCommitHandler::new(lock_dir.path())).await;
+        // Lock files are removed after commits.
+        assert_eq!(std::fs::read_dir(lock_dir.path()).unwrap().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_commit_lock() {
+        let lock = InMemoryCommitLock::default();
+        test_commit_handler(&lock).await;
+        // The lock is released after a failed commit too.
+        assert!(!lock.is_locked(&Path::from("dataset/_versions/1.manifest")));
+    }
+
+    #[tokio::test]
+    async fn test_stale_lock_file() {
+        let lock_dir = tempdir().unwrap();
+        let handler = LockFileCommitHandler::new(lock_dir.path())
+            .with_timeout(Duration::from_millis(50))
+            .with_expiry(Duration::from_millis(100));
+        let path = Path::from("dataset/_versions/1.manifest");
+
+        // A crashed writer never releases its lock file.
+        handler.lock(&path).await.unwrap();
+        assert!(matches!(handler.lock(&path).await, Err(Error::IO { .. })));
+
+        // It is removed once expired.
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        test_commit_handler(&handler).await;
+        assert_eq!(std::fs::read_dir(lock_dir.path()).unwrap().count(), 0);
+    }
+
+    /// A [CommitLock] which counts the commits, and fails to release the lock.
+    #[derive(Debug, Default, Clone)]
+    struct FailingReleaseLock {
+        locks: Arc<AtomicUsize>,
+    }
+
+    struct FailingReleaseLease;
+
+    #[async_trait]
+    impl CommitLease for FailingReleaseLease {
+        async fn release(&self, _success: bool) -> Result<()> {
+            Err(Error::IO {
+                message: "lock service unavailable".to_string(),
+            })
+        }
+    }
+
+    #[async_trait]
+    impl CommitLock for FailingReleaseLock {
+        type Lease = FailingReleaseLease;
+
+        async fn lock(&self, _path: &Path) -> Result<Self::Lease> {
+            self.locks.fetch_add(1, Ordering::SeqCst);
+            Ok(FailingReleaseLease)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_lock_release() {
+        // The version is committed even though the lock is not released.
+        test_commit_handler(&FailingReleaseLock::default()).await;
+    }
+
+    #[tokio::test]
+    async fn test_configured_commit_handler() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let lock = FailingReleaseLock::default();
+        let store_params = ObjectStoreParams {
+            commit_handler: Some(Arc::new(lock.clone())),
+            ..Default::default()
+        };
+
+        let arrow_schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "i",
+            DataType::Int32,
+            false,
+        )]));
+        let batch = RecordBatch::try_new(
+            arrow_schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values(0..10))],
+        )
+        .unwrap();
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], arrow_schema);
+        let dataset = Dataset::write(reader, test_uri, None).await.unwrap();
+
+        // Committing fragments uses the configured handler.
+        let dataset = Dataset::commit_with_params(
+            test_uri,
+            dataset.schema(),
+            dataset.fragments(),
+            WriteMode::Create,
+            &store_params,
+        )
+        .await
+        .unwrap();
+        assert_eq!(dataset.version().version, 2);
+        assert_eq!(lock.locks.load(Ordering::SeqCst), 1);
+
+        // So does a dataset checked out with it.
+        let params = ReadParams {
+            store_options: Some(store_params),
+            ..Default::default()
+        };
+        let mut dataset = Dataset::checkout_with_params(test_uri, 2, &params)
+            .await
+            .unwrap();
+        dataset.delete("i < 5").await.unwrap();
+        assert_eq!(dataset.version().version, 3);
+        assert_eq!(lock.locks.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_lock_timeout() {
+        let lock = InMemoryCommitLock::default().with_timeout(Duration::from_millis(50));
+        let path = Path::from("dataset/_versions/1.manifest");
+        let lease = lock.lock(&path).await.unwrap();
+        assert!(lock.is_locked(&path));
+        assert!(matches!(lock.lock(&path).await, Err(Error::IO { .. })));
+
+        lease.release(true).await.unwrap();
+        assert!(!lock.is_locked(&path));
+        lock.lock(&path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_writes_with_external_lock() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+        let lock = InMemoryCommitLock::default();
+
+        let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "i",
+            DataType::Int32,
+            false,
+        )]));
+        let write = |mode: WriteMode| {
+            let batch = RecordBatch::try_new(
+                schema.clone(),
+                vec![Arc::new(Int32Array::from_iter_values(0..10))],
+            )
+            .unwrap();
+            let reader = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+            let params = WriteParams {
+                mode,
+                store_params: Some(ObjectStoreParams {
+                    commit_handler: Some(Arc::new(lock.clone())),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            };
+            Dataset::write(reader, test_uri, Some(params))
+        };
+
+        write(WriteMode::Create).await.unwrap();
+        let results = join_all((0..4).map(|_| write(WriteMode::Append))).await;
+        assert!(results.iter().all(|r| r.is_ok()));
+
+        let dataset = Dataset::open(test_uri).await.unwrap();
+        assert_eq!(dataset.version().version, 5);
+        assert_eq!(dataset.count_rows().await.unwrap(), 50);
+        dataset.validate().await.unwrap();
+    }
+}
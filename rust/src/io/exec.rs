@@ -12,16 +12,24 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod fts;
+mod hybrid;
 mod knn;
 mod planner;
 mod projection;
+mod pruning;
+mod scalar_index;
 mod scan;
 mod take;
 #[cfg(test)]
 pub mod testing;
 
+pub use fts::FullTextSearchExec;
+pub use hybrid::{Fusion, HybridSearchExec};
 pub use knn::*;
 pub use planner::Planner;
 pub use projection::ProjectionExec;
+pub(crate) use pruning::StatisticsPruner;
+pub use scalar_index::{ScalarIndexExec, ScalarIndexExpr, ScalarIndexQuery};
 pub use scan::LanceScanExec;
 pub use take::TakeExec;
@@ -0,0 +1,230 @@
+// Copyright 2023 Lance Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use arrow_array::{cast::AsArray, types::UInt64Type, Float32Array, RecordBatch, UInt64Array};
+use arrow_cast::cast;
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use datafusion::error::{DataFusionError, Result as DataFusionResult};
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::{
+    ExecutionPlan, Partitioning, SendableRecordBatchStream, Statistics,
+};
+use futures::stream::{self, TryStreamExt};
+
+use crate::dataset::{Dataset, ROW_ID};
+use crate::format::Fragment;
+use crate::index::inverted::{tokenize, FullTextQuery, InvertedIndex};
+use crate::index::vector::SCORE_COL;
+use crate::Result;
+
+/// [ExecutionPlan] that searches an inverted index, and emits the row ids of the
+/// matching rows with their BM25 scores, sorted by descending score.
+///
+/// The rows of the fragments added after the index was built are scored with the
+/// statistics of the indexed rows.
+pub struct FullTextSearchExec {
+    dataset: Arc<Dataset>,
+
+    /// Name of the index.
+    index_name: String,
+
+    index: Arc<InvertedIndex>,
+
+    query: FullTextQuery,
+
+    /// Fragments which are not covered by the index.
+    unindexed_fragments: Vec<Fragment>,
+
+    batch_size: usize,
+}
+
+impl std::fmt::Debug for FullTextSearchExec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "FullTextSearch(name={}, query={:?}, unindexed_fragments={})",
+            self.index_name,
+            self.query.query,
+            self.unindexed_fragments.len()
+        )
+    }
+}
+
+impl FullTextSearchExec {
+    /// Create a new [FullTextSearchExec].
+    pub fn new(
+        dataset: Arc<Dataset>,
+        index_name: &str,
+        index: Arc<InvertedIndex>,
+        query: FullTextQuery,
+        unindexed_fragments: Vec<Fragment>,
+        batch_size: usize,
+    ) -> Self {
+        Self {
+            dataset,
+            index_name: index_name.to_string(),
+            index,
+            query,
+            unindexed_fragments,
+            batch_size,
+        }
+    }
+}
+
+/// Search the index, and score the rows of the unindexed fragments.
+///
+/// Returns the row ids and the scores of the rows which are not deleted, sorted
+/// by descending score.
+async fn search(
+    dataset: Arc<Dataset>,
+    index: Arc<InvertedIndex>,
+    query: FullTextQuery,
+    unindexed_fragments: Vec<Fragment>,
+) -> Result<Vec<(u64, f32)>> {
+    let mut tokens = tokenize(&query.query).collect::<Vec<_>>();
+    tokens.sort();
+    tokens.dedup();
+    let (mut scores, idfs) = index.search(&tokens).await?;
+
+    if !unindexed_fragments.is_empty() {
+        let mut scanner = dataset.scan();
+        scanner.with_fragments(unindexed_fragments);
+        scanner.project(&[&query.column])?;
+        scanner.with_row_id();
+        let mut batches = scanner.try_into_stream().await?;
+        while let Some(batch) = batches.try_next().await? {
+            let texts = cast(
+                batch.column_by_name(&query.column).unwrap(),
+                &DataType::Utf8,
+            )?;
+            let row_ids = batch
+                .column_by_name(ROW_ID)
+                .unwrap()
+                .as_primitive::<UInt64Type>();
+            for (text, row_id) in texts.as_string::<i32>().iter().zip(row_ids.values()) {
+                let score = text
+                    .map(|text| index.bm25().score_text(&tokens, &idfs, text))
+                    .unwrap_or_default();
+                if score > 0.0 {
+                    scores.insert(*row_id, score);
+                }
+            }
+        }
+    }
+
+    // The index may refer to the fragments or rows deleted after it was built.
+    let fragments = dataset
+        .get_fragments()
+        .into_iter()
+        .map(|f| (f.id() as u64, f))
+        .collect::<HashMap<_, _>>();
+    let mut deletion_vectors = HashMap::new();
+    let mut results = Vec::with_capacity(scores.len());
+    for (row_id, score) in scores {
+        let fragment_id = row_id >> 32;
+        let Some(fragment) = fragments.get(&fragment_id) else {
+            continue;
+        };
+        if !deletion_vectors.contains_key(&fragment_id) {
+            let deletion_vector = fragment.get_deletion_vector().await?.unwrap_or_default();
+            deletion_vectors.insert(fragment_id, deletion_vector);
+        }
+        if !deletion_vectors[&fragment_id].contains(row_id as u32) {
+            results.push((row_id, score));
+        }
+    }
+    results.sort_by(|(a_id, a), (b_id, b)| b.total_cmp(a).then(a_id.cmp(b_id)));
+    Ok(results)
+}
+
+impl ExecutionPlan for FullTextSearchExec {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            Field::new(ROW_ID, DataType::UInt64, false),
+            Field::new(SCORE_COL, DataType::Float32, false),
+        ]))
+    }
+
+    fn output_partitioning(&self) -> Partitioning {
+        Partitioning::RoundRobinBatch(1)
+    }
+
+    fn output_ordering(&self) -> Option<&[datafusion::physical_expr::PhysicalSortExpr]> {
+        None
+    }
+
+    /// FullTextSearch is a leaf node, so returns zero children.
+    fn children(&self) -> Vec<Arc<dyn ExecutionPlan>> {
+        vec![]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        _children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> DataFusionResult<Arc<dyn ExecutionPlan>> {
+        Ok(self)
+    }
+
+    fn execute(
+        &self,
+        _partition: usize,
+        _context: Arc<datafusion::execution::context::TaskContext>,
+    ) -> DataFusionResult<SendableRecordBatchStream> {
+        let schema = self.schema();
+        let batch_size = self.batch_size;
+        let results = search(
+            self.dataset.clone(),
+            self.index.clone(),
+            self.query.clone(),
+            self.unindexed_fragments.clone(),
+        );
+        let batches = {
+            let schema = schema.clone();
+            stream::once(results)
+                .map_ok(move |results| {
+                    let batches = results
+                        .chunks(batch_size)
+                        .map(|chunk| {
+                            let row_ids = chunk.iter().map(|(row_id, _)| *row_id);
+                            let scores = chunk.iter().map(|(_, score)| *score);
+                            RecordBatch::try_new(
+                                schema.clone(),
+                                vec![
+                                    Arc::new(UInt64Array::from_iter_values(row_ids)),
+                                    Arc::new(Float32Array::from_iter_values(scores)),
+                                ],
+                            )
+                            .map_err(DataFusionError::from)
+                        })
+                        .collect::<Vec<_>>();
+                    stream::iter(batches)
+                })
+                .map_err(DataFusionError::from)
+                .try_flatten()
+        };
+        Ok(Box::pin(RecordBatchStreamAdapter::new(schema, batches)))
+    }
+
+    fn statistics(&self) -> Statistics {
+        Statistics::default()
+    }
+}
@@ -0,0 +1,322 @@
+// Copyright 2023 Lance Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use arrow_array::{
+    cast::AsArray,
+    types::{Float32Type, UInt64Type},
+    Float32Array, RecordBatch, UInt64Array,
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use datafusion::error::{DataFusionError, Result as DataFusionResult};
+use datafusion::execution::context::TaskContext;
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::{
+    ExecutionPlan, Partitioning, SendableRecordBatchStream, Statistics,
+};
+use futures::{stream, TryStreamExt};
+
+use crate::dataset::ROW_ID;
+use crate::index::vector::SCORE_COL;
+use crate::{Error, Result};
+
+/// How to fuse the ranked rows of a vector search and a full text search.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fusion {
+    /// Reciprocal rank fusion: a row scores `1 / (k + rank)` in each list, with
+    /// the ranks starting from 1.
+    ReciprocalRank { k: f32 },
+
+    /// Weighted sum of the scores, normalized to `[0, 1]` in each list. The vector
+    /// search has the weight `vector_weight`, and the full text search has the
+    /// weight `1 - vector_weight`.
+    Weighted { vector_weight: f32 },
+}
+
+impl Default for Fusion {
+    fn default() -> Self {
+        Self::ReciprocalRank { k: 60.0 }
+    }
+}
+
+impl Fusion {
+    /// Fuse the `(row_id, score)` lists of the vector search, whose scores are
+    /// distances, and of the full text search, whose scores are relevances.
+    ///
+    /// Returns the top `k` rows, sorted by descending fused score.
+    pub fn fuse(&self, vector: &[(u64, f32)], text: &[(u64, f32)], k: usize) -> Vec<(u64, f32)> {
+        let mut vector = vector.to_vec();
+        vector.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+        vector.truncate(k);
+        let mut text = text.to_vec();
+        text.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+        text.truncate(k);
+
+        let mut scores: HashMap<u64, f32> = HashMap::new();
+        match *self {
+            Self::ReciprocalRank { k } => {
+                for list in [&vector, &text] {
+                    for (rank, (row_id, _)) in list.iter().enumerate() {
+                        *scores.entry(*row_id).or_default() += 1.0 / (k + rank as f32 + 1.0);
+                    }
+                }
+            }
+            Self::Weighted { vector_weight } => {
+                let (min, max) = min_max(&vector);
+                for (row_id, distance) in &vector {
+                    // The nearest vector scores 1.
+                    let score = if max > min {
+                        (max - distance) / (max - min)
+                    } else {
+                        1.0
+                    };
+                    *scores.entry(*row_id).or_default() += vector_weight * score;
+                }
+                let (min, max) = min_max(&text);
+                for (row_id, relevance) in &text {
+                    let score = if max > min {
+                        (relevance - min) / (max - min)
+                    } else {
+                        1.0
+                    };
+                    *scores.entry(*row_id).or_default() += (1.0 - vector_weight) * score;
+                }
+            }
+        }
+
+        let mut fused = scores.into_iter().collect::<Vec<_>>();
+        fused.sort_by(|(a_id, a), (b_id, b)| b.total_cmp(a).then(a_id.cmp(b_id)));
+        fused.truncate(k);
+        fused
+    }
+}
+
+fn min_max(scores: &[(u64, f32)]) -> (f32, f32) {
+    scores
+        .iter()
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), (_, s)| {
+            (min.min(*s), max.max(*s))
+        })
+}
+
+/// [ExecutionPlan] that fuses the results of a vector search and a full text
+/// search.
+///
+/// Both inputs have the `_rowid` and `score` columns. It emits the `_rowid` and
+/// the fused `score` of the top `k` rows, sorted by descending score.
+pub struct HybridSearchExec {
+    /// The vector search, i.e., [KNNIndexExec](super::KNNIndexExec) or
+    /// [KNNFlatExec](super::KNNFlatExec).
+    vector: Arc<dyn ExecutionPlan>,
+
+    /// The full text search.
+    text: Arc<dyn ExecutionPlan>,
+
+    fusion: Fusion,
+
+    k: usize,
+}
+
+impl std::fmt::Debug for HybridSearchExec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "HybridSearch(fusion={:?}, k={})", self.fusion, self.k)
+    }
+}
+
+impl HybridSearchExec {
+    /// Create a new [HybridSearchExec] node.
+    ///
+    /// Returns an error if the preconditions are not met.
+    pub fn try_new(
+        vector: Arc<dyn ExecutionPlan>,
+        text: Arc<dyn ExecutionPlan>,
+        fusion: Fusion,
+        k: usize,
+    ) -> Result<Self> {
+        for input in [&vector, &text] {
+            let schema = input.schema();
+            if schema.field_with_name(ROW_ID).is_err() || schema.field_with_name(SCORE_COL).is_err()
+            {
+                return Err(Error::IO {
+                    message: format!(
+                        "HybridSearchExec node: input must have the {ROW_ID} and {SCORE_COL} columns, got: {schema}"
+                    ),
+                });
+            }
+        }
+        if let Fusion::Weighted { vector_weight } = fusion {
+            if !(0.0..=1.0).contains(&vector_weight) {
+                return Err(Error::IO {
+                    message: format!(
+                        "HybridSearchExec node: vector weight must be in [0, 1], got {vector_weight}"
+                    ),
+                });
+            }
+        }
+        Ok(Self {
+            vector,
+            text,
+            fusion,
+            k,
+        })
+    }
+}
+
+/// Collect the `(row_id, score)` of all the rows of the input.
+async fn collect_scores(input: SendableRecordBatchStream) -> DataFusionResult<Vec<(u64, f32)>> {
+    let batches = input.try_collect::<Vec<_>>().await?;
+    let mut scores = vec![];
+    for batch in batches {
+        let row_ids = batch
+            .column_by_name(ROW_ID)
+            .unwrap()
+            .as_primitive::<UInt64Type>();
+        let values = batch
+            .column_by_name(SCORE_COL)
+            .unwrap()
+            .as_primitive::<Float32Type>();
+        scores.extend(
+            row_ids
+                .values()
+                .iter()
+                .copied()
+                .zip(values.values().iter().copied()),
+        );
+    }
+    Ok(scores)
+}
+
+impl ExecutionPlan for HybridSearchExec {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            Field::new(ROW_ID, DataType::UInt64, false),
+            Field::new(SCORE_COL, DataType::Float32, false),
+        ]))
+    }
+
+    fn output_partitioning(&self) -> Partitioning {
+        Partitioning::RoundRobinBatch(1)
+    }
+
+    fn output_ordering(&self) -> Option<&[datafusion::physical_expr::PhysicalSortExpr]> {
+        None
+    }
+
+    fn children(&self) -> Vec<Arc<dyn ExecutionPlan>> {
+        vec![self.vector.clone(), self.text.clone()]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> DataFusionResult<Arc<dyn ExecutionPlan>> {
+        if children.len() != 2 {
+            return Err(DataFusionError::Internal(
+                "HybridSearchExec node must have exactly two children".to_string(),
+            ));
+        }
+        Ok(Arc::new(Self::try_new(
+            children[0].clone(),
+            children[1].clone(),
+            self.fusion,
+            self.k,
+        )?))
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> DataFusionResult<SendableRecordBatchStream> {
+        let vector = self.vector.execute(partition, context.clone())?;
+        let text = self.text.execute(partition, context)?;
+        let fusion = self.fusion;
+        let k = self.k;
+        let schema = self.schema();
+        let batch = {
+            let schema = schema.clone();
+            async move {
+                let (vector, text) =
+                    futures::try_join!(collect_scores(vector), collect_scores(text))?;
+                let fused = fusion.fuse(&vector, &text, k);
+                let row_ids = fused.iter().map(|(row_id, _)| *row_id);
+                let scores = fused.iter().map(|(_, score)| *score);
+                Ok::<_, DataFusionError>(RecordBatch::try_new(
+                    schema,
+                    vec![
+                        Arc::new(UInt64Array::from_iter_values(row_ids)),
+                        Arc::new(Float32Array::from_iter_values(scores)),
+                    ],
+                )?)
+            }
+        };
+        Ok(Box::pin(RecordBatchStreamAdapter::new(
+            schema,
+            stream::once(batch),
+        )))
+    }
+
+    fn statistics(&self) -> Statistics {
+        Statistics::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reciprocal_rank_fusion() {
+        let vector = [(1, 0.5), (2, 0.1), (3, 0.9)];
+        let text = [(3, 7.0), (4, 2.0), (2, 1.0)];
+        let fused = Fusion::ReciprocalRank { k: 60.0 }.fuse(&vector, &text, 3);
+
+        // Row 2 ranks 1st and 3rd, row 3 ranks 3rd and 1st.
+        assert_eq!(
+            fused.iter().map(|(row_id, _)| *row_id).collect::<Vec<_>>(),
+            [2, 3, 1]
+        );
+        let expected = 1.0 / 61.0 + 1.0 / 63.0;
+        assert!((fused[0].1 - expected).abs() < 1e-6);
+        assert_eq!(fused[0].1, fused[1].1);
+    }
+
+    #[test]
+    fn test_weighted_fusion() {
+        let vector = [(1, 0.5), (2, 0.1), (3, 0.9)];
+        let text = [(3, 7.0), (4, 2.0), (2, 1.0)];
+
+        let fused = Fusion::Weighted { vector_weight: 1.0 }.fuse(&vector, &text, 2);
+        assert_eq!(
+            fused.iter().map(|(row_id, _)| *row_id).collect::<Vec<_>>(),
+            [2, 1]
+        );
+        assert_eq!(fused[0].1, 1.0);
+
+        let fused = Fusion::Weighted { vector_weight: 0.3 }.fuse(&vector, &text, 4);
+        assert_eq!(
+            fused.iter().map(|(row_id, _)| *row_id).collect::<Vec<_>>(),
+            [3, 2, 1, 4]
+        );
+        // Row 3 is the farthest vector, and the most relevant text.
+        assert!((fused[0].1 - 0.7).abs() < 1e-6);
+    }
+}
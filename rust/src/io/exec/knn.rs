@@ -17,7 +17,7 @@ use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
 
-use arrow_array::RecordBatch;
+use arrow_array::{cast::AsArray, types::UInt64Type, RecordBatch};
 use arrow_schema::{DataType, Field, Schema};
 use datafusion::error::{DataFusionError, Result as DataFusionResult};
 use datafusion::physical_plan::{
@@ -25,7 +25,8 @@ use datafusion::physical_plan::{
     SendableRecordBatchStream, Statistics,
 };
 use futures::stream::Stream;
-use futures::FutureExt;
+use futures::{FutureExt, TryStreamExt};
+use roaring::RoaringTreemap;
 use tokio::sync::mpsc::Receiver;
 use tokio::task::JoinHandle;
 
@@ -240,12 +241,33 @@ pub struct KNNIndexStream {
 }
 
 impl KNNIndexStream {
-    pub fn new(dataset: Arc<Dataset>, index_name: &str, query: &Query) -> Self {
+    /// Search the vector index.
+    ///
+    /// If `prefilter` is presented, only the rows whose row ids it emits are searched.
+    pub fn new(
+        dataset: Arc<Dataset>,
+        index_name: &str,
+        query: &Query,
+        prefilter: Option<SendableRecordBatchStream>,
+    ) -> Self {
         let (tx, rx) = tokio::sync::mpsc::channel(2);
 
-        let q = query.clone();
+        let mut q = query.clone();
         let name = index_name.to_string();
         let bg_thread = tokio::spawn(async move {
+            if let Some(prefilter) = prefilter {
+                match collect_row_ids(prefilter).await {
+                    Ok(row_ids) => q.allow_list = Some(Arc::new(row_ids)),
+                    Err(e) => {
+                        tx.send(Err(DataFusionError::Execution(format!(
+                            "Failed to prefilter vector index: {name}: {e}"
+                        ))))
+                        .await
+                        .expect("KNNIndex failed to send message");
+                        return;
+                    }
+                }
+            }
             let index = match open_index(dataset, &q.column, &name).await {
                 Ok(idx) => idx,
                 Err(e) => {
@@ -284,6 +306,18 @@ impl KNNIndexStream {
     }
 }
 
+/// Collect the row ids emitted by the input.
+async fn collect_row_ids(mut input: SendableRecordBatchStream) -> DataFusionResult<RoaringTreemap> {
+    let mut row_ids = RoaringTreemap::new();
+    while let Some(batch) = input.try_next().await? {
+        let column = batch.column_by_name(ROW_ID).ok_or_else(|| {
+            DataFusionError::Execution(format!("{ROW_ID} column does not exist in the prefilter"))
+        })?;
+        row_ids.extend(column.as_primitive::<UInt64Type>().values().iter().copied());
+    }
+    Ok(row_ids)
+}
+
 impl DFRecordBatchStream for KNNIndexStream {
     fn schema(&self) -> arrow_schema::SchemaRef {
         Arc::new(Schema::new(vec![
@@ -330,21 +364,33 @@ pub struct KNNIndexExec {
     index_name: String,
     /// The vector query to execute.
     query: Query,
+    /// The rows to search, if presented. It emits the `_rowid` of the rows
+    /// matching a prefilter.
+    prefilter: Option<Arc<dyn ExecutionPlan>>,
 }
 
 impl std::fmt::Debug for KNNIndexExec {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "KNN(index, name={}, k={})",
-            self.index_name, self.query.k
+            "KNN(index, name={}, k={}, prefilter={})",
+            self.index_name,
+            self.query.k,
+            self.prefilter.is_some()
         )
     }
 }
 
 impl KNNIndexExec {
     /// Create a new [KNNIndexExec].
-    pub fn try_new(dataset: Arc<Dataset>, index_name: &str, query: &Query) -> Result<Self> {
+    ///
+    /// If `prefilter` is presented, only the rows whose row ids it emits are searched.
+    pub fn try_new(
+        dataset: Arc<Dataset>,
+        index_name: &str,
+        query: &Query,
+        prefilter: Option<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Self> {
         let schema = dataset.schema();
         if schema.field(query.column.as_str()).is_none() {
             return Err(Error::IO {
@@ -354,11 +400,22 @@ impl KNNIndexExec {
                 ),
             });
         };
+        if let Some(prefilter) = prefilter.as_ref() {
+            if prefilter.schema().field_with_name(ROW_ID).is_err() {
+                return Err(Error::IO {
+                    message: format!(
+                        "KNNIndexExec node: prefilter must have the {ROW_ID} column, got: {}",
+                        prefilter.schema()
+                    ),
+                });
+            }
+        }
 
         Ok(Self {
             dataset,
             index_name: index_name.to_string(),
             query: query.clone(),
+            prefilter,
         })
     }
 }
@@ -383,27 +440,43 @@ impl ExecutionPlan for KNNIndexExec {
         None
     }
 
-    /// KNNIndex is a leaf node, so returns zero children.
+    /// KNNIndex is a leaf node, unless it has a prefilter.
     fn children(&self) -> Vec<Arc<dyn ExecutionPlan>> {
-        vec![]
+        self.prefilter.iter().cloned().collect()
     }
 
     fn with_new_children(
         self: Arc<Self>,
-        _children: Vec<Arc<dyn ExecutionPlan>>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
     ) -> DataFusionResult<Arc<dyn ExecutionPlan>> {
-        Ok(self)
+        if children.len() != self.children().len() {
+            return Err(DataFusionError::Internal(
+                "KNNIndexExec node must have as many children as it had".to_string(),
+            ));
+        }
+        Ok(Arc::new(Self::try_new(
+            self.dataset.clone(),
+            &self.index_name,
+            &self.query,
+            children.into_iter().next(),
+        )?))
     }
 
     fn execute(
         &self,
-        _partition: usize,
-        _context: Arc<datafusion::execution::context::TaskContext>,
+        partition: usize,
+        context: Arc<datafusion::execution::context::TaskContext>,
     ) -> DataFusionResult<datafusion::physical_plan::SendableRecordBatchStream> {
+        let prefilter = self
+            .prefilter
+            .as_ref()
+            .map(|prefilter| prefilter.execute(partition, context))
+            .transpose()?;
         Ok(Box::pin(KNNIndexStream::new(
             self.dataset.clone(),
             &self.index_name,
             &self.query,
+            prefilter,
         )))
     }
 
@@ -511,6 +584,7 @@ mod tests {
                 refine_factor: None,
                 metric_type: MetricType::L2,
                 use_index: false,
+                allow_list: None,
             },
         )
         .await
@@ -544,6 +618,7 @@ mod tests {
             refine_factor: None,
             metric_type: MetricType::L2,
             use_index: false,
+            allow_list: None,
         };
 
         let input: Arc<dyn ExecutionPlan> = Arc::new(TestingExec::new(vec![batch]));
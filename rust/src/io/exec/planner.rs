@@ -26,7 +26,7 @@ use datafusion::{
     logical_expr::{
         col,
         expr::{InList, ScalarFunction},
-        BinaryExpr, BuiltinScalarFunction, Like, Operator,
+        Between, BinaryExpr, BuiltinScalarFunction, Like, Operator,
     },
     physical_expr::execution_props::ExecutionProps,
     physical_plan::{
@@ -330,6 +330,17 @@ impl Planner {
                     .collect::<Result<Vec<_>>>()?;
                 Ok(value_expr.in_list(list_exprs, *negated))
             }
+            SQLExpr::Between {
+                expr,
+                negated,
+                low,
+                high,
+            } => Ok(Expr::Between(Between::new(
+                Box::new(self.parse_sql_expr(expr)?),
+                *negated,
+                Box::new(self.parse_sql_expr(low)?),
+                Box::new(self.parse_sql_expr(high)?),
+            ))),
             SQLExpr::Nested(inner) => self.parse_sql_expr(inner.as_ref()),
             SQLExpr::Function(func) => self.parse_function(func),
             SQLExpr::Like {
@@ -364,6 +375,16 @@ impl Planner {
         coerce_filter_type_to_boolean(resolved)
     }
 
+    /// Create Logical [Expr] from a SQL expression, i.e., `a * 2 + 1`.
+    ///
+    /// Unlike [Self::parse_filter], the result is not required to be a boolean.
+    pub fn parse_expr(&self, expr: &str) -> Result<Expr> {
+        let ast_expr = parse_sql_filter(expr)?;
+        let expr = self.parse_sql_expr(&ast_expr)?;
+        let schema = Schema::try_from(self.schema.as_ref())?;
+        resolve_expr(&expr, &schema)
+    }
+
     /// Create the [`PhysicalExpr`] from a logical [`Expr`]
     pub fn create_physical_expr(&self, expr: &Expr) -> Result<Arc<dyn PhysicalExpr>> {
         use crate::datafusion::physical_expr::Column;
@@ -426,6 +447,23 @@ impl Planner {
 
                 Arc::new(InListExpr::new(expr, list, *negated, None))
             }
+            Expr::Between(Between {
+                expr,
+                negated,
+                low,
+                high,
+            }) => {
+                let value = expr.as_ref().clone();
+                let range = value
+                    .clone()
+                    .gt_eq(low.as_ref().clone())
+                    .and(value.lt_eq(high.as_ref().clone()));
+                if *negated {
+                    Arc::new(NotExpr::new(self.create_physical_expr(&range)?))
+                } else {
+                    self.create_physical_expr(&range)?
+                }
+            }
             Expr::Like(expr) => Arc::new(LikeExpr::new(
                 expr.negated,
                 true,
@@ -594,6 +632,29 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_expr() {
+        let schema = Arc::new(Schema::new(vec![Field::new("x", DataType::Int64, false)]));
+
+        let planner = Planner::new(schema.clone());
+
+        let expected = col("x") * lit(2_i64) + lit(1_i64);
+        let expr = planner.parse_expr("x * 2 + 1").unwrap();
+        assert_eq!(expr, expected);
+
+        let physical_expr = planner.create_physical_expr(&expr).unwrap();
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![Arc::new(Int64Array::from_iter_values(0..5)) as ArrayRef],
+        )
+        .unwrap();
+        let values = physical_expr.evaluate(&batch).unwrap();
+        assert_eq!(
+            values.into_array(0).as_ref(),
+            &Int64Array::from(vec![1, 3, 5, 7, 9])
+        );
+    }
+
     #[test]
     fn test_sql_like() {
         let schema = Arc::new(Schema::new(vec![Field::new("s", DataType::Utf8, true)]));
@@ -678,6 +739,38 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_sql_between() {
+        let schema = Arc::new(Schema::new(vec![Field::new("i", DataType::Int32, true)]));
+
+        let planner = Planner::new(schema.clone());
+
+        // The bounds are resolved to the type of the column.
+        let expr = planner.parse_filter("i BETWEEN 3 AND 5").unwrap();
+        assert_eq!(expr, col("i").between(lit(3_i32), lit(5_i32)));
+
+        let batch =
+            RecordBatch::try_new(schema, vec![Arc::new(Int32Array::from_iter_values(0..10))])
+                .unwrap();
+        let evaluate = |filter: &str| {
+            let expr = planner.parse_filter(filter).unwrap();
+            let physical_expr = planner.create_physical_expr(&expr).unwrap();
+            physical_expr.evaluate(&batch).unwrap().into_array(0)
+        };
+        assert_eq!(
+            evaluate("i BETWEEN 3 AND 5").as_ref(),
+            &BooleanArray::from(vec![
+                false, false, false, true, true, true, false, false, false, false
+            ])
+        );
+        assert_eq!(
+            evaluate("i NOT BETWEEN 3 AND 5").as_ref(),
+            &BooleanArray::from(vec![
+                true, true, true, false, false, false, true, true, true, true
+            ])
+        );
+    }
+
     #[test]
     fn test_sql_is_null() {
         let schema = Arc::new(Schema::new(vec![Field::new("s", DataType::Utf8, true)]));
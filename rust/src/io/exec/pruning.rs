@@ -0,0 +1,391 @@
+// Copyright 2023 Lance Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Skip the fragments and batches that cannot match a filter, using the page statistics.
+
+use std::sync::Arc;
+
+use arrow_array::{ArrayRef, UInt64Array};
+use arrow_schema::{DataType, Field as ArrowField, Schema as ArrowSchema, SchemaRef};
+use datafusion::common::Column as StatisticsColumn;
+use datafusion::logical_expr::Accumulator;
+use datafusion::physical_expr::expressions::{
+    Column as DataFusionColumn, MaxAccumulator, MinAccumulator,
+};
+use datafusion::physical_optimizer::pruning::{PruningPredicate, PruningStatistics};
+use datafusion::physical_plan::PhysicalExpr;
+use datafusion::scalar::ScalarValue;
+
+use crate::datafusion::physical_expr::{column_names_in_expr, Column};
+use crate::dataset::fragment::FragmentReader;
+use crate::datatypes::Schema;
+use crate::format::PageStatistics;
+use crate::{Error, Result};
+
+/// Decides which fragments, and which batches of a fragment, may have rows matching
+/// a filter, from the min / max values and null counts of the pages of the columns
+/// in the filter.
+pub(crate) struct StatisticsPruner {
+    predicate: PruningPredicate,
+
+    /// Schema of the columns in the filter, named by their qualified names.
+    schema: SchemaRef,
+}
+
+impl StatisticsPruner {
+    /// Create a pruner for the `filter` on the dataset `schema`.
+    pub(crate) fn try_new(filter: Arc<dyn PhysicalExpr>, schema: &Schema) -> Result<Self> {
+        let mut columns = column_names_in_expr(filter.as_ref());
+        columns.sort();
+        columns.dedup();
+        let fields = columns
+            .iter()
+            .map(|name| {
+                let field = schema.field(name).ok_or_else(|| Error::Schema {
+                    message: format!("Column {} does not exist in the dataset", name),
+                })?;
+                Ok(ArrowField::new(name, field.data_type(), field.nullable))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let schema = Arc::new(ArrowSchema::new(fields));
+
+        // DataFusion only knows about its own column expressions, that are
+        // resolved by their index in the schema.
+        let expr = to_datafusion_columns(filter, &schema)?;
+        let predicate = PruningPredicate::try_new(expr, schema.clone())?;
+        Ok(Self { predicate, schema })
+    }
+
+    /// Returns, for each batch of the fragment, if it may have matching rows.
+    pub(crate) fn prune(&self, reader: &FragmentReader) -> Result<Vec<bool>> {
+        let statistics = FragmentStatistics {
+            reader,
+            schema: &self.schema,
+        };
+        Ok(self.predicate.prune(&statistics)?)
+    }
+
+    /// Returns, for each fragment, if it may have matching rows.
+    ///
+    /// The statistics of all the pages of a fragment are aggregated, so a fragment is
+    /// skipped without reading any of its batches.
+    pub(crate) fn prune_fragments(&self, readers: &[FragmentReader]) -> Result<Vec<bool>> {
+        let columns = self
+            .schema
+            .fields()
+            .iter()
+            .map(|field| aggregate_statistics(readers, field))
+            .collect::<Result<Vec<_>>>()?;
+        let statistics = FragmentsStatistics {
+            num_fragments: readers.len(),
+            schema: &self.schema,
+            columns,
+        };
+        Ok(self.predicate.prune(&statistics)?)
+    }
+}
+
+fn to_datafusion_columns(
+    expr: Arc<dyn PhysicalExpr>,
+    schema: &ArrowSchema,
+) -> Result<Arc<dyn PhysicalExpr>> {
+    if let Some(column) = expr.as_any().downcast_ref::<Column>() {
+        let index = schema.index_of(&column.name)?;
+        return Ok(Arc::new(DataFusionColumn::new(&column.name, index)));
+    }
+    let children = expr.children();
+    if children.is_empty() {
+        return Ok(expr);
+    }
+    let children = children
+        .into_iter()
+        .map(|child| to_datafusion_columns(child, schema))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(expr.with_new_children(children)?)
+}
+
+/// The page statistics of a fragment, where each batch is a container.
+struct FragmentStatistics<'a> {
+    reader: &'a FragmentReader,
+    schema: &'a ArrowSchema,
+}
+
+impl FragmentStatistics<'_> {
+    /// The statistics of a column, if they are usable for all the batches.
+    fn page_statistics(&self, column: &StatisticsColumn) -> Option<&PageStatistics> {
+        let data_type: &DataType = self.schema.field_with_name(&column.name).ok()?.data_type();
+        self.reader.page_statistics(&column.name).filter(|stats| {
+            stats.min_values.data_type() == data_type
+                && stats.null_counts.len() == self.num_containers()
+        })
+    }
+}
+
+impl PruningStatistics for FragmentStatistics<'_> {
+    fn min_values(&self, column: &StatisticsColumn) -> Option<ArrayRef> {
+        self.page_statistics(column)
+            .map(|stats| stats.min_values.clone())
+    }
+
+    fn max_values(&self, column: &StatisticsColumn) -> Option<ArrayRef> {
+        self.page_statistics(column)
+            .map(|stats| stats.max_values.clone())
+    }
+
+    fn num_containers(&self) -> usize {
+        self.reader.num_batches()
+    }
+
+    fn null_counts(&self, column: &StatisticsColumn) -> Option<ArrayRef> {
+        self.page_statistics(column).map(|stats| {
+            let null_counts = stats.null_counts.iter().map(|c| *c as u64);
+            Arc::new(UInt64Array::from_iter_values(null_counts)) as ArrayRef
+        })
+    }
+}
+
+/// Statistics of one column over several fragments, with one value per fragment.
+struct ColumnStatistics {
+    min_values: ArrayRef,
+    max_values: ArrayRef,
+    null_counts: ArrayRef,
+}
+
+/// Aggregate the page statistics of the column, in each fragment. The values are
+/// null for the fragments without statistics, so they are never skipped.
+fn aggregate_statistics(
+    readers: &[FragmentReader],
+    field: &ArrowField,
+) -> Result<ColumnStatistics> {
+    let data_type = field.data_type();
+    let mut min_values = Vec::with_capacity(readers.len());
+    let mut max_values = Vec::with_capacity(readers.len());
+    let mut null_counts = Vec::with_capacity(readers.len());
+    for reader in readers {
+        let stats = reader
+            .page_statistics(field.name())
+            .filter(|stats| stats.min_values.data_type() == data_type);
+        if let Some(stats) = stats {
+            let mut min = MinAccumulator::try_new(data_type)?;
+            let mut max = MaxAccumulator::try_new(data_type)?;
+            min.update_batch(&[stats.min_values.clone()])?;
+            max.update_batch(&[stats.max_values.clone()])?;
+            // A page without max value, i.e., a string that could not be rounded up,
+            // leaves the max of the fragment unbounded.
+            let max_value = if stats.max_values.null_count() > stats.min_values.null_count() {
+                ScalarValue::try_from(data_type)?
+            } else {
+                max.evaluate()?
+            };
+            min_values.push(min.evaluate()?);
+            max_values.push(max_value);
+            null_counts.push(Some(stats.null_counts.iter().sum::<i64>() as u64));
+        } else {
+            min_values.push(ScalarValue::try_from(data_type)?);
+            max_values.push(ScalarValue::try_from(data_type)?);
+            null_counts.push(None);
+        }
+    }
+    Ok(ColumnStatistics {
+        min_values: ScalarValue::iter_to_array(min_values)?,
+        max_values: ScalarValue::iter_to_array(max_values)?,
+        null_counts: Arc::new(UInt64Array::from(null_counts)),
+    })
+}
+
+/// The aggregated page statistics of several fragments, where each fragment is a container.
+struct FragmentsStatistics<'a> {
+    num_fragments: usize,
+    schema: &'a ArrowSchema,
+    /// Statistics of the columns, in the order of the schema.
+    columns: Vec<ColumnStatistics>,
+}
+
+impl FragmentsStatistics<'_> {
+    fn column_statistics(&self, column: &StatisticsColumn) -> Option<&ColumnStatistics> {
+        let index = self.schema.index_of(&column.name).ok()?;
+        self.columns.get(index)
+    }
+}
+
+impl PruningStatistics for FragmentsStatistics<'_> {
+    fn min_values(&self, column: &StatisticsColumn) -> Option<ArrayRef> {
+        self.column_statistics(column)
+            .map(|stats| stats.min_values.clone())
+    }
+
+    fn max_values(&self, column: &StatisticsColumn) -> Option<ArrayRef> {
+        self.column_statistics(column)
+            .map(|stats| stats.max_values.clone())
+    }
+
+    fn num_containers(&self) -> usize {
+        self.num_fragments
+    }
+
+    fn null_counts(&self, column: &StatisticsColumn) -> Option<ArrayRef> {
+        self.column_statistics(column)
+            .map(|stats| stats.null_counts.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arrow_array::{Int32Array, RecordBatch, RecordBatchIterator, StringArray};
+    use arrow_schema::DataType;
+    use futures::TryStreamExt;
+    use tempfile::tempdir;
+
+    use crate::dataset::{Dataset, WriteParams};
+    use crate::io::exec::Planner;
+
+    #[tokio::test]
+    async fn test_prune_batches() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("i", DataType::Int32, false),
+            ArrowField::new("s", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from_iter_values(0..100)),
+                Arc::new(StringArray::from_iter(
+                    (0..100).map(|i| (i >= 10).then(|| format!("s-{:03}", i))),
+                )),
+            ],
+        )
+        .unwrap();
+        let write_params = WriteParams {
+            max_rows_per_group: 10,
+            ..Default::default()
+        };
+        let batches = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+        let dataset = Dataset::write(batches, test_uri, Some(write_params))
+            .await
+            .unwrap();
+
+        let fragment = &dataset.get_fragments()[0];
+        let reader = fragment.open(dataset.schema()).await.unwrap();
+        assert_eq!(reader.num_batches(), 10);
+
+        let planner = Planner::new(schema.clone());
+        let prune = |filter: &str| {
+            let expr = planner
+                .create_physical_expr(&planner.parse_filter(filter).unwrap())
+                .unwrap();
+            let pruner = StatisticsPruner::try_new(expr, dataset.schema()).unwrap();
+            pruner
+                .prune(&reader)
+                .unwrap()
+                .into_iter()
+                .enumerate()
+                .filter_map(|(batch_id, keep)| keep.then_some(batch_id))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(prune("i >= 95"), vec![9]);
+        assert_eq!(prune("i > 200 OR i < 5"), vec![0]);
+        // The first batch only has nulls, so it has no min / max values and is kept.
+        assert_eq!(prune("i < 35 AND s = 's-021'"), vec![0, 2]);
+        assert_eq!(prune("s < 's-015'"), vec![0, 1]);
+        // Not supported by the statistics, so nothing is skipped.
+        assert_eq!(prune("i % 7 = 0"), (0..10).collect::<Vec<_>>());
+
+        // The skipped batches do not change the results.
+        let results = dataset
+            .scan()
+            .filter("i >= 95")
+            .unwrap()
+            .try_into_stream()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let num_rows = results.iter().map(|b| b.num_rows()).sum::<usize>();
+        assert_eq!(num_rows, 5);
+    }
+
+    #[tokio::test]
+    async fn test_prune_fragments() {
+        let test_dir = tempdir().unwrap();
+        let test_uri = test_dir.path().to_str().unwrap();
+
+        let schema = Arc::new(ArrowSchema::new(vec![ArrowField::new(
+            "s",
+            DataType::Utf8,
+            false,
+        )]));
+        // Long strings, so the min / max values are truncated.
+        let suffix = "x".repeat(100);
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(StringArray::from_iter_values(
+                (0..100).map(|i| format!("{:03}-{suffix}", i)),
+            ))],
+        )
+        .unwrap();
+        let write_params = WriteParams {
+            max_rows_per_file: 20,
+            max_rows_per_group: 10,
+            ..Default::default()
+        };
+        let batches = RecordBatchIterator::new(vec![Ok(batch)], schema.clone());
+        let dataset = Dataset::write(batches, test_uri, Some(write_params))
+            .await
+            .unwrap();
+        let fragments = dataset.get_fragments();
+        assert_eq!(fragments.len(), 5);
+        let mut readers = vec![];
+        for fragment in fragments.iter() {
+            readers.push(fragment.open(dataset.schema()).await.unwrap());
+        }
+
+        let planner = Planner::new(schema.clone());
+        let prune = |filter: &str| {
+            let expr = planner
+                .create_physical_expr(&planner.parse_filter(filter).unwrap())
+                .unwrap();
+            let pruner = StatisticsPruner::try_new(expr, dataset.schema()).unwrap();
+            pruner
+                .prune_fragments(&readers)
+                .unwrap()
+                .into_iter()
+                .enumerate()
+                .filter_map(|(fragment_id, keep)| keep.then_some(fragment_id))
+                .collect::<Vec<_>>()
+        };
+        // The max values are rounded up, so the last value of a fragment still matches.
+        assert_eq!(prune(&format!("s = '059-{suffix}'")), vec![2]);
+        assert_eq!(prune("s >= '080'"), vec![4]);
+        assert_eq!(prune("s < '000'"), Vec::<usize>::new());
+
+        let results = dataset
+            .scan()
+            .filter(&format!("s >= '059-{suffix}' AND s < '061'"))
+            .unwrap()
+            .try_into_stream()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let num_rows = results.iter().map(|b| b.num_rows()).sum::<usize>();
+        assert_eq!(num_rows, 2);
+    }
+}
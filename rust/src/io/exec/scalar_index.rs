@@ -0,0 +1,229 @@
+// Copyright 2023 Lance Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use arrow_array::{RecordBatch, UInt64Array};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use async_recursion::async_recursion;
+use datafusion::error::{DataFusionError, Result as DataFusionResult};
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::{
+    ExecutionPlan, Partitioning, SendableRecordBatchStream, Statistics,
+};
+use futures::stream::{self, TryStreamExt};
+use roaring::RoaringTreemap;
+
+use crate::dataset::fragment::FileFragment;
+use crate::dataset::{Dataset, ROW_ID};
+use crate::format::Fragment;
+use crate::index::scalar::{ScalarIndex, ScalarQuery};
+use crate::Result;
+
+/// A search of one scalar index.
+#[derive(Debug, Clone)]
+pub struct ScalarIndexQuery {
+    /// Name of the index.
+    pub index_name: String,
+
+    pub index: Arc<dyn ScalarIndex>,
+
+    pub query: ScalarQuery,
+
+    /// Fragments which are not covered by the index.
+    pub unindexed_fragments: Vec<Fragment>,
+}
+
+/// The searches of scalar indices, combined by the `AND` and `OR` of a filter.
+#[derive(Debug, Clone)]
+pub enum ScalarIndexExpr {
+    Query(ScalarIndexQuery),
+    And(Box<ScalarIndexExpr>, Box<ScalarIndexExpr>),
+    Or(Box<ScalarIndexExpr>, Box<ScalarIndexExpr>),
+}
+
+impl std::fmt::Display for ScalarIndexExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Query(query) => write!(
+                f,
+                "{}: {:?}, unindexed_fragments={}",
+                query.index_name,
+                query.query,
+                query.unindexed_fragments.len()
+            ),
+            Self::And(left, right) => write!(f, "({left}) AND ({right})"),
+            Self::Or(left, right) => write!(f, "({left}) OR ({right})"),
+        }
+    }
+}
+
+impl ScalarIndexExpr {
+    /// Search the indices, and combine their results.
+    ///
+    /// All the rows of the fragments not covered by an index are matched by its
+    /// search, so the result is a superset of the matching rows, which may contain
+    /// deleted rows.
+    #[async_recursion]
+    async fn evaluate(&self, dataset: &Arc<Dataset>) -> Result<RoaringTreemap> {
+        match self {
+            Self::Query(query) => {
+                let mut row_ids = query.index.search(&query.query).await?;
+                for fragment in &query.unindexed_fragments {
+                    let fragment = FileFragment::new(dataset.clone(), fragment.clone());
+                    let start = (fragment.id() as u64) << 32;
+                    let num_rows = fragment.fragment_length().await? as u64;
+                    row_ids.insert_range(start..start + num_rows);
+                }
+                Ok(row_ids)
+            }
+            Self::And(left, right) => {
+                let (left, right) =
+                    futures::try_join!(left.evaluate(dataset), right.evaluate(dataset))?;
+                Ok(left & right)
+            }
+            Self::Or(left, right) => {
+                let (left, right) =
+                    futures::try_join!(left.evaluate(dataset), right.evaluate(dataset))?;
+                Ok(left | right)
+            }
+        }
+    }
+}
+
+/// [ExecutionPlan] that searches scalar indices for the row ids matching a filter.
+///
+/// The rows of the fragments added after an index was built are all returned,
+/// so the filter must still be applied on the rows.
+pub struct ScalarIndexExec {
+    dataset: Arc<Dataset>,
+
+    expr: ScalarIndexExpr,
+
+    batch_size: usize,
+}
+
+impl std::fmt::Debug for ScalarIndexExec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ScalarIndex({})", self.expr)
+    }
+}
+
+impl ScalarIndexExec {
+    /// Create a new [ScalarIndexExec].
+    pub fn new(dataset: Arc<Dataset>, expr: ScalarIndexExpr, batch_size: usize) -> Self {
+        Self {
+            dataset,
+            expr,
+            batch_size,
+        }
+    }
+}
+
+/// Search the indices, and collect the row ids of the rows which are not deleted.
+async fn search_row_ids(dataset: Arc<Dataset>, expr: ScalarIndexExpr) -> Result<Vec<u64>> {
+    let matched = expr.evaluate(&dataset).await?;
+
+    // The indices may refer to the fragments or rows deleted after they were built.
+    let fragments = dataset
+        .get_fragments()
+        .into_iter()
+        .map(|f| (f.id() as u64, f))
+        .collect::<HashMap<_, _>>();
+    let mut row_ids = Vec::with_capacity(matched.len() as usize);
+    for (fragment_id, offsets) in matched.bitmaps() {
+        let fragment_id = fragment_id as u64;
+        let Some(fragment) = fragments.get(&fragment_id) else {
+            continue;
+        };
+        let deletion_vector = fragment.get_deletion_vector().await?.unwrap_or_default();
+        row_ids.extend(
+            offsets
+                .iter()
+                .filter(|offset| !deletion_vector.contains(*offset))
+                .map(|offset| (fragment_id << 32) | offset as u64),
+        );
+    }
+    Ok(row_ids)
+}
+
+impl ExecutionPlan for ScalarIndexExec {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        Arc::new(Schema::new(vec![Field::new(
+            ROW_ID,
+            DataType::UInt64,
+            false,
+        )]))
+    }
+
+    fn output_partitioning(&self) -> Partitioning {
+        Partitioning::RoundRobinBatch(1)
+    }
+
+    fn output_ordering(&self) -> Option<&[datafusion::physical_expr::PhysicalSortExpr]> {
+        None
+    }
+
+    /// ScalarIndex is a leaf node, so returns zero children.
+    fn children(&self) -> Vec<Arc<dyn ExecutionPlan>> {
+        vec![]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        _children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> DataFusionResult<Arc<dyn ExecutionPlan>> {
+        Ok(self)
+    }
+
+    fn execute(
+        &self,
+        _partition: usize,
+        _context: Arc<datafusion::execution::context::TaskContext>,
+    ) -> DataFusionResult<SendableRecordBatchStream> {
+        let schema = self.schema();
+        let batch_size = self.batch_size;
+        let row_ids = search_row_ids(self.dataset.clone(), self.expr.clone());
+        let batches = {
+            let schema = schema.clone();
+            stream::once(row_ids)
+                .map_ok(move |row_ids| {
+                    let batches = row_ids
+                        .chunks(batch_size)
+                        .map(|chunk| {
+                            RecordBatch::try_new(
+                                schema.clone(),
+                                vec![Arc::new(UInt64Array::from(chunk.to_vec()))],
+                            )
+                            .map_err(DataFusionError::from)
+                        })
+                        .collect::<Vec<_>>();
+                    stream::iter(batches)
+                })
+                .map_err(DataFusionError::from)
+                .try_flatten()
+        };
+        Ok(Box::pin(RecordBatchStreamAdapter::new(schema, batches)))
+    }
+
+    fn statistics(&self) -> Statistics {
+        Statistics::default()
+    }
+}
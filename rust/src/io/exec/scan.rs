@@ -22,7 +22,7 @@ use arrow_array::RecordBatch;
 use arrow_schema::{DataType, Field, Schema as ArrowSchema, SchemaRef};
 use datafusion::error::{DataFusionError, Result};
 use datafusion::physical_plan::{
-    ExecutionPlan, Partitioning, RecordBatchStream, SendableRecordBatchStream,
+    ExecutionPlan, Partitioning, PhysicalExpr, RecordBatchStream, SendableRecordBatchStream,
 };
 use futures::stream::Stream;
 use futures::{stream, Future};
@@ -33,28 +33,44 @@ use crate::dataset::{Dataset, ROW_ID};
 use crate::datatypes::Schema;
 use crate::format::Fragment;
 
+use super::pruning::StatisticsPruner;
+
+/// Open the fragment, and find the batches to read.
 async fn open_file(
     file_fragment: FileFragment,
     projection: Arc<Schema>,
     with_row_id: bool,
-) -> Result<FragmentReader> {
+    pruner: Option<Arc<StatisticsPruner>>,
+) -> Result<(FragmentReader, Vec<usize>)> {
     let mut reader = file_fragment.open(projection.as_ref()).await?;
     if with_row_id {
         reader.with_row_id();
     };
-    Ok(reader)
+    let batch_ids = if let Some(pruner) = pruner {
+        // Skip the batches whose statistics show that no row can match.
+        pruner
+            .prune(&reader)?
+            .into_iter()
+            .enumerate()
+            .filter_map(|(batch_id, keep)| keep.then_some(batch_id))
+            .collect()
+    } else {
+        (0..reader.num_batches()).collect()
+    };
+    Ok((reader, batch_ids))
 }
 
 /// Convert a [`FragmentReader`] into a [`Stream`] of [`RecordBatch`].
 fn scan_batches(
     reader: FragmentReader,
+    batch_ids: Vec<usize>,
     read_size: usize,
 ) -> impl Stream<Item = Result<impl Future<Output = Result<RecordBatch>>>> {
     // To make sure the reader lives long enough, we put it in an Arc.
     let reader = Arc::new(reader);
     let reader2 = reader.clone();
 
-    let read_params_iter = (0..reader.num_batches()).flat_map(move |batch_id| {
+    let read_params_iter = batch_ids.into_iter().flat_map(move |batch_id| {
         let rows_in_batch = reader.num_rows_in_batch(batch_id);
         (0..rows_in_batch)
             .step_by(read_size)
@@ -92,7 +108,9 @@ impl LanceStream {
     ///
     ///  - ***dataset***: The source dataset.
     ///  - ***projection***: the projection [Schema].
-    ///  - ***filter***: filter [`PhysicalExpr`], optional.
+    ///  - ***filter***: filter [`PhysicalExpr`], optional. It is only used to skip the
+    ///    batches that can not match, with the page statistics. The rows of the other
+    ///    batches are not filtered.
     ///  - ***read_size***: the number of rows to read for each request.
     ///  - ***batch_readahead***: the number of batches to read ahead.
     ///  - ***fragment_readahead***: the number of fragments to read ahead (only
@@ -104,6 +122,7 @@ impl LanceStream {
         dataset: Arc<Dataset>,
         fragments: Arc<Vec<Fragment>>,
         projection: Arc<Schema>,
+        filter: Option<Arc<dyn PhysicalExpr>>,
         read_size: usize,
         batch_readahead: usize,
         fragment_readahead: usize,
@@ -111,6 +130,9 @@ impl LanceStream {
         scan_in_order: bool,
     ) -> Result<Self> {
         let project_schema = projection.clone();
+        let pruner = filter
+            .map(|filter| StatisticsPruner::try_new(filter, dataset.schema()).map(Arc::new))
+            .transpose()?;
 
         let file_fragments = fragments
             .iter()
@@ -120,9 +142,14 @@ impl LanceStream {
         let inner_stream = if scan_in_order {
             stream::iter(file_fragments)
                 .then(move |file_fragment| {
-                    open_file(file_fragment, project_schema.clone(), with_row_id)
+                    open_file(
+                        file_fragment,
+                        project_schema.clone(),
+                        with_row_id,
+                        pruner.clone(),
+                    )
                 })
-                .map_ok(move |reader| scan_batches(reader, read_size))
+                .map_ok(move |(reader, batch_ids)| scan_batches(reader, batch_ids, read_size))
                 .try_flatten()
                 // We buffer up to `batch_readahead` batches across all streams.
                 .try_buffered(batch_readahead)
@@ -130,9 +157,14 @@ impl LanceStream {
         } else {
             stream::iter(file_fragments)
                 .then(move |file_fragment| {
-                    open_file(file_fragment, project_schema.clone(), with_row_id)
+                    open_file(
+                        file_fragment,
+                        project_schema.clone(),
+                        with_row_id,
+                        pruner.clone(),
+                    )
                 })
-                .map_ok(move |reader| scan_batches(reader, read_size))
+                .map_ok(move |(reader, batch_ids)| scan_batches(reader, batch_ids, read_size))
                 // When we flatten the streams (one stream per fragment), we allow
                 // `fragment_readahead` stream to be read concurrently.
                 .try_flatten_unordered(fragment_readahead)
@@ -184,6 +216,7 @@ pub struct LanceScanExec {
     dataset: Arc<Dataset>,
     fragments: Arc<Vec<Fragment>>,
     projection: Arc<Schema>,
+    filter: Option<Arc<dyn PhysicalExpr>>,
     read_size: usize,
     batch_readahead: usize,
     fragment_readahead: usize,
@@ -201,21 +234,28 @@ impl std::fmt::Debug for LanceScanExec {
             .collect::<Vec<_>>();
         write!(
             f,
-            "LanceScan(uri={}, projection={:#?}, row_id={}, ordered={})",
+            "LanceScan(uri={}, projection={:#?}, row_id={}, ordered={}",
             self.dataset.data_dir(),
             columns,
             self.with_row_id,
             self.ordered_output
-        )
+        )?;
+        if let Some(filter) = self.filter.as_ref() {
+            write!(f, ", pruning_filter={}", filter)?;
+        }
+        write!(f, ")")
     }
 }
 
 impl LanceScanExec {
+    /// Create a new scan node. The `filter` is only used to skip the batches that can
+    /// not match, see [LanceStream::try_new].
     #[allow(clippy::too_many_arguments)]
     pub fn new(
         dataset: Arc<Dataset>,
         fragments: Arc<Vec<Fragment>>,
         projection: Arc<Schema>,
+        filter: Option<Arc<dyn PhysicalExpr>>,
         read_size: usize,
         batch_readahead: usize,
         fragment_readahead: usize,
@@ -226,6 +266,7 @@ impl LanceScanExec {
             dataset,
             fragments,
             projection,
+            filter,
             read_size,
             batch_readahead,
             fragment_readahead,
@@ -280,6 +321,7 @@ impl ExecutionPlan for LanceScanExec {
             self.dataset.clone(),
             self.fragments.clone(),
             self.projection.clone(),
+            self.filter.clone(),
             self.read_size,
             self.batch_readahead,
             self.fragment_readahead,
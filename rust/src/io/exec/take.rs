@@ -317,6 +317,7 @@ mod tests {
             dataset.clone(),
             dataset.fragments().clone(),
             scan_schema,
+            None,
             10,
             10,
             4,
@@ -349,6 +350,7 @@ mod tests {
             dataset.clone(),
             dataset.fragments().clone(),
             scan_schema,
+            None,
             10,
             10,
             4,
@@ -381,6 +383,7 @@ mod tests {
             dataset.clone(),
             dataset.fragments().clone(),
             scan_schema,
+            None,
             10,
             10,
             4,
@@ -35,6 +35,7 @@ use tokio::sync::RwLock;
 use url::Url;
 
 use crate::error::{Error, Result};
+use crate::io::commit::{CommitHandler, RenameCommitHandler, UnsafeCommitHandler};
 use crate::io::object_reader::CloudObjectReader;
 use crate::io::object_writer::ObjectWriter;
 
@@ -49,6 +50,7 @@ pub struct ObjectStore {
     scheme: String,
     base_path: Path,
     block_size: usize,
+    pub(crate) commit_handler: Arc<dyn CommitHandler>,
 }
 
 impl std::fmt::Display for ObjectStore {
@@ -202,6 +204,12 @@ pub struct ObjectStoreParams {
 
     // Custom AWS Credentials
     pub aws_credentials: Option<Arc<dyn CredentialProvider<Credential = ObjectStoreAwsCredential>>>,
+
+    /// Handler to commit new dataset versions.
+    /// Object stores without atomic rename, i.e., S3, need a lock based [CommitHandler]
+    /// to be safe against concurrent writers. The default handler on S3 is an
+    /// [UnsafeCommitHandler](crate::io::commit::UnsafeCommitHandler).
+    pub commit_handler: Option<Arc<dyn CommitHandler>>,
 }
 
 // Need this for setting a non-zero default duration
@@ -211,6 +219,7 @@ impl Default for ObjectStoreParams {
             object_store_wrapper: None,
             s3_credentials_refresh_offset: Duration::from_secs(60),
             aws_credentials: None,
+            commit_handler: None,
         }
     }
 }
@@ -246,6 +255,7 @@ impl ObjectStore {
                     .object_store_wrapper
                     .map(|w| w.wrap(object_store.inner.clone()))
                     .unwrap_or(object_store.inner),
+                commit_handler: params.commit_handler.unwrap_or(object_store.commit_handler),
                 ..object_store
             },
             base_path,
@@ -271,6 +281,7 @@ impl ObjectStore {
                 scheme: String::from("file"),
                 base_path: Path::from_absolute_path(&expanded_path)?,
                 block_size: 4 * 1024, // 4KB block size
+                commit_handler: Arc::new(RenameCommitHandler),
             },
             Path::from_filesystem_path(&expanded_path)?,
         ))
@@ -288,18 +299,21 @@ impl ObjectStore {
                 scheme: String::from("s3"),
                 base_path: Path::from(url.path()),
                 block_size: 64 * 1024,
+                commit_handler: Arc::new(UnsafeCommitHandler),
             }),
             "gs" => Ok(Self {
                 inner: build_gcs_object_store(url.to_string().as_str()).await?,
                 scheme: String::from("gs"),
                 base_path: Path::from(url.path()),
                 block_size: 64 * 1024,
+                commit_handler: Arc::new(RenameCommitHandler),
             }),
             "az" => Ok(Self {
                 inner: build_azure_object_store(url.to_string().as_str()).await?,
                 scheme: String::from("az"),
                 base_path: Path::from(url.path()),
                 block_size: 64 * 1024,
+                commit_handler: Arc::new(RenameCommitHandler),
             }),
             "file" => Ok(Self::new_from_path(url.path())?.0),
             "memory" => Ok(Self {
@@ -307,6 +321,7 @@ impl ObjectStore {
                 scheme: String::from("memory"),
                 base_path: Path::from(url.path()),
                 block_size: 64 * 1024,
+                commit_handler: Arc::new(RenameCommitHandler),
             }),
             s => Err(Error::IO {
                 message: format!("Unsupported URI scheme: {}", s),
@@ -321,6 +336,7 @@ impl ObjectStore {
             scheme: String::from("memory"),
             base_path: Path::from("/"),
             block_size: 64 * 1024,
+            commit_handler: Arc::new(RenameCommitHandler),
         }
     }
 
@@ -341,6 +357,11 @@ impl ObjectStore {
         &self.base_path
     }
 
+    /// Set the handler to commit new dataset versions.
+    pub fn set_commit_handler(&mut self, commit_handler: Arc<dyn CommitHandler>) {
+        self.commit_handler = commit_handler;
+    }
+
     /// Open a file for path.
     ///
     /// Parameters
@@ -41,10 +41,12 @@ use prost::Message;
 use super::deletion::{read_deletion_file, DeletionVector};
 use super::ReadBatchParams;
 use crate::arrow::*;
-use crate::encodings::{dictionary::DictionaryDecoder, AsyncIndex};
+use crate::encodings::{
+    dictionary::DictionaryDecoder, plain::PlainDecoder, rle::RLEDecoder, AsyncIndex, Encoding,
+};
 use crate::error::{Error, Result};
 use crate::format::Manifest;
-use crate::format::{pb, Metadata, PageTable};
+use crate::format::{pb, Metadata, PageStatistics, PageTable};
 use crate::io::object_reader::{read_fixed_stride_array, read_struct, ObjectReader};
 use crate::io::{read_metadata_offset, read_struct_from_buf};
 use crate::{
@@ -257,6 +259,11 @@ impl FileReader {
         self.metadata.get_batch_length(batch_id).unwrap_or_default() as usize
     }
 
+    /// Statistics of the pages of a field, if they were written.
+    pub(crate) fn page_statistics(&self, field_id: i32) -> Option<&PageStatistics> {
+        self.metadata.page_statistics.get(&field_id)
+    }
+
     /// Count the number of rows in this file.
     pub fn len(&self) -> usize {
         self.metadata.len()
@@ -406,7 +413,9 @@ async fn read_array(
 
     use DataType::*;
 
-    if data_type.is_fixed_stride() {
+    if data_type.is_fixed_stride() && field.encoding == Some(Encoding::RLE) {
+        read_rle_array(reader, field, batch_id, params).await
+    } else if data_type.is_fixed_stride() {
         _read_fixed_stride_array(reader, field, batch_id, params).await
     } else {
         match data_type {
@@ -446,15 +455,44 @@ async fn _read_fixed_stride_array(
     params: &ReadBatchParams,
 ) -> Result<ArrayRef> {
     let page_info = get_page_info(&reader.page_table, field, batch_id)?;
+    let data_type = field.data_type();
+
+    if reader.metadata.has_validity(field.id) {
+        let decoder = PlainDecoder::new_nullable(
+            reader.object_reader.as_ref(),
+            &data_type,
+            page_info.position,
+            page_info.length,
+        )?;
+        decoder.get(params.clone()).await
+    } else {
+        read_fixed_stride_array(
+            reader.object_reader.as_ref(),
+            &data_type,
+            page_info.position,
+            page_info.length,
+            params.clone(),
+        )
+        .await
+    }
+}
 
-    read_fixed_stride_array(
+/// Read run-length encoded array for batch `batch_idx`.
+async fn read_rle_array(
+    reader: &FileReader,
+    field: &Field,
+    batch_id: i32,
+    params: &ReadBatchParams,
+) -> Result<ArrayRef> {
+    let page_info = get_page_info(&reader.page_table, field, batch_id)?;
+    let data_type = field.data_type();
+    let decoder = RLEDecoder::new(
         reader.object_reader.as_ref(),
-        &field.data_type(),
+        &data_type,
         page_info.position,
         page_info.length,
-        params.clone(),
-    )
-    .await
+    );
+    decoder.get(params.clone()).await
 }
 
 fn read_null_array(
@@ -12,6 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::BTreeMap;
 use std::sync::Arc;
 
 use arrow_array::builder::{ArrayBuilder, PrimitiveBuilder};
@@ -21,13 +22,18 @@ use arrow_array::{Array, ArrayRef, RecordBatch, StructArray};
 use arrow_buffer::ArrowNativeType;
 use arrow_schema::DataType;
 use async_recursion::async_recursion;
+use datafusion::logical_expr::Accumulator;
+use datafusion::physical_plan::expressions::{MaxAccumulator, MinAccumulator};
+use datafusion::scalar::ScalarValue;
 use object_store::path::Path;
 
 use crate::arrow::*;
 use crate::datatypes::{Field, Schema};
 use crate::encodings::dictionary::DictionaryEncoder;
-use crate::encodings::{binary::BinaryEncoder, plain::PlainEncoder, Encoder, Encoding};
-use crate::format::{pb, Index, Manifest, Metadata, PageInfo, PageTable};
+use crate::encodings::{
+    binary::BinaryEncoder, plain::PlainEncoder, rle::RLEEncoder, Encoder, Encoding,
+};
+use crate::format::{pb, Index, Manifest, Metadata, PageInfo, PageStatistics, PageTable};
 use crate::io::object_writer::ObjectWriter;
 use crate::{Error, Result};
 
@@ -109,6 +115,119 @@ pub struct FileWriter {
     batch_id: i32,
     page_table: PageTable,
     metadata: Metadata,
+    page_statistics: BTreeMap<i32, PageStatisticsBuilder>,
+}
+
+/// Returns true if the min and max values of the pages are collected for the type.
+pub(crate) fn supports_statistics(data_type: &DataType) -> bool {
+    use DataType::*;
+    matches!(
+        data_type,
+        Int8 | Int16
+            | Int32
+            | Int64
+            | UInt8
+            | UInt16
+            | UInt32
+            | UInt64
+            | Float32
+            | Float64
+            | Utf8
+            | LargeUtf8
+            | Date32
+            | Date64
+            | Time32(_)
+            | Time64(_)
+            | Timestamp(_, _)
+    )
+}
+
+/// Max length, in bytes, of the string min / max values kept in the page statistics.
+const MAX_STATISTICS_STRING_LENGTH: usize = 64;
+
+/// The longest prefix of `value` of at most [MAX_STATISTICS_STRING_LENGTH] bytes.
+fn truncated_prefix(value: &str) -> &str {
+    let mut end = MAX_STATISTICS_STRING_LENGTH.min(value.len());
+    while !value.is_char_boundary(end) {
+        end -= 1;
+    }
+    &value[..end]
+}
+
+/// Truncate a string min value. A prefix is never greater than the value, so it is
+/// still a lower bound.
+fn truncate_min(value: ScalarValue) -> ScalarValue {
+    match value {
+        ScalarValue::Utf8(Some(s)) => ScalarValue::Utf8(Some(truncated_prefix(&s).to_string())),
+        ScalarValue::LargeUtf8(Some(s)) => {
+            ScalarValue::LargeUtf8(Some(truncated_prefix(&s).to_string()))
+        }
+        value => value,
+    }
+}
+
+/// Round a string max value up to a shorter upper bound, by incrementing the last
+/// character of its truncated prefix. The max is dropped, i.e., null, if no character
+/// can be incremented.
+fn truncate_max(value: ScalarValue) -> ScalarValue {
+    fn round_up(value: &str) -> Option<String> {
+        if value.len() <= MAX_STATISTICS_STRING_LENGTH {
+            return Some(value.to_string());
+        }
+        let mut prefix = truncated_prefix(value).to_string();
+        while let Some(c) = prefix.pop() {
+            // Skip the surrogates, which are not valid chars.
+            let next = match c {
+                '\u{D7FF}' => Some('\u{E000}'),
+                c => char::from_u32(c as u32 + 1),
+            };
+            if let Some(next) = next {
+                prefix.push(next);
+                return Some(prefix);
+            }
+        }
+        None
+    }
+    match value {
+        ScalarValue::Utf8(Some(s)) => ScalarValue::Utf8(round_up(&s)),
+        ScalarValue::LargeUtf8(Some(s)) => ScalarValue::LargeUtf8(round_up(&s)),
+        value => value,
+    }
+}
+
+/// Collects the statistics of the pages of one field.
+#[derive(Default)]
+struct PageStatisticsBuilder {
+    min_values: Vec<ScalarValue>,
+    max_values: Vec<ScalarValue>,
+    null_counts: Vec<i64>,
+}
+
+impl PageStatisticsBuilder {
+    /// Append the statistics of the page made of `arrs`.
+    fn append(&mut self, arrs: &[&ArrayRef]) -> Result<()> {
+        let data_type = arrs[0].data_type();
+        let mut min = MinAccumulator::try_new(data_type)?;
+        let mut max = MaxAccumulator::try_new(data_type)?;
+        let mut null_count = 0;
+        for arr in arrs {
+            min.update_batch(&[(*arr).clone()])?;
+            max.update_batch(&[(*arr).clone()])?;
+            null_count += arr.null_count() as i64;
+        }
+        self.min_values.push(truncate_min(min.evaluate()?));
+        self.max_values.push(truncate_max(max.evaluate()?));
+        self.null_counts.push(null_count);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<PageStatistics> {
+        Ok(PageStatistics {
+            min_values: ScalarValue::iter_to_array(self.min_values)?,
+            max_values: ScalarValue::iter_to_array(self.max_values)?,
+            null_counts: self.null_counts,
+        })
+    }
 }
 
 impl FileWriter {
@@ -120,6 +239,7 @@ impl FileWriter {
             batch_id: 0,
             page_table: PageTable::default(),
             metadata: Metadata::default(),
+            page_statistics: BTreeMap::new(),
         })
     }
 
@@ -140,6 +260,7 @@ impl FileWriter {
                 })
                 .collect::<Result<Vec<_>>>()?;
 
+            self.collect_statistics(field, &arrs)?;
             self.write_array(field, &arrs).await?;
         }
         let batch_length = batches.iter().map(|b| b.num_rows() as i32).sum();
@@ -162,6 +283,28 @@ impl FileWriter {
         self.len() == 0
     }
 
+    /// Collect the page statistics of the field, or of its children for a struct field.
+    fn collect_statistics(&mut self, field: &Field, arrs: &[&ArrayRef]) -> Result<()> {
+        let data_type = arrs[0].data_type();
+        if supports_statistics(data_type) {
+            self.page_statistics
+                .entry(field.id)
+                .or_default()
+                .append(arrs)?;
+        } else if data_type.is_struct() {
+            for child in &field.children {
+                let child_arrs = arrs
+                    .iter()
+                    .filter_map(|a| as_struct_array(a).column_by_name(&child.name))
+                    .collect::<Vec<_>>();
+                if child_arrs.len() == arrs.len() {
+                    self.collect_statistics(child, &child_arrs)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
     #[async_recursion]
     async fn write_array(&mut self, field: &Field, arrs: &[&ArrayRef]) -> Result<()> {
         assert!(!arrs.is_empty());
@@ -170,6 +313,9 @@ impl FileWriter {
 
         match data_type {
             DataType::Null => self.write_null_array(field, arrs_ref.as_slice()).await,
+            dt if dt.is_fixed_stride() && field.encoding == Some(Encoding::RLE) => {
+                self.write_rle_array(field, arrs_ref.as_slice()).await
+            }
             dt if dt.is_fixed_stride() => {
                 self.write_fixed_stride_array(field, arrs_ref.as_slice())
                     .await
@@ -207,12 +353,39 @@ impl FileWriter {
     }
 
     /// Write fixed size array, including, primtiives, fixed size binary, and fixed size list.
+    ///
+    /// The validity bitmap is persisted alongside the values if the field is nullable.
     async fn write_fixed_stride_array(&mut self, field: &Field, arrs: &[&dyn Array]) -> Result<()> {
+        self.write_plain_array(field, arrs, field.nullable).await
+    }
+
+    async fn write_plain_array(
+        &mut self,
+        field: &Field,
+        arrs: &[&dyn Array],
+        nullable: bool,
+    ) -> Result<()> {
         assert_eq!(field.encoding, Some(Encoding::Plain));
         assert!(!arrs.is_empty());
         let data_type = arrs[0].data_type();
 
-        let mut encoder = PlainEncoder::new(&mut self.object_writer, data_type);
+        let mut encoder = if nullable {
+            self.metadata.set_has_validity(field.id);
+            PlainEncoder::new_nullable(&mut self.object_writer, data_type)
+        } else {
+            PlainEncoder::new(&mut self.object_writer, data_type)
+        };
+        let pos = encoder.encode(arrs).await?;
+        let arrs_length: i32 = arrs.iter().map(|a| a.len() as i32).sum();
+        let page_info = PageInfo::new(pos, arrs_length as usize);
+        self.page_table.set(field.id, self.batch_id, page_info);
+        Ok(())
+    }
+
+    /// Write fixed width arrays with run-length encoding.
+    async fn write_rle_array(&mut self, field: &Field, arrs: &[&dyn Array]) -> Result<()> {
+        assert_eq!(field.encoding, Some(Encoding::RLE));
+        let mut encoder = RLEEncoder::new(&mut self.object_writer);
         let pos = encoder.encode(arrs).await?;
         let arrs_length: i32 = arrs.iter().map(|a| a.len() as i32).sum();
         let page_info = PageInfo::new(pos, arrs_length as usize);
@@ -302,7 +475,7 @@ impl FileWriter {
         }
 
         let positions: &dyn Array = &pos_builder.finish();
-        self.write_fixed_stride_array(field, &[positions]).await?;
+        self.write_plain_array(field, &[positions], false).await?;
         let arrs = list_arrs.iter().collect::<Vec<_>>();
         self.write_array(&field.children[0], arrs.as_slice()).await
     }
@@ -337,7 +510,7 @@ impl FileWriter {
         }
 
         let positions: &dyn Array = &pos_builder.finish();
-        self.write_fixed_stride_array(field, &[positions]).await?;
+        self.write_plain_array(field, &[positions], false).await?;
         let arrs = list_arrs.iter().collect::<Vec<_>>();
         self.write_array(&field.children[0], arrs.as_slice()).await
     }
@@ -353,6 +526,11 @@ impl FileWriter {
 
         // Step 3. Write metadata.
         self.metadata.manifest_position = Some(pos);
+        for (field_id, stats) in std::mem::take(&mut self.page_statistics) {
+            self.metadata
+                .page_statistics
+                .insert(field_id, stats.finish()?);
+        }
         let pos = self.object_writer.write_struct(&self.metadata).await?;
 
         // Step 4. Write magics.
@@ -619,4 +797,124 @@ mod tests {
         let actual = reader.read_batch(0, .., reader.schema()).await.unwrap();
         assert_eq!(actual, batch);
     }
+
+    #[tokio::test]
+    async fn test_write_page_statistics() {
+        let arrow_schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("i", DataType::Int64, true),
+            ArrowField::new(
+                "st",
+                DataType::Struct(ArrowFields::from(vec![ArrowField::new(
+                    "s",
+                    DataType::Utf8,
+                    true,
+                )])),
+                true,
+            ),
+            ArrowField::new("b", DataType::Boolean, true),
+        ]));
+        let schema = Schema::try_from(arrow_schema.as_ref()).unwrap();
+        let make_batch = |ints: Vec<Option<i64>>, strings: Vec<Option<&str>>| {
+            let len = ints.len();
+            let strings: ArrayRef = Arc::new(StringArray::from(strings));
+            RecordBatch::try_new(
+                arrow_schema.clone(),
+                vec![
+                    Arc::new(Int64Array::from(ints)),
+                    Arc::new(StructArray::from(vec![(
+                        Arc::new(ArrowField::new("s", DataType::Utf8, true)),
+                        strings,
+                    )])),
+                    Arc::new(BooleanArray::from(vec![true; len])),
+                ],
+            )
+            .unwrap()
+        };
+
+        let store = ObjectStore::memory();
+        let path = Path::from("/foo");
+        let mut file_writer = FileWriter::try_new(&store, &path, schema).await.unwrap();
+        file_writer
+            .write(&[make_batch(
+                vec![Some(3), None, Some(-1)],
+                vec![Some("b"), Some("a"), None],
+            )])
+            .await
+            .unwrap();
+        file_writer
+            .write(&[make_batch(vec![None, None], vec![Some("z"), Some("c")])])
+            .await
+            .unwrap();
+        file_writer.finish().await.unwrap();
+
+        let reader = FileReader::try_new(&store, &path).await.unwrap();
+        let schema = reader.schema();
+        let stats = reader
+            .page_statistics(schema.field("i").unwrap().id)
+            .unwrap();
+        assert_eq!(
+            stats,
+            &PageStatistics {
+                min_values: Arc::new(Int64Array::from(vec![Some(-1), None])),
+                max_values: Arc::new(Int64Array::from(vec![Some(3), None])),
+                null_counts: vec![1, 2],
+            }
+        );
+        let stats = reader
+            .page_statistics(schema.field("st.s").unwrap().id)
+            .unwrap();
+        assert_eq!(
+            stats,
+            &PageStatistics {
+                min_values: Arc::new(StringArray::from(vec!["a", "c"])),
+                max_values: Arc::new(StringArray::from(vec!["b", "z"])),
+                null_counts: vec![1, 0],
+            }
+        );
+        // No statistics for the struct itself, nor for the unsupported types.
+        assert!(reader
+            .page_statistics(schema.field("st").unwrap().id)
+            .is_none());
+        assert!(reader
+            .page_statistics(schema.field("b").unwrap().id)
+            .is_none());
+    }
+
+    #[test]
+    fn test_truncate_string_statistics() {
+        let long = "a".repeat(100);
+        assert_eq!(
+            truncate_min(ScalarValue::Utf8(Some(long.clone()))),
+            ScalarValue::Utf8(Some("a".repeat(64)))
+        );
+        assert_eq!(
+            truncate_max(ScalarValue::Utf8(Some(long))),
+            ScalarValue::Utf8(Some("a".repeat(63) + "b"))
+        );
+        // Short values are kept as is.
+        assert_eq!(
+            truncate_max(ScalarValue::LargeUtf8(Some("abc".to_string()))),
+            ScalarValue::LargeUtf8(Some("abc".to_string()))
+        );
+        // Truncated on a char boundary: 63 bytes of "a" then the 2 bytes "é".
+        let value = "a".repeat(63) + "éz";
+        assert_eq!(
+            truncate_min(ScalarValue::Utf8(Some(value.clone()))),
+            ScalarValue::Utf8(Some("a".repeat(63)))
+        );
+        assert_eq!(
+            truncate_max(ScalarValue::Utf8(Some(value))),
+            ScalarValue::Utf8(Some("a".repeat(62) + "b"))
+        );
+        // No char can be incremented, so there is no upper bound.
+        let value = char::MAX.to_string().repeat(20);
+        assert_eq!(
+            truncate_max(ScalarValue::Utf8(Some(value))),
+            ScalarValue::Utf8(None)
+        );
+        assert_eq!(
+            truncate_max(ScalarValue::Int32(Some(3))),
+            ScalarValue::Int32(Some(3))
+        );
+    }
 }
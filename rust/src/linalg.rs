@@ -16,6 +16,7 @@ pub mod cosine;
 pub mod dot;
 pub mod l2;
 pub mod norm_l2;
+pub mod uint8;
 
 #[cfg(target_arch = "x86_64")]
 pub mod x86_64;
@@ -0,0 +1,113 @@
+// Copyright 2023 Lance Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Distances between a `f32` vector and `u8` codes.
+//!
+//! The vector is expected to be mapped into the space of the codes beforehand,
+//! so the codes are never decoded back to `f32` vectors.
+
+use std::sync::Arc;
+
+use arrow_array::Float32Array;
+
+/// Weighted L2 distance between a vector and a `u8` code, i.e.,
+/// `sum(weights[i] * (from[i] - to[i]) ^ 2)`.
+///
+/// Rely on compiler auto-vectorization.
+#[inline]
+pub fn l2_uint8(from: &[f32], to: &[u8], weights: &[f32]) -> f32 {
+    debug_assert_eq!(from.len(), to.len());
+    debug_assert_eq!(from.len(), weights.len());
+    from.iter()
+        .zip(to.iter())
+        .zip(weights.iter())
+        .map(|((x, c), w)| {
+            let d = x - *c as f32;
+            w * d * d
+        })
+        .sum::<f32>()
+}
+
+/// Dot product between a vector and a `u8` code.
+#[inline]
+pub fn dot_uint8(from: &[f32], to: &[u8]) -> f32 {
+    debug_assert_eq!(from.len(), to.len());
+    from.iter()
+        .zip(to.iter())
+        .map(|(x, c)| x * *c as f32)
+        .sum::<f32>()
+}
+
+/// Compute the weighted L2 distance between a vector and a batch of `u8` codes.
+///
+/// Parameters
+///
+/// - `from`: the vector to compute distance from.
+/// - `to`: a list of codes to compute distance to, each of `from.len()` bytes.
+/// - `weights`: the weight of each dimension.
+pub fn l2_distance_uint8_batch(from: &[f32], to: &[u8], weights: &[f32]) -> Arc<Float32Array> {
+    let dimension = from.len();
+    assert_eq!(weights.len(), dimension);
+    assert_eq!(to.len() % dimension, 0);
+
+    let dists = unsafe {
+        Float32Array::from_trusted_len_iter(
+            to.chunks_exact(dimension)
+                .map(|c| Some(l2_uint8(from, c, weights))),
+        )
+    };
+    Arc::new(dists)
+}
+
+/// Compute the dot product between a vector and a batch of `u8` codes.
+pub fn dot_uint8_batch(from: &[f32], to: &[u8]) -> Arc<Float32Array> {
+    let dimension = from.len();
+    assert_eq!(to.len() % dimension, 0);
+
+    let dists = unsafe {
+        Float32Array::from_trusted_len_iter(
+            to.chunks_exact(dimension).map(|c| Some(dot_uint8(from, c))),
+        )
+    };
+    Arc::new(dists)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uint8_distances() {
+        let codes = [0_u8, 1, 2, 255, 10, 20, 30, 40];
+        let point = [1.0_f32, 1.0, 1.0, 1.0];
+
+        let scores = l2_distance_uint8_batch(&point, &codes, &[1.0, 1.0, 1.0, 1.0]);
+        assert_eq!(
+            scores.as_ref(),
+            &Float32Array::from(vec![
+                1.0 + 0.0 + 1.0 + 254.0 * 254.0,
+                81.0 + 361.0 + 841.0 + 1521.0
+            ])
+        );
+
+        let scores = l2_distance_uint8_batch(&point, &codes, &[0.5, 0.0, 2.0, 0.0]);
+        assert_eq!(
+            scores.as_ref(),
+            &Float32Array::from(vec![0.5 + 2.0, 40.5 + 1682.0])
+        );
+
+        let scores = dot_uint8_batch(&point, &codes);
+        assert_eq!(scores.as_ref(), &Float32Array::from(vec![258.0, 100.0]));
+    }
+}
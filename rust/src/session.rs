@@ -82,7 +82,7 @@ mod tests {
             100,
         ));
 
-        let pq = Arc::new(ProductQuantizer::new(1, 8, 1));
+        let pq = Arc::new(ProductQuantizer::new(1, 8, 1).unwrap());
         let idx = Arc::new(PQIndex::new(pq, MetricType::L2, deletion_cache));
         no_cache.index_cache.insert("abc", idx);
 
@@ -20,8 +20,11 @@
 use rand::rngs::StdRng;
 use rand::{Rng, SeedableRng};
 use std::iter::repeat_with;
+use std::ops::Range;
+use std::sync::Arc;
 
-use arrow_array::Float32Array;
+use arrow_array::{Float32Array, RecordBatch, RecordBatchIterator, UInt32Array};
+use arrow_schema::{ArrowError, DataType, Field, Schema as ArrowSchema};
 
 /// Create a random float32 array.
 pub fn generate_random_array_with_seed(n: usize, seed: [u8; 32]) -> Float32Array {
@@ -42,3 +45,20 @@ pub fn generate_random_array(n: usize) -> Float32Array {
             .collect::<Vec<f32>>(),
     )
 }
+
+/// Create a single batch with a non-nullable `u32` column `i` holding the values of `range`.
+pub fn sequence_batches(
+    range: Range<u32>,
+) -> RecordBatchIterator<Vec<Result<RecordBatch, ArrowError>>> {
+    let schema = Arc::new(ArrowSchema::new(vec![Field::new(
+        "i",
+        DataType::UInt32,
+        false,
+    )]));
+    let batch = RecordBatch::try_new(
+        schema.clone(),
+        vec![Arc::new(UInt32Array::from_iter_values(range))],
+    )
+    .unwrap();
+    RecordBatchIterator::new(vec![Ok(batch)], schema)
+}